@@ -0,0 +1,4 @@
+-- Keep processed rows around as a delivery history instead of deleting them.
+ALTER TABLE issue_delivery_queue ADD COLUMN status TEXT NOT NULL DEFAULT 'pending';
+ALTER TABLE issue_delivery_queue ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+ALTER TABLE issue_delivery_queue ADD COLUMN processed_at timestamptz NULL;
@@ -0,0 +1,7 @@
+CREATE TABLE subscriber_tags (
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY(subscriber_id, tag)
+);
@@ -0,0 +1,10 @@
+CREATE TABLE newsletter_templates (
+    template_id uuid NOT NULL,
+    name TEXT NOT NULL,
+    title TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+    PRIMARY KEY(template_id)
+);
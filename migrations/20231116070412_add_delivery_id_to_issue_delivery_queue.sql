@@ -0,0 +1,3 @@
+-- Give each queue row a stable handle so admins can act on a single delivery.
+ALTER TABLE issue_delivery_queue
+    ADD COLUMN delivery_id uuid NOT NULL DEFAULT gen_random_uuid() UNIQUE;
@@ -0,0 +1,8 @@
+CREATE TABLE audit_log (
+    audit_log_id uuid NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY(audit_log_id)
+);
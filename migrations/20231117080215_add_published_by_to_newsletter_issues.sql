@@ -0,0 +1,3 @@
+-- Issues published before this column existed have no recorded owner and
+-- stay visible to every admin.
+ALTER TABLE newsletter_issues ADD COLUMN published_by uuid NULL REFERENCES users (user_id);
@@ -0,0 +1,10 @@
+CREATE TABLE delivery_attempts (
+    delivery_attempt_id uuid NOT NULL,
+    delivery_id uuid NOT NULL
+        REFERENCES issue_delivery_queue (delivery_id) ON DELETE CASCADE,
+    attempted_at timestamptz NOT NULL,
+    result TEXT NOT NULL,
+    error TEXT NULL,
+    PRIMARY KEY(delivery_attempt_id)
+);
+CREATE INDEX delivery_attempts_delivery_id_idx ON delivery_attempts (delivery_id, attempted_at);
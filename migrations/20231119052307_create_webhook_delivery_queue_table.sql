@@ -0,0 +1,12 @@
+CREATE TABLE webhook_delivery_queue (
+    webhook_delivery_id uuid NOT NULL,
+    url TEXT NOT NULL,
+    -- Serialized JSON body, sent as-is.
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    n_attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at timestamptz NOT NULL,
+    last_error TEXT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY(webhook_delivery_id)
+);
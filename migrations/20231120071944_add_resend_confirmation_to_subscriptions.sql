@@ -0,0 +1,3 @@
+-- Set when the confirmation email could not be sent: the reminder worker
+-- picks the subscriber up again once this point in time has passed.
+ALTER TABLE subscriptions ADD COLUMN resend_confirmation_at timestamptz NULL;
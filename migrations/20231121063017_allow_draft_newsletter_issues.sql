@@ -0,0 +1,2 @@
+-- Drafts are issues that have not been published yet.
+ALTER TABLE newsletter_issues ALTER COLUMN published_at DROP NOT NULL;
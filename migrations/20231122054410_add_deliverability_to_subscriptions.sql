@@ -0,0 +1,3 @@
+-- What the email provider's validation API said about the address when
+-- the subscriber signed up. NULL when it was not (or could not be) checked.
+ALTER TABLE subscriptions ADD COLUMN deliverability TEXT NULL;
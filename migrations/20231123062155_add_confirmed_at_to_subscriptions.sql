@@ -0,0 +1,5 @@
+-- When the subscriber last clicked a confirmation link. Used to decide
+-- whether someone re-subscribing has to confirm their address again.
+ALTER TABLE subscriptions ADD COLUMN confirmed_at timestamptz NULL;
+-- The best we know for subscribers confirmed before this column existed.
+UPDATE subscriptions SET confirmed_at = subscribed_at WHERE status = 'confirmed';
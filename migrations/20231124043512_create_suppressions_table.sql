@@ -0,0 +1,6 @@
+-- Addresses that must never be emailed, whether or not they are (still)
+-- subscribed, e.g. because they were unsubscribed in a previous system.
+CREATE TABLE suppressions(
+    email TEXT NOT NULL PRIMARY KEY,
+    suppressed_at timestamptz NOT NULL DEFAULT now()
+);
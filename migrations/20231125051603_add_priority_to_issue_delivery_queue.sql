@@ -0,0 +1,5 @@
+-- Higher priority deliveries are sent first. 0 is normal.
+ALTER TABLE issue_delivery_queue ADD COLUMN priority SMALLINT NOT NULL DEFAULT 0;
+CREATE INDEX issue_delivery_queue_pending_by_priority
+    ON issue_delivery_queue (priority DESC, created_at)
+    WHERE status = 'pending';
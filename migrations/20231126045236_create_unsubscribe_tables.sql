@@ -0,0 +1,14 @@
+-- One-click unsubscribe links.
+CREATE TABLE unsubscribe_tokens(
+    unsubscribe_token TEXT NOT NULL PRIMARY KEY,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE
+);
+-- Optional feedback left after unsubscribing.
+CREATE TABLE unsubscribe_reasons(
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    details TEXT NULL,
+    recorded_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,4 @@
+-- How many times a failed delivery has been put back in the queue automatically.
+ALTER TABLE issue_delivery_queue ADD COLUMN auto_retries SMALLINT NOT NULL DEFAULT 0;
+CREATE INDEX issue_delivery_queue_failed_idx
+    ON issue_delivery_queue (processed_at) WHERE status = 'failed';
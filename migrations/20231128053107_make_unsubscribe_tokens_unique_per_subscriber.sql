@@ -0,0 +1,2 @@
+-- Every subscriber keeps the same unsubscribe link across issues.
+CREATE UNIQUE INDEX unsubscribe_tokens_subscriber_id_idx ON unsubscribe_tokens (subscriber_id);
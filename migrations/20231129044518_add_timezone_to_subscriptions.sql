@@ -0,0 +1,4 @@
+-- An IANA name such as 'Europe/Rome'. NULL means the configured default.
+ALTER TABLE subscriptions ADD COLUMN timezone TEXT NULL;
+-- Deliveries scheduled for the recipient's local time are held back until then.
+ALTER TABLE issue_delivery_queue ADD COLUMN not_before timestamptz NULL;
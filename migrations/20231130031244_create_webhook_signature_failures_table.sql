@@ -0,0 +1,13 @@
+-- Inbound webhook calls that failed signature validation, kept for
+-- security review: a burst of mismatches after a secret rotation points
+-- at misconfiguration, a trickle from unknown addresses at probing.
+CREATE TABLE webhook_signature_failures(
+    id uuid NOT NULL PRIMARY KEY,
+    endpoint TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    remote_addr TEXT,
+    headers TEXT NOT NULL,
+    received_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX webhook_signature_failures_received_at_idx
+    ON webhook_signature_failures (received_at DESC);
@@ -0,0 +1,3 @@
+-- Pauses emails to one subscriber, e.g. after a complaint. Issues
+-- published while the pause is in effect are not sent to them.
+ALTER TABLE subscriptions ADD COLUMN suppressed_until timestamptz;
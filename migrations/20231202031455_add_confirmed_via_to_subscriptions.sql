@@ -0,0 +1,3 @@
+-- How the subscriber confirmed, e.g. 'link'. NULL for subscribers who
+-- confirmed before this was tracked, or who have not confirmed yet.
+ALTER TABLE subscriptions ADD COLUMN confirmed_via TEXT;
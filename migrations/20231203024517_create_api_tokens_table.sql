@@ -0,0 +1,7 @@
+-- Bearer tokens for the JSON API. Only a SHA-256 hash of each token is
+-- stored; the token itself is shown once, when it is created.
+CREATE TABLE api_tokens (
+    token_hash TEXT NOT NULL PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    created_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,7 @@
+-- Keys are unique within a scope: the id of the user who sent them, or
+-- 'global' when every user shares the same keys.
+ALTER TABLE idempotency ADD COLUMN scope TEXT;
+UPDATE idempotency SET scope = user_id::text;
+ALTER TABLE idempotency ALTER COLUMN scope SET NOT NULL;
+ALTER TABLE idempotency DROP CONSTRAINT idempotency_pkey;
+ALTER TABLE idempotency ADD PRIMARY KEY (scope, idempotency_key);
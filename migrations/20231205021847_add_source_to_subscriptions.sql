@@ -0,0 +1,3 @@
+-- Where an imported subscriber came from, e.g. the list they were on with a
+-- previous provider. NULL for subscribers who signed up through the form.
+ALTER TABLE subscriptions ADD COLUMN source TEXT;
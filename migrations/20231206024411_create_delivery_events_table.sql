@@ -0,0 +1,10 @@
+-- What happened to a delivered email afterwards, as reported by the email
+-- provider: 'bounced', 'opened' or 'clicked'.
+CREATE TABLE delivery_events(
+    delivery_event_id uuid NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+    delivery_id uuid NOT NULL
+        REFERENCES issue_delivery_queue (delivery_id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    occurred_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX delivery_events_delivery_id_idx ON delivery_events (delivery_id, occurred_at);
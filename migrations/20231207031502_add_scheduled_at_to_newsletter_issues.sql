@@ -0,0 +1,3 @@
+-- Issues published for later delivery. Their deliveries are held back
+-- until then, and the issue can be cancelled until it starts sending.
+ALTER TABLE newsletter_issues ADD COLUMN scheduled_at timestamptz NULL;
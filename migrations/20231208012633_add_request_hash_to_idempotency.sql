@@ -0,0 +1,3 @@
+-- Tells a retry apart from a different request that reuses the same key.
+-- NULL for keys saved before hashes were recorded.
+ALTER TABLE idempotency ADD COLUMN request_hash TEXT NULL;
@@ -0,0 +1,2 @@
+-- Where test sends of a newsletter issue go. NULL until the admin has one.
+ALTER TABLE users ADD COLUMN email TEXT NULL;
@@ -0,0 +1,3 @@
+-- Whether the last failed attempt might succeed if tried again later, e.g. the
+-- provider timed out, rather than being rejected for good.
+ALTER TABLE issue_delivery_queue ADD COLUMN transient_failure BOOLEAN NOT NULL DEFAULT FALSE;
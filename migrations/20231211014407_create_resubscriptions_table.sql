@@ -0,0 +1,9 @@
+-- Subscribers coming back after unsubscribing, for their consent history.
+CREATE TABLE resubscriptions(
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    -- Whether their earlier confirmation was still trusted, or they were
+    -- asked to confirm again.
+    confirmation_kept BOOLEAN NOT NULL,
+    resubscribed_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,3 @@
+-- No supported email provider has an address validation API, so the
+-- column could never hold anything but 'unknown'.
+ALTER TABLE subscriptions DROP COLUMN deliverability;
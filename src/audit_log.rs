@@ -0,0 +1,26 @@
+use sqlx::{Executor, Postgres, Transaction};
+use uuid::Uuid;
+
+/// Records a manual intervention performed by an admin.
+/// It runs inside the caller's transaction, so the entry is only kept if
+/// the change it describes is committed too.
+#[tracing::instrument(name = "Record audit log entry", skip(transaction))]
+pub async fn record_audit_event(
+    transaction: &mut Transaction<'static, Postgres>,
+    user_id: Uuid,
+    action: &str,
+    target: &str,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO audit_log (audit_log_id, user_id, action, target, created_at)
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        action,
+        target
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use anyhow::Context;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::AuthError;
+
+/// A new random token, to be shown to its owner once and then stored
+/// with `store_api_token`.
+pub fn generate_api_token() -> Secret<String> {
+    let mut rng = thread_rng();
+    let token = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(40)
+        .collect();
+    Secret::new(token)
+}
+
+/// Tokens are random and long enough that a fast hash is sufficient.
+fn hash_api_token(token: &Secret<String>) -> String {
+    hex::encode(Sha256::digest(token.expose_secret().as_bytes()))
+}
+
+#[tracing::instrument(name = "Store API token", skip(token, pool))]
+pub async fn store_api_token(
+    user_id: Uuid,
+    token: &Secret<String>,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"INSERT INTO api_tokens (token_hash, user_id) VALUES ($1, $2)"#,
+        hash_api_token(token),
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store an API token.")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Validate API token", skip(token, pool))]
+pub async fn validate_api_token(token: &Secret<String>, pool: &PgPool) -> Result<Uuid, AuthError> {
+    sqlx::query!(
+        r#"SELECT user_id FROM api_tokens WHERE token_hash = $1"#,
+        hash_api_token(token)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve an API token.")?
+    .map(|row| row.user_id)
+    .ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Unknown API token.")))
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::configuration::LoginLockoutSettings;
+
+/// Windows that ended this long ago are dropped, so that every username and
+/// client ever seen is not kept in memory.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Counts failed logins per username and per client. Once either has failed
+/// too often within the window, logins for it are refused until the window
+/// that started with its first failure ends. Every attempt is counted as a
+/// failure up front and refunded if it turns out not to be one.
+/// Unknown usernames are counted like any other, so a lockout says nothing
+/// about whether the account exists. Counts are local to one instance.
+pub struct LoginLockout {
+    settings: LoginLockoutSettings,
+    state: Mutex<Failures>,
+}
+
+struct Failures {
+    windows: HashMap<String, FailureWindow>,
+    evicted_at: Instant,
+}
+
+struct FailureWindow {
+    count: u32,
+    started_at: Instant,
+}
+
+impl LoginLockout {
+    pub fn new(settings: LoginLockoutSettings) -> Self {
+        Self {
+            settings,
+            state: Mutex::new(Failures {
+                windows: HashMap::new(),
+                evicted_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Counts an attempt against `username` and `client` before the
+    /// password is checked, so that concurrent attempts cannot all slip in
+    /// under the threshold. Fails with how long until `username` may log in
+    /// from `client` again if either is locked out, counting nothing.
+    pub fn reserve_attempt(&self, username: &str, client: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let window = self.settings.window();
+        let mut state = self.state.lock().unwrap();
+
+        if now.duration_since(state.evicted_at) >= EVICTION_INTERVAL {
+            state
+                .windows
+                .retain(|_, failures| now.duration_since(failures.started_at) < window);
+            state.evicted_at = now;
+        }
+
+        if let Some(retry_after) = self.retry_after(&state, username, client, now) {
+            return Err(retry_after);
+        }
+        for key in [username_key(username), client_key(client)] {
+            let failures = state.windows.entry(key).or_insert(FailureWindow {
+                count: 0,
+                started_at: now,
+            });
+            if now.duration_since(failures.started_at) >= window {
+                failures.count = 0;
+                failures.started_at = now;
+            }
+            failures.count += 1;
+        }
+        Ok(())
+    }
+
+    /// Clears the failures of `username` and gives `client` its attempt
+    /// back. The client's earlier failures are kept, so that one known
+    /// account cannot be used to keep guessing at others.
+    pub fn record_success(&self, username: &str, client: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.windows.remove(&username_key(username));
+        refund(&mut state, client_key(client));
+    }
+
+    /// Gives back an attempt that could not be checked, e.g. because the
+    /// database was down, as it says nothing about the password.
+    pub fn refund(&self, username: &str, client: &str) {
+        let mut state = self.state.lock().unwrap();
+        refund(&mut state, username_key(username));
+        refund(&mut state, client_key(client));
+    }
+
+    fn retry_after(
+        &self,
+        state: &Failures,
+        username: &str,
+        client: &str,
+        now: Instant,
+    ) -> Option<Duration> {
+        [
+            (
+                username_key(username),
+                self.settings.max_failures_per_username,
+            ),
+            (client_key(client), self.settings.max_failures_per_client),
+        ]
+        .into_iter()
+        .filter_map(|(key, max_failures)| {
+            let window = state.windows.get(&key)?;
+            let ends_at = window.started_at + self.settings.window();
+            (window.count >= max_failures && ends_at > now).then(|| ends_at - now)
+        })
+        .max()
+    }
+}
+
+fn refund(state: &mut Failures, key: String) {
+    if let Some(failures) = state.windows.get_mut(&key) {
+        failures.count = failures.count.saturating_sub(1);
+    }
+}
+
+fn username_key(username: &str) -> String {
+    format!("username:{}", username.trim().to_lowercase())
+}
+
+fn client_key(client: &str) -> String {
+    format!("client:{client}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::LoginLockout;
+    use crate::configuration::LoginLockoutSettings;
+
+    impl LoginLockout {
+        fn locked_for(&self, username: &str, client: &str) -> Option<Duration> {
+            let state = self.state.lock().unwrap();
+            self.retry_after(&state, username, client, Instant::now())
+        }
+    }
+
+    fn lockout(window_seconds: u64) -> LoginLockout {
+        LoginLockout::new(LoginLockoutSettings {
+            max_failures_per_username: 2,
+            max_failures_per_client: 3,
+            window_seconds,
+        })
+    }
+
+    #[test]
+    fn usernames_and_clients_have_their_own_thresholds() {
+        let lockout = lockout(60);
+
+        lockout.reserve_attempt("admin", "1.2.3.4").unwrap();
+        assert_eq!(lockout.locked_for("admin", "1.2.3.4"), None);
+        lockout.reserve_attempt("Admin", "1.2.3.4").unwrap();
+        assert!(lockout.locked_for("admin", "5.6.7.8").is_some());
+        assert_eq!(lockout.locked_for("editor", "1.2.3.4"), None);
+        lockout.reserve_attempt("editor", "1.2.3.4").unwrap();
+        assert!(lockout.locked_for("writer", "1.2.3.4").is_some());
+        assert_eq!(lockout.locked_for("writer", "5.6.7.8"), None);
+    }
+
+    #[test]
+    fn attempts_are_refused_once_the_threshold_is_reserved() {
+        let lockout = lockout(60);
+
+        lockout.reserve_attempt("admin", "1.2.3.4").unwrap();
+        lockout.reserve_attempt("admin", "5.6.7.8").unwrap();
+
+        assert!(lockout.reserve_attempt("admin", "9.9.9.9").is_err());
+        // A refused attempt is not counted against the client.
+        assert_eq!(lockout.locked_for("editor", "9.9.9.9"), None);
+    }
+
+    #[test]
+    fn a_success_only_clears_the_username() {
+        let lockout = lockout(60);
+        lockout.reserve_attempt("admin", "1.2.3.4").unwrap();
+        lockout.reserve_attempt("editor", "1.2.3.4").unwrap();
+        lockout.reserve_attempt("admin", "1.2.3.4").unwrap();
+
+        lockout.record_success("admin", "1.2.3.4");
+
+        assert_eq!(lockout.locked_for("admin", "5.6.7.8"), None);
+        lockout.reserve_attempt("writer", "1.2.3.4").unwrap();
+        assert!(lockout.locked_for("writer", "1.2.3.4").is_some());
+    }
+
+    #[test]
+    fn a_refunded_attempt_is_not_counted() {
+        let lockout = lockout(60);
+        lockout.reserve_attempt("admin", "1.2.3.4").unwrap();
+        lockout.reserve_attempt("admin", "1.2.3.4").unwrap();
+
+        lockout.refund("admin", "1.2.3.4");
+
+        assert_eq!(lockout.locked_for("admin", "1.2.3.4"), None);
+    }
+
+    #[test]
+    fn failures_are_forgotten_once_the_window_ends() {
+        let lockout = lockout(0);
+
+        lockout.reserve_attempt("admin", "1.2.3.4").unwrap();
+        lockout.reserve_attempt("admin", "1.2.3.4").unwrap();
+
+        assert_eq!(lockout.locked_for("admin", "1.2.3.4"), None);
+    }
+}
@@ -1,12 +1,21 @@
 use std::ops::Deref;
+use std::time::Duration;
 
 use actix_web::body::MessageBody;
 use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::InternalError;
-use actix_web::{FromRequest, HttpMessage};
+use actix_web::http::header::{HeaderMap, AUTHORIZATION, RETRY_AFTER, WWW_AUTHENTICATE};
+use actix_web::http::Method;
+use actix_web::{web, FromRequest, HttpMessage, HttpResponse};
 use actix_web_lab::middleware::Next;
+use anyhow::Context;
+use base64::Engine;
+use secrecy::Secret;
+use sqlx::PgPool;
 use uuid::Uuid;
 
+use super::{validate_api_token, validate_credentials, AuthError, Credentials, LoginLockout};
+use crate::routes::login_url;
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 
@@ -42,9 +51,119 @@ pub async fn reject_anonymous_users(
             next.call(req).await
         }
         None => {
-            let response = see_other("/login");
+            // Pages can be bookmarked or linked to, so bring the user back
+            // to them after logging in. Form submissions cannot be replayed.
+            let next = (req.method() == Method::GET)
+                .then(|| req.uri().path_and_query().map(|p| p.as_str()))
+                .flatten();
+            let response = see_other(&login_url(next));
             let e = anyhow::anyhow!("The user has not logged in");
             Err(InternalError::from_response(e, response).into())
         }
     }
 }
+
+/// How an API client identified itself in its `Authorization` header.
+enum ApiCredentials {
+    Basic(Credentials),
+    Bearer(Secret<String>),
+}
+
+/// Authenticates API clients from the `Authorization` header rather than
+/// a session: either an API token (`Bearer`) or a username and password
+/// (`Basic`). Anything else gets a `401`.
+/// Passwords are guessed at here as much as on the login form, so `Basic`
+/// credentials count towards the same `LoginLockout`.
+pub async fn reject_unauthenticated_api_clients(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .expect("The database pool is registered as app data")
+        .clone();
+    let lockout = req
+        .app_data::<web::Data<LoginLockout>>()
+        .expect("The login lockout is registered as app data")
+        .clone();
+    let client = req
+        .connection_info()
+        .realip_remote_addr()
+        .unwrap_or("unknown")
+        .to_string();
+    let credentials = api_credentials(req.headers()).map_err(unauthorized)?;
+    let user_id = match credentials {
+        ApiCredentials::Basic(credentials) => {
+            let username = credentials.username.clone();
+            if let Err(retry_after) = lockout.reserve_attempt(&username, &client) {
+                tracing::warn!(
+                    client,
+                    "Refusing an API client after too many failed attempts."
+                );
+                return Err(locked_out(retry_after));
+            }
+            let user_id = validate_credentials(credentials, &pool).await;
+            match &user_id {
+                Ok(_) => lockout.record_success(&username, &client),
+                Err(AuthError::InvalidCredentials(_)) => {}
+                Err(AuthError::UnexpectedError(_)) => lockout.refund(&username, &client),
+            }
+            user_id
+        }
+        ApiCredentials::Bearer(token) => validate_api_token(&token, &pool).await,
+    }
+    .map_err(|e| match e {
+        AuthError::InvalidCredentials(_) => unauthorized(e.into()),
+        AuthError::UnexpectedError(_) => e500(e),
+    })?;
+    req.extensions_mut().insert(UserId(user_id));
+    next.call(req).await
+}
+
+fn api_credentials(headers: &HeaderMap) -> Result<ApiCredentials, anyhow::Error> {
+    let header_value = headers
+        .get(AUTHORIZATION)
+        .context("The 'Authorization' header was missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string.")?;
+    if let Some(token) = header_value.strip_prefix("Bearer ") {
+        return Ok(ApiCredentials::Bearer(Secret::new(
+            token.trim().to_string(),
+        )));
+    }
+    let base64encoded_segment = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic' or 'Bearer'.")?;
+    let decoded_bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64encoded_segment)
+        .context("Failed to base64-decode 'Basic' credentials.")?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .context("The decoded credential string is not valid UTF8.")?;
+    let (username, password) = decoded_credentials
+        .split_once(':')
+        .context("'Basic' credentials must be a username and a password separated by ':'.")?;
+    Ok(ApiCredentials::Basic(Credentials {
+        username: username.to_string(),
+        password: Secret::new(password.to_string()),
+    }))
+}
+
+fn unauthorized(e: anyhow::Error) -> actix_web::Error {
+    let response = HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, r#"Basic realm="api", Bearer"#))
+        .finish();
+    InternalError::from_response(e, response).into()
+}
+
+/// A `401` like any other failure, so it does not tell whether the password
+/// was right, with a hint of when to try again.
+fn locked_out(retry_after: Duration) -> actix_web::Error {
+    // Rounded up, so that retrying after this long always succeeds.
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let response = HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, r#"Basic realm="api", Bearer"#))
+        .insert_header((RETRY_AFTER, seconds))
+        .finish();
+    let e = anyhow::anyhow!("Too many failed attempts for the username or client");
+    InternalError::from_response(e, response).into()
+}
@@ -1,5 +1,12 @@
+mod api_token;
+mod lockout;
 mod middleware;
 mod password;
 
-pub use middleware::{reject_anonymous_users, UserId};
-pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use api_token::{generate_api_token, store_api_token, validate_api_token};
+pub use lockout::LoginLockout;
+pub use middleware::{reject_anonymous_users, reject_unauthenticated_api_clients, UserId};
+pub use password::{
+    change_password, validate_credentials, validate_new_password, AuthError, Credentials,
+    PasswordPolicyError,
+};
@@ -15,6 +15,40 @@ pub enum AuthError {
     UnexpectedError(#[from] anyhow::Error),
 }
 
+/// Following OWASP: long enough to resist guessing, short enough that
+/// hashing it cannot be used to tie up the server.
+const MIN_PASSWORD_LENGTH: usize = 12;
+const MAX_PASSWORD_LENGTH: usize = 128;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum PasswordPolicyError {
+    #[error("The new password you entered is too short - passwords must be at least {MIN_PASSWORD_LENGTH} characters long.")]
+    TooShort,
+    #[error("The new password you entered is too long - passwords must be at most {MAX_PASSWORD_LENGTH} characters long.")]
+    TooLong,
+    #[error("The new password cannot be the same as your username.")]
+    SameAsUsername,
+}
+
+/// Checks a new password against the strength rules before it is stored.
+pub fn validate_new_password(
+    username: &str,
+    password: &Secret<String>,
+) -> Result<(), PasswordPolicyError> {
+    let password = password.expose_secret();
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LENGTH {
+        return Err(PasswordPolicyError::TooShort);
+    }
+    if length > MAX_PASSWORD_LENGTH {
+        return Err(PasswordPolicyError::TooLong);
+    }
+    if password == username {
+        return Err(PasswordPolicyError::SameAsUsername);
+    }
+    Ok(())
+}
+
 pub struct Credentials {
     pub username: String,
     pub password: Secret<String>,
@@ -116,3 +150,41 @@ fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, any
 
     Ok(Secret::new(password_hash))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_new_password, PasswordPolicyError};
+    use claims::{assert_err_eq, assert_ok};
+    use secrecy::Secret;
+
+    fn validate(username: &str, password: &str) -> Result<(), PasswordPolicyError> {
+        validate_new_password(username, &Secret::new(password.to_string()))
+    }
+
+    #[test]
+    fn passwords_between_12_and_128_characters_are_accepted() {
+        assert_ok!(validate("admin", &"a".repeat(12)));
+        assert_ok!(validate("admin", &"a".repeat(128)));
+        assert_ok!(validate("admin", &"ü".repeat(12)));
+    }
+
+    #[test]
+    fn passwords_outside_the_length_limits_are_rejected() {
+        assert_err_eq!(
+            validate("admin", &"a".repeat(11)),
+            PasswordPolicyError::TooShort
+        );
+        assert_err_eq!(
+            validate("admin", &"a".repeat(129)),
+            PasswordPolicyError::TooLong
+        );
+    }
+
+    #[test]
+    fn the_username_is_not_a_valid_password() {
+        assert_err_eq!(
+            validate("administrator", "administrator"),
+            PasswordPolicyError::SameAsUsername
+        );
+    }
+}
@@ -5,13 +5,27 @@ use sqlx::{
     ConnectOptions,
 };
 
-use crate::{domain::SubscriberEmail, email_client::EmailClient};
+use crate::{
+    domain::{
+        ConfirmationEmailTemplate, EmailDomainPolicy, NameFormatting, SenderNameTemplate,
+        SubscriberEmail,
+    },
+    email_client::{EmailClient, EmailProvider, PostmarkProvider, RetryPolicy, SesProvider},
+    form::FormWhitespace,
+    idempotency::{IdempotencyScope, KeyReusePolicy},
+    secrets::{ReloadableSecret, SecretSource},
+};
 
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
     pub application: ApplicationSettings,
     pub database: DatabaseSettings,
     pub email_client: EmailClientSettings,
+    pub idempotency: IdempotencySettings,
+    pub subscriptions: SubscriptionSettings,
+    pub newsletter: NewsletterSettings,
+    pub webhooks: WebhookSettings,
+    pub rate_limits: RateLimitSettings,
     pub redis_uri: Secret<String>,
 }
 
@@ -21,7 +35,30 @@ pub struct ApplicationSettings {
     pub port: u16,
     pub host: String,
     pub base_url: String,
-    pub hmac_secret: Secret<String>,
+    /// Signs session and flash message cookies. Read once at startup.
+    pub hmac_secret: SecretSource,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub request_timeout_milliseconds: u64,
+    /// Sent as `Content-Security-Policy` with every admin page.
+    pub admin_content_security_policy: String,
+    /// How long in-flight requests, and the delivery worker's current
+    /// batch, get to finish once the process is asked to stop.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub shutdown_grace_seconds: u64,
+    /// Whether whitespace around submitted form values is trimmed before
+    /// they are validated.
+    #[serde(default)]
+    pub form_whitespace: FormWhitespace,
+}
+
+impl ApplicationSettings {
+    pub fn request_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.request_timeout_milliseconds)
+    }
+
+    pub fn shutdown_grace(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.shutdown_grace_seconds)
+    }
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -33,6 +70,31 @@ pub struct DatabaseSettings {
     pub port: u16,
     pub database_name: String,
     pub require_ssl: bool,
+    #[serde(default)]
+    pub read_only: ReadOnlySettings,
+    /// Apply pending migrations before serving traffic. Startup fails if
+    /// any of them does.
+    #[serde(default)]
+    pub migrate_on_start: bool,
+}
+
+/// How writes are answered while the database only accepts reads, e.g.
+/// while a replica is being promoted during a failover.
+#[derive(serde::Deserialize, Clone, Copy)]
+pub struct ReadOnlySettings {
+    /// Answers such writes with a 503 and a `Retry-After` header.
+    /// Otherwise they fail like any other database error.
+    pub enabled: bool,
+    pub retry_after_seconds: u64,
+}
+
+impl Default for ReadOnlySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            retry_after_seconds: 5,
+        }
+    }
 }
 
 impl DatabaseSettings {
@@ -60,21 +122,201 @@ impl DatabaseSettings {
 
 #[derive(serde::Deserialize, Clone)]
 pub struct EmailClientSettings {
+    /// Which API emails are sent through. `base_url` and `auth_token`
+    /// belong to that provider.
+    #[serde(default)]
+    pub provider: EmailProviderKind,
+    /// Required when `provider` is "ses".
+    #[serde(default)]
+    pub ses: Option<SesSettings>,
     pub base_url: String,
-    pub sender_email: String,
-    pub auth_token: Secret<String>,
+    /// The `From` address of confirmations and other one-off emails.
+    pub transactional_sender_email: String,
+    /// The `From` address of newsletter issues.
+    pub broadcast_sender_email: String,
+    /// Display name shown in front of the broadcast address, e.g.
+    /// "Acme for {{first_name}}". Tokens are filled in for each recipient.
+    #[serde(default)]
+    pub broadcast_sender_name: Option<String>,
+    /// Postmark's server token or SES's secret access key. Picked up
+    /// without a restart when it comes from a file.
+    pub auth_token: SecretSource,
     pub timeout_milliseconds: u64,
+    pub min_tls_version: TlsVersion,
+    /// How many emails the delivery worker sends per API call.
+    /// 1 sends each email on its own, anything larger uses the batch endpoint.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Asks new subscribers to confirm their subscription.
+    #[serde(default)]
+    pub confirmation_template: ConfirmationTemplate,
+    /// Sent to subscribers once they confirm. No welcome email when unset.
+    #[serde(default)]
+    pub welcome_template: Option<WelcomeTemplate>,
+    /// Log emails instead of sending them, e.g. for staging or demos.
+    /// Queues, delivery records and idempotency behave as usual.
+    #[serde(default)]
+    pub test_mode: bool,
+    /// Including the first attempt; 1 disables retries.
+    pub max_attempts: u32,
+    pub retry_base_delay_milliseconds: u64,
+    /// How much randomness to add to each retry delay.
+    pub retry_jitter: RetryJitter,
+    /// Give each newsletter email threading headers derived from its
+    /// issue, so subscribers' replies to an issue are grouped together.
+    #[serde(default)]
+    pub thread_replies: bool,
+    /// Only these recipients are emailed, e.g. the team in staging, so
+    /// that a copy of production data never reaches real subscribers.
+    /// Entries are addresses or `@domain`s. Everyone is emailed when unset.
+    #[serde(default)]
+    pub recipient_allowlist: Option<Vec<String>>,
+}
+
+/// As many as Postmark takes in one call to its batch endpoint.
+fn default_batch_size() -> usize {
+    500
+}
+
+/// `{{confirmation_link}}` is replaced with the subscriber's link and
+/// `{{expiry}}` with a note on when it expires, or nothing if links do not
+/// expire. Both are only available in the bodies. Unknown tokens are
+/// rejected when the configuration is loaded.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct ConfirmationTemplate {
+    /// May use `{{name}}`, `{{first_name}}` and `{{product_name}}`, e.g.
+    /// "Confirm your subscription to {{product_name}}, {{first_name}}".
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+    #[serde(default)]
+    pub product_name: String,
+}
+
+impl Default for ConfirmationTemplate {
+    fn default() -> Self {
+        Self {
+            subject: "Welcome!".into(),
+            html_content: "Welcome to our newsletter!<br />\
+                Click <a href=\"{{confirmation_link}}\">here</a> to confirm your subscription.\
+                {{expiry}}"
+                .into(),
+            text_content: "Welcome to our newsletter!\n\
+                Visit {{confirmation_link}} to confirm your subscription.{{expiry}}"
+                .into(),
+            product_name: String::new(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailProviderKind {
+    #[default]
+    Postmark,
+    Ses,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SesSettings {
+    /// e.g. "eu-west-1". Must match the region of `base_url`.
+    pub region: String,
+    pub access_key_id: String,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct WelcomeTemplate {
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
 }
 
 impl EmailClientSettings {
+    /// A client sending from the transactional address.
     pub fn client(self) -> EmailClient {
-        let sender_email = self.sender().expect("invalid sender email address.");
+        let sender = self
+            .transactional_sender()
+            .expect("invalid transactional sender email address.");
+        self.client_for(sender)
+    }
+
+    /// A client sending from the broadcast address, for newsletter issues.
+    pub fn broadcast_client(self) -> EmailClient {
+        let sender = self
+            .broadcast_sender()
+            .expect("invalid broadcast sender email address.");
+        let sender_name = self
+            .broadcast_sender_name()
+            .expect("invalid broadcast sender name.");
+        let thread_replies = self.thread_replies;
+        self.client_for(sender)
+            .with_sender_name(sender_name)
+            .with_thread_replies(thread_replies)
+    }
+
+    fn client_for(self, sender: SubscriberEmail) -> EmailClient {
         let timeout = self.timeout();
-        EmailClient::new(self.base_url, sender_email, self.auth_token, timeout)
+        let retry_policy = self.retry_policy();
+        let auth_token = ReloadableSecret::new(self.auth_token)
+            .expect("Failed to read the email provider's auth token.");
+        let provider: Box<dyn EmailProvider> = match self.provider {
+            EmailProviderKind::Postmark => Box::new(PostmarkProvider::new(
+                self.base_url,
+                auth_token,
+                timeout,
+                self.min_tls_version.into(),
+            )),
+            EmailProviderKind::Ses => {
+                let ses = self
+                    .ses
+                    .expect("The SES provider needs `email_client.ses` settings.");
+                Box::new(SesProvider::new(
+                    self.base_url,
+                    ses.region,
+                    ses.access_key_id,
+                    auth_token,
+                    timeout,
+                    self.min_tls_version.into(),
+                ))
+            }
+        };
+        EmailClient::with_provider(provider, sender, timeout)
+            .with_test_mode(self.test_mode)
+            .with_retry_policy(retry_policy)
+            .with_recipient_allowlist(self.recipient_allowlist)
     }
 
-    pub fn sender(&self) -> Result<SubscriberEmail, String> {
-        SubscriberEmail::parse(self.sender_email.clone())
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_attempts.max(1),
+            base_delay: std::time::Duration::from_millis(self.retry_base_delay_milliseconds),
+            jitter: self.retry_jitter,
+        }
+    }
+
+    pub fn transactional_sender(&self) -> Result<SubscriberEmail, String> {
+        SubscriberEmail::parse(self.transactional_sender_email.clone())
+    }
+
+    pub fn broadcast_sender(&self) -> Result<SubscriberEmail, String> {
+        SubscriberEmail::parse(self.broadcast_sender_email.clone())
+    }
+
+    pub fn confirmation_template(&self) -> Result<ConfirmationEmailTemplate, String> {
+        let template = self.confirmation_template.clone();
+        ConfirmationEmailTemplate::parse(
+            template.subject,
+            template.html_content,
+            template.text_content,
+            template.product_name,
+        )
+    }
+
+    pub fn broadcast_sender_name(&self) -> Result<Option<SenderNameTemplate>, String> {
+        self.broadcast_sender_name
+            .clone()
+            .map(SenderNameTemplate::parse)
+            .transpose()
     }
 
     pub fn timeout(&self) -> std::time::Duration {
@@ -82,6 +324,296 @@ impl EmailClientSettings {
     }
 }
 
+#[derive(serde::Deserialize, Clone)]
+pub struct IdempotencySettings {
+    /// How long saved responses are kept. Older ones are deleted in the
+    /// background and their keys are processed again as new requests.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub ttl_seconds: u64,
+    /// Whether keys are unique per user or across all users.
+    #[serde(default)]
+    pub scope: IdempotencyScope,
+    /// What to do with a key sent again with different content.
+    #[serde(default)]
+    pub key_reuse: KeyReusePolicy,
+}
+
+impl IdempotencySettings {
+    pub fn ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.ttl_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SubscriptionSettings {
+    pub unknown_token: UnknownTokenResponse,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_tags_per_subscriber: u32,
+    pub normalize_names: bool,
+    pub title_case_names: bool,
+    pub email_domains: EmailDomainSettings,
+    pub confirmation_email_failure: ConfirmationEmailFailurePolicy,
+    /// How long a confirmation is trusted for. An unsubscribed address that
+    /// signs up again within this long of its last confirmation is confirmed
+    /// straight away, otherwise it has to confirm again. 0 always asks.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub resubscribe_grace_seconds: u64,
+    /// Show an optional "why are you leaving?" form after unsubscribing.
+    pub ask_unsubscribe_reason: bool,
+    pub pending_expiry: PendingExpirySettings,
+}
+
+/// Subscribers who never confirm are deleted once they have been pending
+/// for `max_age_seconds`, optionally after telling them their link expired.
+#[derive(serde::Deserialize, Clone)]
+pub struct PendingExpirySettings {
+    pub enabled: bool,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_age_seconds: u64,
+    /// Send a "your confirmation expired, sign up again" email first.
+    pub notify: bool,
+}
+
+impl PendingExpirySettings {
+    pub fn max_age(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.max_age_seconds)
+    }
+
+    /// How long a confirmation link works for, if it expires at all.
+    pub fn confirmation_link_ttl(&self) -> Option<std::time::Duration> {
+        self.enabled.then(|| self.max_age())
+    }
+}
+
+/// What `subscribe` does when the confirmation email cannot be sent.
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfirmationEmailFailurePolicy {
+    /// Roll the subscription back and report the failure.
+    Strict,
+    /// Keep the pending subscription and let the reminder worker resend it.
+    Lenient,
+}
+
+impl SubscriptionSettings {
+    pub fn resubscribe_grace(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.resubscribe_grace_seconds)
+    }
+
+    pub fn name_formatting(&self) -> NameFormatting {
+        NameFormatting {
+            normalize_whitespace: self.normalize_names,
+            title_case: self.title_case_names,
+        }
+    }
+
+    pub fn email_domain_policy(&self) -> EmailDomainPolicy {
+        let domains = self.email_domains.domains.clone();
+        match self.email_domains.mode {
+            EmailDomainMode::Any => EmailDomainPolicy::Any,
+            EmailDomainMode::Allowlist => EmailDomainPolicy::Allowlist(domains),
+            EmailDomainMode::Denylist => EmailDomainPolicy::Denylist(domains),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct EmailDomainSettings {
+    pub mode: EmailDomainMode,
+    #[serde(default)]
+    pub domains: Vec<String>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailDomainMode {
+    Any,
+    Allowlist,
+    Denylist,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct NewsletterSettings {
+    /// Issues with more HTML than this get a warning that they may be clipped.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub clipping_warning_bytes: usize,
+    /// Strip comments and redundant whitespace from an issue's HTML before
+    /// it is sent. The stored issue is left untouched.
+    pub minify_html: bool,
+    pub dead_letter_retry: DeadLetterRetrySettings,
+    /// Used for scheduled sends to subscribers who did not give a timezone.
+    pub default_timezone: String,
+    /// How many issues may be sending at the same time. Issues published
+    /// while all slots are taken wait for one to free up. Unset means no
+    /// limit; zero is rejected.
+    #[serde(default)]
+    pub max_issues_in_flight: Option<std::num::NonZeroU32>,
+}
+
+/// Failed deliveries are tried again every `interval_seconds`, up to
+/// `max_retries` times, before they are given up on for good. This rides
+/// out provider outages that last longer than a single attempt.
+#[derive(serde::Deserialize, Clone)]
+pub struct DeadLetterRetrySettings {
+    pub enabled: bool,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub interval_seconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_retries: i16,
+}
+
+impl DeadLetterRetrySettings {
+    pub fn interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.interval_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct WebhookSettings {
+    /// Called with the subscriber's details whenever a subscription is confirmed.
+    pub subscription_confirmed_url: Option<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: i32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retry_base_delay_milliseconds: u64,
+    /// How much randomness to add to each retry delay, so that webhooks
+    /// that failed together are not all retried at the same moment.
+    pub retry_jitter: RetryJitter,
+    /// Shared with the email provider to sign the notifications it posts to
+    /// `/webhooks/bounces` and `/webhooks/engagement`.
+    pub bounce_signing_secret: Secret<String>,
+}
+
+/// The jitter strategies from AWS's "Exponential Backoff And Jitter".
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryJitter {
+    /// Always wait exactly the backoff delay.
+    None,
+    /// Wait anywhere between zero and the backoff delay.
+    Full,
+    /// Wait at least half the backoff delay, plus up to another half.
+    Equal,
+}
+
+impl RetryJitter {
+    pub fn apply(
+        &self,
+        delay: std::time::Duration,
+        rng: &mut impl rand::Rng,
+    ) -> std::time::Duration {
+        match self {
+            RetryJitter::None => delay,
+            RetryJitter::Full => delay.mul_f64(rng.gen_range(0.0..=1.0)),
+            RetryJitter::Equal => delay / 2 + (delay / 2).mul_f64(rng.gen_range(0.0..=1.0)),
+        }
+    }
+}
+
+impl WebhookSettings {
+    pub fn client(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .timeout(std::time::Duration::from_millis(self.timeout_milliseconds))
+            .build()
+            .unwrap()
+    }
+
+    /// The wait before retrying a webhook that has already failed
+    /// `n_attempts` times: the base delay, doubled after every failure,
+    /// with `retry_jitter` applied.
+    pub fn retry_delay(&self, n_attempts: i32) -> std::time::Duration {
+        let factor = 2u32.saturating_pow(n_attempts.clamp(0, 16) as u32);
+        let backoff = std::time::Duration::from_millis(self.retry_base_delay_milliseconds) * factor;
+        self.retry_jitter.apply(backoff, &mut rand::thread_rng())
+    }
+}
+
+/// Independent limits for each group of public routes.
+#[derive(serde::Deserialize, Clone)]
+pub struct RateLimitSettings {
+    pub subscriptions: RateLimit,
+    pub login: RateLimit,
+    pub confirm: RateLimit,
+    /// Unsubscribe links and the reason form, which check a subscriber's
+    /// token like confirmation links do.
+    pub validate: RateLimit,
+    /// Resending issues and replaying deliveries from the admin panel, each
+    /// of which can queue many emails at once.
+    pub resend: RateLimit,
+    /// Every request to the API, whichever way the client authenticates.
+    pub api: RateLimit,
+    /// Calls to the email provider's webhooks, signed or not. Generous, as
+    /// the provider may report a burst of bounces after a large send.
+    pub webhooks: RateLimit,
+    /// Shared by every sign up rather than counted per client. Confirmation
+    /// emails over the limit are left to the reminder worker.
+    pub confirmation_emails: RateLimit,
+    pub login_lockout: LoginLockoutSettings,
+}
+
+/// Failed logins allowed within a window before further attempts are
+/// refused until it ends, counted per username and per client.
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+pub struct LoginLockoutSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_username: u32,
+    /// Higher than the per-username threshold, as several users may share
+    /// an address.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_client: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+}
+
+impl LoginLockoutSettings {
+    pub fn window(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.window_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+pub struct RateLimit {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_requests: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+}
+
+impl RateLimit {
+    pub fn window(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.window_seconds)
+    }
+}
+
+/// What to show when a confirmation link carries a token we don't know.
+/// Either way the response does not reveal whether the token ever existed.
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownTokenResponse {
+    NeutralPage,
+    Redirect(String),
+}
+
+/// The oldest TLS version the email client will negotiate with the provider.
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+impl From<TlsVersion> for reqwest::tls::Version {
+    fn from(value: TlsVersion) -> Self {
+        match value {
+            TlsVersion::Tls12 => reqwest::tls::Version::TLS_1_2,
+            TlsVersion::Tls13 => reqwest::tls::Version::TLS_1_3,
+        }
+    }
+}
+
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     let base_path = std::env::current_dir().expect("Failed to determine the current directory.");
     let configuration_directory = base_path.join("configuration");
@@ -135,3 +667,37 @@ impl TryFrom<String> for Environment {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::RetryJitter;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::time::Duration;
+
+    const DELAY: Duration = Duration::from_secs(8);
+
+    fn delays(jitter: RetryJitter) -> Vec<Duration> {
+        let mut rng = StdRng::seed_from_u64(42);
+        (0..1000).map(|_| jitter.apply(DELAY, &mut rng)).collect()
+    }
+
+    #[test]
+    fn no_jitter_keeps_the_backoff_delay() {
+        assert!(delays(RetryJitter::None).iter().all(|d| *d == DELAY));
+    }
+
+    #[test]
+    fn full_jitter_waits_up_to_the_backoff_delay() {
+        let delays = delays(RetryJitter::Full);
+        assert!(delays.iter().all(|d| *d <= DELAY));
+        assert!(delays.iter().any(|d| *d < DELAY / 2));
+    }
+
+    #[test]
+    fn equal_jitter_waits_at_least_half_the_backoff_delay() {
+        let delays = delays(RetryJitter::Equal);
+        assert!(delays.iter().all(|d| *d >= DELAY / 2 && *d <= DELAY));
+        assert!(delays.iter().any(|d| *d != delays[0]));
+    }
+}
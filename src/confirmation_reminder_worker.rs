@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use tracing::field::display;
+use tracing::Span;
+use uuid::Uuid;
+
+use crate::configuration::Settings;
+use crate::domain::{ConfirmationEmailTemplate, SubscriberEmail};
+use crate::email_client::EmailClient;
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::rate_limit::RateLimitDecision;
+use crate::routes::{render_confirmation_email, send_confirmation_email};
+use crate::startup::{get_connection_pool, ConfirmationEmailLimiter};
+
+/// How long to wait before trying a failed confirmation email again.
+const RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// `limiter` is the budget sign ups send their confirmation emails from, so
+/// emails queued during a spike trickle out rather than all going at once.
+pub async fn run_reminder_worker_until_stopped(
+    configuration: Settings,
+    limiter: ConfirmationEmailLimiter,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    let template = configuration
+        .email_client
+        .confirmation_template()
+        .map_err(|e| anyhow::anyhow!("Invalid confirmation email template: {e}"))?;
+    let email_client = configuration.email_client.client();
+    let link_ttl = configuration
+        .subscriptions
+        .pending_expiry
+        .confirmation_link_ttl();
+    worker_loop(
+        connection_pool,
+        email_client,
+        template,
+        configuration.application.base_url,
+        link_ttl,
+        limiter,
+    )
+    .await
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    template: ConfirmationEmailTemplate,
+    base_url: String,
+    link_ttl: Option<Duration>,
+    limiter: ConfirmationEmailLimiter,
+) -> Result<(), anyhow::Error> {
+    loop {
+        if let Ok(RateLimitDecision::Limited { retry_after }) = limiter.take().await {
+            tokio::time::sleep(retry_after).await;
+            continue;
+        }
+        match try_resend_confirmation(&pool, &email_client, &template, &base_url, link_ttl).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(subscriber_id=tracing::field::Empty),
+    err
+)]
+pub async fn try_resend_confirmation(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    template: &ConfirmationEmailTemplate,
+    base_url: &str,
+    link_ttl: Option<Duration>,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some((transaction, task)) = dequeue_task(pool).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current().record("subscriber_id", display(task.subscriber_id));
+    // The link expires relative to the original sign up, not to this resend.
+    let pending_for = (Utc::now() - task.subscribed_at)
+        .to_std()
+        .unwrap_or_default();
+    let link_ttl = link_ttl.map(|ttl| ttl.saturating_sub(pending_for));
+    let email = match render_confirmation_email(
+        template,
+        &task.name,
+        base_url,
+        &task.subscription_token,
+        link_ttl,
+    ) {
+        Ok(email) => email,
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                "Skipping a confirmation resend - the email could not be rendered.",
+            );
+            return clear_flag(transaction, task.subscriber_id).await;
+        }
+    };
+    let outcome = match SubscriberEmail::parse(task.email) {
+        Ok(recipient) => send_confirmation_email(email_client, &email, &recipient, None).await,
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                "Skipping a confirmation resend - the stored email address is invalid.",
+            );
+            return clear_flag(transaction, task.subscriber_id).await;
+        }
+    };
+    match outcome {
+        Ok(()) => clear_flag(transaction, task.subscriber_id).await,
+        Err(e) => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                error.kind = e.kind(),
+                "Failed to resend a confirmation email.",
+            );
+            postpone(transaction, task.subscriber_id).await
+        }
+    }
+}
+
+struct ReminderTask {
+    subscriber_id: Uuid,
+    email: String,
+    name: String,
+    subscription_token: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_task(
+    pool: &PgPool,
+) -> Result<Option<(PgTransaction, ReminderTask)>, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let task = sqlx::query_as!(
+        ReminderTask,
+        r#"
+        SELECT s.id AS subscriber_id, s.email, s.name, t.subscription_token, s.subscribed_at
+        FROM subscriptions s
+        JOIN subscription_tokens t ON t.subscriber_id = s.id
+        WHERE s.status = 'pending_confirmation' AND s.resend_confirmation_at <= now()
+        ORDER BY s.resend_confirmation_at
+        FOR UPDATE OF s
+        SKIP LOCKED
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    Ok(task.map(|t| (transaction, t)))
+}
+
+#[tracing::instrument(skip_all)]
+async fn clear_flag(
+    mut transaction: PgTransaction,
+    subscriber_id: Uuid,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let query = sqlx::query!(
+        r#"UPDATE subscriptions SET resend_confirmation_at = NULL WHERE id = $1"#,
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+#[tracing::instrument(skip_all)]
+async fn postpone(
+    mut transaction: PgTransaction,
+    subscriber_id: Uuid,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET resend_confirmation_at = now() + make_interval(secs => $2)
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        RETRY_DELAY.as_secs_f64()
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tracing::Span;
+
+use crate::configuration::{DeadLetterRetrySettings, Settings};
+use crate::issue_delivery_worker::{DeliveryStatus, ExecutionOutcome};
+use crate::startup::get_connection_pool;
+
+/// How many failed deliveries are put back in the queue per round.
+const BATCH_SIZE: i64 = 100;
+
+pub async fn run_dead_letter_retry_worker_until_stopped(
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    worker_loop(connection_pool, configuration.newsletter.dead_letter_retry).await
+}
+
+async fn worker_loop(pool: PgPool, settings: DeadLetterRetrySettings) -> Result<(), anyhow::Error> {
+    loop {
+        match try_requeue_dead_letters(&pool, &settings).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+/// Puts failed deliveries whose last attempt was at least one retry
+/// interval ago back in the queue, unless they have already been retried
+/// `max_retries` times. Only transient failures are retried: an address the
+/// provider rejected will be rejected again. The issue delivery worker then
+/// sends them as usual.
+#[tracing::instrument(skip_all, fields(n_requeued=tracing::field::Empty), err)]
+pub async fn try_requeue_dead_letters(
+    pool: &PgPool,
+    settings: &DeadLetterRetrySettings,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    if !settings.enabled {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    }
+    let n_requeued = sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET
+            status = $1,
+            processed_at = NULL,
+            auto_retries = auto_retries + 1
+        WHERE delivery_id IN (
+            SELECT delivery_id
+            FROM issue_delivery_queue
+            WHERE
+                status = $2 AND
+                transient_failure AND
+                auto_retries < $3 AND
+                processed_at <= now() - make_interval(secs => $4)
+            FOR UPDATE
+            SKIP LOCKED
+            LIMIT $5
+        )
+        "#,
+        DeliveryStatus::Pending.as_str(),
+        DeliveryStatus::Failed.as_str(),
+        settings.max_retries,
+        settings.interval().as_secs_f64(),
+        BATCH_SIZE
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Span::current().record("n_requeued", n_requeued);
+
+    if n_requeued == 0 {
+        Ok(ExecutionOutcome::EmptyQueue)
+    } else {
+        Ok(ExecutionOutcome::TaskCompleted)
+    }
+}
@@ -0,0 +1,345 @@
+use htmlescape::encode_minimal;
+
+/// The email asking new subscribers to confirm, checked when the
+/// configuration is loaded. The bodies must link to `{{confirmation_link}}`
+/// and may use `{{expiry}}`, a note on when the link expires. Every part may
+/// use `{{product_name}}`, `{{name}}` and `{{first_name}}`.
+#[derive(Debug, Clone)]
+pub struct ConfirmationEmailTemplate {
+    subject: String,
+    html_content: String,
+    text_content: String,
+    product_name: String,
+}
+
+/// A confirmation email with its tokens filled in.
+#[derive(Debug)]
+pub struct ConfirmationEmail {
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+}
+
+const SUBJECT_TOKENS: [&str; 3] = ["{{product_name}}", "{{name}}", "{{first_name}}"];
+const BODY_TOKENS: [&str; 5] = [
+    "{{confirmation_link}}",
+    "{{expiry}}",
+    "{{product_name}}",
+    "{{name}}",
+    "{{first_name}}",
+];
+
+impl ConfirmationEmailTemplate {
+    pub fn parse(
+        subject: String,
+        html_content: String,
+        text_content: String,
+        product_name: String,
+    ) -> Result<ConfirmationEmailTemplate, String> {
+        check_tokens("subject", &subject, &SUBJECT_TOKENS)?;
+        check_tokens("HTML body", &html_content, &BODY_TOKENS)?;
+        check_tokens("plain text body", &text_content, &BODY_TOKENS)?;
+        // The literal parts of the subject must be a valid header on their
+        // own, whatever the recipient's name turns out to be.
+        if subject.trim().is_empty() || subject.chars().any(char::is_control) {
+            return Err(format!(
+                "{subject:?} is not a valid confirmation email subject."
+            ));
+        }
+        if product_name.chars().any(char::is_control) {
+            return Err(format!("{product_name:?} is not a valid product name."));
+        }
+        Ok(Self {
+            subject,
+            html_content,
+            text_content,
+            product_name,
+        })
+    }
+
+    /// Fills in the recipient's details, escaping them in the HTML body.
+    /// Fails if the subject is not a valid header, e.g. because it is only
+    /// the recipient's name and they left it blank.
+    pub fn render(
+        &self,
+        recipient_name: &str,
+        confirmation_link: &str,
+        link_ttl: Option<std::time::Duration>,
+    ) -> Result<ConfirmationEmail, String> {
+        // Names may contain line breaks, which must not end up in a header.
+        let name: String = recipient_name.chars().filter(|c| !c.is_control()).collect();
+        let first_name = name.split_whitespace().next().unwrap_or_default();
+        let expires_in = link_ttl.map(describe_duration);
+
+        let subject = fill(
+            &self.subject,
+            &[
+                ("{{product_name}}", &self.product_name),
+                ("{{name}}", &name),
+                ("{{first_name}}", first_name),
+            ],
+        );
+        if subject.trim().is_empty() || subject.chars().any(char::is_control) {
+            return Err(format!(
+                "{subject:?} is not a valid confirmation email subject."
+            ));
+        }
+
+        let html_expiry = expires_in
+            .as_ref()
+            .map(|d| format!("<br />This link expires in {d}."))
+            .unwrap_or_default();
+        let html_content = fill(
+            &self.html_content,
+            &[
+                ("{{confirmation_link}}", &encode_minimal(confirmation_link)),
+                ("{{expiry}}", &html_expiry),
+                ("{{product_name}}", &encode_minimal(&self.product_name)),
+                ("{{name}}", &encode_minimal(&name)),
+                ("{{first_name}}", &encode_minimal(first_name)),
+            ],
+        );
+
+        let text_expiry = expires_in
+            .as_ref()
+            .map(|d| format!("\nThis link expires in {d}."))
+            .unwrap_or_default();
+        let text_content = fill(
+            &self.text_content,
+            &[
+                ("{{confirmation_link}}", confirmation_link),
+                ("{{expiry}}", &text_expiry),
+                ("{{product_name}}", &self.product_name),
+                ("{{name}}", &name),
+                ("{{first_name}}", first_name),
+            ],
+        );
+
+        Ok(ConfirmationEmail {
+            subject,
+            html_content,
+            text_content,
+        })
+    }
+}
+
+/// Rejects tokens other than `tokens`, and bodies without a link.
+fn check_tokens(part: &str, content: &str, tokens: &[&str]) -> Result<(), String> {
+    let mut without_tokens = content.to_string();
+    for token in tokens {
+        without_tokens = without_tokens.replace(token, "");
+    }
+    if without_tokens.contains("{{") || without_tokens.contains("}}") {
+        return Err(format!(
+            "The confirmation email's {part} uses an unknown token. Only {} are supported.",
+            tokens.join(", ")
+        ));
+    }
+    if tokens.contains(&"{{confirmation_link}}") && !content.contains("{{confirmation_link}}") {
+        return Err(format!(
+            "The confirmation email's {part} has no {{{{confirmation_link}}}}."
+        ));
+    }
+    Ok(())
+}
+
+/// Replaces every token in one pass, so that a value is never read as a
+/// token itself.
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match values.iter().find(|(token, _)| rest.starts_with(token)) {
+            Some((token, value)) => {
+                rendered.push_str(value);
+                rest = &rest[token.len()..];
+            }
+            None => {
+                rendered.push_str("{{");
+                rest = &rest[2..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// A rough, rounded down length of time, e.g. "48 hours" or "14 days".
+fn describe_duration(duration: std::time::Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    let hours = minutes / 60;
+    let days = hours / 24;
+    let (n, unit) = if hours > 48 {
+        (days, "day")
+    } else if minutes > 120 {
+        (hours, "hour")
+    } else {
+        (minutes, "minute")
+    };
+    if n == 1 {
+        format!("1 {unit}")
+    } else {
+        format!("{n} {unit}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use std::time::Duration;
+
+    use super::{describe_duration, ConfirmationEmailTemplate};
+
+    fn template(
+        subject: &str,
+        html: &str,
+        text: &str,
+    ) -> Result<ConfirmationEmailTemplate, String> {
+        ConfirmationEmailTemplate::parse(subject.into(), html.into(), text.into(), "Acme".into())
+    }
+
+    #[test]
+    fn unknown_tokens_are_rejected() {
+        assert_err!(template(
+            "Hi {{email}}",
+            "{{confirmation_link}}",
+            "{{confirmation_link}}"
+        ));
+        assert_err!(template(
+            "Hi",
+            "{{confirmation_link}} {{unsubscribe_link}}",
+            "{{confirmation_link}}"
+        ));
+        assert_err!(template(
+            "Hi {{first_name",
+            "{{confirmation_link}}",
+            "{{confirmation_link}}"
+        ));
+        // The link does not belong in a header.
+        assert_err!(template(
+            "{{confirmation_link}}",
+            "{{confirmation_link}}",
+            "{{confirmation_link}}"
+        ));
+    }
+
+    #[test]
+    fn bodies_must_link_to_the_confirmation() {
+        assert_err!(template("Hi", "Welcome!", "{{confirmation_link}}"));
+        assert_err!(template("Hi", "{{confirmation_link}}", "Welcome!"));
+        assert_ok!(template(
+            "Hi",
+            "{{confirmation_link}}",
+            "{{confirmation_link}}"
+        ));
+    }
+
+    #[test]
+    fn subjects_with_line_breaks_are_rejected() {
+        assert_err!(template(
+            "Hi\r\nBcc: {{name}}",
+            "{{confirmation_link}}",
+            "{{confirmation_link}}"
+        ));
+    }
+
+    #[test]
+    fn the_expiry_is_markup_only_in_the_html_body() {
+        let template = template(
+            "Hi",
+            "<a href=\"{{confirmation_link}}\">Confirm</a>{{expiry}}",
+            "Visit {{confirmation_link}}.{{expiry}}",
+        )
+        .unwrap();
+
+        let email = template
+            .render(
+                "Jane",
+                "https://example.com/confirm?t=abc",
+                Some(Duration::from_secs(48 * 3600)),
+            )
+            .unwrap();
+
+        assert_eq!(
+            email.html_content,
+            "<a href=\"https://example.com/confirm?t=abc\">Confirm</a>\
+            <br />This link expires in 48 hours."
+        );
+        assert_eq!(
+            email.text_content,
+            "Visit https://example.com/confirm?t=abc.\nThis link expires in 48 hours."
+        );
+    }
+
+    #[test]
+    fn names_are_escaped_in_the_html_body_only() {
+        let template = template(
+            "Hi {{first_name}}",
+            "<p>Hi {{name}}</p>{{confirmation_link}}",
+            "Hi {{name}} {{confirmation_link}}",
+        )
+        .unwrap();
+
+        let email = template.render("Jane & Joe", "link", None).unwrap();
+
+        assert_eq!(email.subject, "Hi Jane");
+        assert_eq!(email.html_content, "<p>Hi Jane &amp; Joe</p>link");
+        assert_eq!(email.text_content, "Hi Jane & Joe link");
+    }
+
+    #[test]
+    fn values_are_not_read_as_tokens() {
+        let template = template(
+            "Hi {{name}}",
+            "{{confirmation_link}}",
+            "{{name}} {{confirmation_link}}",
+        )
+        .unwrap();
+
+        let email = template
+            .render("{{confirmation_link}}", "link", None)
+            .unwrap();
+
+        assert_eq!(email.subject, "Hi {{confirmation_link}}");
+        assert_eq!(email.text_content, "{{confirmation_link}} link");
+    }
+
+    #[test]
+    fn a_subject_left_blank_by_the_recipient_is_rejected() {
+        let template = template(
+            "{{first_name}}",
+            "{{confirmation_link}}",
+            "{{confirmation_link}}",
+        )
+        .unwrap();
+
+        assert_err!(template.render("\n", "link", None));
+    }
+
+    #[test]
+    fn durations_are_described_in_the_largest_sensible_unit() {
+        assert_eq!(
+            describe_duration(Duration::from_secs(14 * 24 * 3600)),
+            "14 days"
+        );
+        assert_eq!(
+            describe_duration(Duration::from_secs(48 * 3600)),
+            "48 hours"
+        );
+        assert_eq!(
+            describe_duration(Duration::from_secs(90 * 60)),
+            "90 minutes"
+        );
+        assert_eq!(describe_duration(Duration::from_secs(60)), "1 minute");
+    }
+
+    #[test]
+    fn durations_are_rounded_down() {
+        assert_eq!(
+            describe_duration(Duration::from_secs(3 * 24 * 3600 - 1)),
+            "2 days"
+        );
+    }
+}
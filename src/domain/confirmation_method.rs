@@ -0,0 +1,28 @@
+/// How a subscriber confirmed their address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfirmationMethod {
+    /// Followed the link in the confirmation email.
+    Link,
+    /// Imported by an admin from a list that was confirmed elsewhere.
+    Import,
+}
+
+impl ConfirmationMethod {
+    pub const ALL: [ConfirmationMethod; 2] = [ConfirmationMethod::Link, ConfirmationMethod::Import];
+
+    /// How the method is stored.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfirmationMethod::Link => "link",
+            ConfirmationMethod::Import => "import",
+        }
+    }
+
+    /// How the method is shown to admins.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConfirmationMethod::Link => "Confirmation link",
+            ConfirmationMethod::Import => "Imported",
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use uuid::Uuid;
+
+use super::{SubscriberEmail, SubscriberName};
+
+/// A subscriber who has confirmed their subscription and can be sent issues.
+#[derive(Debug)]
+pub struct ConfirmedSubscriber {
+    pub id: Uuid,
+    pub name: SubscriberName,
+    pub email: SubscriberEmail,
+}
+
+impl ConfirmedSubscriber {
+    pub fn parse(id: Uuid, name: String, email: String) -> Result<Self, String> {
+        Ok(Self {
+            id,
+            name: SubscriberName::parse(name)?,
+            email: SubscriberEmail::parse(email)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::ConfirmedSubscriber;
+    use claims::assert_err;
+    use uuid::Uuid;
+
+    #[test]
+    fn a_confirmed_subscriber_carries_its_id_name_and_email() {
+        let id = Uuid::new_v4();
+        let subscriber =
+            ConfirmedSubscriber::parse(id, "Ursula".into(), "ursula@example.com".into()).unwrap();
+        assert_eq!(subscriber.id, id);
+        assert_eq!(subscriber.name.as_ref(), "Ursula");
+        assert_eq!(subscriber.email.as_ref(), "ursula@example.com");
+    }
+
+    #[test]
+    fn invalid_stored_details_are_rejected() {
+        let id = Uuid::new_v4();
+        assert_err!(ConfirmedSubscriber::parse(
+            id,
+            "Ursula".into(),
+            "not-an-email".into()
+        ));
+        assert_err!(ConfirmedSubscriber::parse(
+            id,
+            "".into(),
+            "ursula@example.com".into()
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+use crate::domain::SubscriberEmail;
+
+/// Which email domains may subscribe.
+/// Domains are matched case-insensitively against everything after the `@`.
+#[derive(Debug, Clone, Default)]
+pub enum EmailDomainPolicy {
+    #[default]
+    Any,
+    /// Only the listed domains may subscribe, e.g. for a company newsletter.
+    Allowlist(Vec<String>),
+    /// Every domain except the listed ones may subscribe.
+    Denylist(Vec<String>),
+}
+
+impl EmailDomainPolicy {
+    pub fn check(&self, email: &SubscriberEmail) -> Result<(), String> {
+        let domain = email.domain().to_lowercase();
+        let listed = |domains: &[String]| domains.iter().any(|d| d.eq_ignore_ascii_case(&domain));
+        match self {
+            EmailDomainPolicy::Any => Ok(()),
+            EmailDomainPolicy::Allowlist(domains) if !listed(domains) => Err(format!(
+                "Subscriptions are restricted to approved domains and {domain} is not one of them."
+            )),
+            EmailDomainPolicy::Denylist(domains) if listed(domains) => {
+                Err(format!("Subscriptions from {domain} are not accepted."))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{EmailDomainPolicy, SubscriberEmail};
+    use claims::{assert_err, assert_ok};
+
+    fn email(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn any_domain_is_accepted_by_default() {
+        assert_ok!(EmailDomainPolicy::default().check(&email("ursula@example.com")));
+    }
+
+    #[test]
+    fn an_allowlist_only_accepts_listed_domains() {
+        let policy = EmailDomainPolicy::Allowlist(vec!["example.com".into()]);
+        assert_ok!(policy.check(&email("ursula@Example.COM")));
+        assert_err!(policy.check(&email("ursula@gmail.com")));
+    }
+
+    #[test]
+    fn a_denylist_rejects_listed_domains() {
+        let policy = EmailDomainPolicy::Denylist(vec!["mailinator.com".into()]);
+        assert_err!(policy.check(&email("ursula@mailinator.com")));
+        assert_ok!(policy.check(&email("ursula@example.com")));
+    }
+}
@@ -1,7 +1,23 @@
+mod confirmation_email_template;
+mod confirmation_method;
+mod confirmed_subscriber;
+mod email_domain_policy;
 mod new_subscriber;
+mod newsletter_content;
+mod sender_name;
 mod subscriber_email;
 mod subscriber_name;
+mod subscriber_tag;
+mod unsubscribe_reason;
 
+pub use confirmation_email_template::{ConfirmationEmail, ConfirmationEmailTemplate};
+pub use confirmation_method::ConfirmationMethod;
+pub use confirmed_subscriber::ConfirmedSubscriber;
+pub use email_domain_policy::EmailDomainPolicy;
 pub use new_subscriber::NewSubscriber;
+pub use newsletter_content::{ContentFormat, NewsletterContent};
+pub use sender_name::{SenderName, SenderNameTemplate};
 pub use subscriber_email::SubscriberEmail;
-pub use subscriber_name::SubscriberName;
+pub use subscriber_name::{NameFormatting, SubscriberName};
+pub use subscriber_tag::SubscriberTag;
+pub use unsubscribe_reason::UnsubscribeReason;
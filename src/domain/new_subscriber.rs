@@ -3,4 +3,6 @@ use super::{SubscriberEmail, SubscriberName};
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    /// An IANA timezone name, e.g. `Europe/Rome`, if the subscriber gave one.
+    pub timezone: Option<String>,
 }
@@ -0,0 +1,246 @@
+use std::fmt::Write;
+
+use htmlescape::encode_minimal;
+use pulldown_cmark::{html, Event, LinkType, Parser, Tag};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Subjects longer than this are truncated by most email clients anyway.
+const MAX_TITLE_LENGTH: usize = 256;
+/// Each body is capped well above Gmail's clipping threshold, to keep
+/// mistakes like pasting a base64 image out of the queue.
+const MAX_BODY_BYTES: usize = 1_000_000;
+
+/// How the issue's content was written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentFormat {
+    /// Separate plain text and HTML bodies.
+    Html,
+    /// Plain text only. The HTML body is generated from the text.
+    PlainText,
+    /// Markdown. Both bodies are rendered from it.
+    Markdown,
+}
+
+/// The title and bodies of a newsletter issue, validated once so that
+/// every path that stores or sends an issue applies the same rules.
+#[derive(Debug, Clone)]
+pub struct NewsletterContent {
+    title: String,
+    text: String,
+    html: String,
+    format: ContentFormat,
+}
+
+impl NewsletterContent {
+    /// Large enough for a request carrying both bodies at their limit, even
+    /// if every byte is percent-encoded, along with the other fields.
+    pub const MAX_REQUEST_BYTES: usize = 2 * 3 * MAX_BODY_BYTES + 64 * 1024;
+
+    /// The title becomes the email subject, so it must fit on a single
+    /// line. The plain text body is required, the HTML body is optional.
+    pub fn parse(title: String, text: String, html: String) -> Result<Self, String> {
+        let title = title.trim().to_string();
+        if title.is_empty() {
+            return Err("The title cannot be empty.".into());
+        }
+        if title.graphemes(true).count() > MAX_TITLE_LENGTH {
+            return Err(format!(
+                "The title cannot be longer than {MAX_TITLE_LENGTH} characters."
+            ));
+        }
+        if title.chars().any(char::is_control) {
+            return Err("The title cannot contain line breaks or control characters.".into());
+        }
+        if text.trim().is_empty() {
+            return Err("The plain text content cannot be empty.".into());
+        }
+        if text.len() > MAX_BODY_BYTES || html.len() > MAX_BODY_BYTES {
+            return Err(format!(
+                "The content cannot be larger than {MAX_BODY_BYTES} bytes."
+            ));
+        }
+
+        let (html, format) = if html.trim().is_empty() {
+            (text_to_html(&text), ContentFormat::PlainText)
+        } else {
+            (html, ContentFormat::Html)
+        };
+        Ok(Self {
+            title,
+            text,
+            html,
+            format,
+        })
+    }
+
+    /// Renders the HTML body from Markdown, stripping anything unsafe such
+    /// as scripts, and derives the plain text body from the same source.
+    pub fn parse_markdown(title: String, markdown: String) -> Result<Self, String> {
+        if markdown.trim().is_empty() {
+            return Err("The Markdown content cannot be empty.".into());
+        }
+        if markdown.len() > MAX_BODY_BYTES {
+            return Err(format!(
+                "The content cannot be larger than {MAX_BODY_BYTES} bytes."
+            ));
+        }
+        let html = markdown_to_html(&markdown);
+        let text = markdown_to_text(&markdown);
+        let content = Self::parse(title, text, html)?;
+        Ok(Self {
+            format: ContentFormat::Markdown,
+            ..content
+        })
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn html(&self) -> &str {
+        &self.html
+    }
+
+    pub fn format(&self) -> ContentFormat {
+        self.format
+    }
+}
+
+/// One paragraph per block of text separated by a blank line.
+fn text_to_html(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("<p>{}</p>", encode_minimal(p).replace('\n', "<br/>")))
+        .collect()
+}
+
+fn markdown_to_html(markdown: &str) -> String {
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, Parser::new(markdown));
+    ammonia::clean(&rendered)
+}
+
+/// Keeps the words and the block structure, drops the formatting. Link
+/// targets are kept next to their text so they can still be followed.
+fn markdown_to_text(markdown: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::Start(Tag::Item) => text.push_str("- "),
+            Event::End(Tag::Paragraph | Tag::Heading(..) | Tag::CodeBlock(_)) => {
+                text.truncate(text.trim_end().len());
+                text.push_str("\n\n");
+            }
+            Event::End(Tag::Item) => {
+                text.truncate(text.trim_end().len());
+                text.push('\n');
+            }
+            Event::End(Tag::Link(link_type, url, _)) if link_type != LinkType::Autolink => {
+                let _ = write!(text, " ({url})");
+            }
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{ContentFormat, NewsletterContent};
+    use claims::{assert_err, assert_ok};
+
+    fn parse(title: &str, text: &str, html: &str) -> Result<NewsletterContent, String> {
+        NewsletterContent::parse(title.into(), text.into(), html.into())
+    }
+
+    fn parse_markdown(title: &str, markdown: &str) -> Result<NewsletterContent, String> {
+        NewsletterContent::parse_markdown(title.into(), markdown.into())
+    }
+
+    #[test]
+    fn a_missing_title_is_rejected() {
+        assert_err!(parse("", "Body", "<p>Body</p>"));
+        assert_err!(parse("   ", "Body", "<p>Body</p>"));
+    }
+
+    #[test]
+    fn missing_content_is_rejected() {
+        assert_err!(parse("Title", "", ""));
+        assert_err!(parse("Title", "  ", "<p>Body</p>"));
+    }
+
+    #[test]
+    fn a_title_with_a_line_break_is_rejected() {
+        assert_err!(parse("Title\r\nBcc: evil@example.com", "Body", ""));
+    }
+
+    #[test]
+    fn a_title_longer_than_256_graphemes_is_rejected() {
+        assert_ok!(parse(&"a".repeat(256), "Body", ""));
+        assert_err!(parse(&"a".repeat(257), "Body", ""));
+    }
+
+    #[test]
+    fn an_oversized_body_is_rejected() {
+        assert_err!(parse("Title", "Body", &"a".repeat(1_000_001)));
+    }
+
+    #[test]
+    fn html_content_is_kept_as_is() {
+        let content = parse(" Title ", "Body", "<p>Body</p>").unwrap();
+        assert_eq!(content.title(), "Title");
+        assert_eq!(content.html(), "<p>Body</p>");
+        assert_eq!(content.format(), ContentFormat::Html);
+    }
+
+    #[test]
+    fn plain_text_content_gets_an_escaped_html_body() {
+        let content = parse("Title", "Fish & chips\nto go\n\nBye", "").unwrap();
+        assert_eq!(content.format(), ContentFormat::PlainText);
+        assert_eq!(
+            content.html(),
+            "<p>Fish &amp; chips<br/>to go</p><p>Bye</p>"
+        );
+    }
+
+    #[test]
+    fn empty_markdown_is_rejected() {
+        assert_err!(parse_markdown("Title", ""));
+        assert_err!(parse_markdown("Title", " \n "));
+    }
+
+    #[test]
+    fn markdown_is_rendered_to_html_and_text() {
+        let content = parse_markdown(
+            "Title",
+            "# Hello\n\nSome **bold** news, see [the site](https://example.com).\n\n* one\n* two",
+        )
+        .unwrap();
+        assert_eq!(content.format(), ContentFormat::Markdown);
+        assert!(content.html().contains("<h1>Hello</h1>"));
+        assert!(content.html().contains("<strong>bold</strong>"));
+        assert_eq!(
+            content.text(),
+            "Hello\n\nSome bold news, see the site (https://example.com).\n\n- one\n- two"
+        );
+    }
+
+    #[test]
+    fn scripts_are_stripped_from_rendered_markdown() {
+        let content = parse_markdown(
+            "Title",
+            "Hi <script>alert(1)</script>\n\n[click](javascript:alert(1))",
+        )
+        .unwrap();
+        assert!(!content.html().contains("<script"));
+        assert!(!content.html().contains("javascript:"));
+    }
+}
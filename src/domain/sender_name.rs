@@ -0,0 +1,115 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::SubscriberName;
+
+/// A From display name that may mention the recipient, e.g.
+/// `"Acme for {{first_name}}"`. Supported tokens are `{{first_name}}` and
+/// `{{name}}`.
+#[derive(Debug, Clone)]
+pub struct SenderNameTemplate(String);
+
+/// A display name that is safe to put in front of the sender's address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SenderName(String);
+
+const TOKENS: [&str; 2] = ["{{first_name}}", "{{name}}"];
+
+impl SenderNameTemplate {
+    pub fn parse(s: String) -> Result<SenderNameTemplate, String> {
+        let mut without_tokens = s.clone();
+        for token in TOKENS {
+            without_tokens = without_tokens.replace(token, "");
+        }
+        if without_tokens.contains("{{") || without_tokens.contains("}}") {
+            return Err(format!(
+                "{s} uses an unknown token. Only {} are supported.",
+                TOKENS.join(" and ")
+            ));
+        }
+        // The literal parts must be valid on their own, whatever the
+        // recipient's name turns out to be.
+        if !without_tokens.trim().is_empty() {
+            SenderName::parse(without_tokens)?;
+        }
+        Ok(Self(s))
+    }
+
+    /// Fills in the recipient's details. Fails if the result is not a valid
+    /// display name, e.g. because the recipient's name contains a line break.
+    pub fn render(&self, recipient: &SubscriberName) -> Result<SenderName, String> {
+        let name = recipient.as_ref();
+        let first_name = name.split_whitespace().next().unwrap_or_default();
+        let rendered = self
+            .0
+            .replace("{{first_name}}", first_name)
+            .replace("{{name}}", name);
+        SenderName::parse(rendered)
+    }
+}
+
+impl SenderName {
+    pub fn parse(s: String) -> Result<SenderName, String> {
+        let is_empty_or_whitespace = s.trim().is_empty();
+        let is_too_long = s.graphemes(true).count() > 128;
+        // Line breaks would let a name inject extra headers, quotes and
+        // angle brackets would break out of the quoted display name.
+        let contains_forbidden_characters = s
+            .chars()
+            .any(|c| c.is_control() || ['"', '\\', '<', '>'].contains(&c));
+        if is_empty_or_whitespace || is_too_long || contains_forbidden_characters {
+            Err(format!("{s:?} is not a valid sender name."))
+        } else {
+            Ok(Self(s.trim().to_string()))
+        }
+    }
+}
+
+impl AsRef<str> for SenderName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{SenderName, SenderNameTemplate, SubscriberName};
+    use claims::{assert_err, assert_ok};
+
+    fn name(s: &str) -> SubscriberName {
+        SubscriberName::parse(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn tokens_are_replaced_with_the_recipients_name() {
+        let template = SenderNameTemplate::parse("Acme for {{first_name}}".into()).unwrap();
+        let rendered = template.render(&name("Ursula Le Guin")).unwrap();
+        assert_eq!(rendered.as_ref(), "Acme for Ursula");
+
+        let template = SenderNameTemplate::parse("Hi {{name}}".into()).unwrap();
+        let rendered = template.render(&name("Ursula Le Guin")).unwrap();
+        assert_eq!(rendered.as_ref(), "Hi Ursula Le Guin");
+    }
+
+    #[test]
+    fn unknown_tokens_are_rejected() {
+        assert_err!(SenderNameTemplate::parse("Acme for {{email}}".into()));
+        assert_err!(SenderNameTemplate::parse("Acme for {{first_name".into()));
+    }
+
+    #[test]
+    fn templates_with_line_breaks_are_rejected() {
+        assert_err!(SenderNameTemplate::parse("Acme\r\nBcc: {{name}}".into()));
+    }
+
+    #[test]
+    fn a_name_that_would_inject_a_header_is_rejected() {
+        let template = SenderNameTemplate::parse("Acme for {{name}}".into()).unwrap();
+        assert_err!(template.render(&name("Ursula\r\nBcc: evil@example.com")));
+    }
+
+    #[test]
+    fn quotes_and_angle_brackets_are_rejected() {
+        assert_err!(SenderName::parse(r#"Acme" <evil@example.com>"#.into()));
+        assert_ok!(SenderName::parse("Acme, the newsletter".into()));
+    }
+}
@@ -11,6 +11,11 @@ impl SubscriberEmail {
             Err(format!("{s} is not a valid subscriber email."))
         }
     }
+
+    /// The part of the address after the last `@`.
+    pub fn domain(&self) -> &str {
+        self.0.rsplit_once('@').map(|(_, d)| d).unwrap_or_default()
+    }
 }
 
 impl std::fmt::Display for SubscriberEmail {
@@ -3,18 +3,56 @@ use unicode_segmentation::UnicodeSegmentation;
 #[derive(Debug)]
 pub struct SubscriberName(String);
 
+/// How a name is tidied up before it is stored.
+/// The default keeps the name exactly as it was submitted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NameFormatting {
+    /// Trim the name and collapse runs of whitespace into a single space.
+    pub normalize_whitespace: bool,
+    /// Capitalise the first letter of every word and lowercase the rest.
+    pub title_case: bool,
+}
+
 impl SubscriberName {
     pub fn parse(s: String) -> Result<SubscriberName, String> {
+        Self::parse_with(s, NameFormatting::default())
+    }
+
+    pub fn parse_with(s: String, formatting: NameFormatting) -> Result<SubscriberName, String> {
         let is_empty_or_whitespace = s.trim().is_empty();
         let is_too_long = s.graphemes(true).count() > 256;
         let forbidden_characters = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
         let contains_forbidden_characters = s.chars().any(|c| forbidden_characters.contains(&c));
         if is_empty_or_whitespace || is_too_long || contains_forbidden_characters {
-            Err(format!("{s} is not a valid subscriber name."))
+            return Err(format!("{s} is not a valid subscriber name."));
+        }
+
+        let mut name = s;
+        if formatting.normalize_whitespace {
+            name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+        if formatting.title_case {
+            name = title_case(&name);
+        }
+        Ok(Self(name))
+    }
+}
+
+fn title_case(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut at_word_start = true;
+    for c in s.chars() {
+        if c.is_whitespace() {
+            result.push(c);
+            at_word_start = true;
+        } else if at_word_start {
+            result.extend(c.to_uppercase());
+            at_word_start = false;
         } else {
-            Ok(Self(s))
+            result.extend(c.to_lowercase());
         }
     }
+    result
 }
 
 impl AsRef<str> for SubscriberName {
@@ -25,7 +63,7 @@ impl AsRef<str> for SubscriberName {
 
 #[cfg(test)]
 mod tests {
-    use crate::domain::SubscriberName;
+    use crate::domain::{NameFormatting, SubscriberName};
     use claims::{assert_err, assert_ok};
 
     #[test]
@@ -59,4 +97,30 @@ mod tests {
         let name = "Joe Smith".to_string();
         assert_ok!(SubscriberName::parse(name));
     }
+
+    #[test]
+    fn names_are_kept_as_submitted_by_default() {
+        let name = SubscriberName::parse("  joe   SMITH ".to_string()).unwrap();
+        assert_eq!(name.as_ref(), "  joe   SMITH ");
+    }
+
+    #[test]
+    fn whitespace_is_trimmed_and_collapsed_when_normalizing() {
+        let formatting = NameFormatting {
+            normalize_whitespace: true,
+            title_case: false,
+        };
+        let name = SubscriberName::parse_with(" joe \t  smith ".to_string(), formatting).unwrap();
+        assert_eq!(name.as_ref(), "joe smith");
+    }
+
+    #[test]
+    fn names_are_title_cased_when_the_flag_is_on() {
+        let formatting = NameFormatting {
+            normalize_whitespace: true,
+            title_case: true,
+        };
+        let name = SubscriberName::parse_with("jOE  ursula-smith".to_string(), formatting).unwrap();
+        assert_eq!(name.as_ref(), "Joe Ursula-smith");
+    }
 }
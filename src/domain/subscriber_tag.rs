@@ -0,0 +1,55 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug)]
+pub struct SubscriberTag(String);
+
+impl SubscriberTag {
+    pub fn parse(s: String) -> Result<SubscriberTag, String> {
+        let tag = s.trim().to_lowercase();
+        let is_empty = tag.is_empty();
+        let is_too_long = tag.graphemes(true).count() > 64;
+        let contains_forbidden_characters = tag
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == ',');
+        if is_empty || is_too_long || contains_forbidden_characters {
+            Err(format!("{s} is not a valid tag."))
+        } else {
+            Ok(Self(tag))
+        }
+    }
+}
+
+impl AsRef<str> for SubscriberTag {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberTag;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn tags_are_trimmed_and_lowercased() {
+        let tag = SubscriberTag::parse("  VIP ".to_string()).unwrap();
+        assert_eq!(tag.as_ref(), "vip");
+    }
+
+    #[test]
+    fn empty_tags_are_rejected() {
+        assert_err!(SubscriberTag::parse(" ".to_string()));
+    }
+
+    #[test]
+    fn a_tag_longer_than_64_graphemes_is_rejected() {
+        assert_ok!(SubscriberTag::parse("a".repeat(64)));
+        assert_err!(SubscriberTag::parse("a".repeat(65)));
+    }
+
+    #[test]
+    fn tags_containing_whitespace_or_commas_are_rejected() {
+        assert_err!(SubscriberTag::parse("early adopter".to_string()));
+        assert_err!(SubscriberTag::parse("vip,beta".to_string()));
+    }
+}
@@ -0,0 +1,38 @@
+/// Why a subscriber left, picked from a short list on the unsubscribe page.
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnsubscribeReason {
+    TooFrequent,
+    NotRelevant,
+    NeverSignedUp,
+    Other,
+}
+
+impl UnsubscribeReason {
+    pub const ALL: [UnsubscribeReason; 4] = [
+        UnsubscribeReason::TooFrequent,
+        UnsubscribeReason::NotRelevant,
+        UnsubscribeReason::NeverSignedUp,
+        UnsubscribeReason::Other,
+    ];
+
+    /// How the reason is stored.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UnsubscribeReason::TooFrequent => "too_frequent",
+            UnsubscribeReason::NotRelevant => "not_relevant",
+            UnsubscribeReason::NeverSignedUp => "never_signed_up",
+            UnsubscribeReason::Other => "other",
+        }
+    }
+
+    /// How the reason is shown to subscribers and admins.
+    pub fn label(&self) -> &'static str {
+        match self {
+            UnsubscribeReason::TooFrequent => "I get too many emails",
+            UnsubscribeReason::NotRelevant => "The content is not relevant to me",
+            UnsubscribeReason::NeverSignedUp => "I never signed up",
+            UnsubscribeReason::Other => "Something else",
+        }
+    }
+}
@@ -0,0 +1,1093 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::configuration::RetryJitter;
+use crate::domain::{SenderName, SenderNameTemplate, SubscriberEmail, SubscriberName};
+use crate::secrets::ReloadableSecret;
+
+mod postmark;
+mod provider;
+mod ses;
+
+pub use postmark::PostmarkProvider;
+pub use provider::{EmailProvider, OutgoingEmail, SendFailure};
+pub use ses::SesProvider;
+
+/// One email, e.g. of a batch, with its own sender name and bodies.
+pub struct BatchMessage<'a> {
+    pub recipient: &'a SubscriberEmail,
+    pub from_name: Option<&'a SenderName>,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub thread: Option<EmailThread>,
+}
+
+/// Where an email sits in a conversation: every email of a thread, e.g.
+/// every copy of a newsletter issue, shares the same `thread_id`.
+#[derive(Debug, Clone, Copy)]
+pub struct EmailThread {
+    pub thread_id: Uuid,
+    pub message_id: Uuid,
+}
+
+/// A provider asking us to wait longer than this is treated as down.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// How `send_email` deals with transient failures: timeouts, connection
+/// errors, 5xx responses and 429s. Any other 4xx is never retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Including the first attempt, so 1 disables retries.
+    pub max_attempts: u32,
+    /// Doubled after every failed attempt, unless the provider sent a
+    /// `Retry-After` header.
+    pub base_delay: Duration,
+    /// Randomness added to the doubled delay, so that emails that failed
+    /// together are not all retried at the same moment. A `Retry-After`
+    /// from the provider is waited out as is.
+    pub jitter: RetryJitter,
+}
+
+impl RetryPolicy {
+    pub fn no_retries() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+            jitter: RetryJitter::None,
+        }
+    }
+
+    fn backoff(&self, failed_attempts: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(failed_attempts.saturating_sub(1)));
+        self.jitter.apply(backoff, &mut rand::thread_rng())
+    }
+}
+
+/// Sends emails through whichever `EmailProvider` it was built with, taking
+/// care of retries, test mode, the recipient allowlist and threading.
+#[derive(Debug)]
+pub struct EmailClient {
+    provider: Box<dyn EmailProvider>,
+    sender: SubscriberEmail,
+    sender_name: Option<SenderNameTemplate>,
+    timeout: std::time::Duration,
+    test_mode: bool,
+    retry_policy: RetryPolicy,
+    thread_replies: bool,
+    recipient_allowlist: Option<Vec<String>>,
+}
+
+impl EmailClient {
+    /// A client sending through Postmark.
+    pub fn new(
+        base_url: String,
+        sender: SubscriberEmail,
+        auth_token: impl Into<ReloadableSecret>,
+        timeout: std::time::Duration,
+        min_tls_version: reqwest::tls::Version,
+    ) -> Self {
+        let provider = PostmarkProvider::new(base_url, auth_token, timeout, min_tls_version);
+        Self::with_provider(Box::new(provider), sender, timeout)
+    }
+
+    pub fn with_provider(
+        provider: Box<dyn EmailProvider>,
+        sender: SubscriberEmail,
+        timeout: std::time::Duration,
+    ) -> Self {
+        Self {
+            provider,
+            sender,
+            sender_name: None,
+            timeout,
+            test_mode: false,
+            retry_policy: RetryPolicy::no_retries(),
+            thread_replies: false,
+            recipient_allowlist: None,
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// In test mode nothing reaches the provider: every email is logged
+    /// and reported as sent, so the rest of the pipeline runs as usual.
+    pub fn with_test_mode(mut self, test_mode: bool) -> Self {
+        self.test_mode = test_mode;
+        self
+    }
+
+    /// Emails to anyone else are skipped, logged and reported as sent.
+    /// Entries are full addresses or `@domain`s, compared ignoring case.
+    pub fn with_recipient_allowlist(mut self, allowlist: Option<Vec<String>>) -> Self {
+        self.recipient_allowlist =
+            allowlist.map(|entries| entries.iter().map(|e| e.trim().to_lowercase()).collect());
+        self
+    }
+
+    /// Adds `Message-ID`, `In-Reply-To` and `References` headers to emails
+    /// sent as part of a thread, so replies are grouped together by mail
+    /// clients.
+    pub fn with_thread_replies(mut self, thread_replies: bool) -> Self {
+        self.thread_replies = thread_replies;
+        self
+    }
+
+    /// Puts a display name, personalised for each recipient, in front of
+    /// the sender's address.
+    pub fn with_sender_name(mut self, sender_name: Option<SenderNameTemplate>) -> Self {
+        self.sender_name = sender_name;
+        self
+    }
+
+    /// The display name to use when emailing `recipient`, if one is configured.
+    pub fn sender_name_for(
+        &self,
+        recipient: &SubscriberName,
+    ) -> Result<Option<SenderName>, String> {
+        self.sender_name
+            .as_ref()
+            .map(|template| template.render(recipient))
+            .transpose()
+    }
+
+    pub async fn send_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), EmailError> {
+        self.send_email_as(None, None, recipient, subject, html_content, text_content)
+            .await
+    }
+
+    /// Like `send_email`, with `from_name` as the sender's display name and
+    /// threading headers for `thread`.
+    pub async fn send_email_as(
+        &self,
+        from_name: Option<&SenderName>,
+        thread: Option<&EmailThread>,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), EmailError> {
+        let message = BatchMessage {
+            recipient,
+            from_name,
+            html_content,
+            text_content,
+            thread: thread.copied(),
+        };
+        self.send_email_within(None, &message, subject).await
+    }
+
+    /// Like `send_email`, but gives up once `deadline` has passed, even if
+    /// the client's own timeout has not elapsed yet or retries are left.
+    pub async fn send_email_with_deadline(
+        &self,
+        deadline: Instant,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), EmailError> {
+        let message = BatchMessage {
+            recipient,
+            from_name: None,
+            html_content,
+            text_content,
+            thread: None,
+        };
+        self.send_email_within(Some(deadline), &message, subject)
+            .await
+    }
+
+    async fn send_email_within(
+        &self,
+        deadline: Option<Instant>,
+        message: &BatchMessage<'_>,
+        subject: &str,
+    ) -> Result<(), EmailError> {
+        let from = self.from(message.from_name);
+        if !self.may_email(message.recipient, subject) {
+            return Ok(());
+        }
+        if self.test_mode {
+            log_test_mode_email(&from, message.recipient, subject);
+            return Ok(());
+        }
+        let email = self.outgoing_email(&from, message, subject);
+        let mut failed_attempts = 0;
+        loop {
+            let timeout = match deadline {
+                Some(deadline) => deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(self.timeout),
+                None => self.timeout,
+            };
+            let SendFailure { error, retry_after } =
+                match self.provider.send_email(&email, timeout).await {
+                    Ok(()) => return Ok(()),
+                    Err(failure) => failure,
+                };
+            failed_attempts += 1;
+            if !error.is_transient() || failed_attempts >= self.retry_policy.max_attempts {
+                return Err(error);
+            }
+            let delay = retry_after.unwrap_or_else(|| self.retry_policy.backoff(failed_attempts));
+            let too_late = deadline.is_some_and(|deadline| Instant::now() + delay >= deadline);
+            if delay > MAX_RETRY_AFTER || too_late {
+                return Err(error);
+            }
+            tracing::warn!(
+                error.kind = error.kind(),
+                error.message = %error,
+                attempt = failed_attempts,
+                retry_in_ms = delay.as_millis() as u64,
+                "Failed to send an email. Retrying.",
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Sends the messages in as few calls as the provider allows, e.g. at
+    /// most 500 per call for Postmark. The outcome of each message is
+    /// returned in the same order as `messages`. A call that fails marks its
+    /// messages as not sent without stopping the calls after it.
+    pub async fn send_email_batch(
+        &self,
+        messages: &[BatchMessage<'_>],
+        subject: &str,
+    ) -> Vec<Result<(), String>> {
+        let mut results = Vec::with_capacity(messages.len());
+        for chunk in messages.chunks(self.provider.max_batch_size().max(1)) {
+            match self.send_batch_request(chunk, subject).await {
+                Ok(chunk_results) => results.extend(chunk_results),
+                Err(e) => {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        error.kind = e.kind(),
+                        batch_size = chunk.len(),
+                        "Failed to send a batch of emails.",
+                    );
+                    results.extend(vec![Err(e.to_string()); chunk.len()]);
+                }
+            }
+        }
+        results
+    }
+
+    async fn send_batch_request(
+        &self,
+        messages: &[BatchMessage<'_>],
+        subject: &str,
+    ) -> Result<Vec<Result<(), String>>, EmailError> {
+        let senders: Vec<_> = messages.iter().map(|m| self.from(m.from_name)).collect();
+        let allowed: Vec<bool> = messages
+            .iter()
+            .map(|m| self.may_email(m.recipient, subject))
+            .collect();
+        let to_send = || {
+            messages
+                .iter()
+                .zip(&senders)
+                .zip(&allowed)
+                .filter(|(_, allowed)| **allowed)
+                .map(|(pair, _)| pair)
+        };
+        if self.test_mode {
+            for (message, from) in to_send() {
+                log_test_mode_email(from, message.recipient, subject);
+            }
+            return Ok(vec![Ok(()); messages.len()]);
+        }
+        if !allowed.contains(&true) {
+            return Ok(vec![Ok(()); messages.len()]);
+        }
+        let emails: Vec<_> = to_send()
+            .map(|(message, from)| self.outgoing_email(from, message, subject))
+            .collect();
+        let mut responses = self
+            .provider
+            .send_email_batch(&emails, self.timeout)
+            .await?
+            .into_iter();
+        // Anything missing from the response is treated as not sent.
+        let results = allowed
+            .into_iter()
+            .map(|allowed| {
+                if allowed {
+                    responses
+                        .next()
+                        .unwrap_or_else(|| Err("No result was returned for this message.".into()))
+                } else {
+                    Ok(())
+                }
+            })
+            .collect();
+        Ok(results)
+    }
+
+    /// Whether the provider answers at all. Any response, even an error
+    /// status, means it can be reached.
+    pub async fn check_reachable(&self, timeout: std::time::Duration) -> Result<(), EmailError> {
+        if self.test_mode {
+            return Ok(());
+        }
+        self.provider.check_reachable(timeout).await
+    }
+
+    fn outgoing_email<'a>(
+        &self,
+        from: &'a str,
+        message: &'a BatchMessage<'_>,
+        subject: &'a str,
+    ) -> OutgoingEmail<'a> {
+        OutgoingEmail {
+            from,
+            to: message.recipient,
+            subject,
+            html_body: message.html_content,
+            text_body: message.text_content,
+            headers: self.thread_headers(message.thread.as_ref()),
+            message_id: message.thread.map(|thread| thread.message_id),
+        }
+    }
+
+    /// The email gets its own `Message-ID` and points at the thread as if
+    /// it were a reply to it. Ids are qualified with the sender's domain.
+    fn thread_headers(&self, thread: Option<&EmailThread>) -> Vec<EmailHeader> {
+        let Some(thread) = thread.filter(|_| self.thread_replies) else {
+            return Vec::new();
+        };
+        let domain = self
+            .sender
+            .as_ref()
+            .rsplit_once('@')
+            .map_or("localhost", |(_, domain)| domain);
+        let thread_root = format!("<{}@{domain}>", thread.thread_id);
+        vec![
+            EmailHeader::new(
+                "Message-ID",
+                format!("<{}.{}@{domain}>", thread.thread_id, thread.message_id),
+            ),
+            EmailHeader::new("In-Reply-To", thread_root.clone()),
+            EmailHeader::new("References", thread_root),
+        ]
+    }
+
+    /// Whether `recipient` passes the allowlist, if there is one.
+    pub fn allows(&self, recipient: &SubscriberEmail) -> bool {
+        let Some(allowlist) = &self.recipient_allowlist else {
+            return true;
+        };
+        let address = recipient.as_ref().to_lowercase();
+        let domain = recipient.domain().to_lowercase();
+        allowlist.iter().any(|entry| match entry.strip_prefix('@') {
+            Some(allowed_domain) => domain == allowed_domain,
+            None => *entry == address,
+        })
+    }
+
+    /// Like `allows`, logging the emails it holds back.
+    fn may_email(&self, recipient: &SubscriberEmail, subject: &str) -> bool {
+        let allowed = self.allows(recipient);
+        if !allowed {
+            tracing::info!(
+                email.to = %recipient,
+                email.subject = subject,
+                "Skipping an email to a recipient who is not on the allowlist.",
+            );
+        }
+        allowed
+    }
+
+    fn from(&self, from_name: Option<&SenderName>) -> String {
+        match from_name {
+            Some(name) => format!("\"{}\" <{}>", name.as_ref(), self.sender.as_ref()),
+            None => self.sender.as_ref().to_string(),
+        }
+    }
+}
+
+/// The delay requested by a `Retry-After` header, in seconds or as a date.
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+}
+
+fn log_test_mode_email(from: &str, recipient: &SubscriberEmail, subject: &str) {
+    tracing::info!(
+        email.from = from,
+        email.to = %recipient,
+        email.subject = subject,
+        "Test mode is on - recording the email instead of sending it.",
+    );
+}
+
+/// Why a call to the email provider failed, so that a slow provider can be
+/// told apart from one we cannot reach at all.
+#[derive(thiserror::Error, Debug)]
+pub enum EmailError {
+    #[error("The email provider did not respond in time: {0}")]
+    Timeout(#[source] reqwest::Error),
+    #[error("Failed to connect to the email provider: {0}")]
+    Connection(#[source] reqwest::Error),
+    #[error("The email provider rejected the request: {0}")]
+    Rejected(#[source] reqwest::Error),
+    #[error("Failed to call the email provider: {0}")]
+    Unexpected(#[source] reqwest::Error),
+}
+
+impl EmailError {
+    /// A short, stable label for logs and metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EmailError::Timeout(_) => "timeout",
+            EmailError::Connection(_) => "connection",
+            EmailError::Rejected(_) => "rejected",
+            EmailError::Unexpected(_) => "unexpected",
+        }
+    }
+
+    /// Whether trying again later might succeed: the provider was slow,
+    /// unreachable, failing or throttling us.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            EmailError::Timeout(_) | EmailError::Connection(_) => true,
+            EmailError::Rejected(e) => e
+                .status()
+                .is_some_and(|s| s.is_server_error() || s == StatusCode::TOO_MANY_REQUESTS),
+            EmailError::Unexpected(_) => false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for EmailError {
+    fn from(e: reqwest::Error) -> Self {
+        // A connection attempt that times out is reported as both; it is
+        // the provider being slow to accept us, so it counts as a timeout.
+        if e.is_timeout() {
+            Self::Timeout(e)
+        } else if e.is_connect() {
+            Self::Connection(e)
+        } else if e.is_status() {
+            Self::Rejected(e)
+        } else {
+            Self::Unexpected(e)
+        }
+    }
+}
+
+/// An extra header sent along with an email, e.g. for threading.
+#[derive(serde::Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct EmailHeader {
+    pub name: &'static str,
+    pub value: String,
+}
+
+impl EmailHeader {
+    fn new(name: &'static str, value: String) -> Self {
+        Self { name, value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::{Fake, Faker};
+    use secrecy::Secret;
+    use wiremock::matchers::{any, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::configuration::RetryJitter;
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{BatchMessage, EmailClient, EmailError, EmailThread, RetryPolicy};
+
+    struct SendEmailBodyMatcher;
+
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &wiremock::Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body.get("From").is_some()
+                    && body.get("To").is_some()
+                    && body.get("Subject").is_some()
+                    && body.get("HtmlBody").is_some()
+                    && body.get("TextBody").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    fn email_client(base_url: String) -> EmailClient {
+        EmailClient::new(
+            base_url,
+            email(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            reqwest::tls::Version::TLS_1_2,
+        )
+    }
+
+    #[tokio::test]
+    async fn email_client_can_be_built_with_a_minimum_tls_version() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let email_client = EmailClient::new(
+            mock_server.uri(),
+            email(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            reqwest::tls::Version::TLS_1_3,
+        );
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_sends_the_expected_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let _ = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        // Mock expectations are checked on drop
+    }
+
+    #[tokio::test]
+    async fn threading_headers_are_only_sent_when_enabled() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let thread = EmailThread {
+            thread_id: uuid::Uuid::new_v4(),
+            message_id: uuid::Uuid::new_v4(),
+        };
+
+        // Act
+        for thread_replies in [false, true] {
+            let outcome = EmailClient::new(
+                mock_server.uri(),
+                SubscriberEmail::parse("news@example.com".into()).unwrap(),
+                Secret::new(Faker.fake()),
+                std::time::Duration::from_millis(200),
+                reqwest::tls::Version::TLS_1_2,
+            )
+            .with_thread_replies(thread_replies)
+            .send_email_as(
+                None,
+                Some(&thread),
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+            assert_ok!(outcome);
+        }
+
+        // Assert
+        let requests = mock_server.received_requests().await.unwrap();
+        let headers = |i: usize| {
+            let body: serde_json::Value = serde_json::from_slice(&requests[i].body).unwrap();
+            body.get("Headers").cloned()
+        };
+        assert_eq!(headers(0), None);
+        let thread_root = format!("<{}@example.com>", thread.thread_id);
+        assert_eq!(
+            headers(1).unwrap(),
+            serde_json::json!([
+                {
+                    "Name": "Message-ID",
+                    "Value": format!("<{}.{}@example.com>", thread.thread_id, thread.message_id)
+                },
+                { "Name": "In-Reply-To", "Value": thread_root },
+                { "Name": "References", "Value": thread_root },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_succeeds_if_the_server_returns_200() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_server_returns_500() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    fn retrying_email_client(base_url: String) -> EmailClient {
+        email_client(base_url).with_retry_policy(RetryPolicy {
+            max_attempts: 3,
+            base_delay: std::time::Duration::from_millis(10),
+            jitter: RetryJitter::None,
+        })
+    }
+
+    #[test]
+    fn jitter_keeps_the_retry_delay_within_the_doubled_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: std::time::Duration::from_millis(100),
+            jitter: RetryJitter::Equal,
+        };
+
+        for _ in 0..100 {
+            let delay = policy.backoff(3);
+            assert!(delay >= std::time::Duration::from_millis(200));
+            assert!(delay <= std::time::Duration::from_millis(400));
+        }
+    }
+
+    #[tokio::test]
+    async fn send_email_retries_when_the_server_returns_500() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_gives_up_after_the_maximum_number_of_attempts() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_does_not_retry_when_the_server_returns_400() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_waits_as_long_as_retry_after_asks_on_429() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let start = std::time::Instant::now();
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        assert!(start.elapsed() >= std::time::Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn send_email_does_not_call_the_provider_in_test_mode() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri()).with_test_mode(true);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_batch_sends_every_message_in_one_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(path("/email/batch"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                {"ErrorCode": 0, "Message": "OK"},
+                {"ErrorCode": 300, "Message": "Invalid email request"},
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let (first, second) = (email(), email());
+        let content = content();
+        let message = |recipient| BatchMessage {
+            recipient,
+            from_name: None,
+            html_content: &content,
+            text_content: &content,
+            thread: None,
+        };
+
+        // Act
+        let outcome = email_client
+            .send_email_batch(&[message(&first), message(&second)], &subject())
+            .await;
+
+        // Assert
+        assert_eq!(
+            outcome,
+            vec![Ok(()), Err("Invalid email request".to_string())]
+        );
+        let requests = mock_server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn send_email_batch_keeps_going_when_one_chunk_fails() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        let accepted = vec![serde_json::json!({"ErrorCode": 0, "Message": "OK"}); 500];
+        Mock::given(path("/email/batch"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(accepted))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/email/batch"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(422))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let recipients: Vec<_> = (0..501).map(|_| email()).collect();
+        let content = content();
+        let messages: Vec<_> = recipients
+            .iter()
+            .map(|recipient| BatchMessage {
+                recipient,
+                from_name: None,
+                html_content: &content,
+                text_content: &content,
+                thread: None,
+            })
+            .collect();
+
+        // Act
+        let outcome = email_client.send_email_batch(&messages, &subject()).await;
+
+        // Assert
+        assert_eq!(outcome.len(), 501);
+        assert!(outcome[..500].iter().all(Result::is_ok));
+        assert!(outcome[500].is_err());
+    }
+
+    #[tokio::test]
+    async fn send_email_batch_only_sends_to_allowlisted_recipients() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri()).with_recipient_allowlist(Some(vec![
+            "dev@example.com".into(),
+            "@team.example.com".into(),
+        ]));
+
+        Mock::given(path("/email/batch"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(vec![
+                serde_json::json!({"ErrorCode": 0, "Message": "OK"}),
+                serde_json::json!({"ErrorCode": 406, "Message": "Inactive recipient"}),
+            ]))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let recipients: Vec<_> = [
+            "Dev@Example.com",
+            "customer@example.com",
+            "jo@team.example.com",
+        ]
+        .into_iter()
+        .map(|e| SubscriberEmail::parse(e.into()).unwrap())
+        .collect();
+        let content = content();
+        let messages: Vec<_> = recipients
+            .iter()
+            .map(|recipient| BatchMessage {
+                recipient,
+                from_name: None,
+                html_content: &content,
+                text_content: &content,
+                thread: None,
+            })
+            .collect();
+
+        // Act
+        let outcome = email_client.send_email_batch(&messages, &subject()).await;
+
+        // Assert
+        assert_eq!(
+            outcome,
+            [Ok(()), Ok(()), Err("Inactive recipient".to_string())]
+        );
+        let requests = mock_server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        let recipients: Vec<_> = body.as_array().unwrap().iter().map(|m| &m["To"]).collect();
+        assert_eq!(recipients, ["Dev@Example.com", "jo@team.example.com"]);
+    }
+
+    #[tokio::test]
+    async fn send_email_with_deadline_gives_up_when_the_deadline_passes() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = EmailClient::new(
+            mock_server.uri(),
+            email(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_secs(10),
+            reqwest::tls::Version::TLS_1_2,
+        );
+
+        let response = ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(180));
+        Mock::given(any())
+            .respond_with(response)
+            .mount(&mock_server)
+            .await;
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_millis(100);
+
+        // Act
+        let outcome = email_client
+            .send_email_with_deadline(deadline, &email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        let error = assert_err!(outcome);
+        assert!(matches!(error, EmailError::Timeout(_)));
+        assert!(deadline.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        let response = ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(180));
+
+        Mock::given(any())
+            .respond_with(response)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        let error = assert_err!(outcome);
+        assert!(matches!(error, EmailError::Timeout(_)));
+        assert_eq!(error.kind(), "timeout");
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_a_connection_failure_if_the_server_is_unreachable() {
+        // Arrange
+        // Grab a free port and close it again, so that nothing is listening.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let email_client = email_client(format!("http://127.0.0.1:{port}"));
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        let error = assert_err!(outcome);
+        assert!(matches!(error, EmailError::Connection(_)));
+        assert_eq!(error.kind(), "connection");
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_a_rejection_if_the_server_returns_an_error_status() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        let error = assert_err!(outcome);
+        assert!(matches!(error, EmailError::Rejected(_)));
+    }
+}
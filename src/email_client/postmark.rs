@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use reqwest::Client;
+use secrecy::ExposeSecret;
+use uuid::Uuid;
+
+use super::{retry_after, EmailError, EmailHeader, EmailProvider};
+use super::{OutgoingEmail, SendFailure};
+use crate::secrets::ReloadableSecret;
+
+/// The most messages Postmark accepts in one call to the batch endpoint.
+const MAX_BATCH_MESSAGES: usize = 500;
+
+const SERVER_TOKEN_HEADER: &str = "X-Postmark-Server-Token";
+
+/// Sends through Postmark's HTTP API, authenticating with a server token.
+#[derive(Debug)]
+pub struct PostmarkProvider {
+    http_client: Client,
+    base_url: reqwest::Url,
+    auth_token: ReloadableSecret,
+}
+
+impl PostmarkProvider {
+    pub fn new(
+        base_url: String,
+        auth_token: impl Into<ReloadableSecret>,
+        timeout: Duration,
+        min_tls_version: reqwest::tls::Version,
+    ) -> Self {
+        let http_client = Client::builder()
+            .timeout(timeout)
+            .min_tls_version(min_tls_version)
+            .build()
+            .unwrap();
+        Self {
+            http_client,
+            base_url: reqwest::Url::parse(&base_url).expect("Could not parse url"),
+            auth_token: auth_token.into(),
+        }
+    }
+
+    fn post(&self, path: &str, timeout: Duration) -> reqwest::RequestBuilder {
+        self.http_client
+            .post(self.base_url.join(path).unwrap())
+            .header(
+                SERVER_TOKEN_HEADER,
+                self.auth_token.current().expose_secret(),
+            )
+            .timeout(timeout)
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailProvider for PostmarkProvider {
+    async fn send_email(
+        &self,
+        email: &OutgoingEmail<'_>,
+        timeout: Duration,
+    ) -> Result<(), SendFailure> {
+        let response = self
+            .post("email", timeout)
+            .json(&SendEmailRequest::from(email))
+            .send()
+            .await?;
+        let retry_after = retry_after(&response);
+        match response.error_for_status() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(SendFailure {
+                error: e.into(),
+                retry_after,
+            }),
+        }
+    }
+
+    async fn send_email_batch(
+        &self,
+        emails: &[OutgoingEmail<'_>],
+        timeout: Duration,
+    ) -> Result<Vec<Result<(), String>>, EmailError> {
+        let request_body: Vec<_> = emails.iter().map(SendEmailRequest::from).collect();
+        let responses: Vec<BatchResponseEntry> = self
+            .post("email/batch", timeout)
+            .json(&request_body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let results = responses
+            .into_iter()
+            .map(|r| match r.error_code {
+                0 => Ok(()),
+                _ => Err(r.message),
+            })
+            .collect();
+        Ok(results)
+    }
+
+    fn max_batch_size(&self) -> usize {
+        MAX_BATCH_MESSAGES
+    }
+
+    async fn check_reachable(&self, timeout: Duration) -> Result<(), EmailError> {
+        self.http_client
+            .head(self.base_url.clone())
+            .timeout(timeout)
+            .send()
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    headers: &'a [EmailHeader],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+}
+
+/// Postmark includes it in the webhooks it calls about the email.
+#[derive(serde::Serialize)]
+struct Metadata {
+    message_id: Uuid,
+}
+
+impl<'a> From<&'a OutgoingEmail<'a>> for SendEmailRequest<'a> {
+    fn from(email: &'a OutgoingEmail<'a>) -> Self {
+        Self {
+            from: email.from,
+            to: email.to.as_ref(),
+            subject: email.subject,
+            html_body: email.html_body,
+            text_body: email.text_body,
+            headers: &email.headers,
+            metadata: email.message_id.map(|message_id| Metadata { message_id }),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BatchResponseEntry {
+    error_code: i64,
+    message: String,
+}
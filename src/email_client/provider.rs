@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use super::{EmailError, EmailHeader};
+use crate::domain::SubscriberEmail;
+
+/// One email, ready to be handed over to the provider.
+pub struct OutgoingEmail<'a> {
+    /// The sender's address, with its display name if it has one.
+    pub from: &'a str,
+    pub to: &'a SubscriberEmail,
+    pub subject: &'a str,
+    pub html_body: &'a str,
+    pub text_body: &'a str,
+    pub headers: Vec<EmailHeader>,
+    /// Our id for the email, for providers to hand back in their webhooks.
+    pub message_id: Option<Uuid>,
+}
+
+/// A failed send, with how long the provider asked us to wait before
+/// trying again, if it did.
+#[derive(Debug)]
+pub struct SendFailure {
+    pub error: EmailError,
+    pub retry_after: Option<Duration>,
+}
+
+impl From<reqwest::Error> for SendFailure {
+    fn from(e: reqwest::Error) -> Self {
+        Self {
+            error: e.into(),
+            retry_after: None,
+        }
+    }
+}
+
+/// The API an `EmailClient` hands its emails over to. Retries, test mode
+/// and the recipient allowlist are left to the client, so a provider only
+/// has to make the calls.
+#[async_trait::async_trait]
+pub trait EmailProvider: Send + Sync + std::fmt::Debug {
+    /// Sends one email, giving up after `timeout`.
+    async fn send_email(
+        &self,
+        email: &OutgoingEmail<'_>,
+        timeout: Duration,
+    ) -> Result<(), SendFailure>;
+
+    /// Sends every email in as few calls as the provider allows, returning
+    /// the outcome of each in the same order as `emails`. Providers without
+    /// a batch API send them one at a time.
+    async fn send_email_batch(
+        &self,
+        emails: &[OutgoingEmail<'_>],
+        timeout: Duration,
+    ) -> Result<Vec<Result<(), String>>, EmailError> {
+        let mut results = Vec::with_capacity(emails.len());
+        for email in emails {
+            let result = self.send_email(email, timeout).await;
+            results.push(result.map_err(|failure| failure.error.to_string()));
+        }
+        Ok(results)
+    }
+
+    /// The most emails `send_email_batch` is given at once.
+    fn max_batch_size(&self) -> usize {
+        100
+    }
+
+    /// Whether the provider answers at all. Any response, even an error
+    /// status, means it can be reached.
+    async fn check_reachable(&self, timeout: Duration) -> Result<(), EmailError>;
+}
@@ -0,0 +1,353 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use secrecy::ExposeSecret;
+use sha2::{Digest, Sha256};
+
+use super::{retry_after, EmailError, EmailHeader, EmailProvider};
+use super::{OutgoingEmail, SendFailure};
+use crate::secrets::ReloadableSecret;
+
+const SERVICE: &str = "ses";
+const SIGNED_HEADERS: &str = "content-type;host;x-amz-date";
+
+/// Sends through the Amazon SES v2 API, signing every request with the
+/// access key (AWS Signature Version 4). SES has no batch endpoint for
+/// emails with different bodies, so batches are sent one email at a time.
+#[derive(Debug)]
+pub struct SesProvider {
+    http_client: Client,
+    base_url: reqwest::Url,
+    region: String,
+    access_key_id: String,
+    secret_access_key: ReloadableSecret,
+}
+
+impl SesProvider {
+    /// `base_url` is the regional endpoint, e.g.
+    /// "https://email.eu-west-1.amazonaws.com".
+    pub fn new(
+        base_url: String,
+        region: String,
+        access_key_id: String,
+        secret_access_key: impl Into<ReloadableSecret>,
+        timeout: Duration,
+        min_tls_version: reqwest::tls::Version,
+    ) -> Self {
+        let http_client = Client::builder()
+            .timeout(timeout)
+            .min_tls_version(min_tls_version)
+            .build()
+            .unwrap();
+        Self {
+            http_client,
+            base_url: reqwest::Url::parse(&base_url).expect("Could not parse url"),
+            region,
+            access_key_id,
+            secret_access_key: secret_access_key.into(),
+        }
+    }
+
+    /// The `Authorization` header for a JSON POST of `body` to `url`.
+    fn authorization(&self, url: &reqwest::Url, body: &[u8], now: DateTime<Utc>) -> String {
+        let date = now.format("%Y%m%d").to_string();
+        let host = host_header(url);
+        let canonical_request = format!(
+            "POST\n{}\n{}\ncontent-type:application/json\nhost:{host}\nx-amz-date:{}\n\n{SIGNED_HEADERS}\n{}",
+            url.path(),
+            url.query().unwrap_or_default(),
+            amz_date(now),
+            hex::encode(Sha256::digest(body)),
+        );
+        let scope = format!("{date}/{}/{SERVICE}/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{scope}\n{}",
+            amz_date(now),
+            hex::encode(Sha256::digest(canonical_request.as_bytes())),
+        );
+        let key = signing_key(
+            self.secret_access_key.current().expose_secret(),
+            &date,
+            &self.region,
+            SERVICE,
+        );
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={SIGNED_HEADERS}, Signature={}",
+            self.access_key_id,
+            signature(&key, &string_to_sign),
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailProvider for SesProvider {
+    async fn send_email(
+        &self,
+        email: &OutgoingEmail<'_>,
+        timeout: Duration,
+    ) -> Result<(), SendFailure> {
+        let url = self.base_url.join("v2/email/outbound-emails").unwrap();
+        let body = serde_json::to_vec(&SendEmailRequest::from(email))
+            .expect("Failed to serialize the email.");
+        let now = Utc::now();
+        let response = self
+            .http_client
+            .post(url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Amz-Date", amz_date(now))
+            .header(
+                reqwest::header::AUTHORIZATION,
+                self.authorization(&url, &body, now),
+            )
+            .timeout(timeout)
+            .body(body)
+            .send()
+            .await?;
+        let retry_after = retry_after(&response);
+        match response.error_for_status() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(SendFailure {
+                error: e.into(),
+                retry_after,
+            }),
+        }
+    }
+
+    async fn check_reachable(&self, timeout: Duration) -> Result<(), EmailError> {
+        self.http_client
+            .head(self.base_url.clone())
+            .timeout(timeout)
+            .send()
+            .await?;
+        Ok(())
+    }
+}
+
+fn amz_date(now: DateTime<Utc>) -> String {
+    now.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// The `Host` header as reqwest sends it, with the port unless it is the
+/// scheme's default.
+fn host_header(url: &reqwest::Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac_sha256(format!("AWS4{secret_access_key}").as_bytes(), date);
+    let key = hmac_sha256(&key, region);
+    let key = hmac_sha256(&key, service);
+    hmac_sha256(&key, "aws4_request")
+}
+
+fn signature(signing_key: &[u8], string_to_sign: &str) -> String {
+    hex::encode(hmac_sha256(signing_key, string_to_sign))
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from_email_address: &'a str,
+    destination: Destination<'a>,
+    content: Content<'a>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Destination<'a> {
+    to_addresses: [&'a str; 1],
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Content<'a> {
+    simple: SimpleContent<'a>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SimpleContent<'a> {
+    subject: Data<'a>,
+    body: Body<'a>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    headers: &'a [EmailHeader],
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Body<'a> {
+    text: Data<'a>,
+    html: Data<'a>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Data<'a> {
+    data: &'a str,
+}
+
+impl<'a> From<&'a OutgoingEmail<'a>> for SendEmailRequest<'a> {
+    fn from(email: &'a OutgoingEmail<'a>) -> Self {
+        Self {
+            from_email_address: email.from,
+            destination: Destination {
+                to_addresses: [email.to.as_ref()],
+            },
+            content: Content {
+                simple: SimpleContent {
+                    subject: Data {
+                        data: email.subject,
+                    },
+                    body: Body {
+                        text: Data {
+                            data: email.text_body,
+                        },
+                        html: Data {
+                            data: email.html_body,
+                        },
+                    },
+                    headers: &email.headers,
+                },
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDateTime, TimeZone, Utc};
+    use claims::assert_ok;
+    use secrecy::Secret;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::{signature, signing_key, SesProvider};
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::EmailClient;
+
+    fn provider(base_url: String) -> SesProvider {
+        SesProvider::new(
+            base_url,
+            "eu-west-1".into(),
+            "AKIDEXAMPLE".into(),
+            Secret::new("secret".to_string()),
+            std::time::Duration::from_millis(200),
+            reqwest::tls::Version::TLS_1_2,
+        )
+    }
+
+    /// Matches requests whose `Authorization` header is the signature we
+    /// expect for the request as it was received.
+    struct SignedWith(SesProvider);
+
+    impl wiremock::Match for SignedWith {
+        fn matches(&self, request: &wiremock::Request) -> bool {
+            let header = |name: &str| {
+                request
+                    .headers
+                    .iter()
+                    .find(|(header, _)| header.as_str().eq_ignore_ascii_case(name))
+                    .map(|(_, values)| {
+                        // wiremock splits header values on commas.
+                        values
+                            .iter()
+                            .map(|v| v.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    })
+            };
+            let (Some(authorization), Some(amz_date), Some(host)) = (
+                header("Authorization"),
+                header("X-Amz-Date"),
+                header("Host"),
+            ) else {
+                return false;
+            };
+            // wiremock reports every request as made to localhost.
+            let Ok(url) = reqwest::Url::parse(&format!("http://{host}{}", request.url.path()))
+            else {
+                return false;
+            };
+            let Ok(signed_at) = NaiveDateTime::parse_from_str(&amz_date, "%Y%m%dT%H%M%SZ") else {
+                return false;
+            };
+            let expected =
+                self.0
+                    .authorization(&url, &request.body, Utc.from_utc_datetime(&signed_at));
+            authorization == expected
+        }
+    }
+
+    #[test]
+    fn requests_are_signed_as_in_the_aws_example() {
+        // The example from AWS's Signature Version 4 documentation.
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20150830",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex::encode(&key),
+            "c4afb1cc5771d871763a393e44b703571b55cc28424d1a5e86da6ed3c154a4b9"
+        );
+        let string_to_sign = "AWS4-HMAC-SHA256\n\
+            20150830T123600Z\n\
+            20150830/us-east-1/iam/aws4_request\n\
+            f536975d06c0309214f805bb90ccff089219ecd68b2577efef23edd43b7e1a59";
+        assert_eq!(
+            signature(&key, string_to_sign),
+            "5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+    }
+
+    #[tokio::test]
+    async fn emails_are_sent_to_the_outbound_emails_endpoint() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let sender = SubscriberEmail::parse("news@example.com".into()).unwrap();
+        let email_client = EmailClient::with_provider(
+            Box::new(provider(mock_server.uri())),
+            sender,
+            std::time::Duration::from_millis(200),
+        );
+
+        Mock::given(path("/v2/email/outbound-emails"))
+            .and(method("POST"))
+            .and(header("Content-Type", "application/json"))
+            .and(SignedWith(provider(mock_server.uri())))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let recipient = SubscriberEmail::parse("reader@example.com".into()).unwrap();
+
+        // Act
+        let outcome = email_client
+            .send_email(&recipient, "Hello", "<p>Hi</p>", "Hi")
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        let requests = mock_server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["FromEmailAddress"], "news@example.com");
+        assert_eq!(
+            body["Destination"]["ToAddresses"],
+            serde_json::json!(["reader@example.com"])
+        );
+        assert_eq!(body["Content"]["Simple"]["Body"]["Text"]["Data"], "Hi");
+    }
+}
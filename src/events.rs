@@ -0,0 +1,74 @@
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// How many events a slow listener may fall behind before it misses some.
+const CAPACITY: usize = 1024;
+
+/// Something that happened, announced once it has been committed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// Someone subscribed and is waiting to confirm.
+    SubscriberAdded {
+        subscriber_id: Uuid,
+    },
+    /// A subscriber confirmed, or was restored to, their subscription.
+    SubscriberConfirmed {
+        subscriber_id: Uuid,
+    },
+    /// An issue was published and queued for delivery.
+    NewsletterPublished {
+        issue_id: Uuid,
+    },
+}
+
+/// Broadcasts events to every listener within this process, so features
+/// can react to them without the handlers knowing about each feature.
+/// Events emitted while nobody is listening are dropped.
+#[derive(Clone)]
+pub struct EventBus(broadcast::Sender<Event>);
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self(broadcast::channel(CAPACITY).0)
+    }
+}
+
+impl EventBus {
+    pub fn emit(&self, event: Event) {
+        tracing::debug!(?event, "Emitting event.");
+        // Fails only when there are no listeners.
+        let _ = self.0.send(event);
+    }
+
+    /// Receives every event emitted from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.0.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, EventBus};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn every_listener_receives_the_event() {
+        let bus = EventBus::default();
+        let (mut first, mut second) = (bus.subscribe(), bus.subscribe());
+        let event = Event::NewsletterPublished {
+            issue_id: Uuid::new_v4(),
+        };
+
+        bus.emit(event.clone());
+
+        assert_eq!(first.recv().await.unwrap(), event);
+        assert_eq!(second.recv().await.unwrap(), event);
+    }
+
+    #[test]
+    fn emitting_without_listeners_is_fine() {
+        EventBus::default().emit(Event::SubscriberConfirmed {
+            subscriber_id: Uuid::new_v4(),
+        });
+    }
+}
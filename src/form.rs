@@ -0,0 +1,140 @@
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+
+use actix_web::dev::Payload;
+use actix_web::web::Bytes;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use serde::de::DeserializeOwned;
+
+use crate::utils::e400;
+
+/// A drop-in replacement for `web::Form` that rejects bodies which are not
+/// valid UTF-8 (before or after percent-decoding) instead of silently
+/// replacing the offending bytes. Values are trimmed before they are
+/// deserialized, unless `FormWhitespace::Preserve` is configured.
+pub struct Form<T>(pub T);
+
+/// What `Form` does with whitespace around submitted values, e.g. an
+/// address pasted as "  ursula@gmail.com ". Whitespace inside a value, like
+/// the space in a name, is always kept.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FormWhitespace {
+    /// Strip leading and trailing whitespace from every value but passwords,
+    /// where spaces may be part of the secret.
+    #[default]
+    Trim,
+    /// Hand values over exactly as they were submitted.
+    Preserve,
+}
+
+impl<T> Form<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Form<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> FromRequest for Form<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let is_form = req
+            .content_type()
+            .eq_ignore_ascii_case("application/x-www-form-urlencoded");
+        let whitespace = req
+            .app_data::<web::Data<FormWhitespace>>()
+            .map(|whitespace| *whitespace.get_ref())
+            .unwrap_or_default();
+        let body = Bytes::from_request(req, payload);
+
+        Box::pin(async move {
+            if !is_form {
+                return Err(e400(
+                    "Content type must be application/x-www-form-urlencoded",
+                ));
+            }
+            let body = body.await?;
+            if !is_valid_utf8(&body) {
+                return Err(e400("malformed request body"));
+            }
+            let body = match whitespace {
+                FormWhitespace::Trim => trim_values(&body).map_err(e400)?.into(),
+                FormWhitespace::Preserve => body,
+            };
+            serde_urlencoded::from_bytes(&body).map(Form).map_err(e400)
+        })
+    }
+}
+
+/// Re-encodes the body with every value but passwords trimmed.
+fn trim_values(body: &[u8]) -> Result<String, anyhow::Error> {
+    let pairs: Vec<(String, String)> = serde_urlencoded::from_bytes(body)?;
+    let pairs: Vec<_> = pairs
+        .into_iter()
+        .map(|(key, value)| {
+            if key.contains("password") {
+                (key, value)
+            } else {
+                let value = value.trim().to_string();
+                (key, value)
+            }
+        })
+        .collect();
+    Ok(serde_urlencoded::to_string(pairs)?)
+}
+
+fn is_valid_utf8(body: &[u8]) -> bool {
+    std::str::from_utf8(&urlencoding::decode_binary(body)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_valid_utf8, trim_values};
+
+    #[test]
+    fn plain_and_percent_encoded_utf8_is_accepted() {
+        assert!(is_valid_utf8(b"name=le%20guin&email=ursula%40gmail.com"));
+        assert!(is_valid_utf8(
+            "name=Zoë&email=z%C3%AB%40gmail.com".as_bytes()
+        ));
+    }
+
+    #[test]
+    fn raw_invalid_utf8_is_rejected() {
+        assert!(!is_valid_utf8(b"name=le\xffguin&email=ursula%40gmail.com"));
+    }
+
+    #[test]
+    fn percent_encoded_invalid_utf8_is_rejected() {
+        assert!(!is_valid_utf8(b"name=le%FFguin&email=ursula%40gmail.com"));
+    }
+
+    #[test]
+    fn values_are_trimmed_but_keep_their_inner_spaces() {
+        assert_eq!(
+            trim_values(b"name=%20le%20guin%20&email=%20%20ursula%40gmail.com%09").unwrap(),
+            "name=le+guin&email=ursula%40gmail.com"
+        );
+    }
+
+    #[test]
+    fn passwords_are_never_trimmed() {
+        assert_eq!(
+            trim_values(b"username=%20admin&password=%20secret%20").unwrap(),
+            "username=admin&password=+secret+"
+        );
+    }
+}
@@ -1,6 +1,14 @@
+/// A client-chosen key identifying a request, so that retrying it does not
+/// repeat its side effects. Between 1 and `IdempotencyKey::MAX_LENGTH`
+/// characters long.
 #[derive(Debug)]
 pub struct IdempotencyKey(String);
 
+impl IdempotencyKey {
+    /// Enough for a UUID, with room to spare.
+    pub const MAX_LENGTH: usize = 50;
+}
+
 impl TryFrom<String> for IdempotencyKey {
     type Error = anyhow::Error;
 
@@ -8,9 +16,9 @@ impl TryFrom<String> for IdempotencyKey {
         if value.is_empty() {
             anyhow::bail!("The idempotency key cannot be empty");
         }
-        let max_length = 50;
-        if value.len() >= max_length {
-            anyhow::bail!("The idempotency key must be shorter than {max_length} characters");
+        let max_length = Self::MAX_LENGTH;
+        if value.chars().count() > max_length {
+            anyhow::bail!("The idempotency key cannot be longer than {max_length} characters");
         }
         Ok(Self(value))
     }
@@ -27,3 +35,40 @@ impl AsRef<str> for IdempotencyKey {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::IdempotencyKey;
+    use claims::{assert_err, assert_ok};
+
+    fn parse(length: usize) -> Result<IdempotencyKey, anyhow::Error> {
+        IdempotencyKey::try_from("k".repeat(length))
+    }
+
+    #[test]
+    fn an_empty_key_is_rejected() {
+        let error = parse(0).unwrap_err();
+        assert_eq!(error.to_string(), "The idempotency key cannot be empty");
+    }
+
+    #[test]
+    fn keys_of_1_to_50_characters_are_accepted() {
+        assert_ok!(parse(1));
+        assert_ok!(parse(50));
+    }
+
+    #[test]
+    fn a_key_longer_than_50_characters_is_rejected() {
+        let error = parse(51).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The idempotency key cannot be longer than 50 characters"
+        );
+    }
+
+    #[test]
+    fn the_length_is_counted_in_characters() {
+        assert_ok!(IdempotencyKey::try_from("é".repeat(50)));
+        assert_err!(IdempotencyKey::try_from("é".repeat(51)));
+    }
+}
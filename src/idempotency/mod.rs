@@ -1,5 +1,12 @@
 mod key;
 mod persistence;
+mod reuse;
+mod scope;
 
 pub use key::IdempotencyKey;
-pub use persistence::{get_saved_response, save_response, try_processing, NextAction};
+pub use persistence::{
+    get_idempotency_record, get_idempotency_stats, get_saved_response, save_response,
+    try_processing, IdempotencyRecord, IdempotencyStats, NextAction,
+};
+pub use reuse::KeyReusePolicy;
+pub use scope::IdempotencyScope;
@@ -1,9 +1,10 @@
 use actix_web::{body::to_bytes, HttpResponse};
+use chrono::{DateTime, Utc};
 use reqwest::StatusCode;
 use sqlx::{postgres::PgHasArrayType, Executor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-use super::IdempotencyKey;
+use super::{IdempotencyKey, IdempotencyScope, KeyReusePolicy};
 
 #[derive(Debug, sqlx::Type)]
 #[sqlx(type_name = "header_pair")]
@@ -22,6 +23,7 @@ pub async fn get_saved_response(
     pool: &PgPool,
     idempotency_key: &IdempotencyKey,
     user_id: Uuid,
+    scope: IdempotencyScope,
 ) -> Result<Option<HttpResponse>, anyhow::Error> {
     let saved_response = sqlx::query!(
         r#"
@@ -31,10 +33,10 @@ pub async fn get_saved_response(
             response_body as "response_body!"
         FROM idempotency
         WHERE
-            user_id = $1 AND
+            scope = $1 AND
             idempotency_key = $2
         "#,
-        user_id,
+        scope.owner(user_id),
         idempotency_key.as_ref()
     )
     .fetch_optional(pool)
@@ -52,10 +54,105 @@ pub async fn get_saved_response(
     }
 }
 
+/// How much the idempotency table holds, to help tune the TTL.
+#[derive(Debug, serde::Serialize)]
+pub struct IdempotencyStats {
+    pub stored_keys: i64,
+    pub oldest_key_age_seconds: Option<i64>,
+    /// Keys in the last tenth of their TTL.
+    pub expiring_soon: i64,
+    /// Keys past their TTL. These should not pile up once they are purged.
+    pub expired: i64,
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_idempotency_stats(
+    pool: &PgPool,
+    ttl: std::time::Duration,
+) -> Result<IdempotencyStats, anyhow::Error> {
+    let ttl_seconds = ttl.as_secs_f64();
+    let expiring_after_seconds = ttl_seconds * 0.9;
+    let stats = sqlx::query_as!(
+        IdempotencyStats,
+        r#"
+        SELECT
+            COUNT(*) AS "stored_keys!",
+            EXTRACT(EPOCH FROM now() - MIN(created_at))::bigint AS oldest_key_age_seconds,
+            COUNT(*) FILTER (
+                WHERE
+                    created_at <= now() - make_interval(secs => $2) AND
+                    created_at > now() - make_interval(secs => $1)
+            ) AS "expiring_soon!",
+            COUNT(*) FILTER (
+                WHERE created_at <= now() - make_interval(secs => $1)
+            ) AS "expired!"
+        FROM idempotency
+        "#,
+        ttl_seconds,
+        expiring_after_seconds
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(stats)
+}
+
+/// A stored idempotency entry, as kept in the database.
+/// The response fields are empty while the original request is in flight.
+pub struct IdempotencyRecord {
+    pub response_status_code: Option<i16>,
+    pub response_headers: Vec<(String, Vec<u8>)>,
+    pub response_body: Option<Vec<u8>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl IdempotencyRecord {
+    pub fn is_completed(&self) -> bool {
+        self.response_status_code.is_some()
+    }
+}
+
+pub async fn get_idempotency_record(
+    pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+    scope: IdempotencyScope,
+) -> Result<Option<IdempotencyRecord>, anyhow::Error> {
+    let record = sqlx::query!(
+        r#"
+        SELECT
+            response_status_code,
+            response_headers as "response_headers: Vec<HeaderPairRecord>",
+            response_body,
+            created_at
+        FROM idempotency
+        WHERE
+            scope = $1 AND
+            idempotency_key = $2
+        "#,
+        scope.owner(user_id),
+        idempotency_key.as_ref()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record.map(|r| IdempotencyRecord {
+        response_status_code: r.response_status_code,
+        response_headers: r
+            .response_headers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|h| (h.name, h.value))
+            .collect(),
+        response_body: r.response_body,
+        created_at: r.created_at,
+    }))
+}
+
 pub async fn save_response(
     mut transaction: Transaction<'static, Postgres>,
     idempotency_key: &IdempotencyKey,
     user_id: Uuid,
+    scope: IdempotencyScope,
     http_response: HttpResponse,
 ) -> Result<HttpResponse, anyhow::Error> {
     let (response_head, body) = http_response.into_parts();
@@ -79,10 +176,10 @@ pub async fn save_response(
             response_headers = $4,
             response_body = $5
         WHERE
-            user_id = $1 AND
+            scope = $1 AND
             idempotency_key = $2
         "#,
-        user_id,
+        scope.owner(user_id),
         idempotency_key.as_ref(),
         status_code,
         headers,
@@ -95,38 +192,112 @@ pub async fn save_response(
     Ok(http_response)
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum NextAction {
     StartProcessing(Transaction<'static, Postgres>),
     ReturnSavedResponse(HttpResponse),
+    /// The key was saved for a different request, and the policy is to
+    /// reject it.
+    RejectReusedKey,
 }
 
+/// `request_hash` identifies what the request asks for, so that a key
+/// reused for a different request is handled according to `reuse_policy`
+/// rather than answered with the saved response. A key saved more than
+/// `ttl` ago is treated as new, whether or not it has been purged yet.
 pub async fn try_processing(
     pool: &PgPool,
     idempotency_key: &IdempotencyKey,
     user_id: Uuid,
+    scope: IdempotencyScope,
+    request_hash: &str,
+    reuse_policy: KeyReusePolicy,
+    ttl: std::time::Duration,
 ) -> Result<NextAction, anyhow::Error> {
     let mut transaction = pool.begin().await?;
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM idempotency
+        WHERE
+            scope = $1 AND
+            idempotency_key = $2 AND
+            created_at <= now() - make_interval(secs => $3)
+        "#,
+        scope.owner(user_id),
+        idempotency_key.as_ref(),
+        ttl.as_secs_f64()
+    );
+    transaction.execute(query).await?;
     let query = sqlx::query!(
         r#"
         INSERT INTO idempotency (
             user_id,
+            scope,
             idempotency_key,
-            created_at
+            created_at,
+            request_hash
         )
-        VALUES ($1, $2, now())
+        VALUES ($1, $2, $3, now(), $4)
         ON CONFLICT DO NOTHING
         "#,
         user_id,
-        idempotency_key.as_ref()
+        scope.owner(user_id),
+        idempotency_key.as_ref(),
+        request_hash
     );
 
     let n_inserted_rows = transaction.execute(query).await?.rows_affected();
     if n_inserted_rows > 0 {
-        Ok(NextAction::StartProcessing(transaction))
-    } else {
-        let saved_response = get_saved_response(pool, idempotency_key, user_id)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("We expected a saved response, we didn't find it"))?;
-        Ok(NextAction::ReturnSavedResponse(saved_response))
+        return Ok(NextAction::StartProcessing(transaction));
+    }
+    let saved_hash = sqlx::query!(
+        r#"
+        SELECT request_hash
+        FROM idempotency
+        WHERE
+            scope = $1 AND
+            idempotency_key = $2
+        FOR UPDATE
+        "#,
+        scope.owner(user_id),
+        idempotency_key.as_ref()
+    )
+    .fetch_one(&mut *transaction)
+    .await?
+    .request_hash;
+    let reused = saved_hash.is_some_and(|saved_hash| saved_hash != request_hash);
+    match (reused, reuse_policy) {
+        (true, KeyReusePolicy::Strict) => Ok(NextAction::RejectReusedKey),
+        (true, KeyReusePolicy::LatestWins) => {
+            let query = sqlx::query!(
+                r#"
+                UPDATE idempotency
+                SET
+                    request_hash = $3,
+                    created_at = now(),
+                    response_status_code = NULL,
+                    response_headers = NULL,
+                    response_body = NULL
+                WHERE
+                    scope = $1 AND
+                    idempotency_key = $2
+                "#,
+                scope.owner(user_id),
+                idempotency_key.as_ref(),
+                request_hash
+            );
+            transaction.execute(query).await?;
+            Ok(NextAction::StartProcessing(transaction))
+        }
+        (false, _) => {
+            // Releases the row lock taken above.
+            transaction.rollback().await?;
+            let saved_response = get_saved_response(pool, idempotency_key, user_id, scope)
+                .await?
+                .ok_or_else(|| {
+                    anyhow::anyhow!("We expected a saved response, we didn't find it")
+                })?;
+            Ok(NextAction::ReturnSavedResponse(saved_response))
+        }
     }
 }
@@ -0,0 +1,27 @@
+/// What happens when a key is sent again with a different request, e.g. a
+/// client that reused a key for a new issue by mistake.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyReusePolicy {
+    /// The new request is rejected with a 409 Conflict and the saved
+    /// response is kept.
+    #[default]
+    Strict,
+    /// The new request is processed and its response replaces the saved
+    /// one.
+    #[serde(alias = "latest_wins")]
+    LatestWins,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeyReusePolicy;
+
+    #[test]
+    fn policies_are_configured_in_kebab_case() {
+        let policy: KeyReusePolicy = serde_json::from_str(r#""latest-wins""#).unwrap();
+        assert_eq!(policy, KeyReusePolicy::LatestWins);
+        let policy: KeyReusePolicy = serde_json::from_str(r#""strict""#).unwrap();
+        assert_eq!(policy, KeyReusePolicy::Strict);
+    }
+}
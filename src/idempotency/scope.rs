@@ -0,0 +1,27 @@
+use uuid::Uuid;
+
+/// Who an idempotency key belongs to.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IdempotencyScope {
+    /// Each user has their own keys: the same key sent by two users starts
+    /// two separate requests.
+    #[default]
+    PerUser,
+    /// Keys are shared by every user, e.g. when several clients publish
+    /// with the same API credentials. A key reused by another user gets the
+    /// first user's saved response, including its body, instead of being
+    /// processed. Clients must generate keys that are unique across all of
+    /// them, e.g. UUIDs.
+    Global,
+}
+
+impl IdempotencyScope {
+    /// What keys are stored under for requests sent by `user_id`.
+    pub(super) fn owner(&self, user_id: Uuid) -> String {
+        match self {
+            IdempotencyScope::PerUser => user_id.to_string(),
+            IdempotencyScope::Global => "global".into(),
+        }
+    }
+}
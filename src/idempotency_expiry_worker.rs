@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+use crate::configuration::{IdempotencySettings, Settings};
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::startup::get_connection_pool;
+
+/// How many expired keys are deleted per statement, to keep each
+/// transaction short when a backlog has built up.
+const BATCH_SIZE: i64 = 1000;
+
+pub async fn run_idempotency_expiry_worker_until_stopped(
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    worker_loop(connection_pool, configuration.idempotency).await
+}
+
+async fn worker_loop(pool: PgPool, settings: IdempotencySettings) -> Result<(), anyhow::Error> {
+    loop {
+        match try_delete_expired_keys(&pool, &settings).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+/// Deletes a batch of saved responses older than the idempotency TTL.
+/// Their keys can then be used again, and are processed as new requests.
+#[tracing::instrument(skip_all, err)]
+pub async fn try_delete_expired_keys(
+    pool: &PgPool,
+    settings: &IdempotencySettings,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let n_deleted = sqlx::query!(
+        r#"
+        DELETE FROM idempotency
+        WHERE (scope, idempotency_key) IN (
+            SELECT scope, idempotency_key
+            FROM idempotency
+            WHERE created_at <= now() - make_interval(secs => $1)
+            FOR UPDATE
+            SKIP LOCKED
+            LIMIT $2
+        )
+        "#,
+        settings.ttl().as_secs_f64(),
+        BATCH_SIZE
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    if n_deleted == 0 {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    }
+    tracing::info!(n_deleted, "Deleted expired idempotency keys.");
+    Ok(ExecutionOutcome::TaskCompleted)
+}
@@ -1,33 +1,74 @@
+use std::num::NonZeroU32;
 use std::time::Duration;
 
+use anyhow::Context;
 use sqlx::{Executor, PgPool, Postgres, Transaction};
 use tracing::field::display;
 use tracing::Span;
 use uuid::Uuid;
 
-use crate::configuration::Settings;
-use crate::domain::SubscriberEmail;
-use crate::email_client::EmailClient;
+use crate::configuration::{NewsletterSettings, Settings};
+use crate::domain::{ConfirmedSubscriber, SenderName, SubscriberEmail};
+use crate::email_client::{BatchMessage, EmailClient, EmailThread};
+use crate::minify;
+use crate::routes::generate_subscription_token;
+use crate::shutdown::ShutdownSignal;
 use crate::startup::get_connection_pool;
 
-pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+pub async fn run_worker_until_stopped(
+    configuration: Settings,
+    shutdown: ShutdownSignal,
+) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
-    let email_client = configuration.email_client.client();
-    worker_loop(connection_pool, email_client).await
+    let batch_size = configuration.email_client.batch_size;
+    let minify_html = configuration.newsletter.minify_html;
+    let issue_slots = IssueSlots::from_settings(&configuration.newsletter);
+    let email_client = configuration.email_client.broadcast_client();
+    worker_loop(
+        &connection_pool,
+        &email_client,
+        batch_size,
+        minify_html,
+        &configuration.application.base_url,
+        issue_slots,
+        shutdown,
+    )
+    .await
 }
 
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
-    loop {
-        match try_execute_task(&pool, &email_client).await {
+/// Delivers batches until `shutdown` is triggered. The signal is only
+/// checked between batches, so a batch that has started is always finished.
+pub async fn worker_loop(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    batch_size: usize,
+    minify_html: bool,
+    base_url: &str,
+    issue_slots: Option<IssueSlots>,
+    mut shutdown: ShutdownSignal,
+) -> Result<(), anyhow::Error> {
+    while !shutdown.is_triggered() {
+        match try_execute_task(
+            pool,
+            email_client,
+            batch_size,
+            minify_html,
+            base_url,
+            issue_slots,
+        )
+        .await
+        {
             Ok(ExecutionOutcome::EmptyQueue) => {
-                tokio::time::sleep(Duration::from_secs(10)).await;
+                shutdown.sleep(Duration::from_secs(10)).await;
             }
             Err(_) => {
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                shutdown.sleep(Duration::from_secs(1)).await;
             }
             Ok(ExecutionOutcome::TaskCompleted) => {}
         }
     }
+    tracing::info!("Stopping the newsletter delivery worker for shutdown.");
+    Ok(())
 }
 
 pub enum ExecutionOutcome {
@@ -35,108 +76,499 @@ pub enum ExecutionOutcome {
     EmptyQueue,
 }
 
+/// How many issues may be sent from at the same time. An issue keeps its
+/// slot until none of its deliveries are left to make, including those held
+/// back until a scheduled time and failures that will be retried.
+#[derive(Debug, Clone, Copy)]
+pub struct IssueSlots {
+    pub max_in_flight: NonZeroU32,
+    /// Failed deliveries retried fewer times than this will be retried again.
+    pub max_auto_retries: i16,
+}
+
+impl IssueSlots {
+    /// `None` when the number of issues in flight is not limited.
+    pub fn from_settings(settings: &NewsletterSettings) -> Option<Self> {
+        let retry = &settings.dead_letter_retry;
+        settings.max_issues_in_flight.map(|max_in_flight| Self {
+            max_in_flight,
+            max_auto_retries: if retry.enabled { retry.max_retries } else { 0 },
+        })
+    }
+
+    fn max_in_flight(&self) -> i64 {
+        i64::from(self.max_in_flight.get())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    Pending,
+    Sent,
+    Failed,
+    /// Held back because the recipient is not on the allowlist.
+    Skipped,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Sent => "sent",
+            DeliveryStatus::Failed => "failed",
+            DeliveryStatus::Skipped => "skipped",
+        }
+    }
+}
+
+/// The order in which queued deliveries are picked up. Higher priority
+/// deliveries go out first; within a priority, the oldest go first.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryPriority {
+    /// Backfills, e.g. resending an issue to the subscribers who missed it.
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl DeliveryPriority {
+    pub fn value(&self) -> i16 {
+        match self {
+            DeliveryPriority::Low => -10,
+            DeliveryPriority::Normal => 0,
+            DeliveryPriority::High => 10,
+        }
+    }
+}
+
+struct Task {
+    delivery_id: Uuid,
+    subscriber_email: String,
+    subscriber_id: Option<Uuid>,
+    subscriber_name: Option<String>,
+}
+
+impl Task {
+    /// The recipient's stored details. Fails if they are invalid or if the
+    /// address no longer belongs to a confirmed subscriber.
+    fn subscriber(&self) -> Result<ConfirmedSubscriber, String> {
+        match (self.subscriber_id, &self.subscriber_name) {
+            (Some(id), Some(name)) => {
+                ConfirmedSubscriber::parse(id, name.clone(), self.subscriber_email.clone())
+            }
+            _ => Err(format!(
+                "{} is no longer a confirmed subscriber.",
+                self.subscriber_email
+            )),
+        }
+    }
+}
+
+/// Why a delivery failed, and whether trying again later might succeed.
+struct Failure {
+    message: String,
+    is_transient: bool,
+}
+
+type Outcome = (DeliveryStatus, Option<Failure>);
+
+/// Delivers up to `batch_size` pending emails of a single issue.
+/// With a `batch_size` of 1 every email is its own API call, otherwise
+/// they are sent together through the batch endpoint. Every email carries
+/// its recipient's unsubscribe link, built on `base_url`.
 #[tracing::instrument(
     skip_all,
     fields(
         newsletter_issue_id=tracing::field::Empty,
-        subscriber_email=tracing::field::Empty
+        n_tasks=tracing::field::Empty
     ),
     err
 )]
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
+    batch_size: usize,
+    minify_html: bool,
+    base_url: &str,
+    issue_slots: Option<IssueSlots>,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
-    let task = dequeue_task(pool).await?;
-    if task.is_none() {
+    let Some((mut transaction, issue_id, tasks)) =
+        dequeue_tasks(pool, batch_size, issue_slots).await?
+    else {
         return Ok(ExecutionOutcome::EmptyQueue);
-    }
-    let (transaction, issue_id, email) = task.unwrap();
+    };
     Span::current()
-        .record("newsletter_issue_id", &display(issue_id))
-        .record("subscriber_email", &display(&email));
-    match SubscriberEmail::parse(email.clone()) {
-        Ok(email) => {
-            let issue = get_issue(pool, issue_id).await?;
-            if let Err(e) = email_client
-                .send_email(
-                    &email,
-                    &issue.title,
-                    &issue.html_content,
-                    &issue.text_content,
-                )
-                .await
-            {
-                tracing::error!(
-                    error.cause_chain = ?e,
-                    error.message = %e,
-                    "Failed to deliver issue to a confirmed subscriber. \
-                        Skipping.",
-                );
+        .record("newsletter_issue_id", display(issue_id))
+        .record("n_tasks", tasks.len());
+    let mut issue = get_issue(pool, issue_id).await?;
+    if minify_html {
+        issue.html_content = minify::minify_html(&issue.html_content);
+    }
+    let mut bodies = Vec::with_capacity(tasks.len());
+    for task in &tasks {
+        let unsubscribe_link = match task.subscriber_id {
+            Some(subscriber_id) => {
+                let token =
+                    get_or_create_unsubscribe_token(&mut transaction, subscriber_id).await?;
+                Some(format!(
+                    "{base_url}/subscriptions/unsubscribe?token={token}"
+                ))
             }
+            None => None,
+        };
+        bodies.push(issue.body_for(unsubscribe_link.as_deref()));
+    }
+    let outcomes = if batch_size > 1 {
+        deliver_batch(email_client, issue_id, &issue.title, &tasks, &bodies).await
+    } else {
+        let mut outcomes = Vec::with_capacity(tasks.len());
+        for (task, body) in tasks.iter().zip(&bodies) {
+            let outcome = match recipient(email_client, task) {
+                Ok((subscriber, _)) if !email_client.allows(&subscriber.email) => {
+                    not_allowed(&subscriber.email)
+                }
+                Ok((subscriber, from_name)) => {
+                    deliver(
+                        email_client,
+                        &issue.title,
+                        body,
+                        thread(issue_id, task),
+                        &subscriber.email,
+                        from_name.as_ref(),
+                    )
+                    .await
+                }
+                Err(e) => undeliverable(e),
+            };
+            outcomes.push(outcome);
         }
+        outcomes
+    };
+    for (task, (status, failure)) in tasks.iter().zip(outcomes) {
+        complete_task(&mut transaction, task.delivery_id, status, failure.as_ref()).await?;
+    }
+    transaction.commit().await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// Sends the whole batch through the batch endpoint. Recipients the batch
+/// could not reach are retried one at a time, so only those that still fail are
+/// marked as failed. Recipients not on the allowlist are left out.
+async fn deliver_batch(
+    email_client: &EmailClient,
+    issue_id: Uuid,
+    subject: &str,
+    tasks: &[Task],
+    bodies: &[Body],
+) -> Vec<Outcome> {
+    let subscribers: Vec<_> = tasks
+        .iter()
+        .map(|task| recipient(email_client, task))
+        .collect();
+    let messages: Vec<_> = subscribers
+        .iter()
+        .zip(tasks.iter().zip(bodies))
+        .filter_map(|(s, task_and_body)| Some((s.as_ref().ok()?, task_and_body)))
+        .filter(|((s, _), _)| email_client.allows(&s.email))
+        .map(|((s, from_name), (task, body))| BatchMessage {
+            recipient: &s.email,
+            from_name: from_name.as_ref(),
+            html_content: &body.html_content,
+            text_content: &body.text_content,
+            thread: Some(thread(issue_id, task)),
+        })
+        .collect();
+    let batch_results = email_client.send_email_batch(&messages, subject).await;
+
+    let mut batch_results = batch_results.into_iter();
+    let mut outcomes = Vec::with_capacity(tasks.len());
+    for ((subscriber, task), body) in subscribers.into_iter().zip(tasks).zip(bodies) {
+        let outcome = match subscriber {
+            Ok((subscriber, _)) if !email_client.allows(&subscriber.email) => {
+                not_allowed(&subscriber.email)
+            }
+            Ok((subscriber, from_name)) => match batch_results.next() {
+                Some(Ok(())) => (DeliveryStatus::Sent, None),
+                _ => {
+                    deliver(
+                        email_client,
+                        subject,
+                        body,
+                        thread(issue_id, task),
+                        &subscriber.email,
+                        from_name.as_ref(),
+                    )
+                    .await
+                }
+            },
+            Err(e) => undeliverable(e),
+        };
+        outcomes.push(outcome);
+    }
+    outcomes
+}
+
+/// The recipient of a task together with the sender name personalised for
+/// them. A name that cannot be turned into a safe sender name makes the
+/// recipient undeliverable rather than being sent as is.
+fn recipient(
+    email_client: &EmailClient,
+    task: &Task,
+) -> Result<(ConfirmedSubscriber, Option<SenderName>), String> {
+    let subscriber = task.subscriber()?;
+    let from_name = email_client.sender_name_for(&subscriber.name)?;
+    Ok((subscriber, from_name))
+}
+
+/// Every copy of an issue belongs to the issue's thread.
+fn thread(issue_id: Uuid, task: &Task) -> EmailThread {
+    EmailThread {
+        thread_id: issue_id,
+        message_id: task.delivery_id,
+    }
+}
+
+async fn deliver(
+    email_client: &EmailClient,
+    subject: &str,
+    body: &Body,
+    thread: EmailThread,
+    email: &SubscriberEmail,
+    from_name: Option<&SenderName>,
+) -> Outcome {
+    match email_client
+        .send_email_as(
+            from_name,
+            Some(&thread),
+            email,
+            subject,
+            &body.html_content,
+            &body.text_content,
+        )
+        .await
+    {
+        Ok(()) => (DeliveryStatus::Sent, None),
         Err(e) => {
             tracing::error!(
                 error.cause_chain = ?e,
                 error.message = %e,
-                "Skipping a confirmed subscriber. \
-                    Their stored contact details are invalid",
+                error.kind = e.kind(),
+                subscriber_email = %email,
+                "Failed to deliver issue to a confirmed subscriber. \
+                    Skipping.",
             );
+            let failure = Failure {
+                message: e.to_string(),
+                is_transient: e.is_transient(),
+            };
+            (DeliveryStatus::Failed, Some(failure))
         }
     }
-    delete_task(transaction, issue_id, &email).await?;
-    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+fn not_allowed(email: &SubscriberEmail) -> Outcome {
+    tracing::info!(
+        subscriber_email = %email,
+        "Skipping a recipient who is not on the allowlist.",
+    );
+    (DeliveryStatus::Skipped, None)
+}
+
+fn undeliverable(e: String) -> Outcome {
+    tracing::error!(
+        error.message = %e,
+        "Skipping a recipient. \
+            Their stored details are invalid or they are no longer confirmed",
+    );
+    let failure = Failure {
+        message: e,
+        is_transient: false,
+    };
+    (DeliveryStatus::Failed, Some(failure))
 }
 
 type PgTransaction = Transaction<'static, Postgres>;
 
+/// Whether the issue has deliveries due but is not sent from yet, because
+/// as many older issues as there are slots are still sending.
+#[tracing::instrument(skip(pool))]
+pub async fn is_waiting_for_a_slot(
+    pool: &PgPool,
+    issue_id: Uuid,
+    issue_slots: Option<IssueSlots>,
+) -> Result<bool, anyhow::Error> {
+    let Some(issue_slots) = issue_slots else {
+        return Ok(false);
+    };
+    let waiting = sqlx::query!(
+        r#"
+        WITH in_flight AS (
+            SELECT newsletter_issue_id, MIN(created_at) AS queued_at
+            FROM issue_delivery_queue
+            WHERE
+                (
+                    (status = 'pending' AND (not_before IS NULL OR not_before <= now())) OR
+                    (status = 'failed' AND transient_failure AND auto_retries < $3)
+                ) AND
+                NOT EXISTS (
+                    SELECT 1 FROM subscriptions
+                    WHERE
+                        subscriptions.email = issue_delivery_queue.subscriber_email AND
+                        subscriptions.suppressed_until > now()
+                )
+            GROUP BY newsletter_issue_id
+        )
+        SELECT COUNT(*) >= $2 AS "waiting!"
+        FROM in_flight
+        WHERE queued_at < (SELECT queued_at FROM in_flight WHERE newsletter_issue_id = $1)
+        "#,
+        issue_id,
+        issue_slots.max_in_flight(),
+        issue_slots.max_auto_retries
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to check whether the issue is waiting to be sent.")?
+    .waiting;
+    Ok(waiting)
+}
+
+/// Locks up to `n` pending deliveries, all belonging to the same issue.
+/// Deliveries to paused subscribers stay queued until the pause ends.
+/// Only as many issues as there are slots are sent from, oldest first; the
+/// others wait for one of them to finish. An issue whose remaining
+/// deliveries are waiting to be retried keeps its slot, while one that is
+/// scheduled for later only takes a slot once it is due.
 #[tracing::instrument(skip_all)]
-async fn dequeue_task(
+async fn dequeue_tasks(
     pool: &PgPool,
-) -> Result<Option<(PgTransaction, Uuid, String)>, anyhow::Error> {
+    n: usize,
+    issue_slots: Option<IssueSlots>,
+) -> Result<Option<(PgTransaction, Uuid, Vec<Task>)>, anyhow::Error> {
     let mut transaction = pool.begin().await?;
-    let r = sqlx::query!(
+    let Some(issue_id) = sqlx::query!(
         r#"
-        SELECT newsletter_issue_id, subscriber_email
+        WITH in_flight AS (
+            SELECT newsletter_issue_id
+            FROM issue_delivery_queue
+            WHERE
+                (
+                    (status = 'pending' AND (not_before IS NULL OR not_before <= now())) OR
+                    (status = 'failed' AND transient_failure AND auto_retries < $2)
+                ) AND
+                NOT EXISTS (
+                    SELECT 1 FROM subscriptions
+                    WHERE
+                        subscriptions.email = issue_delivery_queue.subscriber_email AND
+                        subscriptions.suppressed_until > now()
+                )
+            GROUP BY newsletter_issue_id
+            ORDER BY MIN(created_at)
+            LIMIT $1
+        )
+        SELECT newsletter_issue_id
         FROM issue_delivery_queue
+        WHERE
+            status = 'pending' AND
+            (not_before IS NULL OR not_before <= now()) AND
+            NOT EXISTS (
+                SELECT 1 FROM subscriptions
+                WHERE
+                    subscriptions.email = issue_delivery_queue.subscriber_email AND
+                    subscriptions.suppressed_until > now()
+            ) AND
+            newsletter_issue_id IN (SELECT newsletter_issue_id FROM in_flight)
+        ORDER BY priority DESC, created_at
         FOR UPDATE
         SKIP LOCKED
         LIMIT 1
-        "#
+        "#,
+        issue_slots.map(|slots| slots.max_in_flight()),
+        issue_slots.map_or(0, |slots| slots.max_auto_retries)
     )
     .fetch_optional(&mut *transaction)
-    .await?;
+    .await?
+    .map(|r| r.newsletter_issue_id) else {
+        return Ok(None);
+    };
 
-    if let Some(r) = r {
-        Ok(Some((
-            transaction,
-            r.newsletter_issue_id,
-            r.subscriber_email,
-        )))
-    } else {
-        Ok(None)
-    }
+    let tasks = sqlx::query_as!(
+        Task,
+        r#"
+        SELECT
+            issue_delivery_queue.delivery_id,
+            issue_delivery_queue.subscriber_email,
+            subscriptions.id AS "subscriber_id?",
+            subscriptions.name AS "subscriber_name?"
+        FROM issue_delivery_queue
+        LEFT JOIN subscriptions ON
+            subscriptions.email = issue_delivery_queue.subscriber_email AND
+            subscriptions.status = 'confirmed'
+        WHERE
+            issue_delivery_queue.status = 'pending' AND
+            issue_delivery_queue.newsletter_issue_id = $1 AND
+            (issue_delivery_queue.not_before IS NULL OR issue_delivery_queue.not_before <= now()) AND
+            NOT EXISTS (
+                SELECT 1 FROM subscriptions paused
+                WHERE
+                    paused.email = issue_delivery_queue.subscriber_email AND
+                    paused.suppressed_until > now()
+            )
+        ORDER BY issue_delivery_queue.priority DESC, issue_delivery_queue.created_at
+        FOR UPDATE OF issue_delivery_queue
+        SKIP LOCKED
+        LIMIT $2
+        "#,
+        issue_id,
+        n.max(1) as i64
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    Ok(Some((transaction, issue_id, tasks)))
 }
 
 #[tracing::instrument(skip_all)]
-async fn delete_task(
-    mut transaction: PgTransaction,
-    issue_id: Uuid,
-    email: &str,
+async fn complete_task(
+    transaction: &mut PgTransaction,
+    delivery_id: Uuid,
+    status: DeliveryStatus,
+    failure: Option<&Failure>,
 ) -> Result<(), anyhow::Error> {
     let query = sqlx::query!(
         r#"
-        DELETE FROM issue_delivery_queue
+        UPDATE issue_delivery_queue
+        SET
+            status = $2,
+            processed_at = now(),
+            transient_failure = $3
         WHERE
-            newsletter_issue_id = $1 AND
-            subscriber_email = $2
+            delivery_id = $1
         "#,
-        issue_id,
-        email
+        delivery_id,
+        status.as_str(),
+        failure.is_some_and(|f| f.is_transient)
     );
+    transaction.execute(query).await?;
 
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO delivery_attempts (
+            delivery_attempt_id,
+            delivery_id,
+            attempted_at,
+            result,
+            error
+        )
+        VALUES ($1, $2, now(), $3, $4)
+        "#,
+        Uuid::new_v4(),
+        delivery_id,
+        status.as_str(),
+        failure.map(|f| f.message.as_str())
+    );
     transaction.execute(query).await?;
-    transaction.commit().await?;
     Ok(())
 }
 
@@ -146,6 +578,64 @@ struct NewsletterIssue {
     html_content: String,
 }
 
+/// The content of an issue as sent to one recipient.
+struct Body {
+    text_content: String,
+    html_content: String,
+}
+
+impl NewsletterIssue {
+    fn body_for(&self, unsubscribe_link: Option<&str>) -> Body {
+        let Some(link) = unsubscribe_link else {
+            return Body {
+                text_content: self.text_content.clone(),
+                html_content: self.html_content.clone(),
+            };
+        };
+        let text_content = format!("{}\n\nUnsubscribe: {link}", self.text_content);
+        let footer = format!(r#"<p><a href="{link}">Unsubscribe</a></p>"#);
+        // Keep the footer inside the document if the issue is a full one.
+        let html_content = match self.html_content.to_ascii_lowercase().rfind("</body>") {
+            Some(i) => format!(
+                "{}{footer}{}",
+                &self.html_content[..i],
+                &self.html_content[i..]
+            ),
+            None => format!("{}{footer}", self.html_content),
+        };
+        Body {
+            text_content,
+            html_content,
+        }
+    }
+}
+
+/// Subscribers keep the same unsubscribe token for every issue.
+#[tracing::instrument(skip(transaction))]
+async fn get_or_create_unsubscribe_token(
+    transaction: &mut PgTransaction,
+    subscriber_id: Uuid,
+) -> Result<String, anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO unsubscribe_tokens (unsubscribe_token, subscriber_id)
+        VALUES ($1, $2)
+        ON CONFLICT (subscriber_id) DO NOTHING
+        "#,
+        generate_subscription_token(),
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+    let token = sqlx::query!(
+        "SELECT unsubscribe_token FROM unsubscribe_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .fetch_one(&mut **transaction)
+    .await?
+    .unsubscribe_token;
+    Ok(token)
+}
+
 #[tracing::instrument(skip_all)]
 async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
     let issue = sqlx::query_as!(
@@ -1,11 +1,26 @@
+pub mod audit_log;
 pub mod authentication;
 pub mod configuration;
+pub mod confirmation_reminder_worker;
+pub mod dead_letter_retry_worker;
 pub mod domain;
 pub mod email_client;
+pub mod events;
+pub mod form;
 pub mod idempotency;
+pub mod idempotency_expiry_worker;
 pub mod issue_delivery_worker;
+pub mod minify;
+pub mod pending_expiry_worker;
+pub mod rate_limit;
+pub mod read_only;
+pub mod request_deadline;
 pub mod routes;
+pub mod secrets;
+pub mod security_headers;
 pub mod session_state;
+pub mod shutdown;
 pub mod startup;
 pub mod telemetry;
 pub mod utils;
+pub mod webhook_delivery_worker;
@@ -1,9 +1,15 @@
 use std::fmt::{Debug, Display};
 use tokio::task::JoinError;
 use zero2prod::configuration::get_configuration;
+use zero2prod::confirmation_reminder_worker::run_reminder_worker_until_stopped;
+use zero2prod::dead_letter_retry_worker::run_dead_letter_retry_worker_until_stopped;
+use zero2prod::idempotency_expiry_worker::run_idempotency_expiry_worker_until_stopped;
 use zero2prod::issue_delivery_worker::run_worker_until_stopped;
+use zero2prod::pending_expiry_worker::run_pending_expiry_worker_until_stopped;
+use zero2prod::shutdown::{shutdown_channel, wait_for_termination};
 use zero2prod::startup::Application;
 use zero2prod::telemetry::{get_subscriber, init_subsciber};
+use zero2prod::webhook_delivery_worker::run_webhook_worker_until_stopped;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -12,15 +18,57 @@ async fn main() -> anyhow::Result<()> {
 
     let configuration = get_configuration().expect("Failed to read configuration.");
 
+    let shutdown_grace = configuration.application.shutdown_grace();
+    let (shutdown_trigger, shutdown) = shutdown_channel();
+    // The API listens for the signal itself; the delivery worker is told
+    // through the shutdown channel.
+    tokio::spawn({
+        let shutdown_trigger = shutdown_trigger.clone();
+        async move {
+            wait_for_termination().await;
+            tracing::info!("Shutting down");
+            shutdown_trigger.trigger();
+        }
+    });
+
     let application = Application::build(configuration.clone()).await?;
+    let confirmation_email_limiter = application.confirmation_email_limiter().clone();
     let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    let mut worker_task = tokio::spawn(run_worker_until_stopped(configuration.clone(), shutdown));
+    let webhook_worker_task = tokio::spawn(run_webhook_worker_until_stopped(configuration.clone()));
+    let reminder_worker_task = tokio::spawn(run_reminder_worker_until_stopped(
+        configuration.clone(),
+        confirmation_email_limiter,
+    ));
+    let dead_letter_worker_task = tokio::spawn(run_dead_letter_retry_worker_until_stopped(
+        configuration.clone(),
+    ));
+    let pending_expiry_worker_task = tokio::spawn(run_pending_expiry_worker_until_stopped(
+        configuration.clone(),
+    ));
+    let idempotency_expiry_worker_task =
+        tokio::spawn(run_idempotency_expiry_worker_until_stopped(configuration));
 
     tokio::select! {
         o = application_task => report_exit("API", o),
-        o = worker_task => report_exit("Background worker", o),
+        o = &mut worker_task => report_exit("Background worker", o),
+        o = webhook_worker_task => report_exit("Webhook worker", o),
+        o = reminder_worker_task => report_exit("Confirmation reminder worker", o),
+        o = dead_letter_worker_task => report_exit("Dead letter retry worker", o),
+        o = pending_expiry_worker_task => report_exit("Pending expiry worker", o),
+        o = idempotency_expiry_worker_task => report_exit("Idempotency expiry worker", o),
     };
 
+    // Whatever stopped first, let the delivery worker finish the batch it
+    // is sending rather than cutting it off halfway.
+    shutdown_trigger.trigger();
+    if !worker_task.is_finished() {
+        match tokio::time::timeout(shutdown_grace, worker_task).await {
+            Ok(o) => report_exit("Background worker", o),
+            Err(_) => tracing::warn!("Background worker did not stop within the grace period"),
+        }
+    }
+
     Ok(())
 }
 
@@ -0,0 +1,115 @@
+/// Elements whose content is rendered (or executed) exactly as written.
+const RAW_TEXT_ELEMENTS: [&str; 4] = ["pre", "textarea", "script", "style"];
+
+/// Shrinks an email's HTML without changing how it renders: comments are
+/// dropped and runs of whitespace in text collapse into a single space.
+/// Tags, attribute values, the content of `RAW_TEXT_ELEMENTS` and Outlook's
+/// conditional comments (`<!--[if mso]>`) are kept as they are.
+pub fn minify_html(html: &str) -> String {
+    let mut minified = String::with_capacity(html.len());
+    let mut rest = html;
+    while !rest.is_empty() {
+        if rest.starts_with("<!--") && !rest.starts_with("<!--[if") {
+            rest = match rest.find("-->") {
+                Some(end) => &rest[end + "-->".len()..],
+                None => "",
+            };
+        } else if rest.starts_with('<') {
+            let tag_end = find_tag_end(rest);
+            let tag = &rest[..tag_end];
+            minified.push_str(tag);
+            rest = &rest[tag_end..];
+            if let Some(element) = raw_text_element(tag) {
+                let closing_tag = format!("</{element}");
+                let content_end = rest
+                    .to_ascii_lowercase()
+                    .find(&closing_tag)
+                    .unwrap_or(rest.len());
+                minified.push_str(&rest[..content_end]);
+                rest = &rest[content_end..];
+            }
+        } else {
+            let text_end = rest.find('<').unwrap_or(rest.len());
+            collapse_whitespace(&rest[..text_end], &mut minified);
+            rest = &rest[text_end..];
+        }
+    }
+    minified
+}
+
+/// The index just past the `>` closing the tag `s` starts with, ignoring
+/// any `>` inside quoted attribute values.
+fn find_tag_end(s: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return i + 1,
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+fn raw_text_element(tag: &str) -> Option<&'static str> {
+    let name: String = tag
+        .trim_start_matches('<')
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    RAW_TEXT_ELEMENTS.into_iter().find(|e| *e == name)
+}
+
+fn collapse_whitespace(text: &str, into: &mut String) {
+    let mut previous_was_whitespace = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !previous_was_whitespace {
+                into.push(' ');
+            }
+            previous_was_whitespace = true;
+        } else {
+            into.push(c);
+            previous_was_whitespace = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::minify_html;
+
+    #[test]
+    fn whitespace_in_text_is_collapsed() {
+        let html = "<p>\n    Hello,\n\n    world!\n</p>\n\n<p>Bye</p>";
+        assert_eq!(minify_html(html), "<p> Hello, world! </p> <p>Bye</p>");
+    }
+
+    #[test]
+    fn comments_are_dropped() {
+        assert_eq!(minify_html("<p>a<!-- note -->b</p>"), "<p>ab</p>");
+    }
+
+    #[test]
+    fn conditional_comments_are_kept() {
+        let html = "<!--[if mso]><table><![endif]-->";
+        assert_eq!(minify_html(html), html);
+    }
+
+    #[test]
+    fn preformatted_content_is_kept_verbatim() {
+        let html = "<div>\n  <PRE class=\"code\">fn main() {\n    42\n}</PRE>\n</div>";
+        assert_eq!(
+            minify_html(html),
+            "<div> <PRE class=\"code\">fn main() {\n    42\n}</PRE> </div>"
+        );
+    }
+
+    #[test]
+    fn attribute_values_are_kept_verbatim() {
+        let html = r#"<a title="a  >  b" href="/x">link</a>"#;
+        assert_eq!(minify_html(html), html);
+    }
+}
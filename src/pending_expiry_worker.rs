@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use tracing::field::display;
+use tracing::Span;
+use uuid::Uuid;
+
+use crate::configuration::{PendingExpirySettings, Settings};
+use crate::domain::SubscriberEmail;
+use crate::email_client::{EmailClient, EmailError};
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::startup::get_connection_pool;
+
+pub async fn run_pending_expiry_worker_until_stopped(
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    let email_client = configuration.email_client.client();
+    worker_loop(
+        connection_pool,
+        email_client,
+        configuration.subscriptions.pending_expiry,
+        configuration.application.base_url,
+    )
+    .await
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    settings: PendingExpirySettings,
+    base_url: String,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_purge_expired_subscriber(&pool, &email_client, &settings, &base_url).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+/// Deletes one subscriber who has been waiting to confirm for longer than
+/// the configured maximum age, emailing them first if `notify` is set.
+/// The notification is a courtesy: if it cannot be sent the subscriber is
+/// purged anyway.
+#[tracing::instrument(
+    skip_all,
+    fields(subscriber_id=tracing::field::Empty),
+    err
+)]
+pub async fn try_purge_expired_subscriber(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    settings: &PendingExpirySettings,
+    base_url: &str,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    if !settings.enabled {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    }
+    let Some((transaction, task)) = dequeue_task(pool, settings.max_age()).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current().record("subscriber_id", display(task.subscriber_id));
+    if settings.notify {
+        let outcome = match SubscriberEmail::parse(task.email) {
+            Ok(email) => send_expiry_email(email_client, &email, base_url)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = outcome {
+            tracing::warn!(
+                error.message = %e,
+                "Failed to tell a subscriber their confirmation expired. Purging them anyway.",
+            );
+        }
+    }
+    delete_subscriber(transaction, task.subscriber_id).await
+}
+
+struct ExpiryTask {
+    subscriber_id: Uuid,
+    email: String,
+}
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_task(
+    pool: &PgPool,
+    max_age: Duration,
+) -> Result<Option<(PgTransaction, ExpiryTask)>, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let task = sqlx::query_as!(
+        ExpiryTask,
+        r#"
+        SELECT id AS subscriber_id, email
+        FROM subscriptions
+        WHERE
+            status = 'pending_confirmation' AND
+            subscribed_at <= now() - make_interval(secs => $1)
+        ORDER BY subscribed_at
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+        max_age.as_secs_f64()
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    Ok(task.map(|t| (transaction, t)))
+}
+
+async fn send_expiry_email(
+    email_client: &EmailClient,
+    recipient: &SubscriberEmail,
+    base_url: &str,
+) -> Result<(), EmailError> {
+    let html_body = format!(
+        "Your confirmation link has expired, so we have removed your pending subscription.<br />\
+            If you still want to hear from us, please sign up again at <a href=\"{base_url}\">{base_url}</a>.",
+    );
+    let plain_body = format!(
+        "Your confirmation link has expired, so we have removed your pending subscription.\n\
+            If you still want to hear from us, please sign up again at {base_url}.",
+    );
+    email_client
+        .send_email(
+            recipient,
+            "Your confirmation link has expired",
+            &html_body,
+            &plain_body,
+        )
+        .await
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_subscriber(
+    mut transaction: PgTransaction,
+    subscriber_id: Uuid,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let query = sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, subscriber_id);
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{web, HttpResponse};
+use actix_web_lab::middleware::Next;
+use tokio::time::Instant;
+
+use crate::configuration::RateLimit;
+
+/// Whether a request may go ahead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateLimitDecision {
+    Allowed,
+    /// Over the limit; the client may try again after this long.
+    Limited {
+        retry_after: Duration,
+    },
+}
+
+/// Keeps a token bucket per key: each bucket holds up to `max_requests`
+/// tokens and refills at `max_requests` per window, so short bursts are
+/// allowed while the average rate stays within the limit.
+/// The in-memory store is local to one instance; a shared store (e.g. Redis)
+/// can be plugged in by implementing this trait.
+#[async_trait::async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Takes a token from the bucket for `key`, if there is one.
+    async fn take(&self, key: &str, limit: RateLimit) -> Result<RateLimitDecision, anyhow::Error>;
+}
+
+/// Buckets untouched for this long are full again and can be dropped.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct InMemoryRateLimitStore {
+    state: Mutex<Buckets>,
+}
+
+struct Buckets {
+    buckets: HashMap<String, Bucket>,
+    evicted_at: Instant,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    /// How long the bucket takes to fill up again once left alone.
+    idle_after: Duration,
+}
+
+impl Default for InMemoryRateLimitStore {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                evicted_at: Instant::now(),
+            }),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for InMemoryRateLimitStore {
+    async fn take(&self, key: &str, limit: RateLimit) -> Result<RateLimitDecision, anyhow::Error> {
+        Ok(self.take_at(key, limit, Instant::now()))
+    }
+}
+
+impl InMemoryRateLimitStore {
+    fn take_at(&self, key: &str, limit: RateLimit, now: Instant) -> RateLimitDecision {
+        let capacity = f64::from(limit.max_requests);
+        let refill_per_second = capacity / limit.window().as_secs_f64().max(f64::EPSILON);
+        let mut state = self.state.lock().unwrap();
+
+        // Drop idle buckets every now and then, so that every client ever
+        // seen is not kept in memory. Each bucket is only dropped once it
+        // has been idle for its own window, as groups with longer windows
+        // share the store.
+        if now.duration_since(state.evicted_at) >= EVICTION_INTERVAL {
+            state
+                .buckets
+                .retain(|_, bucket| now.duration_since(bucket.updated_at) < bucket.idle_after);
+            state.evicted_at = now;
+        }
+
+        let bucket = state.buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+            idle_after: EVICTION_INTERVAL,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_second).min(capacity);
+        bucket.updated_at = now;
+        bucket.idle_after = limit.window().max(EVICTION_INTERVAL);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            RateLimitDecision::Allowed
+        } else {
+            let retry_after = (1.0 - bucket.tokens) / refill_per_second;
+            RateLimitDecision::Limited {
+                retry_after: Duration::from_secs_f64(retry_after),
+            }
+        }
+    }
+}
+
+/// The limit for one group of routes. Each group is counted separately,
+/// so hitting the limit on one group does not affect the others.
+#[derive(Clone)]
+pub struct RateLimiter {
+    route_group: &'static str,
+    limit: RateLimit,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimiter {
+    pub fn new(
+        route_group: &'static str,
+        limit: RateLimit,
+        store: Arc<dyn RateLimitStore>,
+    ) -> Self {
+        Self {
+            route_group,
+            limit,
+            store,
+        }
+    }
+
+    /// Takes a token from the bucket `client` has in this route group.
+    pub async fn take(&self, client: &str) -> Result<RateLimitDecision, anyhow::Error> {
+        let key = format!("{}:{client}", self.route_group);
+        self.store.take(&key, self.limit).await
+    }
+}
+
+/// Rejects requests with a 429 once the client has exceeded the limit of the
+/// `RateLimiter` registered on the route. Clients are told apart by the
+/// `Forwarded` or `X-Forwarded-For` header, or by their peer address when
+/// neither is set. If the store cannot be reached the request is let
+/// through rather than locking everyone out.
+pub async fn enforce_rate_limit(
+    limiter: web::Data<RateLimiter>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let client = req
+        .connection_info()
+        .realip_remote_addr()
+        .unwrap_or("unknown")
+        .to_string();
+    match limiter.take(&client).await {
+        Ok(RateLimitDecision::Limited { retry_after }) => {
+            // Rounded up, so that retrying after this long always succeeds.
+            let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            let response = HttpResponse::TooManyRequests()
+                .insert_header((RETRY_AFTER, retry_after))
+                .body("Too many requests. Please try again later.");
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+        Ok(RateLimitDecision::Allowed) => {}
+        Err(e) => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to check the rate limit.",
+            );
+        }
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InMemoryRateLimitStore, RateLimitDecision, RateLimitStore};
+    use crate::configuration::RateLimit;
+    use claims::assert_ok_eq;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    fn limit(max_requests: u32, window_seconds: u64) -> RateLimit {
+        RateLimit {
+            max_requests,
+            window_seconds,
+        }
+    }
+
+    #[tokio::test]
+    async fn buckets_are_kept_per_key() {
+        let store = InMemoryRateLimitStore::default();
+        let limit = limit(2, 60);
+
+        assert_ok_eq!(
+            store.take("login:1.2.3.4", limit).await,
+            RateLimitDecision::Allowed
+        );
+        assert_ok_eq!(
+            store.take("login:1.2.3.4", limit).await,
+            RateLimitDecision::Allowed
+        );
+        assert!(matches!(
+            store.take("login:1.2.3.4", limit).await.unwrap(),
+            RateLimitDecision::Limited { .. }
+        ));
+        assert_ok_eq!(
+            store.take("subscriptions:1.2.3.4", limit).await,
+            RateLimitDecision::Allowed
+        );
+    }
+
+    #[tokio::test]
+    async fn a_limited_client_is_told_when_the_next_token_is_due() {
+        let store = InMemoryRateLimitStore::default();
+        let limit = limit(5, 60);
+        for _ in 0..5 {
+            store.take("subscriptions:1.2.3.4", limit).await.unwrap();
+        }
+
+        let decision = store.take("subscriptions:1.2.3.4", limit).await.unwrap();
+
+        let RateLimitDecision::Limited { retry_after } = decision else {
+            panic!("The sixth request was not limited.");
+        };
+        assert!(retry_after > Duration::from_secs(11));
+        assert!(retry_after <= Duration::from_secs(12));
+    }
+
+    #[tokio::test]
+    async fn tokens_are_refilled_over_time() {
+        let store = InMemoryRateLimitStore::default();
+        let limit = limit(2, 1);
+        store.take("login:1.2.3.4", limit).await.unwrap();
+        store.take("login:1.2.3.4", limit).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        assert_ok_eq!(
+            store.take("login:1.2.3.4", limit).await,
+            RateLimitDecision::Allowed
+        );
+        assert!(matches!(
+            store.take("login:1.2.3.4", limit).await.unwrap(),
+            RateLimitDecision::Limited { .. }
+        ));
+    }
+
+    #[test]
+    fn buckets_with_long_windows_outlive_the_eviction_of_short_ones() {
+        let store = InMemoryRateLimitStore::default();
+        let start = Instant::now();
+        let daily = limit(1, 24 * 60 * 60);
+        store.take_at("confirmation_emails:all", daily, start);
+
+        store.take_at(
+            "login:1.2.3.4",
+            limit(5, 1),
+            start + Duration::from_secs(120),
+        );
+
+        assert!(matches!(
+            store.take_at(
+                "confirmation_emails:all",
+                daily,
+                start + Duration::from_secs(121)
+            ),
+            RateLimitDecision::Limited { .. }
+        ));
+    }
+}
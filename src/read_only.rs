@@ -0,0 +1,96 @@
+use std::any::Any;
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{web, HttpResponse};
+use actix_web_lab::middleware::Next;
+
+use crate::authentication::AuthError;
+use crate::configuration::ReadOnlySettings;
+
+/// The SQLSTATE Postgres reports when a write reaches a read-only server.
+const READ_ONLY_SQL_TRANSACTION: &str = "25006";
+
+/// Marks a response as failed because the database refused a write.
+struct DatabaseWasReadOnly;
+
+/// Whether the database refused a write because it only accepts reads.
+pub fn is_read_only_error(e: &anyhow::Error) -> bool {
+    e.chain().any(is_read_only_sqlx_cause)
+}
+
+/// Whether `e`, or any error it was caused by, is a read-only refusal.
+fn has_read_only_source(e: &(dyn std::error::Error + 'static)) -> bool {
+    std::iter::successors(Some(e), |e| e.source()).any(is_read_only_sqlx_cause)
+}
+
+fn is_read_only_sqlx_cause(cause: &(dyn std::error::Error + 'static)) -> bool {
+    cause.downcast_ref().is_some_and(is_read_only_sqlx_error)
+}
+
+fn is_read_only_sqlx_error(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .and_then(|db_error| db_error.code())
+        .is_some_and(|code| code == READ_ONLY_SQL_TRANSACTION)
+}
+
+/// Like `is_read_only_error`, for the causes handlers pass to `e500`.
+/// The whole chain of causes is checked, whichever error wraps it.
+pub(crate) fn is_read_only_cause<T: Any>(e: &T) -> bool {
+    let e: &dyn Any = e;
+    if let Some(e) = e.downcast_ref::<anyhow::Error>() {
+        is_read_only_error(e)
+    } else if let Some(e) = e.downcast_ref::<AuthError>() {
+        // `AuthError` is transparent, so its own `source` skips the
+        // error the `anyhow::Error` was created from.
+        match e {
+            AuthError::UnexpectedError(e) | AuthError::InvalidCredentials(e) => {
+                is_read_only_error(e)
+            }
+        }
+    } else if let Some(e) = e.downcast_ref::<sqlx::Error>() {
+        has_read_only_source(e)
+    } else {
+        false
+    }
+}
+
+/// The response to a write refused by a read-only database.
+/// `degrade_when_read_only` tells the client when to try again.
+pub fn read_only_response() -> HttpResponse {
+    let mut response = HttpResponse::ServiceUnavailable()
+        .body("We cannot save changes right now. Please try again shortly.");
+    response.extensions_mut().insert(DatabaseWasReadOnly);
+    response
+}
+
+/// Adds a `Retry-After` header to writes that failed because the database
+/// was read-only. When the mode is disabled they are answered with a 500
+/// like any other database error.
+pub async fn degrade_when_read_only(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let settings = req
+        .app_data::<web::Data<ReadOnlySettings>>()
+        .map(|settings| *settings.get_ref())
+        .unwrap_or_default();
+    let mut response = next.call(req).await?;
+    if !response
+        .response()
+        .extensions()
+        .contains::<DatabaseWasReadOnly>()
+    {
+        return Ok(response.map_into_left_body());
+    }
+    if !settings.enabled {
+        let (request, _) = response.into_parts();
+        let response = HttpResponse::InternalServerError().finish();
+        return Ok(ServiceResponse::new(request, response).map_into_right_body());
+    }
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, settings.retry_after_seconds.into());
+    Ok(response.map_into_left_body())
+}
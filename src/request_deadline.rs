@@ -0,0 +1,47 @@
+use std::ops::Deref;
+use std::time::Duration;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::{web, HttpMessage};
+use actix_web_lab::middleware::Next;
+use tokio::time::Instant;
+
+use crate::utils::e500;
+
+/// How long a request may take before it is abandoned.
+pub struct RequestTimeout(pub Duration);
+
+/// The instant by which the current request must have completed.
+/// Downstream calls should use it to bound their own timeouts.
+#[derive(Copy, Clone, Debug)]
+pub struct RequestDeadline(Instant);
+
+impl Deref for RequestDeadline {
+    type Target = Instant;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Stamps every request with a `RequestDeadline` and cancels the handler
+/// if it is still running once the deadline has passed.
+pub async fn enforce_request_deadline(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let timeout = req
+        .app_data::<web::Data<RequestTimeout>>()
+        .ok_or_else(|| e500("The request timeout has not been configured."))?
+        .0;
+    let deadline = Instant::now() + timeout;
+    req.extensions_mut().insert(RequestDeadline(deadline));
+
+    match tokio::time::timeout_at(deadline, next.call(req)).await {
+        Ok(response) => response,
+        Err(_) => Err(actix_web::error::ErrorGatewayTimeout(
+            "The request did not complete in time.",
+        )),
+    }
+}
@@ -0,0 +1,51 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::authentication::UserId;
+use crate::domain::SubscriberEmail;
+use crate::form::Form;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    email: String,
+}
+
+/// `POST /admin/account/email`: sets the address test sends of an issue go
+/// to. The form is on the password page.
+#[tracing::instrument(name = "Change the admin's email address", skip(form, pool))]
+pub async fn change_account_email(
+    form: Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Ok(email) = SubscriberEmail::parse(form.0.email) else {
+        FlashMessage::error("Please enter a valid email address.").send();
+        return Ok(see_other("/admin/password"));
+    };
+    sqlx::query!(
+        r#"UPDATE users SET email = $2 WHERE user_id = $1"#,
+        *user_id.into_inner(),
+        email.as_ref()
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to change the admin's email address.")
+    .map_err(e500)?;
+    FlashMessage::info(format!("Test emails will be sent to {email}.")).send();
+    Ok(see_other("/admin/password"))
+}
+
+#[tracing::instrument(name = "Get the admin's email address", skip(pool))]
+pub async fn get_admin_email(
+    pool: &PgPool,
+    user_id: UserId,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(r#"SELECT email FROM users WHERE user_id = $1"#, *user_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to retrieve the admin's email address.")?;
+    Ok(row.email)
+}
@@ -0,0 +1,41 @@
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::authentication::{generate_api_token, store_api_token, UserId};
+use crate::utils::e500;
+
+/// Creates an API token for the logged in user. The token is only ever
+/// shown on this page, since just its hash is stored.
+#[tracing::instrument(name = "Create API token", skip(pool))]
+pub async fn create_api_token(
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = generate_api_token();
+    store_api_token(*user_id.into_inner(), &token, &pool)
+        .await
+        .map_err(e500)?;
+    let token = token.expose_secret();
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>API token</title>
+</head>
+<body>
+    <p>Your new API token is:</p>
+    <pre id="api-token">{token}</pre>
+    <p>Copy it now, it will not be shown again.
+    Send it as <code>Authorization: Bearer &lt;token&gt;</code>.</p>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
@@ -1,7 +1,11 @@
 use actix_web::http::header::ContentType;
 use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
 use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
 use sqlx::PgPool;
+use std::fmt::Write;
 use uuid::Uuid;
 
 use crate::session_state::TypedSession;
@@ -10,12 +14,35 @@ use crate::utils::{e500, see_other};
 pub async fn admin_dashboard(
     session: TypedSession,
     pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let username = if let Some(user_id) = session.get_user_id().map_err(e500)? {
-        get_username(user_id, &pool).await.map_err(e500)?
-    } else {
+    let Some(user_id) = session.get_user_id().map_err(e500)? else {
         return Ok(see_other("/login"));
     };
+    let username = get_username(user_id, &pool).await.map_err(e500)?;
+    let SubscriberStats {
+        confirmed,
+        pending_confirmation,
+        unsubscribed,
+        total,
+        added_last_7_days,
+    } = get_subscriber_stats(&pool).await.map_err(e500)?;
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", encode_minimal(m.content())).unwrap();
+    }
+    let mut scheduled_html = String::new();
+    for issue in get_scheduled_issues(&pool, user_id).await.map_err(e500)? {
+        writeln!(
+            scheduled_html,
+            r#"<li>{} - {} <form action="/admin/newsletter/{}/cancel" method="post"><button type="submit">Cancel</button></form></li>"#,
+            encode_minimal(&issue.title),
+            issue.scheduled_at.to_rfc3339(),
+            issue.newsletter_issue_id,
+        )
+        .unwrap();
+    }
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -27,11 +54,34 @@ pub async fn admin_dashboard(
     <title>Admin dashboard</title>
 </head>
 <body>
+    {msg_html}
     <p>Welcome {username}!</p>
+    <table>
+        <tr><th>Confirmed</th><td>{confirmed}</td></tr>
+        <tr><th>Pending confirmation</th><td>{pending_confirmation}</td></tr>
+        <tr><th>Unsubscribed</th><td>{unsubscribed}</td></tr>
+        <tr><th>Total</th><td>{total}</td></tr>
+        <tr><th>Added in the last 7 days</th><td>{added_last_7_days}</td></tr>
+    </table>
+    <h2>Scheduled issues</h2>
+    <ul>
+        {scheduled_html}
+    </ul>
     <p>Available actions:</p>
     <ol>
         <li><a href="/admin/newsletter">Send a newsletter issue</a></li>
+        <li><a href="/admin/subscribers">Search subscribers</a></li>
+        <li><a href="/admin/subscribers/export.csv">Export subscribers</a></li>
+        <li><a href="/admin/emails/confirmation/preview">Preview the confirmation email</a></li>
+        <li><a href="/admin/reports/unsubscribe-reasons">Unsubscribe reasons</a></li>
+        <li><a href="/admin/reports/confirmation-methods">Confirmation methods</a></li>
+        <li><a href="/admin/reports/webhook-signature-failures">Webhook signature failures</a></li>
         <li><a href="/admin/password">Change password</a></li>
+        <li>
+            <form action="/admin/api-tokens" method="post">
+                <button type="submit">Create an API token</button>
+            </form>
+        </li>
         <li>
             <form name="logoutForm" action="/admin/logout" method="post" >
                 <input type="submit" value="logout" />
@@ -52,3 +102,66 @@ pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow
 
     Ok(row.username)
 }
+
+/// The size of the list at a glance.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SubscriberStats {
+    pub confirmed: i64,
+    pub pending_confirmation: i64,
+    pub unsubscribed: i64,
+    /// Every stored subscriber, whatever their status.
+    pub total: i64,
+    pub added_last_7_days: i64,
+}
+
+#[tracing::instrument(name = "Get subscriber stats", skip(pool))]
+pub async fn get_subscriber_stats(pool: &PgPool) -> Result<SubscriberStats, anyhow::Error> {
+    let stats = sqlx::query_as!(
+        SubscriberStats,
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE status = 'confirmed') AS "confirmed!",
+            COUNT(*) FILTER (WHERE status = 'pending_confirmation') AS "pending_confirmation!",
+            COUNT(*) FILTER (WHERE status = 'unsubscribed') AS "unsubscribed!",
+            COUNT(*) AS "total!",
+            COUNT(*) FILTER (WHERE subscribed_at > now() - interval '7 days') AS "added_last_7_days!"
+        FROM subscriptions
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count subscribers.")?;
+
+    Ok(stats)
+}
+
+struct ScheduledIssue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    scheduled_at: DateTime<Utc>,
+}
+
+/// Issues waiting for their scheduled time, soonest first.
+#[tracing::instrument(name = "Get scheduled issues", skip(pool))]
+async fn get_scheduled_issues(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<ScheduledIssue>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        ScheduledIssue,
+        r#"
+        SELECT newsletter_issue_id, title, scheduled_at AS "scheduled_at!"
+        FROM newsletter_issues
+        WHERE
+            published_at IS NOT NULL AND
+            scheduled_at > now() AND
+            (published_by = $1 OR published_by IS NULL)
+        ORDER BY scheduled_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve scheduled newsletter issues.")?;
+    Ok(issues)
+}
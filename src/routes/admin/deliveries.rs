@@ -0,0 +1,89 @@
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::audit_log::record_audit_event;
+use crate::authentication::UserId;
+use crate::issue_delivery_worker::DeliveryStatus;
+use crate::routes::load_issue_for;
+use crate::utils::{e500, see_other};
+
+/// Puts a single queue row back to `pending` so the worker attempts it
+/// again. Rows that were already sent are left alone to avoid emailing a
+/// subscriber twice.
+#[tracing::instrument(name = "Replay a delivery", skip(pool))]
+pub async fn replay_delivery(
+    delivery_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let delivery_id = delivery_id.into_inner();
+    let user_id = user_id.into_inner();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+
+    let Some(delivery) = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, status
+        FROM issue_delivery_queue
+        WHERE delivery_id = $1
+        FOR UPDATE
+        "#,
+        delivery_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve the delivery.")
+    .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    // Deliveries of other admins' issues are treated as missing too.
+    load_issue_for(&pool, user_id, &delivery.newsletter_issue_id.to_string()).await?;
+    let location = format!(
+        "/admin/newsletter/{}/deliveries",
+        delivery.newsletter_issue_id
+    );
+
+    if delivery.status == DeliveryStatus::Sent.as_str() {
+        FlashMessage::error("This delivery has already been sent and cannot be replayed.").send();
+        return Ok(see_other(&location));
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET status = $2, processed_at = NULL
+        WHERE delivery_id = $1
+        "#,
+        delivery_id,
+        DeliveryStatus::Pending.as_str()
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to reset the delivery.")
+    .map_err(e500)?;
+    record_audit_event(
+        &mut transaction,
+        *user_id,
+        "replay_delivery",
+        &delivery_id.to_string(),
+    )
+    .await
+    .context("Failed to record the replay in the audit log.")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to replay the delivery.")
+        .map_err(e500)?;
+
+    FlashMessage::info("The delivery has been queued for another attempt.").send();
+    Ok(see_other(&location))
+}
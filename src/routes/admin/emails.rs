@@ -0,0 +1,59 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use htmlescape::encode_minimal;
+
+use crate::configuration::SubscriptionSettings;
+use crate::domain::ConfirmationEmailTemplate;
+use crate::routes::render_confirmation_email;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::e500;
+
+/// Stands in for a real subscription token, which only exists once someone
+/// subscribes. The link it produces does not confirm anybody.
+const SAMPLE_SUBSCRIPTION_TOKEN: &str = "sample-subscription-token";
+/// Fills in the subscriber's name where the subject asks for it.
+const SAMPLE_SUBSCRIBER_NAME: &str = "Jane Doe";
+
+/// Shows the confirmation email as new subscribers would get it, rendered
+/// from the configured template with a sample link. Nothing is sent.
+#[tracing::instrument(name = "Preview the confirmation email", skip_all)]
+pub async fn preview_confirmation_email(
+    template: web::Data<ConfirmationEmailTemplate>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = render_confirmation_email(
+        &template,
+        SAMPLE_SUBSCRIBER_NAME,
+        &base_url.0,
+        SAMPLE_SUBSCRIPTION_TOKEN,
+        settings.pending_expiry.confirmation_link_ttl(),
+    )
+    .map_err(e500)?;
+    let subject = encode_minimal(&email.subject);
+    // The HTML body is shown in a sandboxed frame, so it cannot run scripts
+    // or restyle the page around it.
+    let html_content = encode_minimal(&email.html_content);
+    let text_content = encode_minimal(&email.text_content);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Confirmation email preview</title>
+</head>
+<body>
+    <h1>Confirmation email preview</h1>
+    <p>Subject: {subject}</p>
+    <h2>HTML</h2>
+    <iframe sandbox srcdoc="{html_content}"></iframe>
+    <h2>Plain text</h2>
+    <pre>{text_content}</pre>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
@@ -0,0 +1,110 @@
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::UserId;
+use crate::configuration::IdempotencySettings;
+use crate::idempotency::{get_idempotency_record, get_idempotency_stats, IdempotencyKey};
+use crate::utils::{e400, e500};
+
+/// Reports how many keys are stored and how close they are to expiring,
+/// for monitoring rather than for humans.
+#[tracing::instrument(name = "Report idempotency store stats", skip_all)]
+pub async fn idempotency_stats(
+    pool: web::Data<PgPool>,
+    settings: web::Data<IdempotencySettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let stats = get_idempotency_stats(&pool, settings.ttl())
+        .await
+        .map_err(e500)?;
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+/// Stored bodies can be large, only the start is shown.
+const MAX_BODY_BYTES: usize = 1024;
+
+#[tracing::instrument(name = "Show idempotency record", skip(pool, settings))]
+pub async fn idempotency_record(
+    idempotency_key: web::Path<String>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<IdempotencySettings>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let idempotency_key: IdempotencyKey = idempotency_key.into_inner().try_into().map_err(e400)?;
+    let Some(record) = get_idempotency_record(
+        &pool,
+        &idempotency_key,
+        *user_id.into_inner(),
+        settings.scope,
+    )
+    .await
+    .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let state = if record.is_completed() {
+        "completed"
+    } else {
+        "in progress"
+    };
+    let age = (Utc::now() - record.created_at).num_seconds();
+    let status = record
+        .response_status_code
+        .map(|s| s.to_string())
+        .unwrap_or_default();
+
+    let mut headers_html = String::new();
+    for (name, value) in &record.response_headers {
+        writeln!(
+            headers_html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            encode_minimal(name),
+            encode_minimal(&String::from_utf8_lossy(value)),
+        )
+        .unwrap();
+    }
+
+    let body = record.response_body.unwrap_or_default();
+    let truncated = if body.len() > MAX_BODY_BYTES {
+        format!(" (truncated, {} bytes in total)", body.len())
+    } else {
+        String::new()
+    };
+    let body = encode_minimal(&String::from_utf8_lossy(
+        &body[..body.len().min(MAX_BODY_BYTES)],
+    ));
+    let key = encode_minimal(idempotency_key.as_ref());
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Idempotency record</title>
+</head>
+<body>
+    <h1>{key}</h1>
+    <ul>
+        <li>State: {state}</li>
+        <li>Age: {age}s</li>
+        <li>Status: {status}</li>
+    </ul>
+    <h2>Headers</h2>
+    <table>
+        <tr><th>Name</th><th>Value</th></tr>
+        {headers_html}
+    </table>
+    <h2>Body{truncated}</h2>
+    <pre>{body}</pre>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
@@ -0,0 +1,36 @@
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+
+use crate::authentication::UserId;
+use crate::telemetry::{current_log_filter, set_log_filter};
+use crate::utils::{e400, e500};
+
+#[derive(serde::Serialize)]
+struct LogLevel {
+    filter: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct LogLevelUpdate {
+    /// Any `EnvFilter` directives, e.g. `debug` or `info,zero2prod=debug`.
+    level: String,
+}
+
+#[tracing::instrument(name = "Show log level", skip_all)]
+pub async fn log_level() -> Result<HttpResponse, actix_web::Error> {
+    let filter = current_log_filter().map_err(e500)?;
+    Ok(HttpResponse::Ok().json(LogLevel { filter }))
+}
+
+/// Changes the log filter of the running process. It is not persisted:
+/// the configured level comes back on the next restart.
+#[tracing::instrument(name = "Change log level", skip(body, user_id), fields(user_id=%*user_id))]
+pub async fn change_log_level(
+    body: web::Json<LogLevelUpdate>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    set_log_filter(&body.level).map_err(e400)?;
+    let filter = current_log_filter().map_err(e500)?;
+    tracing::warn!(filter = %filter, "The log filter was changed at runtime.");
+    Ok(HttpResponse::Ok().json(LogLevel { filter }))
+}
@@ -1,9 +1,38 @@
+mod account;
+mod api_tokens;
 mod dashboard;
+mod deliveries;
+mod emails;
+mod idempotency;
+mod log_level;
 mod logout;
 mod newsletter;
 mod password;
+mod reports;
+mod subscribers;
+mod suppressions;
 
-pub use dashboard::admin_dashboard;
+pub use account::change_account_email;
+pub use api_tokens::create_api_token;
+pub use dashboard::{admin_dashboard, get_subscriber_stats, SubscriberStats};
+pub use deliveries::replay_delivery;
+pub use emails::preview_confirmation_email;
+pub use idempotency::{idempotency_record, idempotency_stats};
+pub use log_level::{change_log_level, log_level};
 pub use logout::logout;
-pub use newsletter::{publish_newsletter, publish_newsletter_form};
+pub use newsletter::{
+    cancel_scheduled_issue, clone_issue, create_template, delete_template, dispatch_queue,
+    edit_template_form, issue_activity, issue_deliveries, list_templates, load_issue_for,
+    publish_newsletter, publish_newsletter_api, publish_newsletter_form, resend_issue,
+    review_newsletter, send_test_newsletter, update_template, IssueLookupError, NewsletterIssue,
+};
 pub use password::{change_password, change_password_form};
+pub use reports::{
+    confirmation_methods_report, signature_failures_report, unsubscribe_reasons_report,
+};
+pub use subscribers::{
+    add_subscriber_tag, bulk_tag_form, bulk_tag_subscribers, export_subscribers,
+    get_subscribers_page, import_subscribers, pause_subscriber, search_subscribers,
+    subscriber_consent, subscriber_details, SubscriberCursor, SubscriberRecord,
+};
+pub use suppressions::import_suppressions;
@@ -0,0 +1,145 @@
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use super::{load_issue_for, NewsletterIssue};
+use crate::authentication::UserId;
+use crate::utils::e500;
+
+const PAGE_SIZE: i64 = 100;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct QueryParams {
+    #[serde(default = "first_page")]
+    page: i64,
+}
+
+fn first_page() -> i64 {
+    1
+}
+
+struct ActivityRow {
+    occurred_at: DateTime<Utc>,
+    subscriber_email: String,
+    event: String,
+}
+
+/// Everything that happened to an issue's emails, oldest first: when each
+/// was queued, every send attempt, and the bounces, opens and clicks the
+/// email provider reported afterwards.
+#[tracing::instrument(name = "Show issue activity", skip(pool))]
+pub async fn issue_activity(
+    issue_id: web::Path<String>,
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let NewsletterIssue {
+        newsletter_issue_id: issue_id,
+        title,
+    } = load_issue_for(&pool, user_id.into_inner(), &issue_id).await?;
+    let page = query.page.max(1);
+    let mut rows = get_activity(&pool, issue_id, page).await.map_err(e500)?;
+    let has_next_page = rows.len() as i64 > PAGE_SIZE;
+    rows.truncate(PAGE_SIZE as usize);
+
+    let mut rows_html = String::new();
+    for r in &rows {
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            r.occurred_at.to_rfc3339(),
+            encode_minimal(&r.subscriber_email),
+            encode_minimal(&r.event),
+        )
+        .unwrap();
+    }
+    let mut pages_html = String::new();
+    if page > 1 {
+        write!(
+            pages_html,
+            r#"<a href="/admin/newsletter/{issue_id}/activity?page={}">Previous</a> "#,
+            page - 1
+        )
+        .unwrap();
+    }
+    if has_next_page {
+        write!(
+            pages_html,
+            r#"<a href="/admin/newsletter/{issue_id}/activity?page={}">Next</a>"#,
+            page.saturating_add(1)
+        )
+        .unwrap();
+    }
+
+    let title = encode_minimal(&title);
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Issue activity</title>
+</head>
+<body>
+    <h1>{title}</h1>
+    <table>
+        <tr><th>When</th><th>Recipient</th><th>Event</th></tr>
+        {rows_html}
+    </table>
+    <p>Page {page} {pages_html}</p>
+    <p><a href="/admin/newsletter/{issue_id}/deliveries">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
+
+/// Events at the same instant keep their natural order: queued, then
+/// attempted, then whatever the provider reported.
+#[tracing::instrument(name = "Get issue activity", skip(pool))]
+async fn get_activity(
+    pool: &PgPool,
+    issue_id: Uuid,
+    page: i64,
+) -> Result<Vec<ActivityRow>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        ActivityRow,
+        r#"
+        SELECT
+            occurred_at AS "occurred_at!",
+            subscriber_email AS "subscriber_email!",
+            event AS "event!"
+        FROM (
+            SELECT q.created_at AS occurred_at, q.subscriber_email, 'queued' AS event, 0 AS stage
+            FROM issue_delivery_queue q
+            WHERE q.newsletter_issue_id = $1
+            UNION ALL
+            SELECT a.attempted_at, q.subscriber_email, a.result, 1
+            FROM delivery_attempts a
+            JOIN issue_delivery_queue q USING (delivery_id)
+            WHERE q.newsletter_issue_id = $1
+            UNION ALL
+            SELECT e.occurred_at, q.subscriber_email, e.kind, 2
+            FROM delivery_events e
+            JOIN issue_delivery_queue q USING (delivery_id)
+            WHERE q.newsletter_issue_id = $1
+        ) activity
+        ORDER BY occurred_at, stage, subscriber_email
+        LIMIT $2 OFFSET $3
+        "#,
+        issue_id,
+        PAGE_SIZE + 1,
+        (page - 1).saturating_mul(PAGE_SIZE),
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the issue activity.")?;
+    Ok(rows)
+}
@@ -0,0 +1,87 @@
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::load_issue_for;
+use crate::audit_log::record_audit_event;
+use crate::authentication::UserId;
+use crate::utils::{e500, see_other};
+
+/// Takes back an issue scheduled for later delivery, as long as it has not
+/// started sending. Its queued emails are dropped and it goes back to
+/// being a draft, so it can be edited and published again.
+#[tracing::instrument(name = "Cancel a scheduled issue", skip(pool))]
+pub async fn cancel_scheduled_issue(
+    issue_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let issue = load_issue_for(&pool, user_id, &issue_id).await?;
+    let issue_id = issue.newsletter_issue_id;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let cancelled = unschedule_issue(&mut transaction, issue_id)
+        .await
+        .context("Failed to cancel the scheduled issue")
+        .map_err(e500)?;
+    if !cancelled {
+        FlashMessage::warning("The issue is not scheduled, or has already started sending.").send();
+        return Ok(see_other("/admin/dashboard"));
+    }
+    record_audit_event(
+        &mut transaction,
+        *user_id,
+        "cancel_scheduled_issue",
+        &issue_id.to_string(),
+    )
+    .await
+    .context("Failed to record the cancellation in the audit log.")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to cancel the issue.")
+        .map_err(e500)?;
+
+    FlashMessage::info("The scheduled issue has been cancelled and kept as a draft.").send();
+    Ok(see_other(&format!("/admin/newsletter?draft_id={issue_id}")))
+}
+
+/// Whether the issue was still waiting for its scheduled time.
+#[tracing::instrument(skip(transaction))]
+async fn unschedule_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET scheduled_at = NULL, published_at = NULL
+        WHERE
+            newsletter_issue_id = $1 AND
+            published_at IS NOT NULL AND
+            scheduled_at > now()
+        "#,
+        newsletter_issue_id
+    );
+    if transaction.execute(query).await?.rows_affected() == 0 {
+        return Ok(false);
+    }
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1 AND status = 'pending'
+        "#,
+        newsletter_issue_id
+    );
+    transaction.execute(query).await?;
+    Ok(true)
+}
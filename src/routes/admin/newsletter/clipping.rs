@@ -0,0 +1,32 @@
+/// Gmail clips messages whose HTML is larger than roughly 102KB and hides
+/// the rest behind a "View entire message" link.
+/// Returns an advisory message when `html_content` is over `threshold` bytes.
+pub fn clipping_warning(html_content: &str, threshold: usize) -> Option<String> {
+    let size = html_content.len();
+    if size > threshold {
+        Some(format!(
+            "The HTML content is {size} bytes, over the {threshold} bytes limit - \
+            some email clients may clip it."
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::clipping_warning;
+    use claims::{assert_none, assert_some};
+
+    #[test]
+    fn a_body_at_the_threshold_produces_no_warning() {
+        assert_none!(clipping_warning(&"a".repeat(100), 100));
+    }
+
+    #[test]
+    fn a_body_over_the_threshold_produces_a_clipping_warning() {
+        let warning = assert_some!(clipping_warning(&"a".repeat(101), 100));
+        assert!(warning.contains("101 bytes"));
+        assert!(warning.contains("may clip it"));
+    }
+}
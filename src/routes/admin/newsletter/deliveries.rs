@@ -0,0 +1,440 @@
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use super::{load_issue_for, NewsletterIssue};
+use crate::authentication::UserId;
+use crate::configuration::{DeadLetterRetrySettings, NewsletterSettings};
+use crate::issue_delivery_worker::{is_waiting_for_a_slot, DeliveryStatus, IssueSlots};
+use crate::utils::e500;
+
+struct DeliveryRecord {
+    delivery_id: Uuid,
+    subscriber_email: String,
+    status: String,
+    processed_at: Option<DateTime<Utc>>,
+    not_before: Option<DateTime<Utc>>,
+    auto_retries: i16,
+    attempts: i64,
+    last_error: Option<String>,
+}
+
+/// Where a single delivery stands with respect to retries.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RetryState {
+    Sent,
+    /// Never sent, because the recipient is not on the allowlist.
+    Skipped,
+    /// Will be picked up by the next run of the delivery worker.
+    Queued,
+    /// Held back until `until`, e.g. a scheduled send.
+    Waiting {
+        until: DateTime<Utc>,
+    },
+    /// Failed, and will be tried again automatically at `retry_at`.
+    BackingOff {
+        retry_at: DateTime<Utc>,
+    },
+    /// Failed with no automatic retries left. Only a replay sends it again.
+    GivenUp,
+}
+
+impl RetryState {
+    pub fn new(
+        status: &str,
+        processed_at: Option<DateTime<Utc>>,
+        not_before: Option<DateTime<Utc>>,
+        auto_retries: i16,
+        retry: &DeadLetterRetrySettings,
+        now: DateTime<Utc>,
+    ) -> Self {
+        if status == DeliveryStatus::Sent.as_str() {
+            return Self::Sent;
+        }
+        if status == DeliveryStatus::Skipped.as_str() {
+            return Self::Skipped;
+        }
+        if status == DeliveryStatus::Failed.as_str() {
+            let retry_at =
+                processed_at.filter(|_| retry.enabled && auto_retries < retry.max_retries);
+            return match retry_at {
+                Some(processed_at) => Self::BackingOff {
+                    retry_at: processed_at
+                        + chrono::Duration::seconds(retry.interval_seconds as i64),
+                },
+                None => Self::GivenUp,
+            };
+        }
+        match not_before {
+            Some(until) if until > now => Self::Waiting { until },
+            _ => Self::Queued,
+        }
+    }
+}
+
+impl std::fmt::Display for RetryState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sent => write!(f, "sent"),
+            Self::Skipped => write!(f, "skipped, not on the allowlist"),
+            Self::Queued => write!(f, "queued"),
+            Self::Waiting { until } => write!(f, "waiting until {}", until.to_rfc3339()),
+            Self::BackingOff { retry_at } => {
+                write!(f, "backing off, retrying at {}", retry_at.to_rfc3339())
+            }
+            Self::GivenUp => write!(f, "failed, no retries left"),
+        }
+    }
+}
+
+/// Aggregate figures describing how an issue's delivery went.
+#[derive(Debug)]
+pub struct DeliveryReport {
+    pub sent: i64,
+    pub failed: i64,
+    pub skipped: i64,
+    pub pending: i64,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl DeliveryReport {
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        Some(self.finished_at? - self.started_at?)
+    }
+
+    /// Emails sent per second between the first and the last delivery.
+    /// A single delivery (or a batch that completed within the same
+    /// instant) has no measurable duration, so it has no throughput either.
+    pub fn throughput(&self) -> Option<f64> {
+        let seconds = self.duration()?.num_milliseconds() as f64 / 1000.0;
+        if seconds > 0.0 {
+            Some(self.sent as f64 / seconds)
+        } else {
+            None
+        }
+    }
+}
+
+/// Where an issue's delivery stands, as shown to admins.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DeliveryProgress {
+    /// Queued behind other issues, until one of the sending slots frees up.
+    Waiting,
+    /// Some deliveries are still pending: `sent` of `total` have gone out.
+    Sending { sent: i64, total: i64 },
+    /// Every delivery has been processed, successfully or not.
+    Completed,
+}
+
+impl DeliveryProgress {
+    /// `not_sent` counts the deliveries that failed or were skipped.
+    pub fn new(sent: i64, not_sent: i64, pending: i64) -> Self {
+        if pending > 0 {
+            Self::Sending {
+                sent,
+                total: sent + not_sent + pending,
+            }
+        } else {
+            Self::Completed
+        }
+    }
+}
+
+impl std::fmt::Display for DeliveryProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Waiting => write!(f, "waiting"),
+            Self::Sending { sent, total } => write!(f, "sending ({sent} of {total})"),
+            Self::Completed => write!(f, "completed"),
+        }
+    }
+}
+
+#[tracing::instrument(name = "Show issue deliveries", skip(pool, settings, flash_messages))]
+pub async fn issue_deliveries(
+    issue_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<NewsletterSettings>,
+    flash_messages: IncomingFlashMessages,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let NewsletterIssue {
+        newsletter_issue_id: issue_id,
+        title,
+    } = load_issue_for(&pool, user_id.into_inner(), &issue_id).await?;
+    let report = get_delivery_report(&pool, issue_id).await.map_err(e500)?;
+    let deliveries = get_deliveries(&pool, issue_id).await.map_err(e500)?;
+    let waiting = is_waiting_for_a_slot(&pool, issue_id, IssueSlots::from_settings(&settings))
+        .await
+        .map_err(e500)?;
+
+    let duration = report
+        .duration()
+        .map(|d| format!("{:.3}s", d.num_milliseconds() as f64 / 1000.0))
+        .unwrap_or_else(|| "n/a".into());
+    let throughput = report
+        .throughput()
+        .map(|t| format!("{t:.2} emails/s"))
+        .unwrap_or_else(|| "n/a".into());
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let now = Utc::now();
+    let mut deliveries_html = String::new();
+    for d in &deliveries {
+        let state = RetryState::new(
+            &d.status,
+            d.processed_at,
+            d.not_before,
+            d.auto_retries,
+            &settings.dead_letter_retry,
+            now,
+        );
+        let replay = if d.status == DeliveryStatus::Failed.as_str() {
+            format!(
+                r#"<form action="/admin/deliveries/{}/replay" method="post"><button type="submit">Replay</button></form>"#,
+                d.delivery_id
+            )
+        } else {
+            String::new()
+        };
+        writeln!(
+            deliveries_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            encode_minimal(&d.subscriber_email),
+            d.status,
+            state,
+            d.attempts,
+            encode_minimal(d.last_error.as_deref().unwrap_or_default()),
+            d.processed_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            replay,
+        )
+        .unwrap();
+    }
+
+    let title = encode_minimal(&title);
+    let progress = if waiting {
+        DeliveryProgress::Waiting
+    } else {
+        DeliveryProgress::new(report.sent, report.failed + report.skipped, report.pending)
+    };
+    let DeliveryReport {
+        sent,
+        failed,
+        skipped,
+        pending,
+        ..
+    } = report;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Issue deliveries</title>
+</head>
+<body>
+    {msg_html}
+    <h1>{title}</h1>
+    <h2>Report</h2>
+    <ul>
+        <li>State: {progress}</li>
+        <li>Sent: {sent}</li>
+        <li>Failed: {failed}</li>
+        <li>Skipped: {skipped}</li>
+        <li>Pending: {pending}</li>
+        <li>Duration: {duration}</li>
+        <li>Throughput: {throughput}</li>
+    </ul>
+    <h2>Deliveries</h2>
+    <table>
+        <tr><th>Recipient</th><th>Status</th><th>Retry state</th><th>Attempts</th><th>Last error</th><th>Processed at</th><th></th></tr>
+        {deliveries_html}
+    </table>
+    <p><a href="/admin/newsletter/{issue_id}/activity">Activity</a></p>
+    <form action="/admin/newsletter/{issue_id}/resend-all" method="post">
+        <button type="submit">Resend to subscribers who have not received it</button>
+    </form>
+    <p><a href="/admin/newsletter">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
+
+#[tracing::instrument(name = "Get delivery report", skip(pool))]
+pub async fn get_delivery_report(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<DeliveryReport, anyhow::Error> {
+    let report = sqlx::query_as!(
+        DeliveryReport,
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE status = 'sent') AS "sent!",
+            COUNT(*) FILTER (WHERE status = 'failed') AS "failed!",
+            COUNT(*) FILTER (WHERE status = 'skipped') AS "skipped!",
+            COUNT(*) FILTER (WHERE status = 'pending') AS "pending!",
+            MIN(processed_at) AS started_at,
+            MAX(processed_at) AS finished_at
+        FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to compute the delivery report.")?;
+    Ok(report)
+}
+
+#[tracing::instrument(name = "Get issue deliveries", skip(pool))]
+async fn get_deliveries(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Vec<DeliveryRecord>, anyhow::Error> {
+    let deliveries = sqlx::query_as!(
+        DeliveryRecord,
+        r#"
+        SELECT
+            q.delivery_id,
+            q.subscriber_email,
+            q.status,
+            q.processed_at,
+            q.not_before,
+            q.auto_retries,
+            (
+                SELECT COUNT(*) FROM delivery_attempts a WHERE a.delivery_id = q.delivery_id
+            ) AS "attempts!",
+            (
+                SELECT a.error FROM delivery_attempts a
+                WHERE a.delivery_id = q.delivery_id
+                ORDER BY a.attempted_at DESC
+                LIMIT 1
+            ) AS last_error
+        FROM issue_delivery_queue q
+        WHERE q.newsletter_issue_id = $1
+        ORDER BY q.processed_at NULLS LAST, q.subscriber_email
+        "#,
+        issue_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve issue deliveries.")?;
+    Ok(deliveries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeliveryProgress, DeliveryReport, RetryState};
+    use crate::configuration::DeadLetterRetrySettings;
+    use chrono::{Duration, Utc};
+
+    fn report(sent: i64, elapsed: Option<Duration>) -> DeliveryReport {
+        let started_at = Utc::now();
+        DeliveryReport {
+            sent,
+            failed: 0,
+            skipped: 0,
+            pending: 0,
+            started_at: elapsed.map(|_| started_at),
+            finished_at: elapsed.map(|e| started_at + e),
+        }
+    }
+
+    #[test]
+    fn throughput_is_sent_emails_per_second() {
+        let report = report(10, Some(Duration::seconds(4)));
+        assert_eq!(report.throughput(), Some(2.5));
+    }
+
+    #[test]
+    fn an_issue_with_pending_deliveries_is_still_sending() {
+        let progress = DeliveryProgress::new(3, 1, 6);
+        assert_eq!(progress, DeliveryProgress::Sending { sent: 3, total: 10 });
+        assert_eq!(progress.to_string(), "sending (3 of 10)");
+    }
+
+    #[test]
+    fn an_issue_without_pending_deliveries_is_completed() {
+        assert_eq!(DeliveryProgress::new(9, 1, 0), DeliveryProgress::Completed);
+        assert_eq!(DeliveryProgress::new(0, 0, 0), DeliveryProgress::Completed);
+    }
+
+    #[test]
+    fn there_is_no_throughput_without_a_measurable_duration() {
+        assert_eq!(report(1, Some(Duration::zero())).throughput(), None);
+        assert_eq!(report(0, None).throughput(), None);
+    }
+
+    fn retry_settings(enabled: bool) -> DeadLetterRetrySettings {
+        DeadLetterRetrySettings {
+            enabled,
+            interval_seconds: 3600,
+            max_retries: 2,
+        }
+    }
+
+    #[test]
+    fn a_failed_delivery_with_retries_left_is_backing_off() {
+        let now = Utc::now();
+        let state = RetryState::new("failed", Some(now), None, 1, &retry_settings(true), now);
+        assert_eq!(
+            state,
+            RetryState::BackingOff {
+                retry_at: now + Duration::hours(1)
+            }
+        );
+    }
+
+    #[test]
+    fn a_failed_delivery_without_retries_has_given_up() {
+        let now = Utc::now();
+        let retry = retry_settings(true);
+        assert_eq!(
+            RetryState::new("failed", Some(now), None, 2, &retry, now),
+            RetryState::GivenUp
+        );
+        let retry = retry_settings(false);
+        assert_eq!(
+            RetryState::new("failed", Some(now), None, 0, &retry, now),
+            RetryState::GivenUp
+        );
+    }
+
+    #[test]
+    fn a_skipped_delivery_is_not_retried() {
+        let now = Utc::now();
+        assert_eq!(
+            RetryState::new("skipped", Some(now), None, 0, &retry_settings(true), now),
+            RetryState::Skipped
+        );
+    }
+
+    #[test]
+    fn a_pending_delivery_is_waiting_until_its_not_before() {
+        let now = Utc::now();
+        let retry = retry_settings(true);
+        let later = now + Duration::minutes(5);
+        assert_eq!(
+            RetryState::new("pending", None, Some(later), 0, &retry, now),
+            RetryState::Waiting { until: later }
+        );
+        assert_eq!(
+            RetryState::new("pending", None, Some(now), 0, &retry, now),
+            RetryState::Queued
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use tokio::time::Instant;
+
+use crate::configuration::NewsletterSettings;
+use crate::issue_delivery_worker::{try_execute_task, ExecutionOutcome, IssueSlots};
+use crate::request_deadline::RequestDeadline;
+use crate::startup::{ApplicationBaseUrl, BroadcastEmailClient};
+use crate::utils::{e500, see_other};
+
+/// Sends everything in the delivery queue right away, for deployments that
+/// do not run the background worker. Batches locked by the worker or by
+/// another drain are skipped, so concurrent calls never send twice.
+/// The drain runs in its own task: the request deadline cannot cancel it
+/// between sending a batch and committing it, which would send that batch
+/// again. If it is not done within half of the request, it carries on after
+/// the response.
+#[tracing::instrument(name = "Dispatch the delivery queue", skip_all)]
+pub async fn dispatch_queue(
+    pool: web::Data<sqlx::PgPool>,
+    broadcast: web::Data<BroadcastEmailClient>,
+    settings: web::Data<NewsletterSettings>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    deadline: web::ReqData<RequestDeadline>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let wait_until = Instant::now() + deadline.saturating_duration_since(Instant::now()) / 2;
+    let drain = tokio::spawn(async move {
+        let drained = drain_queue(&pool, &broadcast, &settings, &base_url.0).await;
+        if let Err(e) = &drained {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to dispatch the delivery queue",
+            );
+        }
+        drained
+    });
+    match tokio::time::timeout_at(wait_until, drain).await {
+        Ok(drained) => {
+            drained.map_err(e500)?.map_err(e500)?;
+            FlashMessage::info("The delivery queue has been dispatched.").send();
+        }
+        Err(_) => {
+            FlashMessage::info("The delivery queue is being dispatched in the background.").send();
+        }
+    }
+    Ok(see_other("/admin/newsletter"))
+}
+
+async fn drain_queue(
+    pool: &sqlx::PgPool,
+    broadcast: &BroadcastEmailClient,
+    settings: &NewsletterSettings,
+    base_url: &str,
+) -> Result<(), anyhow::Error> {
+    loop {
+        let outcome = try_execute_task(
+            pool,
+            &broadcast.client,
+            broadcast.batch_size,
+            settings.minify_html,
+            base_url,
+            IssueSlots::from_settings(settings),
+        )
+        .await?;
+        if let ExecutionOutcome::EmptyQueue = outcome {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::load_issue_for;
+use crate::authentication::UserId;
+use crate::domain::NewsletterContent;
+use crate::utils::{e500, see_other};
+
+pub struct Draft {
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+}
+
+/// Copies an issue into a new, unpublished draft owned by the current admin
+/// and opens it in the publish form. Nothing is sent until it is published.
+#[tracing::instrument(name = "Clone a newsletter issue", skip(pool))]
+pub async fn clone_issue(
+    issue_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let issue = load_issue_for(&pool, user_id, &issue_id).await?;
+    let draft_id = insert_draft_copy(&pool, user_id, issue.newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("A draft copy of the issue has been created.").send();
+    Ok(see_other(&format!("/admin/newsletter?draft_id={draft_id}")))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn insert_draft_copy(
+    pool: &PgPool,
+    user_id: UserId,
+    source_issue_id: Uuid,
+) -> Result<Uuid, anyhow::Error> {
+    let draft_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            published_at,
+            published_by
+        )
+        SELECT $1, title, text_content, html_content, NULL, $2
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $3
+        "#,
+        draft_id,
+        *user_id,
+        source_issue_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the draft copy of a newsletter issue.")?;
+    Ok(draft_id)
+}
+
+#[tracing::instrument(name = "Get newsletter draft", skip(pool))]
+pub async fn get_draft(
+    pool: &PgPool,
+    user_id: UserId,
+    draft_id: Uuid,
+) -> Result<Option<Draft>, anyhow::Error> {
+    let draft = sqlx::query_as!(
+        Draft,
+        r#"
+        SELECT title, text_content, html_content
+        FROM newsletter_issues
+        WHERE
+            newsletter_issue_id = $1 AND
+            published_by = $2 AND
+            published_at IS NULL
+        "#,
+        draft_id,
+        *user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve newsletter draft.")?;
+    Ok(draft)
+}
+
+/// Publishes a draft with the (possibly edited) content from the form.
+/// Returns `false` if there is no such draft for this admin, e.g. because
+/// it has already been published.
+#[tracing::instrument(skip(transaction, content))]
+pub async fn publish_draft(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: UserId,
+    draft_id: Uuid,
+    content: &NewsletterContent,
+) -> Result<bool, sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            title = $3,
+            text_content = $4,
+            html_content = $5,
+            published_at = now()
+        WHERE
+            newsletter_issue_id = $1 AND
+            published_by = $2 AND
+            published_at IS NULL
+        "#,
+        draft_id,
+        *user_id,
+        content.title(),
+        content.text(),
+        content.html()
+    );
+    let n_updated = transaction.execute(query).await?.rows_affected();
+    Ok(n_updated > 0)
+}
@@ -1,13 +1,103 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web::web::ReqData;
+use actix_web::{http::header::ContentType, web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
 use std::fmt::Write;
+use uuid::Uuid;
+
+use super::clipping_warning;
+use super::deliveries::DeliveryProgress;
+use super::drafts::get_draft;
+use super::templates::{get_template, get_templates};
+use crate::authentication::UserId;
+use crate::configuration::NewsletterSettings;
+use crate::session_state::TypedSession;
+use crate::utils::e500;
+
+#[derive(serde::Deserialize)]
+pub struct QueryParams {
+    template_id: Option<Uuid>,
+    draft_id: Option<Uuid>,
+}
 
 pub async fn publish_newsletter_form(
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<NewsletterSettings>,
     flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    user_id: ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+        writeln!(msg_html, "<p><i>{}</i></p>", encode_minimal(m.content())).unwrap();
+    }
+    // Sandboxed, so that the issue's HTML cannot run scripts in the admin.
+    if let Some(preview) = session.take_newsletter_preview().map_err(e500)? {
+        writeln!(
+            msg_html,
+            r#"<iframe sandbox title="Preview" srcdoc="{}"></iframe>"#,
+            encode_minimal(&preview)
+        )
+        .unwrap();
+    }
+
+    let prefill = match (query.draft_id, query.template_id) {
+        (Some(draft_id), _) => get_draft(&pool, user_id, draft_id)
+            .await
+            .map_err(e500)?
+            .map(|d| (d.title, d.text_content, d.html_content)),
+        (None, Some(template_id)) => get_template(&pool, template_id)
+            .await
+            .map_err(e500)?
+            .map(|t| (t.title, t.text_content, t.html_content)),
+        (None, None) => Some(Default::default()),
+    };
+    let Some((title, text_content, html_content)) = prefill else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    if let Some(warning) = clipping_warning(&html_content, settings.clipping_warning_bytes) {
+        writeln!(msg_html, "<p><i>{warning}</i></p>").unwrap();
+    }
+    let title = encode_minimal(&title);
+    let text_content = encode_minimal(&text_content);
+    let html_content = encode_minimal(&html_content);
+    let draft_input = match query.draft_id {
+        Some(draft_id) => {
+            format!(r#"<input hidden type="text" name="draft_id" value="{draft_id}" />"#)
+        }
+        None => String::new(),
+    };
+
+    let mut templates_html = String::new();
+    for t in get_templates(&pool).await.map_err(e500)? {
+        let selected = if Some(t.template_id) == query.template_id {
+            " selected"
+        } else {
+            ""
+        };
+        writeln!(
+            templates_html,
+            r#"<option value="{}"{selected}>{}</option>"#,
+            t.template_id,
+            encode_minimal(&t.name)
+        )
+        .unwrap();
+    }
+
+    let mut issues_html = String::new();
+    for issue in get_recent_issues(&pool, user_id).await.map_err(e500)? {
+        let progress = DeliveryProgress::new(issue.sent, issue.not_sent, issue.pending);
+        writeln!(
+            issues_html,
+            r#"<li><a href="/admin/newsletter/{}/deliveries">{}</a> - {progress}</li>"#,
+            issue.newsletter_issue_id,
+            encode_minimal(&issue.title),
+        )
+        .unwrap();
     }
 
     let idempotency_key = uuid::Uuid::new_v4();
@@ -23,24 +113,111 @@ pub async fn publish_newsletter_form(
 </head>
 <body>
     {msg_html}
+    <form action="/admin/newsletter" method="get">
+        <label>Start from template
+            <select name="template_id">
+                {templates_html}
+            </select>
+        </label>
+        <button type="submit">Use template</button>
+        <a href="/admin/newsletter/templates">Manage templates</a>
+    </form>
     <form action="/admin/newsletter" method="post">
         <label>Title
-            <input type="text" placeholder="Enter title of newsletter issue" name="title" />
+            <input type="text" placeholder="Enter title of newsletter issue" name="title" value="{title}" />
         </label>
         <br/>
         <label>Text
-            <input type="text" placeholder="Enter content of newsletter issue" name="text_content" />
+            <input type="text" placeholder="Enter content of newsletter issue" name="text_content" value="{text_content}" />
         </label>
         <br/>
         <label>HTML
-            <input type="text" placeholder="Enter HTML of newsletter issue" name="html_content" />
+            <input type="text" placeholder="Enter HTML of newsletter issue (optional)" name="html_content" value="{html_content}" />
+        </label>
+        <br/>
+        <label>Markdown (optional, replaces Text and HTML)
+            <textarea placeholder="Enter Markdown of newsletter issue" name="markdown_content"></textarea>
+        </label>
+        <br/>
+        <label>Priority
+            <select name="priority">
+                <option value="high">High</option>
+                <option value="normal" selected>Normal</option>
+                <option value="low">Low</option>
+            </select>
+        </label>
+        <br/>
+        <label>Send at this hour in each subscriber's timezone (0-23, optional)
+            <input type="number" min="0" max="23" name="send_at_local_hour" />
+        </label>
+        <br/>
+        <label>Send at (UTC, optional, up to a year ahead)
+            <input type="datetime-local" name="scheduled_at" />
+        </label>
+        <br/>
+        <label>Only subscribers who confirmed before (UTC, optional)
+            <input type="datetime-local" name="confirmed_before" />
+        </label>
+        <br/>
+        <label>
+            <input type="checkbox" name="dry_run" value="true" />
+            Dry run: count the recipients and preview the issue without sending it
         </label>
         <br/>
         <input hidden type="text" name="idempotency_key" value="{idempotency_key}" />
+        {draft_input}
         <button type="submit">Publish newsletter</button>
+        <button type="submit" formaction="/admin/newsletter/review">Review before publishing</button>
+        <button type="submit" formaction="/admin/newsletter/test">Send test to myself</button>
     </form>
+    <form action="/admin/newsletter/dispatch" method="post">
+        <button type="submit">Send queued emails now</button>
+    </form>
+    <h2>Recent issues</h2>
+    <ul>
+        {issues_html}
+    </ul>
     <p><a href="/admin/dashboard">&lt;- Back</a></p>
 </body>
 </html"#
         )))
 }
+
+struct IssueSummary {
+    newsletter_issue_id: Uuid,
+    title: String,
+    sent: i64,
+    not_sent: i64,
+    pending: i64,
+}
+
+#[tracing::instrument(name = "Get recent newsletter issues", skip(pool))]
+async fn get_recent_issues(
+    pool: &PgPool,
+    user_id: UserId,
+) -> Result<Vec<IssueSummary>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        IssueSummary,
+        r#"
+        SELECT
+            i.newsletter_issue_id,
+            i.title,
+            COUNT(*) FILTER (WHERE q.status = 'sent') AS "sent!",
+            COUNT(*) FILTER (WHERE q.status IN ('failed', 'skipped')) AS "not_sent!",
+            COUNT(*) FILTER (WHERE q.status = 'pending') AS "pending!"
+        FROM newsletter_issues i
+        LEFT JOIN issue_delivery_queue q USING (newsletter_issue_id)
+        WHERE
+            i.published_at IS NOT NULL AND
+            (i.published_by = $1 OR i.published_by IS NULL)
+        GROUP BY i.newsletter_issue_id
+        ORDER BY i.published_at DESC
+        LIMIT 10
+        "#,
+        *user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve recent newsletter issues.")?;
+    Ok(issues)
+}
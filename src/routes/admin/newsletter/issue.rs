@@ -0,0 +1,68 @@
+use actix_web::http::StatusCode;
+use actix_web::ResponseError;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::UserId;
+use crate::routes::error_chain_fmt;
+
+pub struct NewsletterIssue {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum IssueLookupError {
+    #[error("{0} is not a valid newsletter issue id.")]
+    MalformedId(String),
+    #[error("The newsletter issue could not be found.")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for IssueLookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for IssueLookupError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            IssueLookupError::MalformedId(_) => StatusCode::BAD_REQUEST,
+            IssueLookupError::NotFound => StatusCode::NOT_FOUND,
+            IssueLookupError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Loads an issue on behalf of `user_id`.
+/// Issues published by another admin are reported as `NotFound` rather
+/// than forbidden, so an id cannot be used to probe for their existence.
+#[tracing::instrument(name = "Load newsletter issue", skip(pool))]
+pub async fn load_issue_for(
+    pool: &PgPool,
+    user_id: UserId,
+    issue_id: &str,
+) -> Result<NewsletterIssue, IssueLookupError> {
+    let issue_id = Uuid::parse_str(issue_id)
+        .map_err(|_| IssueLookupError::MalformedId(issue_id.to_string()))?;
+    let issue = sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+        SELECT newsletter_issue_id, title
+        FROM newsletter_issues
+        WHERE
+            newsletter_issue_id = $1 AND
+            (published_by = $2 OR published_by IS NULL)
+        "#,
+        issue_id,
+        *user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve newsletter issue.")?;
+    issue.ok_or(IssueLookupError::NotFound)
+}
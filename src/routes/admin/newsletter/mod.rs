@@ -1,5 +1,30 @@
+mod activity;
+mod cancel;
+mod clipping;
+mod deliveries;
+mod dispatch;
+mod drafts;
 mod get;
+mod issue;
 mod post;
+mod resend;
+mod review;
+mod templates;
+mod test_send;
+mod warnings;
 
+pub use activity::issue_activity;
+pub use cancel::cancel_scheduled_issue;
+pub use clipping::clipping_warning;
+pub use deliveries::issue_deliveries;
+pub use dispatch::dispatch_queue;
+pub use drafts::clone_issue;
 pub use get::publish_newsletter_form;
-pub use post::publish_newsletter;
+pub use issue::{load_issue_for, IssueLookupError, NewsletterIssue};
+pub use post::{publish_newsletter, publish_newsletter_api};
+pub use resend::resend_issue;
+pub use review::review_newsletter;
+pub use templates::{
+    create_template, delete_template, edit_template_form, list_templates, update_template,
+};
+pub use test_send::send_test_newsletter;
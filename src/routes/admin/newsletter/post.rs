@@ -1,81 +1,459 @@
-use actix_web::web::ReqData;
+use std::time::SystemTime;
+
+use actix_web::error::ErrorConflict;
+use actix_web::http::header::{HttpDate, LOCATION};
+use actix_web::web::{Either, ReqData};
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
 use sqlx::{Executor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+use super::drafts::publish_draft;
+use super::warnings::{publish_warnings, PublishWarning};
 use crate::authentication::UserId;
+use crate::configuration::{IdempotencySettings, NewsletterSettings};
+use crate::domain::NewsletterContent;
+use crate::events::{Event, EventBus};
+use crate::form::Form;
 use crate::idempotency::{save_response, try_processing, IdempotencyKey, NextAction};
-use crate::utils::{e400, e500, see_other};
+use crate::issue_delivery_worker::DeliveryPriority;
+use crate::session_state::TypedSession;
+use crate::utils::{e400, e500, parse_datetime, see_other};
+
+/// What JSON clients get back instead of the redirect.
+#[derive(serde::Serialize)]
+struct PublishOutcome {
+    issue_id: Option<Uuid>,
+    status: &'static str,
+    /// How many emails were queued.
+    queued: u64,
+    /// Confirmed subscribers left out, e.g. because they are suppressed,
+    /// paused or confirmed after the cutoff.
+    skipped: u64,
+    /// Whether this is the saved outcome of an earlier request with the
+    /// same idempotency key.
+    idempotency_replayed: bool,
+    /// How long the idempotency key is honoured for after the first request.
+    idempotency_ttl_seconds: u64,
+    /// Advisory only, the issue was published regardless.
+    warnings: Vec<PublishWarning>,
+}
+
+/// What JSON clients get back for a dry run.
+#[derive(serde::Serialize)]
+struct DryRunOutcome {
+    status: &'static str,
+    /// How many emails would have been queued.
+    recipients: u64,
+    html_preview: String,
+    warnings: Vec<PublishWarning>,
+}
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
+    #[serde(default)]
     title: String,
+    #[serde(default)]
     html_content: String,
+    #[serde(default)]
     text_content: String,
+    /// Replaces both bodies with ones rendered from this Markdown.
+    #[serde(default)]
+    markdown_content: String,
     idempotency_key: String,
+    /// Set when publishing a draft rather than a brand new issue.
+    draft_id: Option<Uuid>,
+    /// Leave out subscribers who confirmed at or after this time.
+    /// Empty means everyone.
+    #[serde(default)]
+    confirmed_before: String,
+    #[serde(default)]
+    priority: DeliveryPriority,
+    /// Hold each email back until this hour (0-23) in the subscriber's own
+    /// timezone. Empty sends straight away.
+    #[serde(default)]
+    send_at_local_hour: String,
+    /// Hold every email back until this time (UTC). Empty sends straight
+    /// away.
+    #[serde(default)]
+    scheduled_at: String,
+    /// Report how many subscribers would receive the issue and preview it,
+    /// without publishing anything or using up the idempotency key.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// How far ahead an issue can be scheduled.
+const MAX_SCHEDULE_AHEAD_DAYS: i64 = 365;
+
+/// Only checked for requests that are processed, so that retrying a
+/// scheduled publish after its time has passed still replays the response.
+fn check_scheduled_at(
+    scheduled_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<(), String> {
+    let Some(scheduled_at) = scheduled_at else {
+        return Ok(());
+    };
+    if scheduled_at <= now {
+        return Err("The scheduled time must be in the future.".into());
+    }
+    if scheduled_at > now + Duration::days(MAX_SCHEDULE_AHEAD_DAYS) {
+        return Err(format!(
+            "Issues cannot be scheduled more than {MAX_SCHEDULE_AHEAD_DAYS} days ahead."
+        ));
+    }
+    Ok(())
+}
+
+fn parse_local_hour(s: &str) -> Result<Option<i32>, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(None);
+    }
+    match s.parse() {
+        Ok(hour) if (0..24).contains(&hour) => Ok(Some(hour)),
+        _ => Err(format!("{s} is not an hour between 0 and 23.")),
+    }
 }
 
 #[tracing::instrument(
     name = "Publish a newsletter issue",
-    skip(form, pool),
+    skip(body, pool, idempotency, settings, events, session),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn publish_newsletter(
-    form: web::Form<FormData>,
+    body: Either<Form<FormData>, web::Json<FormData>>,
     pool: web::Data<PgPool>,
+    idempotency: web::Data<IdempotencySettings>,
+    settings: web::Data<NewsletterSettings>,
+    events: web::Data<EventBus>,
+    session: TypedSession,
     user_id: ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
+    // JSON clients get a `PublishOutcome`, forms keep the redirect and flash.
+    let (form, wants_json) = match body {
+        Either::Left(form) => (form.0, false),
+        Either::Right(json) => (json.into_inner(), true),
+    };
     let FormData {
         title,
         text_content,
         html_content,
+        markdown_content,
         idempotency_key,
-    } = form.0;
+        draft_id,
+        confirmed_before,
+        priority,
+        send_at_local_hour,
+        scheduled_at,
+        dry_run,
+    } = form;
 
+    let content =
+        parse_content(title, text_content, html_content, markdown_content).map_err(e400)?;
+    let warnings = publish_warnings(&content, settings.clipping_warning_bytes);
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
+    let confirmed_before = parse_datetime(&confirmed_before).map_err(e400)?;
+    let send_at_local_hour = parse_local_hour(&send_at_local_hour).map_err(e400)?;
+    let scheduled_at = parse_datetime(&scheduled_at).map_err(e400)?;
+    if scheduled_at.is_some() && send_at_local_hour.is_some() {
+        return Err(e400(
+            "Choose either a time to send at or a local hour, not both.",
+        ));
+    }
 
-    let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
-        .await
-        .map_err(e500)?
+    if dry_run {
+        check_scheduled_at(scheduled_at, Utc::now()).map_err(e400)?;
+        let n_recipients = count_recipients(&pool, confirmed_before)
+            .await
+            .context("Failed to count the issue's recipients")
+            .map_err(e500)?;
+        if wants_json {
+            return Ok(HttpResponse::Ok().json(DryRunOutcome {
+                status: "dry_run",
+                recipients: n_recipients,
+                html_preview: content.html().to_string(),
+                warnings,
+            }));
+        }
+        session
+            .insert_newsletter_preview(content.html())
+            .map_err(e500)?;
+        FlashMessage::info(format!(
+            "Dry run: {n_recipients} confirmed subscribers would receive this issue. Nothing has been sent."
+        ))
+        .send();
+        for warning in warnings {
+            FlashMessage::warning(warning.message).send();
+        }
+        return Ok(see_other("/admin/newsletter"));
+    }
+
+    let request_hash = request_hash(&[
+        content.title(),
+        content.text(),
+        content.html(),
+        &draft_id.map(|id| id.to_string()).unwrap_or_default(),
+        &confirmed_before.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        &priority.value().to_string(),
+        &send_at_local_hour
+            .map(|h| h.to_string())
+            .unwrap_or_default(),
+        &scheduled_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+    ]);
+    let mut transaction = match try_processing(
+        &pool,
+        &idempotency_key,
+        *user_id,
+        idempotency.scope,
+        &request_hash,
+        idempotency.key_reuse,
+        idempotency.ttl(),
+    )
+    .await
+    .map_err(e500)?
     {
-        NextAction::StartProcessing(t) => t,
+        // Dropping the transaction on a bad time gives the key back.
+        NextAction::StartProcessing(t) => {
+            check_scheduled_at(scheduled_at, Utc::now()).map_err(e400)?;
+            t
+        }
+        NextAction::RejectReusedKey => {
+            return Err(ErrorConflict(
+                "This idempotency key has already been used for a different newsletter issue.",
+            ));
+        }
         NextAction::ReturnSavedResponse(saved_response) => {
-            success_message().send();
+            let outcome = publish_outcome(&saved_response, true, idempotency.ttl_seconds, warnings);
+            if wants_json {
+                return Ok(json_outcome(&saved_response, &outcome));
+            }
+            flash_outcome(outcome, scheduled_at);
             return Ok(saved_response);
         }
     };
 
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
-        .await
-        .context("Failed to store newsletter issue details")
-        .map_err(e500)?;
+    let issue_id = match draft_id {
+        Some(draft_id) => {
+            let published = publish_draft(&mut transaction, user_id, draft_id, &content)
+                .await
+                .context("Failed to publish newsletter draft")
+                .map_err(e500)?;
+            if !published {
+                return Err(e400(
+                    "The draft could not be found or has already been published.",
+                ));
+            }
+            draft_id
+        }
+        None => insert_newsletter_issue(&mut transaction, user_id, &content)
+            .await
+            .context("Failed to store newsletter issue details")
+            .map_err(e500)?,
+    };
+    if let Some(scheduled_at) = scheduled_at {
+        schedule_issue(&mut transaction, issue_id, scheduled_at)
+            .await
+            .context("Failed to store the issue's scheduled time")
+            .map_err(e500)?;
+    }
 
-    enqueue_delivery_tasks(&mut transaction, issue_id)
+    let schedule = send_at_local_hour.map(|hour| LocalSchedule {
+        hour,
+        default_timezone: &settings.default_timezone,
+    });
+    let n_recipients = enqueue_delivery_tasks(
+        &mut transaction,
+        issue_id,
+        confirmed_before,
+        priority,
+        schedule,
+        scheduled_at,
+    )
+    .await
+    .context("Failed to enqueue delivery tasks")
+    .map_err(e500)?;
+    let n_skipped = count_confirmed_subscribers(&mut transaction)
         .await
-        .context("Failed to enqueue delivery tasks")
-        .map_err(e500)?;
+        .context("Failed to count confirmed subscribers")
+        .map_err(e500)?
+        .saturating_sub(n_recipients);
 
-    let response = see_other("/admin/newsletter");
-    let response = save_response(transaction, &idempotency_key, *user_id, response)
-        .await
-        .map_err(e500)?;
-    success_message().send();
+    let expires_at = HttpDate::from(SystemTime::now() + idempotency.ttl());
+    let response = HttpResponse::SeeOther()
+        .insert_header((LOCATION, "/admin/newsletter"))
+        .insert_header(("Idempotency-Expires", expires_at.to_string()))
+        .insert_header((RECIPIENTS_HEADER, n_recipients))
+        .insert_header((SKIPPED_HEADER, n_skipped))
+        .insert_header((ISSUE_ID_HEADER, issue_id.to_string()))
+        .finish();
+    let response = save_response(
+        transaction,
+        &idempotency_key,
+        *user_id,
+        idempotency.scope,
+        response,
+    )
+    .await
+    .map_err(e500)?;
+    events.emit(Event::NewsletterPublished { issue_id });
+    let outcome = publish_outcome(&response, false, idempotency.ttl_seconds, warnings);
+    if wants_json {
+        return Ok(json_outcome(&response, &outcome));
+    }
+    flash_outcome(outcome, scheduled_at);
     Ok(response)
 }
 
-fn success_message() -> FlashMessage {
-    FlashMessage::info("The newsletter issue has been accepted - emails will go out shortly.")
+/// A digest of everything that affects what gets published, to tell a
+/// retry apart from another issue sent with the same idempotency key.
+fn request_hash(fields: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for field in fields {
+        // Length-prefixed, so that moving text between fields changes it.
+        hasher.update((field.len() as u64).to_be_bytes());
+        hasher.update(field.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Markdown replaces both bodies, so it cannot be combined with HTML.
+pub(super) fn parse_content(
+    title: String,
+    text_content: String,
+    html_content: String,
+    markdown_content: String,
+) -> Result<NewsletterContent, String> {
+    match (
+        markdown_content.trim().is_empty(),
+        html_content.trim().is_empty(),
+    ) {
+        (true, _) => NewsletterContent::parse(title, text_content, html_content),
+        (false, true) => NewsletterContent::parse_markdown(title, markdown_content),
+        (false, false) => Err("Provide either Markdown or HTML content, not both.".into()),
+    }
+}
+
+/// `POST /api/newsletters`: the same as publishing from the admin panel,
+/// for clients that authenticate with a token or a password rather than a
+/// session. Always answers with a `PublishOutcome`.
+pub async fn publish_newsletter_api(
+    body: web::Json<FormData>,
+    pool: web::Data<PgPool>,
+    idempotency: web::Data<IdempotencySettings>,
+    settings: web::Data<NewsletterSettings>,
+    events: web::Data<EventBus>,
+    session: TypedSession,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    publish_newsletter(
+        Either::Right(body),
+        pool,
+        idempotency,
+        settings,
+        events,
+        session,
+        user_id,
+    )
+    .await
+}
+
+/// Stored on the saved response so that a retried submission reports the
+/// same outcome as the original one.
+const RECIPIENTS_HEADER: &str = "Newsletter-Recipients";
+const SKIPPED_HEADER: &str = "Newsletter-Skipped";
+const ISSUE_ID_HEADER: &str = "Newsletter-Issue-Id";
+
+/// Reads the outcome back from the saved redirect's headers.
+fn publish_outcome(
+    response: &HttpResponse,
+    idempotency_replayed: bool,
+    idempotency_ttl_seconds: u64,
+    warnings: Vec<PublishWarning>,
+) -> PublishOutcome {
+    let header = |name| response.headers().get(name).and_then(|h| h.to_str().ok());
+    let queued = header(RECIPIENTS_HEADER).and_then(|h| h.parse().ok());
+    PublishOutcome {
+        issue_id: header(ISSUE_ID_HEADER).and_then(|h| h.parse().ok()),
+        status: if queued == Some(0) {
+            "no_recipients"
+        } else {
+            "queued"
+        },
+        queued: queued.unwrap_or_default(),
+        skipped: header(SKIPPED_HEADER)
+            .and_then(|h| h.parse().ok())
+            .unwrap_or_default(),
+        idempotency_replayed,
+        idempotency_ttl_seconds,
+        warnings,
+    }
+}
+
+/// The saved redirect, rewritten as a `PublishOutcome`.
+fn json_outcome(response: &HttpResponse, outcome: &PublishOutcome) -> HttpResponse {
+    let mut json_response = HttpResponse::Ok();
+    if let Some(expires_at) = response.headers().get("Idempotency-Expires") {
+        json_response.insert_header(("Idempotency-Expires", expires_at.clone()));
+    }
+    json_response.json(outcome)
+}
+
+/// What the admin panel shows instead of a `PublishOutcome`: a summary,
+/// followed by each warning.
+fn flash_outcome(outcome: PublishOutcome, scheduled_at: Option<DateTime<Utc>>) {
+    if outcome.status == "no_recipients" {
+        FlashMessage::warning("No confirmed subscribers to send to.").send();
+    } else {
+        FlashMessage::info(outcome_summary(&outcome, scheduled_at)).send();
+    }
+    for warning in outcome.warnings {
+        FlashMessage::warning(warning.message).send();
+    }
+}
+
+/// E.g. "Queued 1,234 emails; 3 subscribers skipped; sending in progress."
+fn outcome_summary(outcome: &PublishOutcome, scheduled_at: Option<DateTime<Utc>>) -> String {
+    let mut parts = vec![format!("Queued {}", counted(outcome.queued, "email"))];
+    if outcome.skipped > 0 {
+        parts.push(format!(
+            "{} skipped",
+            counted(outcome.skipped, "subscriber")
+        ));
+    }
+    parts.push(match scheduled_at {
+        Some(scheduled_at) => format!("sending at {}", scheduled_at.to_rfc3339()),
+        None => "sending in progress".into(),
+    });
+    format!("{}.", parts.join("; "))
+}
+
+/// `n` followed by `noun`, made plural unless `n` is 1.
+fn counted(n: u64, noun: &str) -> String {
+    let plural = if n == 1 { "" } else { "s" };
+    format!("{} {noun}{plural}", with_thousands_separators(n))
+}
+
+fn with_thousands_separators(n: u64) -> String {
+    let digits = n.to_string().into_bytes();
+    let groups: Vec<&str> = digits
+        .rchunks(3)
+        .rev()
+        .map(|group| std::str::from_utf8(group).unwrap())
+        .collect();
+    groups.join(",")
 }
 
 #[tracing::instrument(skip_all)]
 async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
-    title: &str,
-    text_content: &str,
-    html_content: &str,
+    user_id: UserId,
+    content: &NewsletterContent,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
     let query = sqlx::query!(
@@ -85,36 +463,201 @@ async fn insert_newsletter_issue(
             title,
             text_content,
             html_content,
-            published_at
+            published_at,
+            published_by
         )
-        VALUES ($1, $2, $3, $4, now())
+        VALUES ($1, $2, $3, $4, now(), $5)
         "#,
         newsletter_issue_id,
-        title,
-        text_content,
-        html_content
+        content.title(),
+        content.text(),
+        content.html(),
+        *user_id
     );
     transaction.execute(query).await?;
     Ok(newsletter_issue_id)
 }
 
+#[tracing::instrument(skip(transaction))]
+async fn schedule_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    scheduled_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET scheduled_at = $2
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+        scheduled_at
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+/// The number of subscribers `enqueue_delivery_tasks` would queue the
+/// issue for.
+#[tracing::instrument(skip(pool))]
+async fn count_recipients(
+    pool: &PgPool,
+    confirmed_before: Option<DateTime<Utc>>,
+) -> Result<u64, sqlx::Error> {
+    let count = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM subscriptions
+        WHERE
+            status = 'confirmed' AND
+            NOT EXISTS (SELECT 1 FROM suppressions WHERE suppressions.email = subscriptions.email) AND
+            (suppressed_until IS NULL OR suppressed_until <= now()) AND
+            ($1::timestamptz IS NULL OR confirmed_at < $1)
+        "#,
+        confirmed_before
+    )
+    .fetch_one(pool)
+    .await?
+    .count;
+    Ok(count as u64)
+}
+
+#[tracing::instrument(skip_all)]
+async fn count_confirmed_subscribers(
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<u64, sqlx::Error> {
+    let count = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM subscriptions WHERE status = 'confirmed'"#
+    )
+    .fetch_one(&mut **transaction)
+    .await?
+    .count;
+    Ok(count as u64)
+}
+
+/// Send at the next occurrence of `hour` o'clock in each subscriber's
+/// timezone, or in `default_timezone` for subscribers without one.
+struct LocalSchedule<'a> {
+    hour: i32,
+    default_timezone: &'a str,
+}
+
 #[tracing::instrument(skip_all)]
 async fn enqueue_delivery_tasks(
     transaction: &mut Transaction<'_, Postgres>,
     newsletter_issue_id: Uuid,
-) -> Result<(), sqlx::Error> {
+    confirmed_before: Option<DateTime<Utc>>,
+    priority: DeliveryPriority,
+    schedule: Option<LocalSchedule<'_>>,
+    scheduled_at: Option<DateTime<Utc>>,
+) -> Result<u64, sqlx::Error> {
     let query = sqlx::query!(
         r#"
         INSERT INTO issue_delivery_queue (
             newsletter_issue_id,
-            subscriber_email
+            subscriber_email,
+            priority,
+            not_before
         )
-        SELECT $1, email
+        SELECT
+            $1,
+            email,
+            $3,
+            COALESCE($6, CASE WHEN $4::int IS NULL THEN NULL ELSE (
+                local.target +
+                CASE WHEN local.target <= local.now THEN interval '1 day' ELSE interval '0' END
+            ) AT TIME ZONE local.zone END)
         FROM subscriptions
-        WHERE status = 'confirmed'
+        CROSS JOIN LATERAL (
+            SELECT
+                COALESCE(timezone, $5) AS zone,
+                now() AT TIME ZONE COALESCE(timezone, $5) AS now,
+                date_trunc('day', now() AT TIME ZONE COALESCE(timezone, $5)) +
+                    make_interval(hours => COALESCE($4, 0)) AS target
+        ) local
+        WHERE
+            status = 'confirmed' AND
+            NOT EXISTS (SELECT 1 FROM suppressions WHERE suppressions.email = subscriptions.email) AND
+            (suppressed_until IS NULL OR suppressed_until <= now()) AND
+            ($2::timestamptz IS NULL OR confirmed_at < $2)
         "#,
         newsletter_issue_id,
+        confirmed_before,
+        priority.value(),
+        schedule.as_ref().map(|s| s.hour),
+        schedule.as_ref().map_or("UTC", |s| s.default_timezone),
+        scheduled_at,
     );
-    transaction.execute(query).await?;
-    Ok(())
+    let n_enqueued = transaction.execute(query).await?.rows_affected();
+    Ok(n_enqueued)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_scheduled_at, outcome_summary, parse_local_hour, request_hash, PublishOutcome,
+    };
+    use chrono::{Duration, TimeZone, Utc};
+    use claims::{assert_err, assert_ok, assert_ok_eq};
+
+    #[test]
+    fn local_hours_must_be_between_0_and_23() {
+        assert_ok_eq!(parse_local_hour(""), None);
+        assert_ok_eq!(parse_local_hour("0"), Some(0));
+        assert_ok_eq!(parse_local_hour(" 23 "), Some(23));
+        assert_err!(parse_local_hour("24"));
+        assert_err!(parse_local_hour("-1"));
+        assert_err!(parse_local_hour("9am"));
+    }
+
+    #[test]
+    fn scheduled_times_must_be_in_the_next_year() {
+        let now = Utc.with_ymd_and_hms(2023, 12, 7, 12, 0, 0).unwrap();
+        assert_ok!(check_scheduled_at(None, now));
+        assert_ok!(check_scheduled_at(
+            Some(Utc.with_ymd_and_hms(2023, 12, 8, 9, 30, 0).unwrap()),
+            now
+        ));
+        assert_ok!(check_scheduled_at(Some(now + Duration::days(365)), now));
+        assert_err!(check_scheduled_at(Some(now - Duration::minutes(1)), now));
+        assert_err!(check_scheduled_at(
+            Some(now + Duration::days(365) + Duration::minutes(1)),
+            now
+        ));
+    }
+
+    #[test]
+    fn the_request_hash_depends_on_which_field_holds_the_text() {
+        assert_eq!(request_hash(&["ab", "c"]), request_hash(&["ab", "c"]));
+        assert_ne!(request_hash(&["ab", "c"]), request_hash(&["a", "bc"]));
+    }
+
+    fn outcome(queued: u64, skipped: u64) -> PublishOutcome {
+        PublishOutcome {
+            issue_id: None,
+            status: "queued",
+            queued,
+            skipped,
+            idempotency_replayed: false,
+            idempotency_ttl_seconds: 86400,
+            warnings: vec![],
+        }
+    }
+
+    #[test]
+    fn the_outcome_summary_counts_queued_and_skipped_emails() {
+        assert_eq!(
+            outcome_summary(&outcome(1_234, 3), None),
+            "Queued 1,234 emails; 3 subscribers skipped; sending in progress."
+        );
+        assert_eq!(
+            outcome_summary(&outcome(1, 0), None),
+            "Queued 1 email; sending in progress."
+        );
+        let scheduled_at = Utc.with_ymd_and_hms(2023, 12, 8, 9, 30, 0).unwrap();
+        assert_eq!(
+            outcome_summary(&outcome(1_000_000, 1), Some(scheduled_at)),
+            "Queued 1,000,000 emails; 1 subscriber skipped; sending at 2023-12-08T09:30:00+00:00."
+        );
+    }
 }
@@ -0,0 +1,93 @@
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::load_issue_for;
+use crate::audit_log::record_audit_event;
+use crate::authentication::UserId;
+use crate::issue_delivery_worker::DeliveryPriority;
+use crate::utils::{e500, see_other};
+
+/// Queues the issue again for every currently confirmed subscriber who has
+/// not received it yet: subscribers who joined after it was published and
+/// those whose delivery failed. Anyone with a `sent` delivery is skipped.
+#[tracing::instrument(name = "Resend an issue to all subscribers", skip(pool))]
+pub async fn resend_issue(
+    issue_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let issue = load_issue_for(&pool, user_id, &issue_id).await?;
+    let issue_id = issue.newsletter_issue_id;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let n_enqueued = enqueue_missing_deliveries(&mut transaction, issue_id)
+        .await
+        .context("Failed to enqueue delivery tasks")
+        .map_err(e500)?;
+    record_audit_event(
+        &mut transaction,
+        *user_id,
+        "resend_issue",
+        &issue_id.to_string(),
+    )
+    .await
+    .context("Failed to record the resend in the audit log.")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to resend the issue.")
+        .map_err(e500)?;
+
+    if n_enqueued == 0 {
+        FlashMessage::info("Every confirmed subscriber has already received this issue.")
+    } else {
+        FlashMessage::info(format!(
+            "The issue has been queued for {n_enqueued} more subscribers."
+        ))
+    }
+    .send();
+    Ok(see_other(&format!(
+        "/admin/newsletter/{issue_id}/deliveries"
+    )))
+}
+
+/// Drafts are never queued: they go out when they are published.
+/// Resends are a backfill, so they wait for any issue being sent right now.
+#[tracing::instrument(skip(transaction))]
+async fn enqueue_missing_deliveries(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<u64, sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email, priority)
+        SELECT $1, email, $2
+        FROM subscriptions
+        WHERE
+            status = 'confirmed' AND
+            NOT EXISTS (SELECT 1 FROM suppressions WHERE suppressions.email = subscriptions.email) AND
+            (suppressed_until IS NULL OR suppressed_until <= now()) AND
+            EXISTS (
+                SELECT 1 FROM newsletter_issues
+                WHERE newsletter_issue_id = $1 AND published_at IS NOT NULL
+            )
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET status = 'pending', processed_at = NULL, priority = $2
+        WHERE issue_delivery_queue.status = 'failed'
+        "#,
+        newsletter_issue_id,
+        DeliveryPriority::Low.value()
+    );
+    let n_enqueued = transaction.execute(query).await?.rows_affected();
+    Ok(n_enqueued)
+}
@@ -0,0 +1,84 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use htmlescape::encode_minimal;
+use std::fmt::Write;
+
+use super::post::parse_content;
+use super::warnings::publish_warnings;
+use crate::configuration::NewsletterSettings;
+use crate::form::Form;
+use crate::utils::e400;
+
+/// `POST /admin/newsletter/review`: lists the warnings for a newsletter
+/// form before anything is published, with a button that submits the very
+/// same form to `POST /admin/newsletter`.
+#[tracing::instrument(name = "Review a newsletter issue", skip_all)]
+pub async fn review_newsletter(
+    form: Form<Vec<(String, String)>>,
+    settings: web::Data<NewsletterSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let fields = form.into_inner();
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+            .unwrap_or_default()
+    };
+    let content = parse_content(
+        field("title"),
+        field("text_content"),
+        field("html_content"),
+        field("markdown_content"),
+    )
+    .map_err(e400)?;
+    let warnings = publish_warnings(&content, settings.clipping_warning_bytes);
+
+    let mut warnings_html = String::new();
+    for warning in &warnings {
+        writeln!(
+            warnings_html,
+            "<li>{}</li>",
+            encode_minimal(&warning.message)
+        )
+        .unwrap();
+    }
+    if warnings.is_empty() {
+        warnings_html.push_str("<li>No warnings.</li>");
+    }
+    let mut hidden_inputs = String::new();
+    for (name, value) in &fields {
+        writeln!(
+            hidden_inputs,
+            r#"<input hidden type="text" name="{}" value="{}" />"#,
+            encode_minimal(name),
+            encode_minimal(value)
+        )
+        .unwrap();
+    }
+    let title = encode_minimal(content.title());
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Review newsletter issue</title>
+</head>
+<body>
+    <h1>Review "{title}"</h1>
+    <h2>Warnings</h2>
+    <ul>
+        {warnings_html}
+    </ul>
+    <form action="/admin/newsletter" method="post">
+        {hidden_inputs}
+        <button type="submit">Publish newsletter</button>
+    </form>
+    <p><a href="/admin/newsletter">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
@@ -0,0 +1,130 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use super::{get_template, get_templates, NewsletterTemplate};
+use crate::utils::e500;
+
+pub async fn list_templates(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let templates = get_templates(&pool).await.map_err(e500)?;
+    let mut templates_html = String::new();
+    for t in &templates {
+        writeln!(
+            templates_html,
+            r#"<li>{name}
+            <a href="/admin/newsletter?template_id={id}">Start a new issue</a>
+            <a href="/admin/newsletter/templates/{id}">Edit</a>
+            <form action="/admin/newsletter/templates/{id}/delete" method="post">
+                <button type="submit">Delete</button>
+            </form>
+        </li>"#,
+            name = encode_minimal(&t.name),
+            id = t.template_id,
+        )
+        .unwrap();
+    }
+    let form_html = template_form("/admin/newsletter/templates", None, "Save template");
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Newsletter templates</title>
+</head>
+<body>
+    {msg_html}
+    <ul>
+        {templates_html}
+    </ul>
+    <h2>New template</h2>
+    {form_html}
+    <p><a href="/admin/newsletter">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
+
+pub async fn edit_template_form(
+    template_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let template_id = template_id.into_inner();
+    let Some(template) = get_template(&pool, template_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let form_html = template_form(
+        &format!("/admin/newsletter/templates/{template_id}"),
+        Some(&template),
+        "Update template",
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Edit newsletter template</title>
+</head>
+<body>
+    {msg_html}
+    {form_html}
+    <p><a href="/admin/newsletter/templates">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
+
+fn template_form(action: &str, template: Option<&NewsletterTemplate>, submit: &str) -> String {
+    let value = |f: fn(&NewsletterTemplate) -> &str| {
+        template.map(|t| encode_minimal(f(t))).unwrap_or_default()
+    };
+    let name = value(|t| &t.name);
+    let title = value(|t| &t.title);
+    let text_content = value(|t| &t.text_content);
+    let html_content = value(|t| &t.html_content);
+
+    format!(
+        r#"<form action="{action}" method="post">
+        <label>Name
+            <input type="text" placeholder="Enter template name" name="name" value="{name}" />
+        </label>
+        <br/>
+        <label>Title
+            <input type="text" placeholder="Enter title" name="title" value="{title}" />
+        </label>
+        <br/>
+        <label>Text
+            <input type="text" placeholder="Enter text layout" name="text_content" value="{text_content}" />
+        </label>
+        <br/>
+        <label>HTML
+            <input type="text" placeholder="Enter HTML layout" name="html_content" value="{html_content}" />
+        </label>
+        <br/>
+        <button type="submit">{submit}</button>
+    </form>"#
+    )
+}
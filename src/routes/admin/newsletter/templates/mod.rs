@@ -0,0 +1,53 @@
+mod get;
+mod post;
+
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub use get::{edit_template_form, list_templates};
+pub use post::{create_template, delete_template, update_template};
+
+pub struct NewsletterTemplate {
+    pub template_id: Uuid,
+    pub name: String,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+}
+
+#[tracing::instrument(name = "Get newsletter template", skip(pool))]
+pub async fn get_template(
+    pool: &PgPool,
+    template_id: Uuid,
+) -> Result<Option<NewsletterTemplate>, anyhow::Error> {
+    let template = sqlx::query_as!(
+        NewsletterTemplate,
+        r#"
+        SELECT template_id, name, title, text_content, html_content
+        FROM newsletter_templates
+        WHERE template_id = $1
+        "#,
+        template_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve newsletter template.")?;
+    Ok(template)
+}
+
+#[tracing::instrument(name = "List newsletter templates", skip(pool))]
+pub async fn get_templates(pool: &PgPool) -> Result<Vec<NewsletterTemplate>, anyhow::Error> {
+    let templates = sqlx::query_as!(
+        NewsletterTemplate,
+        r#"
+        SELECT template_id, name, title, text_content, html_content
+        FROM newsletter_templates
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve newsletter templates.")?;
+    Ok(templates)
+}
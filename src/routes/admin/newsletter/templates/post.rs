@@ -0,0 +1,110 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::form::Form;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+#[tracing::instrument(name = "Create a newsletter template", skip(form, pool))]
+pub async fn create_template(
+    form: Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if form.name.trim().is_empty() {
+        FlashMessage::error("Templates must have a name.").send();
+        return Ok(see_other("/admin/newsletter/templates"));
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_templates (
+            template_id, name, title, text_content, html_content, created_at, updated_at
+        )
+        VALUES ($1, $2, $3, $4, $5, now(), now())
+        "#,
+        Uuid::new_v4(),
+        form.name.trim(),
+        form.title,
+        form.text_content,
+        form.html_content
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to store newsletter template.")
+    .map_err(e500)?;
+
+    FlashMessage::info("The template has been saved.").send();
+    Ok(see_other("/admin/newsletter/templates"))
+}
+
+#[tracing::instrument(name = "Update a newsletter template", skip(form, pool))]
+pub async fn update_template(
+    template_id: web::Path<Uuid>,
+    form: Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let template_id = template_id.into_inner();
+    if form.name.trim().is_empty() {
+        FlashMessage::error("Templates must have a name.").send();
+        return Ok(see_other(&format!(
+            "/admin/newsletter/templates/{template_id}"
+        )));
+    }
+
+    let n_updated = sqlx::query!(
+        r#"
+        UPDATE newsletter_templates
+        SET
+            name = $2,
+            title = $3,
+            text_content = $4,
+            html_content = $5,
+            updated_at = now()
+        WHERE template_id = $1
+        "#,
+        template_id,
+        form.name.trim(),
+        form.title,
+        form.text_content,
+        form.html_content
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to update newsletter template.")
+    .map_err(e500)?
+    .rows_affected();
+    if n_updated == 0 {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    FlashMessage::info("The template has been updated.").send();
+    Ok(see_other("/admin/newsletter/templates"))
+}
+
+#[tracing::instrument(name = "Delete a newsletter template", skip(pool))]
+pub async fn delete_template(
+    template_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    sqlx::query!(
+        r#"DELETE FROM newsletter_templates WHERE template_id = $1"#,
+        template_id.into_inner()
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to delete newsletter template.")
+    .map_err(e500)?;
+
+    FlashMessage::info("The template has been deleted.").send();
+    Ok(see_other("/admin/newsletter/templates"))
+}
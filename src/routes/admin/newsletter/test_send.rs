@@ -0,0 +1,72 @@
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use super::post::parse_content;
+use crate::authentication::UserId;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::form::Form;
+use crate::routes::admin::account::get_admin_email;
+use crate::utils::{e400, e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    html_content: String,
+    #[serde(default)]
+    text_content: String,
+    #[serde(default)]
+    markdown_content: String,
+}
+
+/// `POST /admin/newsletter/test`: sends the issue in the newsletter form to
+/// the logged-in admin only. Nothing is stored, so the form can be
+/// submitted as often as needed and published afterwards.
+#[tracing::instrument(name = "Send a test newsletter issue", skip(form, pool, email_client))]
+pub async fn send_test_newsletter(
+    form: Form<FormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        title,
+        html_content,
+        text_content,
+        markdown_content,
+    } = form.0;
+    let content =
+        parse_content(title, text_content, html_content, markdown_content).map_err(e400)?;
+
+    let Some(email) = get_admin_email(&pool, user_id.into_inner())
+        .await
+        .map_err(e500)?
+    else {
+        FlashMessage::error(
+            "Your account has no email address to send a test to. \
+                You can set one on the password page.",
+        )
+        .send();
+        return Ok(see_other("/admin/newsletter"));
+    };
+    let recipient = match SubscriberEmail::parse(email) {
+        Ok(recipient) => recipient,
+        Err(e) => {
+            FlashMessage::error(format!("Your account's email address is invalid: {e}")).send();
+            return Ok(see_other("/admin/newsletter"));
+        }
+    };
+    match email_client
+        .send_email(&recipient, content.title(), content.html(), content.text())
+        .await
+    {
+        Ok(()) => FlashMessage::info(format!("A test email has been sent to {recipient}.")),
+        Err(e) => FlashMessage::error(format!("Failed to send the test email: {e}")),
+    }
+    .send();
+    Ok(see_other("/admin/newsletter"))
+}
@@ -0,0 +1,157 @@
+use crate::domain::NewsletterContent;
+
+use super::clipping_warning;
+
+/// Phrases that spam filters are known to score against.
+const SPAM_PHRASES: [&str; 6] = [
+    "act now",
+    "100% free",
+    "risk-free",
+    "cash bonus",
+    "you are a winner",
+    "limited time offer",
+];
+
+/// Something worth a second look before an issue goes out. Warnings never
+/// block publishing.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct PublishWarning {
+    pub kind: PublishWarningKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishWarningKind {
+    /// The HTML is large enough for email clients to clip it.
+    Clipping,
+    /// The content looks like spam and may end up in the junk folder.
+    Spam,
+    /// A link that cannot work from inside an email.
+    BrokenLink,
+}
+
+impl PublishWarning {
+    fn new(kind: PublishWarningKind, message: String) -> Self {
+        Self { kind, message }
+    }
+}
+
+/// Runs every advisory check against `content`.
+pub fn publish_warnings(
+    content: &NewsletterContent,
+    clipping_threshold: usize,
+) -> Vec<PublishWarning> {
+    let mut warnings = Vec::new();
+    if let Some(message) = clipping_warning(content.html(), clipping_threshold) {
+        warnings.push(PublishWarning::new(PublishWarningKind::Clipping, message));
+    }
+    warnings.extend(
+        spam_signals(content)
+            .into_iter()
+            .map(|message| PublishWarning::new(PublishWarningKind::Spam, message)),
+    );
+    warnings.extend(
+        links(content.html())
+            .filter(|href| is_broken(href))
+            .map(|href| {
+                PublishWarning::new(
+                    PublishWarningKind::BrokenLink,
+                    format!("The link \"{href}\" will not work in an email."),
+                )
+            }),
+    );
+    warnings
+}
+
+fn spam_signals(content: &NewsletterContent) -> Vec<String> {
+    let mut signals = Vec::new();
+    let title = content.title();
+    let letters: Vec<char> = title.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.len() >= 10 && letters.iter().all(|c| c.is_uppercase()) {
+        signals.push("The title is written in capitals.".to_string());
+    }
+    if title.contains("!!") {
+        signals.push("The title has repeated exclamation marks.".to_string());
+    }
+    let text = content.text().to_lowercase();
+    for phrase in SPAM_PHRASES {
+        if text.contains(phrase) {
+            signals.push(format!(
+                "The content says \"{phrase}\", which spam filters dislike."
+            ));
+        }
+    }
+    signals
+}
+
+/// The targets of every `href` attribute in `html`.
+fn links(html: &str) -> impl Iterator<Item = &str> {
+    // Lowercasing ASCII keeps byte offsets, so they apply to `html` as well.
+    let lowercase = html.to_ascii_lowercase();
+    let starts: Vec<usize> = lowercase
+        .match_indices("href=")
+        .map(|(i, needle)| i + needle.len())
+        .collect();
+    starts.into_iter().map(move |start| {
+        let rest = &html[start..];
+        match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let rest = &rest[1..];
+                &rest[..rest.find(quote).unwrap_or(rest.len())]
+            }
+            _ => {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || c == '>')
+                    .unwrap_or(rest.len());
+                &rest[..end]
+            }
+        }
+    })
+}
+
+/// Relative links and fragments have nothing to resolve against in an
+/// inbox, so only absolute links with a scheme email clients follow pass.
+fn is_broken(href: &str) -> bool {
+    match reqwest::Url::parse(href.trim()) {
+        Ok(url) => !["http", "https", "mailto", "tel"].contains(&url.scheme()),
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{publish_warnings, PublishWarningKind};
+    use crate::domain::NewsletterContent;
+
+    fn kinds(title: &str, text: &str, html: &str) -> Vec<PublishWarningKind> {
+        let content = NewsletterContent::parse(title.into(), text.into(), html.into()).unwrap();
+        publish_warnings(&content, 1_000)
+            .into_iter()
+            .map(|w| w.kind)
+            .collect()
+    }
+
+    #[test]
+    fn ordinary_content_has_no_warnings() {
+        let html = r#"<p>See <a href="https://example.com/post">the post</a>.</p>"#;
+        assert!(kinds("Monthly update", "See the post.", html).is_empty());
+    }
+
+    #[test]
+    fn shouting_titles_and_spam_phrases_are_flagged() {
+        assert_eq!(
+            kinds("BIG NEWS TODAY", "Act now!", ""),
+            [PublishWarningKind::Spam, PublishWarningKind::Spam]
+        );
+    }
+
+    #[test]
+    fn relative_empty_and_script_links_are_flagged() {
+        let html = r#"<a href="/posts/1">a</a><a href=''>b</a><a href=javascript:alert(1)>c</a>"#;
+        assert_eq!(
+            kinds("Links", "Links", html),
+            [PublishWarningKind::BrokenLink; 3]
+        );
+    }
+}
@@ -1,21 +1,32 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web::{http::header::ContentType, web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
 use std::fmt::Write;
 
+use crate::authentication::UserId;
+use crate::routes::admin::account::get_admin_email;
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 
 pub async fn change_password_form(
     session: TypedSession,
     flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
     if session.get_user_id().map_err(e500)?.is_none() {
         return Ok(see_other("/login"));
     };
+    let email = get_admin_email(&pool, user_id.into_inner())
+        .await
+        .map_err(e500)?
+        .unwrap_or_default();
+    let email = encode_minimal(&email);
 
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+        writeln!(msg_html, "<p><i>{}</i></p>", encode_minimal(m.content())).unwrap();
     }
 
     Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
@@ -42,6 +53,12 @@ pub async fn change_password_form(
         <br/>
         <button type="submit">Change password</button>
     </form>
+    <form action="/admin/account/email" method="post">
+        <label>Email address for test sends
+            <input type="email" placeholder="Enter your email address" name="email" value="{email}" />
+        </label>
+        <button type="submit">Change email address</button>
+    </form>
     <p><a href="/admin/dashboard">&lt;- Back</a></p>
 </body>
 </html"#),
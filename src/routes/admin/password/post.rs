@@ -3,7 +3,9 @@ use actix_web_flash_messages::FlashMessage;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 
-use crate::authentication::{validate_credentials, AuthError, Credentials, UserId};
+use crate::authentication::{
+    validate_credentials, validate_new_password, AuthError, Credentials, UserId,
+};
 use crate::routes::admin::dashboard::get_username;
 use crate::utils::{e500, see_other};
 
@@ -29,16 +31,13 @@ pub async fn change_password(
         return Ok(see_other("/admin/password"));
     }
 
-    if form.new_password.expose_secret().len() <= 12 {
-        FlashMessage::error(
-            "The new password you entered is too short - passwords must be longer than 12 characters.",
-        )
-        .send();
+    let username = get_username(*user_id, &pool).await.map_err(e500)?;
+
+    if let Err(e) = validate_new_password(&username, &form.new_password) {
+        FlashMessage::error(e.to_string()).send();
         return Ok(see_other("/admin/password"));
     }
 
-    let username = get_username(*user_id, &pool).await.map_err(e500)?;
-
     let credentials = Credentials {
         username,
         password: form.0.current_password,
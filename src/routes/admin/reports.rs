@@ -0,0 +1,220 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::domain::{ConfirmationMethod, UnsubscribeReason};
+use crate::utils::e500;
+
+/// How many of the latest free text comments are listed.
+const N_RECENT_DETAILS: i64 = 20;
+
+/// How often each reason was given, with the latest comments underneath.
+#[tracing::instrument(name = "Report unsubscribe reasons", skip(pool))]
+pub async fn unsubscribe_reasons_report(
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let counts = sqlx::query!(
+        r#"SELECT reason, COUNT(*) AS "count!" FROM unsubscribe_reasons GROUP BY reason"#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to count unsubscribe reasons.")
+    .map_err(e500)?;
+    let recent = sqlx::query!(
+        r#"
+        SELECT reason, details AS "details!", recorded_at
+        FROM unsubscribe_reasons
+        WHERE details IS NOT NULL
+        ORDER BY recorded_at DESC
+        LIMIT $1
+        "#,
+        N_RECENT_DETAILS
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve recent unsubscribe comments.")
+    .map_err(e500)?;
+
+    let mut counts_html = String::new();
+    for reason in UnsubscribeReason::ALL {
+        let count = counts
+            .iter()
+            .find(|c| c.reason == reason.as_str())
+            .map_or(0, |c| c.count);
+        writeln!(
+            counts_html,
+            "<tr><td>{}</td><td>{count}</td></tr>",
+            reason.label()
+        )
+        .unwrap();
+    }
+
+    let mut recent_html = String::new();
+    for r in &recent {
+        writeln!(
+            recent_html,
+            "<li>{} ({}): {}</li>",
+            r.recorded_at.to_rfc3339(),
+            UnsubscribeReason::ALL
+                .iter()
+                .find(|reason| reason.as_str() == r.reason)
+                .map_or("Unknown", |reason| reason.label()),
+            encode_minimal(&r.details)
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Unsubscribe reasons</title>
+</head>
+<body>
+    <h1>Unsubscribe reasons</h1>
+    <table>
+        <tr><th>Reason</th><th>Count</th></tr>
+        {counts_html}
+    </table>
+    <h2>Recent comments</h2>
+    <ul>
+        {recent_html}
+    </ul>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
+
+/// How many of the latest rejected webhook calls are listed.
+const N_RECENT_SIGNATURE_FAILURES: i64 = 50;
+
+/// Inbound webhook calls rejected because of their signature, newest first.
+#[tracing::instrument(name = "Report webhook signature failures", skip(pool))]
+pub async fn signature_failures_report(
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let failures = sqlx::query!(
+        r#"
+        SELECT endpoint, reason, remote_addr, headers, received_at
+        FROM webhook_signature_failures
+        ORDER BY received_at DESC
+        LIMIT $1
+        "#,
+        N_RECENT_SIGNATURE_FAILURES
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve recent webhook signature failures.")
+    .map_err(e500)?;
+
+    let mut failures_html = String::new();
+    for f in &failures {
+        writeln!(
+            failures_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td><pre>{}</pre></td></tr>",
+            f.received_at.to_rfc3339(),
+            encode_minimal(&f.endpoint),
+            encode_minimal(&f.reason),
+            encode_minimal(f.remote_addr.as_deref().unwrap_or("unknown")),
+            encode_minimal(&f.headers)
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Webhook signature failures</title>
+</head>
+<body>
+    <h1>Webhook signature failures</h1>
+    <table>
+        <tr><th>Received at</th><th>Endpoint</th><th>Reason</th><th>From</th><th>Headers</th></tr>
+        {failures_html}
+    </table>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
+
+/// How confirmed subscribers confirmed their address.
+#[tracing::instrument(name = "Report confirmation methods", skip(pool))]
+pub async fn confirmation_methods_report(
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let counts = sqlx::query!(
+        r#"
+        SELECT confirmed_via, COUNT(*) AS "count!"
+        FROM subscriptions
+        WHERE status = 'confirmed'
+        GROUP BY confirmed_via
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to count confirmation methods.")
+    .map_err(e500)?;
+
+    let mut counts_html = String::new();
+    for method in ConfirmationMethod::ALL {
+        let count = counts
+            .iter()
+            .find(|c| c.confirmed_via.as_deref() == Some(method.as_str()))
+            .map_or(0, |c| c.count);
+        writeln!(
+            counts_html,
+            "<tr><td>{}</td><td>{count}</td></tr>",
+            method.label()
+        )
+        .unwrap();
+    }
+    // Confirmed before the method was recorded.
+    let untracked = counts
+        .iter()
+        .filter(|c| {
+            !ConfirmationMethod::ALL
+                .iter()
+                .any(|m| c.confirmed_via.as_deref() == Some(m.as_str()))
+        })
+        .map(|c| c.count)
+        .sum::<i64>();
+    if untracked > 0 {
+        writeln!(
+            counts_html,
+            "<tr><td>Not recorded</td><td>{untracked}</td></tr>"
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Confirmation methods</title>
+</head>
+<body>
+    <h1>Confirmation methods</h1>
+    <table>
+        <tr><th>Method</th><th>Confirmed subscribers</th></tr>
+        {counts_html}
+    </table>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
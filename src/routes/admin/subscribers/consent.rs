@@ -0,0 +1,151 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::utils::e500;
+
+/// The subscriber's consent history, for answering data access requests.
+#[derive(serde::Serialize)]
+pub struct ConsentRecord {
+    subscriber_id: Uuid,
+    email: String,
+    status: String,
+    /// Emails are held back until then, if the subscriber asked for a pause.
+    paused_until: Option<String>,
+    events: Vec<ConsentEvent>,
+}
+
+/// IP addresses are not captured at sign-up, so `detail` only says where
+/// the subscription came from or how it was confirmed.
+#[derive(serde::Serialize)]
+pub struct ConsentEvent {
+    event: String,
+    at: String,
+    detail: Option<String>,
+}
+
+struct SubscriberRow {
+    email: String,
+    status: String,
+    suppressed_until: Option<DateTime<Utc>>,
+}
+
+struct EventRow {
+    event: String,
+    occurred_at: DateTime<Utc>,
+    detail: Option<String>,
+}
+
+/// Downloads the subscriber's consent timeline as JSON: when they
+/// subscribed, when they confirmed, why they unsubscribed, when they came
+/// back, and any changes an admin made to their subscription, which are
+/// audited with the subscriber's id as the target.
+#[tracing::instrument(name = "Download subscriber consent records", skip(pool))]
+pub async fn subscriber_consent(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(subscriber) = get_subscriber(&pool, subscriber_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let events = get_consent_events(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|e| ConsentEvent {
+            event: e.event,
+            at: e.occurred_at.to_rfc3339(),
+            detail: e.detail,
+        })
+        .collect();
+    let record = ConsentRecord {
+        subscriber_id,
+        email: subscriber.email,
+        status: subscriber.status,
+        paused_until: subscriber
+            .suppressed_until
+            .filter(|until| *until > Utc::now())
+            .map(|until| until.to_rfc3339()),
+        events,
+    };
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "consent-{subscriber_id}.json"
+            ))],
+        })
+        .json(record))
+}
+
+#[tracing::instrument(name = "Get subscriber for consent records", skip(pool))]
+async fn get_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberRow>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        SubscriberRow,
+        r#"SELECT email, status, suppressed_until FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve subscriber.")?;
+    Ok(subscriber)
+}
+
+/// Oldest first. Events at the same instant keep their natural order:
+/// subscribed, confirmed, then anything that happened afterwards.
+#[tracing::instrument(name = "Get subscriber consent events", skip(pool))]
+async fn get_consent_events(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<EventRow>, anyhow::Error> {
+    let events = sqlx::query_as!(
+        EventRow,
+        r#"
+        SELECT
+            event AS "event!",
+            occurred_at AS "occurred_at!",
+            detail
+        FROM (
+            SELECT 'subscribed' AS event, subscribed_at AS occurred_at, source AS detail, 0 AS stage
+            FROM subscriptions
+            WHERE id = $1
+            UNION ALL
+            SELECT 'confirmed', confirmed_at, confirmed_via, 1
+            FROM subscriptions
+            WHERE id = $1 AND confirmed_at IS NOT NULL
+            UNION ALL
+            SELECT 'unsubscribed', recorded_at, reason, 2
+            FROM unsubscribe_reasons
+            WHERE subscriber_id = $1
+            UNION ALL
+            SELECT
+                'resubscribed',
+                resubscribed_at,
+                CASE WHEN confirmation_kept
+                    THEN 'earlier confirmation kept'
+                    ELSE 'asked to confirm again'
+                END,
+                2
+            FROM resubscriptions
+            WHERE subscriber_id = $1
+            UNION ALL
+            SELECT action, created_at, NULL, 2
+            FROM audit_log
+            WHERE target = $1::text
+        ) consent
+        ORDER BY occurred_at, stage
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscriber consent events.")?;
+    Ok(events)
+}
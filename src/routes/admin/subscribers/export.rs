@@ -0,0 +1,211 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::domain::SubscriberTag;
+use crate::utils::e400;
+
+/// How many subscribers are read from the database at a time.
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct QueryParams {
+    /// Only export subscribers with this tag. Everyone is exported when unset.
+    tag: Option<String>,
+    /// Only export subscribers with this status, e.g. `confirmed`.
+    status: Option<String>,
+}
+
+/// A subscriber as listed in the export.
+pub struct SubscriberRecord {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+/// Where a page of subscribers ended. The next page starts right after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriberCursor {
+    subscribed_at: DateTime<Utc>,
+    id: Uuid,
+}
+
+/// Up to `limit` subscribers after `after`, oldest first, matching every
+/// filter that is set. Pages are keyed on when each subscriber signed up,
+/// so subscribers added while paging do not shift the later pages. The
+/// cursor is `None` once there is nothing left.
+#[tracing::instrument(name = "Get a page of subscribers", skip(pool))]
+pub async fn get_subscribers_page(
+    pool: &PgPool,
+    status: Option<&str>,
+    tag: Option<&str>,
+    after: Option<SubscriberCursor>,
+    limit: i64,
+) -> Result<(Vec<SubscriberRecord>, Option<SubscriberCursor>), sqlx::Error> {
+    let subscribers = sqlx::query_as!(
+        SubscriberRecord,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions s
+        WHERE
+            ($1::text IS NULL OR status = $1) AND
+            ($2::text IS NULL OR EXISTS (
+                SELECT 1 FROM subscriber_tags t
+                WHERE t.subscriber_id = s.id AND t.tag = $2
+            )) AND
+            ($3::timestamptz IS NULL OR (subscribed_at, id) > ($3, $4::uuid))
+        ORDER BY subscribed_at, id
+        LIMIT $5
+        "#,
+        status,
+        tag,
+        after.map(|cursor| cursor.subscribed_at),
+        after.map(|cursor| cursor.id),
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    let next = match subscribers.last() {
+        Some(last) if subscribers.len() as i64 == limit => Some(SubscriberCursor {
+            subscribed_at: last.subscribed_at,
+            id: last.id,
+        }),
+        _ => None,
+    };
+    Ok((subscribers, next))
+}
+
+/// Streams the subscriber list as CSV, a page at a time, so the whole
+/// list never has to be held in memory.
+#[tracing::instrument(name = "Export subscribers", skip(pool))]
+pub async fn export_subscribers(
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let QueryParams { tag, status } = query.into_inner();
+    let tag = tag.map(SubscriberTag::parse).transpose().map_err(e400)?;
+    let status = status
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+
+    let (sender, receiver) = mpsc::channel(16);
+    let pool = pool.get_ref().clone();
+    tokio::spawn(async move {
+        let tag = tag.as_ref().map(AsRef::as_ref);
+        let header = Bytes::from_static(b"email,name,status,subscribed_at\n");
+        if sender.send(Ok(header)).await.is_err() {
+            return;
+        }
+        let mut after = None;
+        loop {
+            let page =
+                get_subscribers_page(&pool, status.as_deref(), tag, after, EXPORT_PAGE_SIZE).await;
+            let (chunk, next) = match page {
+                Ok((subscribers, next)) => {
+                    let rows: String = subscribers
+                        .iter()
+                        .map(|r| {
+                            csv_row(&[&r.email, &r.name, &r.status, &r.subscribed_at.to_rfc3339()])
+                        })
+                        .collect();
+                    (Ok(Bytes::from(rows)), next)
+                }
+                Err(e) => {
+                    tracing::error!(error.cause_chain = ?e, "Failed to export subscribers");
+                    (Err(e), None)
+                }
+            };
+            // The client went away, there is no point in carrying on.
+            if sender.send(chunk).await.is_err() {
+                return;
+            }
+            match next {
+                Some(next) => after = Some(next),
+                None => return,
+            }
+        }
+    });
+
+    let body = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("subscribers.csv".into())],
+        })
+        .streaming(body))
+}
+
+fn csv_row(fields: &[&str]) -> String {
+    let mut row = fields
+        .iter()
+        .map(|f| csv_field(f))
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push('\n');
+    row
+}
+
+/// Quotes a field if it contains a separator, a quote or a line break.
+/// Fields that a spreadsheet would run as a formula, e.g. a name like
+/// `=HYPERLINK(...)`, are prefixed with `'` so they are shown as text.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv_field, csv_row};
+
+    #[test]
+    fn plain_fields_are_left_as_they_are() {
+        assert_eq!(
+            csv_field("ursula_le_guin@gmail.com"),
+            "ursula_le_guin@gmail.com"
+        );
+    }
+
+    #[test]
+    fn fields_with_separators_or_quotes_are_quoted() {
+        assert_eq!(csv_field("Le Guin, Ursula"), r#""Le Guin, Ursula""#);
+        assert_eq!(
+            csv_field(r#"Ursula "UKL" Le Guin"#),
+            r#""Ursula ""UKL"" Le Guin""#
+        );
+    }
+
+    #[test]
+    fn fields_that_look_like_formulas_are_escaped() {
+        assert_eq!(csv_field("=1+1"), "'=1+1");
+        assert_eq!(csv_field("+44 20"), "'+44 20");
+        assert_eq!(csv_field("-2"), "'-2");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(
+            csv_field(r#"=HYPERLINK("http://evil.example", "x")"#),
+            r#""'=HYPERLINK(""http://evil.example"", ""x"")""#
+        );
+        assert_eq!(csv_field("Ursula-Le-Guin"), "Ursula-Le-Guin");
+    }
+
+    #[test]
+    fn rows_are_comma_separated_and_newline_terminated() {
+        assert_eq!(csv_row(&["a", "b,c"]), "a,\"b,c\"\n");
+    }
+}
@@ -0,0 +1,236 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::utils::e500;
+
+struct SubscriberRecord {
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    suppressed_until: Option<DateTime<Utc>>,
+    /// When the address was added to the suppression list, if it is on it.
+    suppressed_at: Option<DateTime<Utc>>,
+}
+
+struct DeliveryRecord {
+    title: String,
+    status: String,
+    queued_at: DateTime<Utc>,
+    processed_at: Option<DateTime<Utc>>,
+}
+
+struct AttemptRecord {
+    title: String,
+    attempted_at: DateTime<Utc>,
+    result: String,
+    error: Option<String>,
+}
+
+#[tracing::instrument(name = "Show subscriber details", skip(pool, flash_messages))]
+pub async fn subscriber_details(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(subscriber) = get_subscriber(&pool, subscriber_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let tags = get_tags(&pool, subscriber_id).await.map_err(e500)?;
+    let deliveries = get_deliveries(&pool, &subscriber.email)
+        .await
+        .map_err(e500)?;
+    let attempts = get_attempts(&pool, &subscriber.email).await.map_err(e500)?;
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", encode_minimal(m.content())).unwrap();
+    }
+
+    let mut tags_html = String::new();
+    for tag in &tags {
+        writeln!(tags_html, "<li>{}</li>", encode_minimal(tag)).unwrap();
+    }
+    if tags.is_empty() {
+        tags_html.push_str("<li>No tags</li>");
+    }
+
+    let mut deliveries_html = String::new();
+    for d in &deliveries {
+        writeln!(
+            deliveries_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            encode_minimal(&d.title),
+            d.status,
+            d.queued_at.to_rfc3339(),
+            d.processed_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        )
+        .unwrap();
+    }
+
+    let mut attempts_html = String::new();
+    for a in &attempts {
+        writeln!(
+            attempts_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            encode_minimal(&a.title),
+            a.attempted_at.to_rfc3339(),
+            a.result,
+            encode_minimal(a.error.as_deref().unwrap_or_default()),
+        )
+        .unwrap();
+    }
+
+    let email = encode_minimal(&subscriber.email);
+    let name = encode_minimal(&subscriber.name);
+    let status = &subscriber.status;
+    let subscribed_at = subscriber.subscribed_at.to_rfc3339();
+    let pause_html = match subscriber.suppressed_until {
+        Some(until) if until > Utc::now() => {
+            format!("<p>Emails paused until {}</p>", until.to_rfc3339())
+        }
+        _ => String::new(),
+    };
+    let suppression = match subscriber.suppressed_at {
+        Some(at) => format!("suppressed since {}, never emailed", at.to_rfc3339()),
+        None => "not suppressed".into(),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Subscriber details</title>
+</head>
+<body>
+    {msg_html}
+    <h1>{name} &lt;{email}&gt;</h1>
+    <h2>Status</h2>
+    <p>{status} (subscribed at {subscribed_at})</p>
+    <p>Suppression: {suppression}</p>
+    <p><a href="/admin/subscribers/{subscriber_id}/consent.json">Download consent records</a></p>
+    {pause_html}
+    <form action="/admin/subscribers/{subscriber_id}/pause" method="post">
+        <label>Do not email until
+            <input type="datetime-local" name="suppressed_until" />
+        </label>
+        <button type="submit">Pause emails</button>
+    </form>
+    <h2>Tags</h2>
+    <ul>
+        {tags_html}
+    </ul>
+    <form action="/admin/subscribers/{subscriber_id}/tags" method="post">
+        <input type="text" placeholder="Enter tag" name="tag" />
+        <button type="submit">Add tag</button>
+    </form>
+    <h2>Delivery history</h2>
+    <table>
+        <tr><th>Issue</th><th>Status</th><th>Queued at</th><th>Processed at</th></tr>
+        {deliveries_html}
+    </table>
+    <h2>Delivery attempts</h2>
+    <table>
+        <tr><th>Issue</th><th>Attempted at</th><th>Result</th><th>Error</th></tr>
+        {attempts_html}
+    </table>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
+
+#[tracing::instrument(name = "Get subscriber", skip(pool))]
+async fn get_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberRecord>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        SubscriberRecord,
+        r#"
+        SELECT s.email, s.name, s.status, s.subscribed_at, s.suppressed_until,
+            sp.suppressed_at AS "suppressed_at?"
+        FROM subscriptions s
+        LEFT JOIN suppressions sp ON sp.email = s.email
+        WHERE s.id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve subscriber.")?;
+    Ok(subscriber)
+}
+
+#[tracing::instrument(name = "Get subscriber tags", skip(pool))]
+async fn get_tags(pool: &PgPool, subscriber_id: Uuid) -> Result<Vec<String>, anyhow::Error> {
+    let tags = sqlx::query!(
+        r#"SELECT tag FROM subscriber_tags WHERE subscriber_id = $1 ORDER BY tag"#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscriber tags.")?
+    .into_iter()
+    .map(|r| r.tag)
+    .collect();
+    Ok(tags)
+}
+
+#[tracing::instrument(name = "Get subscriber deliveries", skip(pool, email))]
+async fn get_deliveries(pool: &PgPool, email: &str) -> Result<Vec<DeliveryRecord>, anyhow::Error> {
+    let deliveries = sqlx::query_as!(
+        DeliveryRecord,
+        r#"
+        SELECT
+            newsletter_issues.title,
+            issue_delivery_queue.status,
+            issue_delivery_queue.created_at AS queued_at,
+            issue_delivery_queue.processed_at
+        FROM issue_delivery_queue
+        JOIN newsletter_issues USING (newsletter_issue_id)
+        WHERE issue_delivery_queue.subscriber_email = $1
+        ORDER BY issue_delivery_queue.created_at DESC
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscriber deliveries.")?;
+    Ok(deliveries)
+}
+
+#[tracing::instrument(name = "Get subscriber delivery attempts", skip(pool, email))]
+async fn get_attempts(pool: &PgPool, email: &str) -> Result<Vec<AttemptRecord>, anyhow::Error> {
+    let attempts = sqlx::query_as!(
+        AttemptRecord,
+        r#"
+        SELECT
+            newsletter_issues.title,
+            delivery_attempts.attempted_at,
+            delivery_attempts.result,
+            delivery_attempts.error
+        FROM delivery_attempts
+        JOIN issue_delivery_queue USING (delivery_id)
+        JOIN newsletter_issues USING (newsletter_issue_id)
+        WHERE issue_delivery_queue.subscriber_email = $1
+        ORDER BY delivery_attempts.attempted_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscriber delivery attempts.")?;
+    Ok(attempts)
+}
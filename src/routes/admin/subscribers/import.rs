@@ -0,0 +1,358 @@
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::audit_log::record_audit_event;
+use crate::authentication::UserId;
+use crate::configuration::SubscriptionSettings;
+use crate::domain::{ConfirmationMethod, SubscriberEmail, SubscriberName, SubscriberTag};
+use crate::events::{Event, EventBus};
+use crate::form::Form;
+use crate::routes::{flag_for_resend, generate_subscription_token, store_token};
+use crate::utils::{e400, e500};
+
+#[derive(serde::Deserialize)]
+pub struct ImportFormData {
+    csv: String,
+    email_column: String,
+    name_column: String,
+    #[serde(default)]
+    tag_column: String,
+    #[serde(default)]
+    source_column: String,
+    /// The list was confirmed elsewhere, so subscribers are stored as
+    /// confirmed instead of being asked to confirm again.
+    #[serde(default)]
+    already_confirmed: bool,
+}
+
+#[derive(serde::Serialize, Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Subscribers created by this import.
+    imported: u64,
+    /// Valid addresses that belong to an existing subscriber.
+    already_subscribed: u64,
+    /// Valid addresses that are on the suppression list.
+    suppressed: u64,
+    /// Rows with an invalid email, name or tag.
+    invalid: u64,
+}
+
+/// Where each mapped field lives in a row.
+#[derive(Debug, PartialEq, Eq)]
+struct ColumnMapping {
+    email: usize,
+    name: usize,
+    tag: Option<usize>,
+    source: Option<usize>,
+}
+
+impl ColumnMapping {
+    /// Resolves the mapped column names against the header row, ignoring case.
+    fn resolve(header: &[String], form: &ImportFormData) -> Result<Self, String> {
+        let find = |column: &str| {
+            header
+                .iter()
+                .position(|h| h.eq_ignore_ascii_case(column.trim()))
+                .ok_or_else(|| format!("The CSV has no `{}` column.", column.trim()))
+        };
+        let find_optional = |column: &str| match column.trim() {
+            "" => Ok(None),
+            column => find(column).map(Some),
+        };
+        Ok(Self {
+            email: find(&form.email_column)?,
+            name: find(&form.name_column)?,
+            tag: find_optional(&form.tag_column)?,
+            source: find_optional(&form.source_column)?,
+        })
+    }
+}
+
+struct ImportedSubscriber {
+    email: SubscriberEmail,
+    name: SubscriberName,
+    tag: Option<SubscriberTag>,
+    source: Option<String>,
+}
+
+/// Imports subscribers from a CSV exported by another provider.
+/// The header row is matched against the column mapping in the form, so
+/// the file can be uploaded as-is. Existing and suppressed addresses are
+/// left untouched. Imported subscribers are asked to confirm, like anyone
+/// signing up, unless the admin says the list is already confirmed; every
+/// import is audited either way.
+#[tracing::instrument(name = "Import subscribers", skip_all)]
+pub async fn import_subscribers(
+    form: Form<ImportFormData>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+    events: web::Data<EventBus>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = *user_id.into_inner();
+    let form = form.into_inner();
+    let rows = read_csv(&form.csv).map_err(e400)?;
+    let mut rows = rows.into_iter();
+    let header = rows.next().ok_or_else(|| e400("The CSV is empty."))?;
+    let mapping = ColumnMapping::resolve(&header, &form).map_err(e400)?;
+
+    let mut report = ImportReport::default();
+    let mut subscribers = Vec::new();
+    let mut imported_ids = Vec::new();
+    for row in rows {
+        match parse_row(&row, &mapping, &settings) {
+            Ok(subscriber) => subscribers.push(subscriber),
+            Err(_) => report.invalid += 1,
+        }
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    for subscriber in &subscribers {
+        if is_suppressed(&mut transaction, &subscriber.email)
+            .await
+            .context("Failed to check the suppression list.")
+            .map_err(e500)?
+        {
+            report.suppressed += 1;
+            continue;
+        }
+        let subscriber_id =
+            insert_imported_subscriber(&mut transaction, subscriber, form.already_confirmed)
+                .await
+                .context("Failed to store an imported subscriber.")
+                .map_err(e500)?;
+        let Some(subscriber_id) = subscriber_id else {
+            report.already_subscribed += 1;
+            continue;
+        };
+        if let Some(tag) = &subscriber.tag {
+            tag_imported_subscriber(&mut transaction, subscriber_id, tag)
+                .await
+                .context("Failed to tag an imported subscriber.")
+                .map_err(e500)?;
+        }
+        let action = if form.already_confirmed {
+            "import_confirmed_subscriber"
+        } else {
+            // The reminder worker sends the confirmation email, within the
+            // same budget as sign ups.
+            store_token(
+                &mut transaction,
+                subscriber_id,
+                &generate_subscription_token(),
+            )
+            .await
+            .context("Failed to store the confirmation token for an imported subscriber.")
+            .map_err(e500)?;
+            flag_for_resend(&mut transaction, subscriber_id, Utc::now())
+                .await
+                .context("Failed to queue the confirmation email of an imported subscriber.")
+                .map_err(e500)?;
+            "import_subscriber"
+        };
+        record_audit_event(
+            &mut transaction,
+            user_id,
+            action,
+            &subscriber_id.to_string(),
+        )
+        .await
+        .context("Failed to record an imported subscriber in the audit log.")
+        .map_err(e500)?;
+        imported_ids.push(subscriber_id);
+        report.imported += 1;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to import subscribers.")
+        .map_err(e500)?;
+    for subscriber_id in imported_ids {
+        events.emit(if form.already_confirmed {
+            Event::SubscriberConfirmed { subscriber_id }
+        } else {
+            Event::SubscriberAdded { subscriber_id }
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+fn parse_row(
+    row: &[String],
+    mapping: &ColumnMapping,
+    settings: &SubscriptionSettings,
+) -> Result<ImportedSubscriber, String> {
+    let field = |i: usize| row.get(i).cloned().unwrap_or_default();
+    let email = SubscriberEmail::parse(field(mapping.email))?;
+    settings.email_domain_policy().check(&email)?;
+    let name = SubscriberName::parse_with(field(mapping.name), settings.name_formatting())?;
+    let tag = match mapping.tag.map(field).filter(|t| !t.is_empty()) {
+        Some(tag) => Some(SubscriberTag::parse(tag)?),
+        None => None,
+    };
+    let source = mapping.source.map(field).filter(|s| !s.is_empty());
+    Ok(ImportedSubscriber {
+        email,
+        name,
+        tag,
+        source,
+    })
+}
+
+/// Splits the CSV into rows of trimmed fields, header included. Quoted
+/// fields may contain commas, quotes and line breaks, and rows may have
+/// fewer fields than the header.
+fn read_csv(csv: &str) -> Result<Vec<Vec<String>>, csv::Error> {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(csv.as_bytes())
+        .records()
+        .map(|record| Ok(record?.iter().map(str::to_string).collect()))
+        .collect()
+}
+
+#[tracing::instrument(skip_all)]
+async fn is_suppressed(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<bool, sqlx::Error> {
+    let suppressed = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM suppressions WHERE email = $1) AS "suppressed!""#,
+        email.as_ref()
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(suppressed)
+}
+
+/// Stores a subscriber waiting to confirm, or a confirmed one if the list
+/// was `already_confirmed`. Returns `None` if the address is taken.
+#[tracing::instrument(skip_all)]
+async fn insert_imported_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber: &ImportedSubscriber,
+    already_confirmed: bool,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let now = Utc::now();
+    let (status, confirmed_at, confirmed_via) = if already_confirmed {
+        (
+            "confirmed",
+            Some(now),
+            Some(ConfirmationMethod::Import.as_str()),
+        )
+    } else {
+        ("pending_confirmation", None, None)
+    };
+    let subscriber_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO subscriptions (
+            id, email, name, subscribed_at, status, confirmed_at, confirmed_via, source
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (email) DO NOTHING
+        RETURNING id
+        "#,
+        Uuid::new_v4(),
+        subscriber.email.as_ref(),
+        subscriber.name.as_ref(),
+        now,
+        status,
+        confirmed_at,
+        confirmed_via,
+        subscriber.source,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(subscriber_id)
+}
+
+#[tracing::instrument(skip_all)]
+async fn tag_imported_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    tag: &SubscriberTag,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO subscriber_tags (subscriber_id, tag) VALUES ($1, $2)",
+        subscriber_id,
+        tag.as_ref()
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_csv, ColumnMapping, ImportFormData};
+
+    fn form(email: &str, name: &str, tag: &str) -> ImportFormData {
+        ImportFormData {
+            csv: String::new(),
+            email_column: email.into(),
+            name_column: name.into(),
+            tag_column: tag.into(),
+            source_column: String::new(),
+            already_confirmed: false,
+        }
+    }
+
+    #[test]
+    fn quoted_fields_may_contain_commas_and_quotes() {
+        let rows = read_csv(r#"ursula@example.com ,"Le Guin, Ursula","say ""hi""""#).unwrap();
+        assert_eq!(
+            rows,
+            [["ursula@example.com", "Le Guin, Ursula", r#"say "hi""#]]
+        );
+    }
+
+    #[test]
+    fn quoted_fields_may_span_lines() {
+        let rows = read_csv("name,notes\nUrsula,\"first line\nsecond line\"\n\nOctavia\n").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["name", "notes"],
+                vec!["Ursula", "first line\nsecond line"],
+                vec!["Octavia"],
+            ]
+        );
+    }
+
+    #[test]
+    fn columns_are_mapped_ignoring_case() {
+        let header = read_csv("Full Name,E-mail Address,Segment")
+            .unwrap()
+            .remove(0);
+        let mapping =
+            ColumnMapping::resolve(&header, &form("e-mail address", "full name", "SEGMENT"))
+                .unwrap();
+        assert_eq!(
+            mapping,
+            ColumnMapping {
+                email: 1,
+                name: 0,
+                tag: Some(2),
+                source: None,
+            }
+        );
+    }
+
+    #[test]
+    fn a_missing_mapped_column_is_rejected() {
+        let header = read_csv("email,name").unwrap().remove(0);
+        let err = ColumnMapping::resolve(&header, &form("email", "name", "segment")).unwrap_err();
+        assert_eq!(err, "The CSV has no `segment` column.");
+    }
+}
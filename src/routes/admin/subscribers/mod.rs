@@ -0,0 +1,15 @@
+mod consent;
+mod export;
+mod get;
+mod import;
+mod pause;
+mod search;
+mod tags;
+
+pub use consent::subscriber_consent;
+pub use export::{export_subscribers, get_subscribers_page, SubscriberCursor, SubscriberRecord};
+pub use get::subscriber_details;
+pub use import::import_subscribers;
+pub use pause::pause_subscriber;
+pub use search::search_subscribers;
+pub use tags::{add_subscriber_tag, bulk_tag_form, bulk_tag_subscribers};
@@ -0,0 +1,97 @@
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::audit_log::record_audit_event;
+use crate::authentication::UserId;
+use crate::form::Form;
+use crate::utils::{e500, parse_datetime, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct PauseFormData {
+    /// Empty to resume emails straight away.
+    #[serde(default)]
+    suppressed_until: String,
+}
+
+/// Stops issues from being sent to the subscriber until the given date,
+/// after which they receive them as usual again. The change is audited
+/// with the subscriber as the target, for their consent history.
+#[tracing::instrument(name = "Pause emails to a subscriber", skip(form, pool))]
+pub async fn pause_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    form: Form<PauseFormData>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let location = format!("/admin/subscribers/{subscriber_id}");
+    let suppressed_until = match parse_datetime(&form.0.suppressed_until) {
+        Ok(suppressed_until) => suppressed_until,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&location));
+        }
+    };
+
+    let paused_until = suppressed_until.filter(|until| *until > Utc::now());
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    if !set_suppressed_until(&mut transaction, subscriber_id, suppressed_until)
+        .await
+        .context("Failed to pause emails to a subscriber.")
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let action = match paused_until {
+        Some(_) => "pause_subscriber",
+        None => "resume_subscriber",
+    };
+    record_audit_event(
+        &mut transaction,
+        *user_id.into_inner(),
+        action,
+        &subscriber_id.to_string(),
+    )
+    .await
+    .context("Failed to record the pause in the audit log.")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to pause a subscriber.")
+        .map_err(e500)?;
+
+    match paused_until {
+        Some(until) => FlashMessage::info(format!(
+            "Emails to this subscriber are paused until {}.",
+            until.to_rfc3339()
+        )),
+        None => FlashMessage::info("Emails to this subscriber have been resumed."),
+    }
+    .send();
+    Ok(see_other(&location))
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn set_suppressed_until(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    suppressed_until: Option<DateTime<Utc>>,
+) -> Result<bool, sqlx::Error> {
+    let query = sqlx::query!(
+        r#"UPDATE subscriptions SET suppressed_until = $2 WHERE id = $1"#,
+        subscriber_id,
+        suppressed_until
+    );
+    Ok(transaction.execute(query).await?.rows_affected() > 0)
+}
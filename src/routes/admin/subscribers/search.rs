@@ -0,0 +1,180 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::domain::SubscriberTag;
+use crate::utils::{e400, e500};
+
+const PAGE_SIZE: i64 = 50;
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct QueryParams {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    status: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    tag: String,
+    /// Matched anywhere in the email or the name.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    q: String,
+    #[serde(default = "first_page")]
+    page: i64,
+}
+
+fn first_page() -> i64 {
+    1
+}
+
+impl QueryParams {
+    /// The same search on another page, for the pagination links.
+    fn page_link(&self, page: i64) -> String {
+        let query = serde_urlencoded::to_string(QueryParams {
+            status: self.status.clone(),
+            tag: self.tag.clone(),
+            q: self.q.clone(),
+            page,
+        })
+        .unwrap();
+        format!("/admin/subscribers?{query}")
+    }
+}
+
+struct SubscriberRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+}
+
+/// Lists subscribers matching every filter that is set: status, tag and a
+/// free text search. Empty filters are ignored.
+#[tracing::instrument(name = "Search subscribers", skip(pool))]
+pub async fn search_subscribers(
+    query: web::Query<QueryParams>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let query = query.into_inner();
+    let status = Some(query.status.trim()).filter(|s| !s.is_empty());
+    let tag = Some(&query.tag)
+        .filter(|t| !t.trim().is_empty())
+        .map(|t| SubscriberTag::parse(t.clone()))
+        .transpose()
+        .map_err(e400)?;
+    let pattern = Some(query.q.trim())
+        .filter(|q| !q.is_empty())
+        .map(|q| format!("%{}%", escape_like(q)));
+    let page = query.page.max(1);
+
+    let mut rows = sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        SELECT id, email, name, status
+        FROM subscriptions s
+        WHERE
+            ($1::text IS NULL OR status = $1) AND
+            ($2::text IS NULL OR EXISTS (
+                SELECT 1 FROM subscriber_tags t
+                WHERE t.subscriber_id = s.id AND t.tag = $2
+            )) AND
+            ($3::text IS NULL OR email ILIKE $3 OR name ILIKE $3)
+        ORDER BY subscribed_at, email
+        LIMIT $4 OFFSET $5
+        "#,
+        status,
+        tag.as_ref().map(AsRef::as_ref),
+        pattern,
+        PAGE_SIZE + 1,
+        (page - 1).saturating_mul(PAGE_SIZE),
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to search subscribers.")
+    .map_err(e500)?;
+    let has_next_page = rows.len() as i64 > PAGE_SIZE;
+    rows.truncate(PAGE_SIZE as usize);
+
+    let mut rows_html = String::new();
+    for r in &rows {
+        writeln!(
+            rows_html,
+            r#"<tr><td><a href="/admin/subscribers/{}">{}</a></td><td>{}</td><td>{}</td></tr>"#,
+            r.id,
+            encode_minimal(&r.email),
+            encode_minimal(&r.name),
+            r.status,
+        )
+        .unwrap();
+    }
+    let mut pages_html = String::new();
+    if page > 1 {
+        let link = encode_minimal(&query.page_link(page - 1));
+        write!(pages_html, r#"<a href="{link}">Previous</a> "#).unwrap();
+    }
+    if has_next_page {
+        let link = encode_minimal(&query.page_link(page.saturating_add(1)));
+        write!(pages_html, r#"<a href="{link}">Next</a>"#).unwrap();
+    }
+
+    let status = encode_minimal(&query.status);
+    let tag = encode_minimal(&query.tag);
+    let q = encode_minimal(&query.q);
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Subscribers</title>
+</head>
+<body>
+    <form action="/admin/subscribers" method="get">
+        <input type="text" placeholder="Search email or name" name="q" value="{q}" />
+        <input type="text" placeholder="Status" name="status" value="{status}" />
+        <input type="text" placeholder="Tag" name="tag" value="{tag}" />
+        <button type="submit">Search</button>
+    </form>
+    <table>
+        <tr><th>Email</th><th>Name</th><th>Status</th></tr>
+        {rows_html}
+    </table>
+    <p>Page {page} {pages_html}</p>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
+
+/// Makes `%` and `_` in user input match themselves in a LIKE pattern.
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_like, QueryParams};
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like("100%_off\\"), "100\\%\\_off\\\\");
+    }
+
+    #[test]
+    fn page_links_keep_the_filters_that_are_set() {
+        let query = QueryParams {
+            status: "confirmed".into(),
+            tag: "".into(),
+            q: "le guin".into(),
+            page: 1,
+        };
+        assert_eq!(
+            query.page_link(2),
+            "/admin/subscribers?status=confirmed&q=le+guin&page=2"
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::audit_log::record_audit_event;
+use crate::authentication::UserId;
+use crate::configuration::SubscriptionSettings;
+use crate::domain::SubscriberTag;
+use crate::form::Form;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct TagFormData {
+    tag: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct BulkTagFormData {
+    tag: String,
+    /// One subscriber email per line.
+    emails: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TagOutcome {
+    Added,
+    AlreadyPresent,
+    LimitReached,
+}
+
+#[tracing::instrument(name = "Tag a subscriber", skip(form, pool, settings))]
+pub async fn add_subscriber_tag(
+    subscriber_id: web::Path<Uuid>,
+    form: Form<TagFormData>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let location = format!("/admin/subscribers/{subscriber_id}");
+    if !subscriber_exists(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let tag = match SubscriberTag::parse(form.0.tag) {
+        Ok(tag) => tag,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&location));
+        }
+    };
+
+    let max_tags = settings.max_tags_per_subscriber;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let outcome = add_tag(
+        &mut transaction,
+        *user_id.into_inner(),
+        subscriber_id,
+        &tag,
+        max_tags,
+    )
+    .await
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to tag a subscriber.")
+        .map_err(e500)?;
+    match outcome {
+        TagOutcome::Added => FlashMessage::info("The tag has been added."),
+        TagOutcome::AlreadyPresent => FlashMessage::info("The subscriber already has this tag."),
+        TagOutcome::LimitReached => FlashMessage::error(format!(
+            "The tag was not added - subscribers can have at most {max_tags} tags."
+        )),
+    }
+    .send();
+    Ok(see_other(&location))
+}
+
+pub async fn bulk_tag_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", encode_minimal(m.content())).unwrap();
+    }
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Tag subscribers</title>
+</head>
+<body>
+    {msg_html}
+    <form action="/admin/subscribers/tags" method="post">
+        <label>Tag
+            <input type="text" placeholder="Enter tag" name="tag" />
+        </label>
+        <br/>
+        <label>Subscriber emails (one per line)
+            <textarea name="emails"></textarea>
+        </label>
+        <br/>
+        <button type="submit">Tag subscribers</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        ))
+}
+
+#[tracing::instrument(name = "Tag subscribers in bulk", skip(form, pool, settings))]
+pub async fn bulk_tag_subscribers(
+    form: Form<BulkTagFormData>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = *user_id.into_inner();
+    let BulkTagFormData { tag, emails } = form.0;
+    let tag = match SubscriberTag::parse(tag) {
+        Ok(tag) => tag,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/subscribers/tags"));
+        }
+    };
+
+    let (mut added, mut at_limit, mut unknown) = (0, 0, 0);
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    for email in emails.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let Some(subscriber_id) = get_subscriber_id(&pool, email).await.map_err(e500)? else {
+            unknown += 1;
+            continue;
+        };
+        match add_tag(
+            &mut transaction,
+            user_id,
+            subscriber_id,
+            &tag,
+            settings.max_tags_per_subscriber,
+        )
+        .await
+        .map_err(e500)?
+        {
+            TagOutcome::Added => added += 1,
+            TagOutcome::AlreadyPresent => {}
+            TagOutcome::LimitReached => at_limit += 1,
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to tag subscribers.")
+        .map_err(e500)?;
+
+    FlashMessage::info(format!(
+        "Tagged {added} subscribers. \
+        {at_limit} already had the maximum number of tags, {unknown} were not found."
+    ))
+    .send();
+    Ok(see_other("/admin/subscribers/tags"))
+}
+
+/// Adds `tag` unless the subscriber already has it or is at `max_tags`.
+/// An added tag is audited with the subscriber as the target, for their
+/// consent history.
+#[tracing::instrument(name = "Add tag to subscriber", skip(transaction, tag))]
+pub async fn add_tag(
+    transaction: &mut Transaction<'static, Postgres>,
+    user_id: Uuid,
+    subscriber_id: Uuid,
+    tag: &SubscriberTag,
+    max_tags: u32,
+) -> Result<TagOutcome, anyhow::Error> {
+    let already_present = sqlx::query!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM subscriber_tags WHERE subscriber_id = $1 AND tag = $2
+        ) AS "exists!"
+        "#,
+        subscriber_id,
+        tag.as_ref()
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .context("Failed to check existing subscriber tags.")?
+    .exists;
+    if already_present {
+        return Ok(TagOutcome::AlreadyPresent);
+    }
+
+    let n_inserted = sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag)
+        SELECT $1, $2
+        WHERE (SELECT COUNT(*) FROM subscriber_tags WHERE subscriber_id = $1) < $3
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        tag.as_ref(),
+        i64::from(max_tags)
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to store subscriber tag.")?
+    .rows_affected();
+    if n_inserted == 0 {
+        return Ok(TagOutcome::LimitReached);
+    }
+
+    record_audit_event(
+        transaction,
+        user_id,
+        "tag_subscriber",
+        &subscriber_id.to_string(),
+    )
+    .await
+    .context("Failed to record the tag in the audit log.")?;
+    Ok(TagOutcome::Added)
+}
+
+async fn subscriber_exists(pool: &PgPool, subscriber_id: Uuid) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up subscriber.")?;
+    Ok(row.is_some())
+}
+
+async fn get_subscriber_id(pool: &PgPool, email: &str) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(r#"SELECT id FROM subscriptions WHERE email = $1"#, email)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to look up subscriber.")?;
+    Ok(row.map(|r| r.id))
+}
@@ -0,0 +1,125 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::domain::SubscriberEmail;
+use crate::utils::e500;
+
+#[derive(serde::Serialize, Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Addresses added to the suppression list by this import.
+    suppressed: u64,
+    /// Valid addresses that were on the list already.
+    already_suppressed: u64,
+    /// Subscribers that were unsubscribed because of this import.
+    unsubscribed: u64,
+    /// Rows that did not contain a valid email address.
+    invalid: u64,
+}
+
+/// Suppresses every address in an uploaded CSV, e.g. an unsubscribe list
+/// exported from a previous provider. The email is read from the first
+/// column and a header row is skipped. Matching subscribers are
+/// unsubscribed; the rest are kept from ever being emailed.
+#[tracing::instrument(name = "Import suppression list", skip_all)]
+pub async fn import_suppressions(
+    body: String,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut report = ImportReport::default();
+    let mut emails = Vec::new();
+    for candidate in csv_emails(&body) {
+        match SubscriberEmail::parse(candidate.to_string()) {
+            Ok(email) => emails.push(email.as_ref().to_string()),
+            Err(_) => report.invalid += 1,
+        }
+    }
+    emails.sort();
+    emails.dedup();
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    report.suppressed = suppress(&mut transaction, &emails)
+        .await
+        .context("Failed to store suppressed addresses.")
+        .map_err(e500)?;
+    report.already_suppressed = emails.len() as u64 - report.suppressed;
+    report.unsubscribed = unsubscribe(&mut transaction, &emails)
+        .await
+        .context("Failed to unsubscribe suppressed addresses.")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to import a suppression list.")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// The first field of every non-empty row, header excluded.
+fn csv_emails(csv: &str) -> impl Iterator<Item = &str> {
+    csv.lines()
+        .map(|row| row.split(',').next().unwrap_or_default().trim())
+        .map(|field| field.trim_matches('"').trim())
+        .filter(|field| !field.is_empty())
+        .enumerate()
+        .filter(|(i, field)| !(*i == 0 && field.eq_ignore_ascii_case("email")))
+        .map(|(_, field)| field)
+}
+
+#[tracing::instrument(skip_all)]
+async fn suppress(
+    transaction: &mut Transaction<'_, Postgres>,
+    emails: &[String],
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email)
+        SELECT * FROM UNNEST($1::text[])
+        ON CONFLICT (email) DO NOTHING
+        "#,
+        emails
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[tracing::instrument(skip_all)]
+async fn unsubscribe(
+    transaction: &mut Transaction<'_, Postgres>,
+    emails: &[String],
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'unsubscribed'
+        WHERE email = ANY($1) AND status <> 'unsubscribed'
+        "#,
+        emails
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::csv_emails;
+
+    #[test]
+    fn emails_are_read_from_the_first_column() {
+        let csv = "email,reason\nursula@example.com,complaint\n\"le.guin@example.com\",\n\n";
+        let emails: Vec<_> = csv_emails(csv).collect();
+        assert_eq!(emails, ["ursula@example.com", "le.guin@example.com"]);
+    }
+
+    #[test]
+    fn a_file_without_a_header_is_read_in_full() {
+        let emails: Vec<_> = csv_emails("ursula@example.com\r\nle.guin@example.com").collect();
+        assert_eq!(emails, ["ursula@example.com", "le.guin@example.com"]);
+    }
+}
@@ -1,5 +1,97 @@
-use actix_web::HttpResponse;
+use std::time::Duration;
 
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+use crate::email_client::EmailClient;
+
+/// How long each dependency gets to answer a readiness check.
+const DEPENDENCY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Liveness: the process is up and serving requests.
 pub async fn health_check() -> HttpResponse {
     HttpResponse::Ok().finish()
 }
+
+#[derive(serde::Serialize)]
+struct MigrationStatus {
+    /// `None` until the first migration has been applied.
+    latest_applied: Option<i64>,
+    /// The newest migration this build knows about.
+    latest_known: Option<i64>,
+}
+
+/// The schema version of the database next to the one this build expects.
+#[tracing::instrument(name = "Check migration status", skip_all)]
+pub async fn migration_status(pool: web::Data<PgPool>) -> HttpResponse {
+    let latest_applied = sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT MAX(version) FROM _sqlx_migrations WHERE success",
+    )
+    .fetch_one(pool.get_ref())
+    .await;
+    let latest_applied = match latest_applied {
+        Ok(version) => version,
+        Err(e) => {
+            tracing::warn!(error.message = %e, "Failed to read the applied migrations.");
+            return HttpResponse::ServiceUnavailable().finish();
+        }
+    };
+    let latest_known = sqlx::migrate!("./migrations")
+        .iter()
+        .map(|m| m.version)
+        .max();
+    HttpResponse::Ok().json(MigrationStatus {
+        latest_applied,
+        latest_known,
+    })
+}
+
+#[derive(serde::Serialize)]
+struct Readiness {
+    /// The dependencies that could not be reached.
+    failing: Vec<&'static str>,
+}
+
+/// Readiness: the database and the email provider can both be reached.
+/// Answers with a 503 naming the failing dependencies otherwise.
+#[tracing::instrument(name = "Check readiness", skip_all)]
+pub async fn readiness_check(
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+) -> HttpResponse {
+    let (database, email_provider) = tokio::join!(
+        tokio::time::timeout(
+            DEPENDENCY_TIMEOUT,
+            sqlx::query("SELECT 1").execute(pool.get_ref())
+        ),
+        email_client.check_reachable(DEPENDENCY_TIMEOUT),
+    );
+
+    let mut failing = Vec::new();
+    match database {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => {
+            tracing::warn!(error.message = %e, "The database cannot be reached.");
+            failing.push("database");
+        }
+        Err(_) => {
+            tracing::warn!("The database did not answer in time.");
+            failing.push("database");
+        }
+    }
+    if let Err(e) = email_provider {
+        tracing::warn!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "The email provider cannot be reached.",
+        );
+        failing.push("email_provider");
+    }
+
+    let readiness = Readiness { failing };
+    if readiness.failing.is_empty() {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
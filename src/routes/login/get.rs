@@ -1,14 +1,34 @@
 use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use htmlescape::encode_attribute;
 use std::fmt::Write;
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+use super::next::safe_next;
+
+#[derive(serde::Deserialize)]
+pub struct QueryParams {
+    /// Where to send the user once they have logged in.
+    next: Option<String>,
+}
+
+pub async fn login_form(
+    query: web::Query<QueryParams>,
+    flash_messages: IncomingFlashMessages,
+) -> HttpResponse {
     let mut error_html = String::new();
     for m in flash_messages.iter() {
         writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap()
     }
 
+    let next_html = match query.next.as_deref().and_then(safe_next) {
+        Some(next) => format!(
+            r#"<input hidden type="text" name="next" value="{}" />"#,
+            encode_attribute(next)
+        ),
+        None => String::new(),
+    };
+
     HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -29,6 +49,7 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
         <label>Password
             <input type="password" placeholder="Enter password" name="password" />
         </label>
+        {next_html}
         <button type="submit">Login</button>
     </form>
 </body>
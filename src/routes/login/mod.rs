@@ -1,5 +1,7 @@
 mod get;
+mod next;
 mod post;
 
 pub use get::login_form;
+pub use next::login_url;
 pub use post::login;
@@ -0,0 +1,52 @@
+/// Returns `next` if it is a path on this site, so that it can be used as a
+/// redirect target without turning the login page into an open redirect.
+/// Anything that a browser could resolve to another origin is rejected:
+/// absolute URLs, protocol-relative URLs (`//evil.com`) and backslashes,
+/// which some browsers treat as forward slashes.
+pub fn safe_next(next: &str) -> Option<&str> {
+    let is_local_path = next.starts_with('/') && !next.starts_with("//");
+    let has_forbidden_characters = next.chars().any(|c| c == '\\' || c.is_control());
+    (is_local_path && !has_forbidden_characters).then_some(next)
+}
+
+/// The login page, remembering where to go once the user has logged in.
+pub fn login_url(next: Option<&str>) -> String {
+    match next.and_then(safe_next) {
+        Some(next) => format!("/login?next={}", urlencoding::encode(next)),
+        None => "/login".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{login_url, safe_next};
+    use claims::{assert_none, assert_some_eq};
+
+    #[test]
+    fn local_paths_are_accepted() {
+        assert_some_eq!(safe_next("/admin/newsletter"), "/admin/newsletter");
+        assert_some_eq!(
+            safe_next("/admin/subscribers/export.csv?tag=vip"),
+            "/admin/subscribers/export.csv?tag=vip"
+        );
+    }
+
+    #[test]
+    fn other_origins_are_rejected() {
+        assert_none!(safe_next("https://evil.com/admin"));
+        assert_none!(safe_next("//evil.com/admin"));
+        assert_none!(safe_next("/\\evil.com"));
+        assert_none!(safe_next("admin/dashboard"));
+        assert_none!(safe_next("/admin\r\nSet-Cookie: x=y"));
+    }
+
+    #[test]
+    fn the_login_url_only_carries_safe_targets() {
+        assert_eq!(
+            login_url(Some("/admin/newsletter")),
+            "/login?next=%2Fadmin%2Fnewsletter"
+        );
+        assert_eq!(login_url(Some("https://evil.com")), "/login");
+        assert_eq!(login_url(None), "/login");
+    }
+}
@@ -1,10 +1,15 @@
+use std::time::Duration;
+
 use actix_web::error::InternalError;
-use actix_web::{web, HttpResponse};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
-use secrecy::Secret;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 
-use crate::authentication::{validate_credentials, AuthError, Credentials};
+use super::next::{login_url, safe_next};
+use crate::authentication::{validate_credentials, AuthError, Credentials, LoginLockout};
+use crate::form::Form;
 use crate::routes::error_chain_fmt;
 use crate::session_state::TypedSession;
 use crate::utils::see_other;
@@ -13,50 +18,104 @@ use crate::utils::see_other;
 pub struct FormData {
     username: String,
     password: Secret<String>,
+    /// Set by the login form when the user was sent there from another page.
+    next: Option<String>,
+}
+
+const MAX_USERNAME_LENGTH: usize = 256;
+const MAX_PASSWORD_LENGTH: usize = 1024;
+
+impl FormData {
+    /// Rejects input that cannot possibly be valid credentials.
+    /// The checks only look at the submitted values, never at the stored
+    /// users, so they reveal nothing about which usernames exist.
+    fn parse(self) -> Result<Credentials, String> {
+        if self.username.trim().is_empty() || self.password.expose_secret().is_empty() {
+            return Err("Please enter both a username and a password.".into());
+        }
+        if self.username.chars().count() > MAX_USERNAME_LENGTH
+            || self.password.expose_secret().chars().count() > MAX_PASSWORD_LENGTH
+        {
+            return Err("The username or password you entered is too long.".into());
+        }
+        Ok(Credentials {
+            username: self.username,
+            password: self.password,
+        })
+    }
 }
 
 #[tracing::instrument(
-    skip(form, pool, session),
+    skip(request, form, pool, session, lockout),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn login(
-    form: web::Form<FormData>,
+    request: HttpRequest,
+    form: Form<FormData>,
     pool: web::Data<PgPool>,
     session: TypedSession,
+    lockout: web::Data<LoginLockout>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
-    let credentials = Credentials {
-        username: form.0.username,
-        password: form.0.password,
-    };
+    let mut form = form.into_inner();
+    let next = form.next.take();
+    let next = next.as_deref();
+    let credentials = form
+        .parse()
+        .map_err(|e| login_redirect(LoginError::InvalidInput(e), next))?;
+
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
 
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    let client = request
+        .connection_info()
+        .realip_remote_addr()
+        .unwrap_or("unknown")
+        .to_string();
+    let username = credentials.username.clone();
+    // Reserved before the password is checked, so that a locked out account
+    // cannot be guessed at any further.
+    if let Err(retry_after) = lockout.reserve_attempt(&username, &client) {
+        tracing::warn!(client, "Refusing a login after too many failed attempts.");
+        return Err(login_redirect(LoginError::LockedOut { retry_after }, next));
+    }
 
     match validate_credentials(credentials, &pool).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            lockout.record_success(&username, &client);
 
             session.renew();
             session
                 .insert_user_id(user_id)
-                .map_err(|e| login_redirect(LoginError::AuthError(e.into())))?;
+                .map_err(|e| login_redirect(LoginError::AuthError(e.into()), next))?;
 
-            Ok(see_other("/admin/dashboard"))
+            Ok(see_other(
+                next.and_then(safe_next).unwrap_or("/admin/dashboard"),
+            ))
         }
         Err(e) => {
             let e = match e {
                 AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
-                AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
+                AuthError::UnexpectedError(_) => {
+                    lockout.refund(&username, &client);
+                    LoginError::UnexpectedError(e.into())
+                }
             };
 
-            Err(login_redirect(e))
+            Err(login_redirect(e, next))
         }
     }
 }
 
 #[derive(thiserror::Error)]
 pub enum LoginError {
+    #[error("{0}")]
+    InvalidInput(String),
     #[error("Authentication failed.")]
     AuthError(#[source] anyhow::Error),
+    /// Worded like any other failure, so it does not tell whether the
+    /// password was right or the account exists.
+    #[error("Authentication failed.")]
+    LockedOut { retry_after: Duration },
     #[error("Something went wrong.")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -67,7 +126,14 @@ impl std::fmt::Debug for LoginError {
     }
 }
 
-fn login_redirect(e: LoginError) -> InternalError<LoginError> {
+/// Back to the login form, keeping track of where the user was heading.
+fn login_redirect(e: LoginError, next: Option<&str>) -> InternalError<LoginError> {
     FlashMessage::error(e.to_string()).send();
-    InternalError::from_response(e, see_other("/login"))
+    let mut response = see_other(&login_url(next));
+    if let LoginError::LockedOut { retry_after } = &e {
+        // Rounded up, so that retrying after this long always succeeds.
+        let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        response.headers_mut().insert(RETRY_AFTER, seconds.into());
+    }
+    InternalError::from_response(e, response)
 }
@@ -4,6 +4,8 @@ mod home;
 mod login;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
+mod webhooks;
 
 pub use admin::*;
 pub use health_check::*;
@@ -11,3 +13,5 @@ pub use home::*;
 pub use login::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_unsubscribe::{record_unsubscribe_reason, unsubscribe};
+pub use webhooks::{bounce_webhook, engagement_webhook, SIGNATURE_HEADER};
@@ -1,34 +1,52 @@
-use actix_web::web::{self, Form};
+use actix_web::http::header::ContentType;
+use actix_web::web;
 use actix_web::{HttpResponse, ResponseError};
 use anyhow::Context;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use reqwest::StatusCode;
 use sqlx::{PgPool, Postgres, Transaction};
+use tokio::time::Instant;
 use uuid::Uuid;
 
-use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
-use crate::email_client::EmailClient;
-use crate::startup::ApplicationBaseUrl;
+use crate::configuration::{ConfirmationEmailFailurePolicy, SubscriptionSettings};
+use crate::domain::{
+    ConfirmationEmail, ConfirmationEmailTemplate, NewSubscriber, SubscriberEmail, SubscriberName,
+};
+use crate::email_client::{EmailClient, EmailError};
+use crate::events::{Event, EventBus};
+use crate::form::Form;
+use crate::rate_limit::RateLimitDecision;
+use crate::read_only::{is_read_only_error, read_only_response};
+use crate::request_deadline::RequestDeadline;
+use crate::startup::{ApplicationBaseUrl, ConfirmationEmailLimiter};
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
     pub name: String,
     pub email: String,
+    #[serde(default)]
+    pub timezone: String,
 }
 
-impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
-
-    fn try_from(value: FormData) -> Result<Self, Self::Error> {
-        let email = SubscriberEmail::parse(value.email)?;
-        let name = SubscriberName::parse(value.name)?;
-        Ok(Self { email, name })
+impl FormData {
+    fn parse(self, settings: &SubscriptionSettings) -> Result<NewSubscriber, String> {
+        let email = SubscriberEmail::parse(self.email)?;
+        settings.email_domain_policy().check(&email)?;
+        let name = SubscriberName::parse_with(self.name, settings.name_formatting())?;
+        let timezone = Some(self.timezone.trim().to_string()).filter(|tz| !tz.is_empty());
+        Ok(NewSubscriber {
+            email,
+            name,
+            timezone,
+        })
     }
 }
 
-pub struct StoreTokenError(sqlx::Error);
+#[derive(thiserror::Error)]
+#[error("A database error was encountered while trying to store a subscriptions token.")]
+pub struct StoreTokenError(#[source] sqlx::Error);
 
 impl std::fmt::Debug for StoreTokenError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -36,65 +54,205 @@ impl std::fmt::Debug for StoreTokenError {
     }
 }
 
-impl std::fmt::Display for StoreTokenError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "A database error was encountered while trying to store a subscriptions token."
-        )
-    }
-}
-
-impl std::error::Error for StoreTokenError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        Some(&self.0)
-    }
-}
-
 #[tracing::instrument(
     name = "Adding a new subscriber", 
-    skip(form, pool, email_client, base_url),
+    skip(
+        form,
+        pool,
+        email_client,
+        confirmation_template,
+        base_url,
+        settings,
+        events,
+        deadline,
+        confirmation_email_limiter
+    ),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
     )
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn subscribe(
     form: Form<FormData>,
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
+    confirmation_template: web::Data<ConfirmationEmailTemplate>,
     base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+    events: web::Data<EventBus>,
+    deadline: web::ReqData<RequestDeadline>,
+    confirmation_email_limiter: web::Data<ConfirmationEmailLimiter>,
 ) -> Result<HttpResponse, SubscribeError> {
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    let new_subscriber = form
+        .0
+        .parse(&settings)
+        .map_err(SubscribeError::ValidationError)?;
+
+    if let Some(timezone) = &new_subscriber.timezone {
+        let is_known = is_known_timezone(&pool, timezone)
+            .await
+            .context("Failed to look up the subscriber's timezone.")?;
+        if !is_known {
+            return Err(SubscribeError::ValidationError(format!(
+                "{timezone} is not a known timezone."
+            )));
+        }
+    }
 
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
 
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    let existing = get_existing(&mut transaction, &new_subscriber.email)
         .await
-        .context("Failed to insert new subscriber in the database.")?;
+        .context("Failed to look up an existing subscription.")?;
+    let is_new = !matches!(&existing, Some(existing) if existing.status != "unsubscribed");
+    let subscriber_id = match existing {
+        // Answered like a fresh sign up, so that the response does not
+        // reveal who is subscribed. The address is told by email instead.
+        Some(existing) if existing.status == "confirmed" => {
+            drop(transaction);
+            if is_throttled(&confirmation_email_limiter).await {
+                tracing::info!("Over the confirmation email rate limit. Not sending a notice.");
+            } else {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let sent = send_already_subscribed_email(
+                    &email_client,
+                    &new_subscriber.email,
+                    Instant::now() + remaining / 2,
+                )
+                .await;
+                if let Err(e) = sent {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        error.kind = e.kind(),
+                        "Failed to tell a subscriber that they are already subscribed.",
+                    );
+                }
+            }
+            return Ok(HttpResponse::Ok().finish());
+        }
+        // Sent a new confirmation link. Earlier links keep working.
+        Some(existing) if existing.status == "pending_confirmation" => existing.id,
+        Some(previous) if previous.is_within_grace(settings.resubscribe_grace()) => {
+            resubscribe_confirmed(&mut transaction, previous.id, &new_subscriber)
+                .await
+                .context("Failed to restore a previous subscription.")?;
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit SQL transaction to restore a subscription.")?;
+            events.emit(Event::SubscriberConfirmed {
+                subscriber_id: previous.id,
+            });
+            return Ok(HttpResponse::Ok().finish());
+        }
+        Some(previous) => {
+            resubscribe_pending(&mut transaction, previous.id, &new_subscriber)
+                .await
+                .context("Failed to reopen a previous subscription.")?;
+            previous.id
+        }
+        None => insert_subscriber(&mut transaction, &new_subscriber)
+            .await
+            .map_err(SubscribeError::from_insert_error)?,
+    };
 
     let subscription_token = generate_subscription_token();
 
     store_token(&mut transaction, subscriber_id, &subscription_token)
         .await
         .context("Failed to store the confirmation token for a new subscriber.")?;
+    let confirmation_email = render_confirmation_email(
+        &confirmation_template,
+        new_subscriber.name.as_ref(),
+        &base_url.0,
+        &subscription_token,
+        settings.pending_expiry.confirmation_link_ttl(),
+    )
+    .map_err(|e| anyhow::anyhow!("Failed to render the confirmation email: {e}"))?;
+
+    // The email gets half of what is left of the request, so that a slow
+    // provider cannot get the request cancelled.
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    let send_deadline = Instant::now() + remaining / 2;
+    let policy = settings.confirmation_email_failure;
+    let send_now = !is_throttled(&confirmation_email_limiter).await;
+    if !send_now {
+        tracing::info!("Over the confirmation email rate limit. Queued for the reminder worker.");
+        flag_for_resend(&mut transaction, subscriber_id, Utc::now())
+            .await
+            .context("Failed to queue the confirmation email.")?;
+    } else if policy == ConfirmationEmailFailurePolicy::Lenient {
+        // The email is sent once the subscription is committed. Should that
+        // never happen, e.g. because the request is cancelled, the reminder
+        // worker sends it once this request is over.
+        let resend_at = Utc::now()
+            + chrono::Duration::from_std(remaining).unwrap_or_else(|_| chrono::Duration::zero());
+        flag_for_resend(&mut transaction, subscriber_id, resend_at)
+            .await
+            .context("Failed to flag the confirmation email for a resend.")?;
+    } else {
+        // Sent before committing, so that a failure rolls the subscription
+        // back when the transaction is dropped.
+        send_confirmation_email(
+            &email_client,
+            &confirmation_email,
+            &new_subscriber.email,
+            Some(send_deadline),
+        )
+        .await
+        .context("Failed to send a confirmation email.")?;
+    }
 
     transaction
         .commit()
         .await
-        .context("Failed to send a confirmation email.")?;
+        .context("Failed to commit SQL transaction to store a new subscriber.")?;
+    if is_new {
+        events.emit(Event::SubscriberAdded { subscriber_id });
+    }
 
-    send_confirmation_email(
-        &email_client,
-        new_subscriber,
-        &base_url.0,
-        &subscription_token,
-    )
-    .await
-    .context("Failed to send a confirmation email.")?;
+    if send_now && policy == ConfirmationEmailFailurePolicy::Lenient {
+        let sent = send_confirmation_email(
+            &email_client,
+            &confirmation_email,
+            &new_subscriber.email,
+            Some(send_deadline),
+        )
+        .await;
+        match sent {
+            Ok(()) => {
+                if let Err(e) = clear_resend_flag(&pool, subscriber_id).await {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to clear the resend flag of a sent confirmation email.",
+                    );
+                }
+            }
+            Err(e) => {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    error.kind = e.kind(),
+                    "Failed to send a confirmation email. It will be retried later.",
+                );
+                // The flag was set after the end of the request, in case it
+                // was cancelled. The send is over, so it can be retried now.
+                if let Err(e) = resend_now(&pool, subscriber_id).await {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to queue a confirmation email for an immediate resend.",
+                    );
+                }
+            }
+        }
+    }
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -103,6 +261,10 @@ pub async fn subscribe(
 pub enum SubscribeError {
     #[error("{0}")]
     ValidationError(String),
+    /// Lost a race with another sign up for the same address. Addresses
+    /// that were already there beforehand are answered like a fresh sign up.
+    #[error("This email address is already subscribed.")]
+    AlreadySubscribed,
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -117,9 +279,32 @@ impl ResponseError for SubscribeError {
     fn status_code(&self) -> StatusCode {
         match self {
             SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscribeError::AlreadySubscribed => StatusCode::CONFLICT,
             SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            SubscribeError::UnexpectedError(e) if is_read_only_error(e) => read_only_response(),
+            _ => HttpResponse::build(self.status_code())
+                .content_type(ContentType::plaintext())
+                .body(self.to_string()),
+        }
+    }
+}
+
+impl SubscribeError {
+    /// A clash on the unique email column means another sign up for the
+    /// same address got in first; anything else is unexpected.
+    fn from_insert_error(e: sqlx::Error) -> Self {
+        match e.as_database_error() {
+            Some(db_error) if db_error.is_unique_violation() => Self::AlreadySubscribed,
+            _ => anyhow::Error::from(e)
+                .context("Failed to insert new subscriber in the database.")
+                .into(),
+        }
+    }
 }
 
 impl From<String> for SubscribeError {
@@ -128,6 +313,127 @@ impl From<String> for SubscribeError {
     }
 }
 
+struct ExistingRecord {
+    id: Uuid,
+    status: String,
+    confirmed_at: Option<DateTime<Utc>>,
+}
+
+impl ExistingRecord {
+    /// Whether the address was confirmed recently enough to be trusted
+    /// without asking again.
+    fn is_within_grace(&self, grace: std::time::Duration) -> bool {
+        let Ok(grace) = chrono::Duration::from_std(grace) else {
+            return false;
+        };
+        self.confirmed_at
+            .is_some_and(|confirmed_at| Utc::now() - confirmed_at < grace)
+    }
+}
+
+#[tracing::instrument(name = "Look up an existing subscription", skip(transaction, email))]
+async fn get_existing(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<Option<ExistingRecord>, sqlx::Error> {
+    sqlx::query_as!(
+        ExistingRecord,
+        r#"
+        SELECT id, status, confirmed_at FROM subscriptions
+        WHERE email = $1
+        FOR UPDATE
+        "#,
+        email.as_ref()
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+}
+
+/// Brings back a subscription whose earlier confirmation is still trusted.
+/// `confirmed_at` is left alone, so the grace period cannot be stretched by
+/// unsubscribing and subscribing again.
+#[tracing::instrument(
+    name = "Restore a previous subscription",
+    skip(transaction, new_subscriber)
+)]
+async fn resubscribe_confirmed(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    new_subscriber: &NewSubscriber,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'confirmed', name = $2 WHERE id = $1"#,
+        subscriber_id,
+        new_subscriber.name.as_ref(),
+    )
+    .execute(&mut **transaction)
+    .await?;
+    record_resubscription(transaction, subscriber_id, true).await
+}
+
+/// Puts a previous subscription back to pending confirmation. Old tokens are
+/// dropped so only the link in the new confirmation email works, and the
+/// subscription counts as new for reminders and expiry.
+#[tracing::instrument(
+    name = "Reopen a previous subscription",
+    skip(transaction, new_subscriber)
+)]
+async fn resubscribe_pending(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    new_subscriber: &NewSubscriber,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'pending_confirmation', name = $2, subscribed_at = now()
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        new_subscriber.name.as_ref(),
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    record_resubscription(transaction, subscriber_id, false).await
+}
+
+/// Keeps a note of the subscriber coming back for their consent history.
+async fn record_resubscription(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    confirmation_kept: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO resubscriptions (subscriber_id, confirmation_kept)
+        VALUES ($1, $2)
+        "#,
+        subscriber_id,
+        confirmation_kept
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Whether Postgres, which schedules deliveries, knows the timezone.
+#[tracing::instrument(name = "Checking the subscriber's timezone", skip(pool))]
+async fn is_known_timezone(pool: &PgPool, timezone: &str) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1) AS "is_known!""#,
+        timezone
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.is_known)
+}
+
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
     skip(transaction, new_subscriber)
@@ -138,12 +444,15 @@ pub async fn insert_subscriber(
 ) -> Result<Uuid, sqlx::Error> {
     let subscriber_id = Uuid::new_v4();
     sqlx::query!(
-        r#"INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')"#,
+        r#"INSERT INTO subscriptions (
+            id, email, name, subscribed_at, status, timezone
+        )
+        VALUES ($1, $2, $3, $4, 'pending_confirmation', $5)"#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
-        Utc::now()
+        Utc::now(),
+        new_subscriber.timezone
     )
     .execute(&mut **transaction)
     .await?;
@@ -173,31 +482,130 @@ pub async fn store_token(
 
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url)
+    skip(email_client, email, recipient)
 )]
 pub async fn send_confirmation_email(
     email_client: &EmailClient,
-    new_subscriber: NewSubscriber,
+    email: &ConfirmationEmail,
+    recipient: &SubscriberEmail,
+    deadline: Option<Instant>,
+) -> Result<(), EmailError> {
+    match deadline {
+        Some(deadline) => {
+            email_client
+                .send_email_with_deadline(
+                    deadline,
+                    recipient,
+                    &email.subject,
+                    &email.html_content,
+                    &email.text_content,
+                )
+                .await
+        }
+        None => {
+            email_client
+                .send_email(
+                    recipient,
+                    &email.subject,
+                    &email.html_content,
+                    &email.text_content,
+                )
+                .await
+        }
+    }
+}
+
+/// Sent instead of a confirmation email when a confirmed address signs up
+/// again.
+#[tracing::instrument(name = "Tell a subscriber they are already subscribed", skip_all)]
+async fn send_already_subscribed_email(
+    email_client: &EmailClient,
+    recipient: &SubscriberEmail,
+    deadline: Instant,
+) -> Result<(), EmailError> {
+    email_client
+        .send_email_with_deadline(
+            deadline,
+            recipient,
+            "You are already subscribed",
+            "Someone, hopefully you, asked to subscribe this address to our newsletter.<br />\
+                You are already subscribed, so there is nothing else to do.",
+            "Someone, hopefully you, asked to subscribe this address to our newsletter.\n\
+                You are already subscribed, so there is nothing else to do.",
+        )
+        .await
+}
+
+/// Fills in the template for the subscriber holding `subscription_token`.
+pub fn render_confirmation_email(
+    template: &ConfirmationEmailTemplate,
+    recipient_name: &str,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+    link_ttl: Option<std::time::Duration>,
+) -> Result<ConfirmationEmail, String> {
     let confirmation_link =
         format!("{base_url}/subscriptions/confirm?subscription_token={subscription_token}");
-    let html_body = format!(
-        "Welcome to our newsletter!<br />\
-                Click <a href=\"{confirmation_link}\">here</a> to confirm your subscription.",
-    );
-    let plain_body = format!(
-        "Welcome to our newsletter!<br />\
-                Visit {confirmation_link} to confirm your subscription.",
-    );
+    template.render(recipient_name, &confirmation_link, link_ttl)
+}
 
-    email_client
-        .send_email(&new_subscriber.email, "Welcome!", &html_body, &plain_body)
-        .await
+/// Sign ups are never turned away over the limit, and if the limit cannot
+/// be checked the email is sent as usual.
+async fn is_throttled(limiter: &ConfirmationEmailLimiter) -> bool {
+    match limiter.take().await {
+        Ok(RateLimitDecision::Allowed) => false,
+        Ok(RateLimitDecision::Limited { .. }) => true,
+        Err(e) => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to check the confirmation email rate limit.",
+            );
+            false
+        }
+    }
+}
+
+/// Leaves the confirmation email to the reminder worker from `at` onwards.
+#[tracing::instrument(name = "Flag confirmation email for a resend", skip(transaction))]
+pub async fn flag_for_resend(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET resend_confirmation_at = $2 WHERE id = $1"#,
+        subscriber_id,
+        at
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
 }
 
-fn generate_subscription_token() -> String {
+#[tracing::instrument(name = "Clear the confirmation email resend flag", skip(pool))]
+async fn clear_resend_flag(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET resend_confirmation_at = NULL WHERE id = $1"#,
+        subscriber_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Queue the confirmation email for a resend now", skip(pool))]
+async fn resend_now(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET resend_confirmation_at = now() WHERE id = $1"#,
+        subscriber_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
@@ -217,3 +625,27 @@ pub fn error_chain_fmt(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::ResponseError;
+    use reqwest::StatusCode;
+
+    use super::SubscribeError;
+
+    #[test]
+    fn each_error_maps_to_its_status_code() {
+        assert_eq!(
+            SubscribeError::ValidationError("Invalid email.".into()).status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            SubscribeError::AlreadySubscribed.status_code(),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            SubscribeError::from(anyhow::anyhow!("The database is down.")).status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
@@ -1,60 +1,197 @@
+use actix_web::http::header::{ContentType, LOCATION};
 use actix_web::{web, HttpResponse};
-use sqlx::PgPool;
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+use crate::configuration::{
+    SubscriptionSettings, UnknownTokenResponse, WebhookSettings, WelcomeTemplate,
+};
+use crate::domain::{ConfirmationMethod, SubscriberEmail};
+use crate::email_client::EmailClient;
+use crate::events::{Event, EventBus};
+use crate::request_deadline::RequestDeadline;
+use crate::startup::WelcomeEmail;
+use crate::webhook_delivery_worker::enqueue_webhook;
+
 #[derive(serde::Deserialize)]
 pub struct Parameters {
     subscription_token: String,
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
-pub async fn confirm(parameters: web::Query<Parameters>, pool: web::Data<PgPool>) -> HttpResponse {
-    let id = match get_subscriber_id_from_token(&pool, &parameters.subscription_token).await {
-        Ok(id) => id,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
-    };
-
-    match id {
-        None => HttpResponse::Unauthorized().finish(),
-        Some(subscriber_id) => {
-            if confirm_subscriber(&pool, subscriber_id).await.is_err() {
-                return HttpResponse::InternalServerError().finish();
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(
+        parameters,
+        pool,
+        settings,
+        webhooks,
+        email_client,
+        welcome_email,
+        events,
+        deadline
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn confirm(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+    webhooks: web::Data<WebhookSettings>,
+    email_client: web::Data<EmailClient>,
+    welcome_email: web::Data<WelcomeEmail>,
+    events: web::Data<EventBus>,
+    deadline: web::ReqData<RequestDeadline>,
+) -> HttpResponse {
+    match confirm_and_notify(&pool, &parameters.subscription_token, &webhooks).await {
+        Ok(Some(confirmed)) => {
+            if !confirmed.was_already_confirmed {
+                events.emit(Event::SubscriberConfirmed {
+                    subscriber_id: confirmed.id,
+                });
+            }
+            if let (Some(template), false) = (&welcome_email.0, confirmed.was_already_confirmed) {
+                send_welcome_email(&email_client, template, &confirmed.email, *deadline).await;
             }
             HttpResponse::Ok().finish()
         }
+        Ok(None) => unknown_token_response(&settings.unknown_token),
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to confirm subscriber");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// The subscription is already confirmed at this point, so a failure to
+/// send the welcome email is logged rather than reported to the subscriber.
+#[tracing::instrument(name = "Send a welcome email", skip_all)]
+async fn send_welcome_email(
+    email_client: &EmailClient,
+    template: &WelcomeTemplate,
+    email: &str,
+    deadline: RequestDeadline,
+) {
+    let outcome = match SubscriberEmail::parse(email.to_string()) {
+        Ok(recipient) => email_client
+            .send_email_with_deadline(
+                *deadline,
+                &recipient,
+                &template.subject,
+                &template.html_content,
+                &template.text_content,
+            )
+            .await
+            .map_err(anyhow::Error::from),
+        Err(e) => Err(anyhow::anyhow!(e)),
+    };
+    if let Err(e) = outcome {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a welcome email to a newly confirmed subscriber.",
+        );
+    }
+}
+
+fn unknown_token_response(behaviour: &UnknownTokenResponse) -> HttpResponse {
+    match behaviour {
+        UnknownTokenResponse::NeutralPage => HttpResponse::Unauthorized()
+            .content_type(ContentType::html())
+            .body(include_str!("invalid_link.html")),
+        UnknownTokenResponse::Redirect(url) => HttpResponse::SeeOther()
+            .insert_header((LOCATION, url.as_str()))
+            .finish(),
     }
 }
 
-#[tracing::instrument(name = "Get subscriber_id from token", skip(subscription_token, pool))]
-async fn get_subscriber_id_from_token(
+/// Confirms the subscriber and, the first time round, queues the
+/// subscription-confirmed webhook in the same transaction.
+async fn confirm_and_notify(
     pool: &PgPool,
     subscription_token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"SELECT subscriber_id FROM subscription_tokens
-        WHERE subscription_token = $1"#,
-        subscription_token,
-    )
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
-        e
-    })?;
-    Ok(result.map(|r| r.subscriber_id))
+    webhooks: &WebhookSettings,
+) -> Result<Option<ConfirmedSubscriber>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let Some(confirmed) = confirm_subscriber(&mut transaction, subscription_token).await? else {
+        return Ok(None);
+    };
+    if let (Some(url), false) = (
+        &webhooks.subscription_confirmed_url,
+        confirmed.was_already_confirmed,
+    ) {
+        let payload = serde_json::json!({
+            "event": "subscription.confirmed",
+            "subscriber_id": confirmed.id,
+            "email": confirmed.email,
+        });
+        enqueue_webhook(&mut transaction, url, &payload)
+            .await
+            .context("Failed to enqueue the subscription confirmed webhook.")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to confirm a subscriber.")?;
+    Ok(Some(confirmed))
+}
+
+struct ConfirmedSubscriber {
+    id: Uuid,
+    email: String,
+    was_already_confirmed: bool,
 }
 
-#[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
-        subscriber_id,
+/// Looks up the token and flips the subscriber's status in a single
+/// statement, so known and unknown tokens cost the same round trip.
+#[tracing::instrument(
+    name = "Mark subscriber as confirmed",
+    skip(subscription_token, transaction)
+)]
+async fn confirm_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscription_token: &str,
+) -> Result<Option<ConfirmedSubscriber>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        ConfirmedSubscriber,
+        r#"
+        WITH previous AS (
+            SELECT id, status FROM subscriptions
+            WHERE id = (
+                SELECT subscriber_id FROM subscription_tokens
+                WHERE subscription_token = $1
+            )
+            FOR UPDATE
+        )
+        UPDATE subscriptions
+        SET
+            status = 'confirmed',
+            confirmed_at = CASE
+                WHEN previous.status = 'confirmed' THEN subscriptions.confirmed_at
+                ELSE now()
+            END,
+            confirmed_via = CASE
+                WHEN previous.status = 'confirmed' THEN subscriptions.confirmed_via
+                ELSE $2
+            END
+        FROM previous
+        WHERE subscriptions.id = previous.id
+        RETURNING
+            subscriptions.id,
+            subscriptions.email,
+            previous.status = 'confirmed' AS "was_already_confirmed!"
+        "#,
+        subscription_token,
+        ConfirmationMethod::Link.as_str(),
     )
-    .execute(pool)
+    .fetch_optional(&mut **transaction)
     .await
     .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
         e
     })?;
-    Ok(())
+    Ok(result)
 }
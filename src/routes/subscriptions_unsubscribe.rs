@@ -0,0 +1,185 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use htmlescape::encode_attribute;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::configuration::SubscriptionSettings;
+use crate::domain::UnsubscribeReason;
+use crate::form::Form;
+use crate::utils::{e400, e500};
+
+/// Free text longer than this is rejected rather than stored.
+const MAX_DETAILS_LENGTH: usize = 1000;
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+/// Unsubscribes straight away; the reason form shown afterwards is optional.
+#[tracing::instrument(
+    name = "Unsubscribe a subscriber",
+    skip(parameters, pool, settings),
+    fields(subscriber_id=tracing::field::Empty)
+)]
+pub async fn unsubscribe(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(subscriber_id) = mark_unsubscribed(&pool, &parameters.token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(invalid_link());
+    };
+    tracing::Span::current().record("subscriber_id", tracing::field::display(subscriber_id));
+
+    let reason_html = if settings.ask_unsubscribe_reason {
+        reason_form(&parameters.token)
+    } else {
+        String::new()
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Unsubscribed</title>
+</head>
+<body>
+    <p>You have been unsubscribed and will not receive any more issues.</p>
+    {reason_html}
+</body>
+</html>"#
+        )))
+}
+
+fn reason_form(token: &str) -> String {
+    let mut options_html = String::new();
+    for reason in UnsubscribeReason::ALL {
+        writeln!(
+            options_html,
+            r#"<label><input type="radio" name="reason" value="{}" /> {}</label><br/>"#,
+            reason.as_str(),
+            reason.label()
+        )
+        .unwrap();
+    }
+    let token = encode_attribute(token);
+    format!(
+        r#"<form action="/subscriptions/unsubscribe/reason" method="post">
+        <p>Would you tell us why? (optional)</p>
+        {options_html}
+        <label>Anything else?
+            <textarea name="details"></textarea>
+        </label>
+        <input hidden type="text" name="token" value="{token}" />
+        <button type="submit">Send</button>
+    </form>"#
+    )
+}
+
+#[derive(serde::Deserialize)]
+pub struct ReasonFormData {
+    token: String,
+    reason: UnsubscribeReason,
+    #[serde(default)]
+    details: String,
+}
+
+#[tracing::instrument(name = "Record an unsubscribe reason", skip(form, pool))]
+pub async fn record_unsubscribe_reason(
+    form: Form<ReasonFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ReasonFormData {
+        token,
+        reason,
+        details,
+    } = form.0;
+    let details = Some(details.trim()).filter(|d| !d.is_empty());
+    if details.is_some_and(|d| d.chars().count() > MAX_DETAILS_LENGTH) {
+        return Err(e400(format!(
+            "Please keep it under {MAX_DETAILS_LENGTH} characters."
+        )));
+    }
+
+    let recorded = insert_reason(&pool, &token, reason, details)
+        .await
+        .map_err(e500)?;
+    if !recorded {
+        return Ok(invalid_link());
+    }
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
+    <title>Thank you</title>
+</head>
+<body>
+    <p>Thank you for your feedback.</p>
+</body>
+</html>"#,
+    ))
+}
+
+fn invalid_link() -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .content_type(ContentType::html())
+        .body(include_str!("invalid_link.html"))
+}
+
+#[tracing::instrument(name = "Mark subscriber as unsubscribed", skip_all)]
+async fn mark_unsubscribed(pool: &PgPool, token: &str) -> Result<Option<Uuid>, anyhow::Error> {
+    let subscriber_id = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'unsubscribed'
+        FROM unsubscribe_tokens
+        WHERE
+            unsubscribe_tokens.unsubscribe_token = $1 AND
+            subscriptions.id = unsubscribe_tokens.subscriber_id
+        RETURNING subscriptions.id
+        "#,
+        token
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to unsubscribe.")?
+    .map(|r| r.id);
+    Ok(subscriber_id)
+}
+
+/// Only subscribers who have actually unsubscribed can leave a reason.
+#[tracing::instrument(name = "Store unsubscribe reason", skip(pool, token, details))]
+async fn insert_reason(
+    pool: &PgPool,
+    token: &str,
+    reason: UnsubscribeReason,
+    details: Option<&str>,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO unsubscribe_reasons (subscriber_id, reason, details)
+        SELECT subscriptions.id, $2, $3
+        FROM unsubscribe_tokens
+        JOIN subscriptions ON subscriptions.id = unsubscribe_tokens.subscriber_id
+        WHERE
+            unsubscribe_tokens.unsubscribe_token = $1 AND
+            subscriptions.status = 'unsubscribed'
+        "#,
+        token,
+        reason.as_str(),
+        details
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the unsubscribe reason.")?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,347 @@
+use actix_web::http::header::HeaderMap;
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::configuration::WebhookSettings;
+use crate::domain::SubscriberEmail;
+use crate::utils::{e400, e500};
+
+/// Hex encoded HMAC-SHA256 of the raw request body.
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// Their values are replaced before the headers of a rejected call are
+/// stored, since they may hold credentials for other parts of the app.
+const REDACTED_HEADERS: [&str; 2] = ["authorization", "cookie"];
+
+/// Enough to tell a misconfiguration from probing, without letting
+/// unauthenticated callers grow the table without bound.
+const MAX_SIGNATURE_FAILURES_KEPT: i64 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureFailure {
+    Missing,
+    Malformed,
+    Mismatch,
+}
+
+impl SignatureFailure {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignatureFailure::Missing => "missing",
+            SignatureFailure::Malformed => "malformed",
+            SignatureFailure::Mismatch => "mismatch",
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct BounceNotification {
+    #[serde(alias = "Email")]
+    email: String,
+    #[serde(default, alias = "Metadata")]
+    metadata: EmailMetadata,
+}
+
+/// Postmark's open and click webhooks.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EngagementNotification {
+    record_type: EngagementEvent,
+    #[serde(default)]
+    metadata: EmailMetadata,
+}
+
+/// What we attached to the email when sending it. Copies of an issue carry
+/// their delivery id; other emails carry nothing.
+#[derive(serde::Deserialize, Default)]
+pub struct EmailMetadata {
+    message_id: Option<Uuid>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+pub enum EngagementEvent {
+    #[serde(rename = "Open")]
+    Opened,
+    #[serde(rename = "Click")]
+    Clicked,
+}
+
+impl EngagementEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EngagementEvent::Opened => "opened",
+            EngagementEvent::Clicked => "clicked",
+        }
+    }
+}
+
+/// Called by the email provider when a message to one of our subscribers
+/// bounced permanently. The address is suppressed and unsubscribed.
+/// Calls with an invalid signature are recorded for review and rejected.
+#[tracing::instrument(name = "Handle a bounce notification", skip_all)]
+pub async fn bounce_webhook(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    settings: web::Data<WebhookSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(rejection) = reject_unsigned(&request, &body, &pool, &settings).await? {
+        return Ok(rejection);
+    }
+
+    let notification: BounceNotification = serde_json::from_slice(&body).map_err(e400)?;
+    let email = SubscriberEmail::parse(notification.email).map_err(e400)?;
+    suppress_bounced_address(&pool, &email, notification.metadata.message_id)
+        .await
+        .map_err(e500)?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Called by the email provider when a subscriber opened an issue or
+/// clicked one of its links, for the issue's activity feed. Signed like
+/// bounce notifications.
+#[tracing::instrument(name = "Handle an engagement notification", skip_all)]
+pub async fn engagement_webhook(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    settings: web::Data<WebhookSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(rejection) = reject_unsigned(&request, &body, &pool, &settings).await? {
+        return Ok(rejection);
+    }
+
+    let notification: EngagementNotification = serde_json::from_slice(&body).map_err(e400)?;
+    let Some(delivery_id) = notification.metadata.message_id else {
+        // Opens and clicks of emails other than issues, e.g. a confirmation.
+        return Ok(HttpResponse::Ok().finish());
+    };
+    let recorded = sqlx::query!(
+        r#"
+        INSERT INTO delivery_events (delivery_id, kind)
+        SELECT delivery_id, $2 FROM issue_delivery_queue WHERE delivery_id = $1
+        "#,
+        delivery_id,
+        notification.record_type.as_str()
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to record an engagement event.")
+    .map_err(e500)?
+    .rows_affected();
+    // Deliveries can be deleted along with their issue, there is nothing
+    // for the provider to retry.
+    if recorded == 0 {
+        tracing::warn!(
+            %delivery_id,
+            "Ignored an engagement notification for an unknown delivery."
+        );
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Answers calls with an invalid signature with a 401, after recording
+/// them for review. `None` for correctly signed calls. Only the most recent
+/// failures are kept, so that anyone posting junk cannot fill the table.
+async fn reject_unsigned(
+    request: &HttpRequest,
+    body: &[u8],
+    pool: &PgPool,
+    settings: &WebhookSettings,
+) -> Result<Option<HttpResponse>, actix_web::Error> {
+    let Err(failure) = verify_signature(request.headers(), body, &settings.bounce_signing_secret)
+    else {
+        return Ok(None);
+    };
+    tracing::warn!(
+        reason = failure.as_str(),
+        endpoint = request.path(),
+        "Rejected a webhook call with an invalid signature."
+    );
+    store_signature_failure(pool, request, failure)
+        .await
+        .map_err(e500)?;
+    Ok(Some(HttpResponse::Unauthorized().finish()))
+}
+
+fn verify_signature(
+    headers: &HeaderMap,
+    body: &[u8],
+    secret: &Secret<String>,
+) -> Result<(), SignatureFailure> {
+    let signature = headers
+        .get(SIGNATURE_HEADER)
+        .ok_or(SignatureFailure::Missing)?;
+    let signature = signature
+        .to_str()
+        .ok()
+        .and_then(|s| hex::decode(s.trim()).ok())
+        .ok_or(SignatureFailure::Malformed)?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes()).unwrap();
+    mac.update(body);
+    mac.verify_slice(&signature)
+        .map_err(|_| SignatureFailure::Mismatch)
+}
+
+/// One `name: value` line per header, with credentials redacted.
+fn describe_headers(headers: &HeaderMap) -> String {
+    let mut lines: Vec<String> = headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                "[redacted]"
+            } else {
+                value.to_str().unwrap_or("[non-ascii]")
+            };
+            format!("{name}: {value}")
+        })
+        .collect();
+    lines.sort();
+    lines.join("\n")
+}
+
+#[tracing::instrument(skip_all)]
+async fn store_signature_failure(
+    pool: &PgPool,
+    request: &HttpRequest,
+    failure: SignatureFailure,
+) -> Result<(), anyhow::Error> {
+    let remote_addr = request
+        .connection_info()
+        .realip_remote_addr()
+        .map(str::to_string);
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_signature_failures (id, endpoint, reason, remote_addr, headers)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        Uuid::new_v4(),
+        request.path(),
+        failure.as_str(),
+        remote_addr,
+        describe_headers(request.headers())
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record a webhook signature failure.")?;
+    sqlx::query!(
+        r#"
+        DELETE FROM webhook_signature_failures
+        WHERE id IN (
+            SELECT id FROM webhook_signature_failures
+            ORDER BY received_at DESC
+            OFFSET $1
+        )
+        "#,
+        MAX_SIGNATURE_FAILURES_KEPT
+    )
+    .execute(pool)
+    .await
+    .context("Failed to drop old webhook signature failures.")?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn suppress_bounced_address(
+    pool: &PgPool,
+    email: &SubscriberEmail,
+    delivery_id: Option<Uuid>,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!(
+        r#"INSERT INTO suppressions (email) VALUES ($1) ON CONFLICT (email) DO NOTHING"#,
+        email.as_ref()
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to suppress a bounced address.")?;
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'unsubscribed'
+        WHERE email = $1 AND status <> 'unsubscribed'
+        "#,
+        email.as_ref()
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to unsubscribe a bounced address.")?;
+    // Only put down to an issue when the provider says which copy bounced;
+    // their latest email may not be the one.
+    if let Some(delivery_id) = delivery_id {
+        sqlx::query!(
+            r#"
+            INSERT INTO delivery_events (delivery_id, kind)
+            SELECT delivery_id, 'bounced' FROM issue_delivery_queue
+            WHERE delivery_id = $1 AND subscriber_email = $2
+            "#,
+            delivery_id,
+            email.as_ref()
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to record a bounce against its delivery.")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to handle a bounce.")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_signature, SignatureFailure};
+    use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+    use claims::{assert_err_eq, assert_ok};
+    use hmac::{Hmac, Mac};
+    use secrecy::Secret;
+    use sha2::Sha256;
+
+    fn headers_with_signature(signature: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HeaderName::from_static("x-webhook-signature"),
+            HeaderValue::from_str(signature).unwrap(),
+        );
+        headers
+    }
+
+    fn sign(secret: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn a_valid_signature_is_accepted() {
+        let secret = Secret::new("secret".to_string());
+        let headers = headers_with_signature(&sign("secret", b"body"));
+        assert_ok!(verify_signature(&headers, b"body", &secret));
+    }
+
+    #[test]
+    fn invalid_signatures_are_told_apart() {
+        let secret = Secret::new("secret".to_string());
+        assert_err_eq!(
+            verify_signature(&HeaderMap::new(), b"body", &secret),
+            SignatureFailure::Missing
+        );
+        assert_err_eq!(
+            verify_signature(&headers_with_signature("not-hex"), b"body", &secret),
+            SignatureFailure::Malformed
+        );
+        let headers = headers_with_signature(&sign("another secret", b"body"));
+        assert_err_eq!(
+            verify_signature(&headers, b"body", &secret),
+            SignatureFailure::Mismatch
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use tokio::time::Instant;
+
+/// How often a file-backed secret checks whether its file has changed.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Where a secret's value comes from. Locally it is written straight into
+/// the configuration; in production it can point at a file, e.g. one
+/// mounted by a secrets manager, so it can be rotated without a redeploy:
+///
+/// ```yaml
+/// auth_token:
+///   file: "/run/secrets/postmark_auth_token"
+/// ```
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum SecretSource {
+    File { file: PathBuf },
+    Inline(Secret<String>),
+}
+
+impl SecretSource {
+    /// The current value. Surrounding whitespace is trimmed, since secret
+    /// files usually end with a newline.
+    pub fn read(&self) -> Result<Secret<String>, anyhow::Error> {
+        match self {
+            SecretSource::Inline(secret) => Ok(secret.clone()),
+            SecretSource::File { file } => {
+                let value = std::fs::read_to_string(file)
+                    .with_context(|| format!("Failed to read secret from {}", file.display()))?;
+                Ok(Secret::new(value.trim().to_string()))
+            }
+        }
+    }
+
+    fn modified_at(&self) -> Option<SystemTime> {
+        match self {
+            SecretSource::Inline(_) => None,
+            SecretSource::File { file } => std::fs::metadata(file).ok()?.modified().ok(),
+        }
+    }
+}
+
+/// A secret that picks up changes to its source while the app is running.
+/// Clones share the same value.
+#[derive(Clone, Debug)]
+pub struct ReloadableSecret {
+    source: SecretSource,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    value: Secret<String>,
+    modified_at: Option<SystemTime>,
+    checked_at: Instant,
+}
+
+impl ReloadableSecret {
+    pub fn new(source: SecretSource) -> Result<Self, anyhow::Error> {
+        let state = State {
+            value: source.read()?,
+            modified_at: source.modified_at(),
+            checked_at: Instant::now(),
+        };
+        Ok(Self {
+            source,
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    /// The latest value, re-read first if the file has changed since it
+    /// was last checked.
+    pub fn current(&self) -> Secret<String> {
+        let checked_at = self.state.lock().unwrap().checked_at;
+        if checked_at.elapsed() >= CHECK_INTERVAL {
+            if let Err(e) = self.reload() {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    "Failed to reload a secret. Keeping the previous value."
+                );
+            }
+        }
+        self.state.lock().unwrap().value.clone()
+    }
+
+    /// Re-reads the source if its file has changed. Returns whether the
+    /// value was updated.
+    pub fn reload(&self) -> Result<bool, anyhow::Error> {
+        let modified_at = self.source.modified_at();
+        let mut state = self.state.lock().unwrap();
+        state.checked_at = Instant::now();
+        if modified_at == state.modified_at {
+            return Ok(false);
+        }
+        let value = self.source.read()?;
+        let changed = value.expose_secret() != state.value.expose_secret();
+        state.value = value;
+        state.modified_at = modified_at;
+        if changed {
+            tracing::info!("A secret was rotated.");
+        }
+        Ok(changed)
+    }
+}
+
+impl From<Secret<String>> for ReloadableSecret {
+    fn from(secret: Secret<String>) -> Self {
+        Self::new(SecretSource::Inline(secret)).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReloadableSecret, SecretSource};
+    use claims::assert_ok_eq;
+    use secrecy::{ExposeSecret, Secret};
+
+    fn secret_file(value: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        std::fs::write(&path, value).unwrap();
+        path
+    }
+
+    #[test]
+    fn inline_and_file_sources_can_be_configured() {
+        let inline: SecretSource = serde_json::from_str(r#""my-secret-token""#).unwrap();
+        assert!(matches!(inline, SecretSource::Inline(_)));
+        let file: SecretSource = serde_json::from_str(r#"{"file": "/run/secrets/token"}"#).unwrap();
+        assert!(matches!(file, SecretSource::File { .. }));
+    }
+
+    #[test]
+    fn file_secrets_are_trimmed() {
+        let source = SecretSource::File {
+            file: secret_file("my-secret-token\n"),
+        };
+        assert_eq!(source.read().unwrap().expose_secret(), "my-secret-token");
+    }
+
+    #[test]
+    fn updating_the_file_is_picked_up_on_reload() {
+        let file = secret_file("first");
+        let secret = ReloadableSecret::new(SecretSource::File { file: file.clone() }).unwrap();
+        assert_eq!(secret.current().expose_secret(), "first");
+
+        std::fs::write(&file, "second").unwrap();
+        // Make sure the modification time changes even on coarse clocks.
+        let later = std::fs::metadata(&file).unwrap().modified().unwrap()
+            + std::time::Duration::from_secs(1);
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        assert_ok_eq!(secret.reload(), true);
+        assert_eq!(secret.current().expose_secret(), "second");
+        assert_ok_eq!(secret.reload(), false);
+    }
+
+    #[test]
+    fn inline_secrets_never_change() {
+        let secret = ReloadableSecret::from(Secret::new("token".to_string()));
+        assert_ok_eq!(secret.reload(), false);
+        assert_eq!(secret.current().expose_secret(), "token");
+    }
+}
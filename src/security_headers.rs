@@ -0,0 +1,41 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{
+    HeaderName, HeaderValue, CONTENT_SECURITY_POLICY, REFERRER_POLICY, X_CONTENT_TYPE_OPTIONS,
+    X_FRAME_OPTIONS,
+};
+use actix_web::web;
+use actix_web_lab::middleware::Next;
+
+use crate::utils::e500;
+
+/// The `Content-Security-Policy` sent with every admin page.
+pub struct ContentSecurityPolicy(pub HeaderValue);
+
+/// Hardens admin responses against XSS and clickjacking. Headers already
+/// set by a handler are left alone.
+pub async fn set_security_headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let csp = req
+        .app_data::<web::Data<ContentSecurityPolicy>>()
+        .ok_or_else(|| e500("The content security policy has not been configured."))?
+        .0
+        .clone();
+    let mut response = next.call(req).await?;
+
+    let headers: [(HeaderName, HeaderValue); 4] = [
+        (CONTENT_SECURITY_POLICY, csp),
+        (X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+        (X_FRAME_OPTIONS, HeaderValue::from_static("DENY")),
+        (REFERRER_POLICY, HeaderValue::from_static("same-origin")),
+    ];
+    let response_headers = response.headers_mut();
+    for (name, value) in headers {
+        if !response_headers.contains_key(&name) {
+            response_headers.insert(name, value);
+        }
+    }
+    Ok(response)
+}
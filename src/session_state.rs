@@ -7,6 +7,7 @@ pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const NEWSLETTER_PREVIEW_KEY: &'static str = "newsletter_preview";
 
     pub fn renew(&self) {
         self.0.renew()
@@ -20,6 +21,18 @@ impl TypedSession {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    /// The HTML of a dry-run issue, shown once on the newsletter page. Too
+    /// large for a flash message cookie, so it is kept with the session.
+    pub fn insert_newsletter_preview(&self, html: &str) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::NEWSLETTER_PREVIEW_KEY, html)
+    }
+
+    pub fn take_newsletter_preview(&self) -> Result<Option<String>, SessionGetError> {
+        let preview = self.0.get(Self::NEWSLETTER_PREVIEW_KEY)?;
+        self.0.remove(Self::NEWSLETTER_PREVIEW_KEY);
+        Ok(preview)
+    }
+
     pub fn logout(self) {
         self.0.purge()
     }
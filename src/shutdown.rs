@@ -0,0 +1,66 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+/// Creates a trigger and the signal it flips. The signal can be cloned and
+/// handed to every task that should stop once the process is shutting down.
+pub fn shutdown_channel() -> (ShutdownTrigger, ShutdownSignal) {
+    let (sender, receiver) = watch::channel(false);
+    (ShutdownTrigger(Arc::new(sender)), ShutdownSignal(receiver))
+}
+
+#[derive(Clone)]
+pub struct ShutdownTrigger(Arc<watch::Sender<bool>>);
+
+impl ShutdownTrigger {
+    pub fn trigger(&self) {
+        self.0.send_replace(true);
+    }
+}
+
+#[derive(Clone)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    pub fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once shutdown has been triggered, straight away if it
+    /// already has been.
+    pub async fn triggered(&mut self) {
+        while !*self.0.borrow_and_update() {
+            if self.0.changed().await.is_err() {
+                // The trigger is gone, so shutdown can no longer happen.
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Sleeps for `duration`, waking up early if shutdown is triggered.
+    pub async fn sleep(&mut self, duration: Duration) {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => {}
+            _ = self.triggered() => {}
+        }
+    }
+}
+
+/// Resolves when the process is asked to stop, with SIGTERM or Ctrl-C.
+pub async fn wait_for_termination() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM.");
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
@@ -2,56 +2,130 @@ use actix_session::storage::RedisSessionStore;
 use actix_session::SessionMiddleware;
 use actix_web::cookie::Key;
 use actix_web::dev::Server;
+use actix_web::http::header::HeaderValue;
 use actix_web::{web, App, HttpServer};
 use actix_web_flash_messages::storage::CookieMessageStore;
 use actix_web_flash_messages::FlashMessagesFramework;
 use actix_web_lab::middleware::from_fn;
+use anyhow::Context;
 use secrecy::{ExposeSecret, Secret};
+use sqlx::migrate::Migrate;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
+use std::collections::HashSet;
 use std::net::TcpListener;
+use std::sync::Arc;
 use tracing_actix_web::TracingLogger;
 
-use crate::authentication::reject_anonymous_users;
-use crate::configuration::{DatabaseSettings, Settings};
+use crate::authentication::{
+    reject_anonymous_users, reject_unauthenticated_api_clients, LoginLockout,
+};
+use crate::configuration::{DatabaseSettings, RateLimit, Settings, WelcomeTemplate};
+use crate::domain::NewsletterContent;
 use crate::email_client::EmailClient;
+use crate::events::EventBus;
+use crate::rate_limit::{
+    enforce_rate_limit, InMemoryRateLimitStore, RateLimitDecision, RateLimitStore, RateLimiter,
+};
+use crate::read_only::degrade_when_read_only;
+use crate::request_deadline::{enforce_request_deadline, RequestTimeout};
 use crate::routes::{
-    admin_dashboard, change_password, change_password_form, confirm, health_check, home, login,
-    login_form, logout, publish_newsletter, publish_newsletter_form, subscribe,
+    add_subscriber_tag, admin_dashboard, bounce_webhook, bulk_tag_form, bulk_tag_subscribers,
+    cancel_scheduled_issue, change_account_email, change_log_level, change_password,
+    change_password_form, clone_issue, confirm, confirmation_methods_report, create_api_token,
+    create_template, delete_template, dispatch_queue, edit_template_form, engagement_webhook,
+    export_subscribers, health_check, home, idempotency_record, idempotency_stats,
+    import_subscribers, import_suppressions, issue_activity, issue_deliveries, list_templates,
+    log_level, login, login_form, logout, migration_status, pause_subscriber,
+    preview_confirmation_email, publish_newsletter, publish_newsletter_api,
+    publish_newsletter_form, readiness_check, record_unsubscribe_reason, replay_delivery,
+    resend_issue, review_newsletter, search_subscribers, send_test_newsletter,
+    signature_failures_report, subscribe, subscriber_consent, subscriber_details, unsubscribe,
+    unsubscribe_reasons_report, update_template,
 };
+use crate::security_headers::{set_security_headers, ContentSecurityPolicy};
+use crate::telemetry::RequestSpan;
 
 pub struct Application {
     port: u16,
     server: Server,
+    event_bus: EventBus,
+    confirmation_email_limiter: ConfirmationEmailLimiter,
 }
 
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
+        // Fail fast on a misconfigured sender rather than when the first
+        // email of that kind goes out.
+        configuration
+            .email_client
+            .transactional_sender()
+            .map_err(|e| anyhow::anyhow!("Invalid transactional sender: {e}"))?;
+        configuration
+            .email_client
+            .broadcast_sender()
+            .map_err(|e| anyhow::anyhow!("Invalid broadcast sender: {e}"))?;
+        configuration
+            .email_client
+            .broadcast_sender_name()
+            .map_err(|e| anyhow::anyhow!("Invalid broadcast sender name: {e}"))?;
+        configuration
+            .email_client
+            .confirmation_template()
+            .map_err(|e| anyhow::anyhow!("Invalid confirmation email template: {e}"))?;
+        if configuration.email_client.test_mode {
+            tracing::warn!("Email test mode is on - no emails will actually be sent.");
+        }
         let connection = get_connection_pool(&configuration.database);
-        let email_client = configuration.email_client.client();
+        if configuration.database.migrate_on_start {
+            run_migrations(&connection).await?;
+        }
+        let email_client = configuration.email_client.clone().client();
         let address = format!(
             "{}:{}",
             configuration.application.host, configuration.application.port
         );
         let listener = TcpListener::bind(address)?;
         let port = listener.local_addr().unwrap().port();
+        let event_bus = EventBus::default();
+        let rate_limit_store = Arc::new(InMemoryRateLimitStore::default());
+        let confirmation_email_limiter = ConfirmationEmailLimiter::new(
+            configuration.rate_limits.confirmation_emails,
+            rate_limit_store.clone(),
+        );
         let server = run(
             listener,
             connection,
             email_client,
-            configuration.application.base_url,
-            configuration.application.hmac_secret,
-            configuration.redis_uri,
+            event_bus.clone(),
+            rate_limit_store,
+            confirmation_email_limiter.clone(),
+            configuration,
         )
         .await?;
 
-        Ok(Self { server, port })
+        Ok(Self {
+            server,
+            port,
+            event_bus,
+            confirmation_email_limiter,
+        })
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
 
+    /// The bus the application's handlers emit their events on.
+    pub fn event_bus(&self) -> &EventBus {
+        &self.event_bus
+    }
+
+    /// The confirmation email budget, for the reminder worker to share.
+    pub fn confirmation_email_limiter(&self) -> &ConfirmationEmailLimiter {
+        &self.confirmation_email_limiter
+    }
+
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
         self.server.await
     }
@@ -63,54 +137,339 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
         .connect_lazy_with(configuration.with_db())
 }
 
+/// Applies every migration the database is missing, logging each one.
+pub async fn run_migrations(pool: &PgPool) -> Result<(), anyhow::Error> {
+    let migrator = sqlx::migrate!("./migrations");
+    let applied: HashSet<i64> = {
+        let mut connection = pool
+            .acquire()
+            .await
+            .context("Failed to connect to the database to migrate it.")?;
+        connection.ensure_migrations_table().await?;
+        connection
+            .list_applied_migrations()
+            .await?
+            .into_iter()
+            .map(|m| m.version)
+            .collect()
+    };
+    migrator
+        .run(pool)
+        .await
+        .context("Failed to run database migrations.")?;
+    for migration in migrator.iter().filter(|m| !applied.contains(&m.version)) {
+        tracing::info!(
+            version = migration.version,
+            description = %migration.description,
+            "Applied a database migration."
+        );
+    }
+    Ok(())
+}
+
 pub struct ApplicationBaseUrl(pub String);
 
 pub struct HmacSecret(pub Secret<String>);
 
+pub struct WelcomeEmail(pub Option<WelcomeTemplate>);
+
+/// The client newsletter issues are sent with, for handlers that deliver
+/// them directly instead of leaving it to the worker.
+pub struct BroadcastEmailClient {
+    pub client: EmailClient,
+    pub batch_size: usize,
+}
+
+/// The budget for confirmation emails, shared by every sign up and the
+/// confirmation reminder worker so that together they stay within it.
+#[derive(Clone)]
+pub struct ConfirmationEmailLimiter(RateLimiter);
+
+impl ConfirmationEmailLimiter {
+    pub fn new(limit: RateLimit, store: Arc<dyn RateLimitStore>) -> Self {
+        Self(RateLimiter::new("confirmation_emails", limit, store))
+    }
+
+    /// Takes a token from the one bucket every confirmation email draws from.
+    pub async fn take(&self) -> Result<RateLimitDecision, anyhow::Error> {
+        self.0.take("all").await
+    }
+}
+
 async fn run(
     listener: TcpListener,
     db_pool: PgPool,
     email_client: EmailClient,
-    base_url: String,
-    hmac_secret: Secret<String>,
-    redis_uri: Secret<String>,
+    event_bus: EventBus,
+    rate_limit_store: Arc<InMemoryRateLimitStore>,
+    confirmation_email_limiter: ConfirmationEmailLimiter,
+    configuration: Settings,
 ) -> Result<Server, anyhow::Error> {
     let connection = web::Data::new(db_pool);
+    let event_bus = web::Data::new(event_bus);
     let email_client = web::Data::new(email_client);
-    let base_url = web::Data::new(ApplicationBaseUrl(base_url));
+    let broadcast_email_client = web::Data::new(BroadcastEmailClient {
+        batch_size: configuration.email_client.batch_size,
+        client: configuration.email_client.clone().broadcast_client(),
+    });
+    let request_timeout =
+        web::Data::new(RequestTimeout(configuration.application.request_timeout()));
+    let shutdown_grace = configuration.application.shutdown_grace();
+    let read_only = web::Data::new(configuration.database.read_only);
+    let form_whitespace = web::Data::new(configuration.application.form_whitespace);
+    let base_url = web::Data::new(ApplicationBaseUrl(configuration.application.base_url));
+    let admin_csp = web::Data::new(ContentSecurityPolicy(
+        HeaderValue::from_str(&configuration.application.admin_content_security_policy)
+            .context("The admin content security policy is not a valid header value.")?,
+    ));
+    let confirmation_template = web::Data::new(
+        configuration
+            .email_client
+            .confirmation_template()
+            .map_err(|e| anyhow::anyhow!("Invalid confirmation email template: {e}"))?,
+    );
+    let welcome_email = web::Data::new(WelcomeEmail(configuration.email_client.welcome_template));
+    let idempotency = web::Data::new(configuration.idempotency);
+    let subscriptions = web::Data::new(configuration.subscriptions);
+    let newsletter = web::Data::new(configuration.newsletter);
+    let webhooks = web::Data::new(configuration.webhooks);
+    let rate_limits = configuration.rate_limits;
+    let subscriptions_limiter = web::Data::new(RateLimiter::new(
+        "subscriptions",
+        rate_limits.subscriptions,
+        rate_limit_store.clone(),
+    ));
+    let login_limiter = web::Data::new(RateLimiter::new(
+        "login",
+        rate_limits.login,
+        rate_limit_store.clone(),
+    ));
+    let confirm_limiter = web::Data::new(RateLimiter::new(
+        "confirm",
+        rate_limits.confirm,
+        rate_limit_store.clone(),
+    ));
+    let validate_limiter = web::Data::new(RateLimiter::new(
+        "validate",
+        rate_limits.validate,
+        rate_limit_store.clone(),
+    ));
+    let resend_limiter = web::Data::new(RateLimiter::new(
+        "resend",
+        rate_limits.resend,
+        rate_limit_store.clone(),
+    ));
+    let api_limiter = web::Data::new(RateLimiter::new(
+        "api",
+        rate_limits.api,
+        rate_limit_store.clone(),
+    ));
+    let webhooks_limiter = web::Data::new(RateLimiter::new(
+        "webhooks",
+        rate_limits.webhooks,
+        rate_limit_store.clone(),
+    ));
+    let confirmation_email_limiter = web::Data::new(confirmation_email_limiter);
+    let login_lockout = web::Data::new(LoginLockout::new(rate_limits.login_lockout));
+    let hmac_secret = configuration
+        .application
+        .hmac_secret
+        .read()
+        .context("Failed to read the HMAC secret.")?;
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
-    let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    let redis_store = RedisSessionStore::new(configuration.redis_uri.expose_secret()).await?;
 
     let server = HttpServer::new(move || {
         App::new()
+            .wrap(from_fn(degrade_when_read_only))
+            .wrap(from_fn(enforce_request_deadline))
             .wrap(message_framework.clone())
             .wrap(SessionMiddleware::new(
                 redis_store.clone(),
                 secret_key.clone(),
             ))
-            .wrap(TracingLogger::default())
+            .wrap(TracingLogger::<RequestSpan>::new())
             .route("/", web::get().to(home))
             .route("/health_check", web::get().to(health_check))
+            .route("/health_check/ready", web::get().to(readiness_check))
+            .route("/health_check/migrations", web::get().to(migration_status))
             .route("/login", web::get().to(login_form))
-            .route("/login", web::post().to(login))
-            .route("/subscriptions", web::post().to(subscribe))
-            .route("/subscriptions/confirm", web::get().to(confirm))
+            .service(
+                web::resource("/login")
+                    .app_data(login_limiter.clone())
+                    .app_data(login_lockout.clone())
+                    .wrap(from_fn(enforce_rate_limit))
+                    .route(web::post().to(login)),
+            )
+            .service(
+                web::resource("/subscriptions")
+                    .app_data(subscriptions_limiter.clone())
+                    .wrap(from_fn(enforce_rate_limit))
+                    .route(web::post().to(subscribe)),
+            )
+            .service(
+                web::resource("/subscriptions/confirm")
+                    .app_data(confirm_limiter.clone())
+                    .wrap(from_fn(enforce_rate_limit))
+                    .route(web::get().to(confirm)),
+            )
+            .service(
+                web::resource("/subscriptions/unsubscribe")
+                    .app_data(validate_limiter.clone())
+                    .wrap(from_fn(enforce_rate_limit))
+                    .route(web::get().to(unsubscribe)),
+            )
+            .service(
+                web::resource("/subscriptions/unsubscribe/reason")
+                    .app_data(validate_limiter.clone())
+                    .wrap(from_fn(enforce_rate_limit))
+                    .route(web::post().to(record_unsubscribe_reason)),
+            )
+            .service(
+                web::scope("/webhooks")
+                    .app_data(webhooks_limiter.clone())
+                    .wrap(from_fn(enforce_rate_limit))
+                    .route("/bounces", web::post().to(bounce_webhook))
+                    .route("/engagement", web::post().to(engagement_webhook)),
+            )
+            .service(
+                web::scope("/api")
+                    .app_data(api_limiter.clone())
+                    .app_data(
+                        web::JsonConfig::default().limit(NewsletterContent::MAX_REQUEST_BYTES),
+                    )
+                    .app_data(login_lockout.clone())
+                    .wrap(from_fn(reject_unauthenticated_api_clients))
+                    .wrap(from_fn(enforce_rate_limit))
+                    .route("/newsletters", web::post().to(publish_newsletter_api)),
+            )
             .service(
                 web::scope("/admin")
+                    // Issues are submitted as forms, which are read whole.
+                    .app_data(web::PayloadConfig::new(
+                        NewsletterContent::MAX_REQUEST_BYTES,
+                    ))
                     .wrap(from_fn(reject_anonymous_users))
+                    .wrap(from_fn(set_security_headers))
                     .route("/dashboard", web::get().to(admin_dashboard))
                     .route("/logout", web::post().to(logout))
+                    .route("/api-tokens", web::post().to(create_api_token))
+                    .route(
+                        "/emails/confirmation/preview",
+                        web::get().to(preview_confirmation_email),
+                    )
                     .route("/password", web::get().to(change_password_form))
                     .route("/password", web::post().to(change_password))
+                    .route("/account/email", web::post().to(change_account_email))
                     .route("/newsletter", web::get().to(publish_newsletter_form))
-                    .route("/newsletter", web::post().to(publish_newsletter)),
+                    .route("/newsletter", web::post().to(publish_newsletter))
+                    .route("/newsletter/review", web::post().to(review_newsletter))
+                    .route("/newsletter/test", web::post().to(send_test_newsletter))
+                    .route("/newsletter/dispatch", web::post().to(dispatch_queue))
+                    .route(
+                        "/newsletter/{issue_id}/deliveries",
+                        web::get().to(issue_deliveries),
+                    )
+                    .route(
+                        "/newsletter/{issue_id}/activity",
+                        web::get().to(issue_activity),
+                    )
+                    .route("/newsletter/{issue_id}/clone", web::post().to(clone_issue))
+                    .route(
+                        "/newsletter/{issue_id}/cancel",
+                        web::post().to(cancel_scheduled_issue),
+                    )
+                    .service(
+                        web::resource("/newsletter/{issue_id}/resend-all")
+                            .app_data(resend_limiter.clone())
+                            .wrap(from_fn(enforce_rate_limit))
+                            .route(web::post().to(resend_issue)),
+                    )
+                    .route("/newsletter/templates", web::get().to(list_templates))
+                    .route("/newsletter/templates", web::post().to(create_template))
+                    .route(
+                        "/newsletter/templates/{template_id}",
+                        web::get().to(edit_template_form),
+                    )
+                    .route(
+                        "/newsletter/templates/{template_id}",
+                        web::post().to(update_template),
+                    )
+                    .route(
+                        "/newsletter/templates/{template_id}/delete",
+                        web::post().to(delete_template),
+                    )
+                    .route("/idempotency", web::get().to(idempotency_stats))
+                    .route("/log-level", web::get().to(log_level))
+                    .route("/log-level", web::post().to(change_log_level))
+                    .route(
+                        "/reports/unsubscribe-reasons",
+                        web::get().to(unsubscribe_reasons_report),
+                    )
+                    .route(
+                        "/reports/confirmation-methods",
+                        web::get().to(confirmation_methods_report),
+                    )
+                    .route(
+                        "/reports/webhook-signature-failures",
+                        web::get().to(signature_failures_report),
+                    )
+                    .route(
+                        "/idempotency/{idempotency_key}",
+                        web::get().to(idempotency_record),
+                    )
+                    .service(
+                        web::resource("/deliveries/{delivery_id}/replay")
+                            .app_data(resend_limiter.clone())
+                            .wrap(from_fn(enforce_rate_limit))
+                            .route(web::post().to(replay_delivery)),
+                    )
+                    .route("/subscribers", web::get().to(search_subscribers))
+                    .route("/subscribers/export", web::get().to(export_subscribers))
+                    .route("/subscribers/export.csv", web::get().to(export_subscribers))
+                    .route("/subscribers/import", web::post().to(import_subscribers))
+                    .route("/suppressions/import", web::post().to(import_suppressions))
+                    .route("/subscribers/tags", web::get().to(bulk_tag_form))
+                    .route("/subscribers/tags", web::post().to(bulk_tag_subscribers))
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::get().to(subscriber_details),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/consent.json",
+                        web::get().to(subscriber_consent),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/tags",
+                        web::post().to(add_subscriber_tag),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/pause",
+                        web::post().to(pause_subscriber),
+                    ),
             )
             .app_data(connection.clone())
+            .app_data(event_bus.clone())
             .app_data(email_client.clone())
+            .app_data(broadcast_email_client.clone())
+            .app_data(request_timeout.clone())
+            .app_data(read_only.clone())
+            .app_data(form_whitespace.clone())
             .app_data(base_url.clone())
+            .app_data(admin_csp.clone())
+            .app_data(confirmation_template.clone())
+            .app_data(welcome_email.clone())
+            .app_data(idempotency.clone())
+            .app_data(subscriptions.clone())
+            .app_data(newsletter.clone())
+            .app_data(confirmation_email_limiter.clone())
+            .app_data(webhooks.clone())
     })
+    // Once a SIGTERM arrives, no new connections are accepted and the
+    // in-flight requests get this long to finish.
+    .shutdown_timeout(shutdown_grace.as_secs())
     .listen(listener)?
     .run();
 
@@ -1,9 +1,23 @@
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::HeaderMap;
+use actix_web::HttpMessage;
+use anyhow::Context;
 use tokio::task::JoinHandle;
+use tracing::field::Empty;
 use tracing::subscriber::set_global_default;
-use tracing::Subscriber;
+use tracing::{Span, Subscriber};
+use tracing_actix_web::{DefaultRootSpanBuilder, RootSpanBuilder};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
-use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, reload, EnvFilter, Registry};
+
+/// Lets the filter of the subscriber built by `get_subscriber` be changed
+/// while the application is running. Changes are lost on restart.
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
 
 pub fn get_subscriber<Sink>(
     name: String,
@@ -16,6 +30,10 @@ where
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
 
+    let (env_filter, handle) = reload::Layer::new(env_filter);
+    // Only the first subscriber can become the global default.
+    let _ = LOG_FILTER.set(handle);
+
     let formatting_layer = BunyanFormattingLayer::new(name, sink);
 
     Registry::default()
@@ -29,6 +47,83 @@ pub fn init_subsciber(subscriber: impl Subscriber + Send + Sync) {
     set_global_default(subscriber).expect("Failed to set subscriber");
 }
 
+/// The directives currently used to filter logs, e.g. `info,sqlx=warn`.
+pub fn current_log_filter() -> Result<String, anyhow::Error> {
+    LOG_FILTER
+        .get()
+        .context("No log filter has been set up.")?
+        .with_current(|filter| filter.to_string())
+        .context("The subscriber has been dropped.")
+}
+
+/// Replaces the log filter with `directives` until the next restart.
+pub fn set_log_filter(directives: &str) -> Result<(), anyhow::Error> {
+    let filter = EnvFilter::try_new(directives)?;
+    LOG_FILTER
+        .get()
+        .context("No log filter has been set up.")?
+        .reload(filter)
+        .context("The subscriber has been dropped.")
+}
+
+/// Names the request in the logs of every service it passes through.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Longer ids are replaced, to keep oversized headers out of the logs.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// Wraps every request in a span with its method, path, matched route and
+/// request id, recording the status and how long it took once answered.
+pub struct RequestSpan;
+
+struct RequestStart(Instant);
+
+impl RootSpanBuilder for RequestSpan {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        request
+            .extensions_mut()
+            .insert(RequestStart(Instant::now()));
+        let route = request.match_pattern().unwrap_or_else(|| "default".into());
+        tracing::info_span!(
+            "HTTP request",
+            http.method = %request.method(),
+            http.target = %request.path(),
+            http.route = %route,
+            request_id = %request_id(request.headers()),
+            http.status_code = Empty,
+            elapsed_milliseconds = Empty,
+            otel.status_code = Empty,
+            exception.message = Empty,
+            exception.details = Empty,
+        )
+    }
+
+    fn on_request_end<B: MessageBody>(
+        span: Span,
+        outcome: &Result<ServiceResponse<B>, actix_web::Error>,
+    ) {
+        if let Ok(response) = outcome {
+            if let Some(start) = response.request().extensions().get::<RequestStart>() {
+                span.record("elapsed_milliseconds", start.0.elapsed().as_millis() as u64);
+            }
+        }
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
+
+/// The caller's request id if it sent a usable one, otherwise a new one.
+fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LENGTH
+                && id.chars().all(|c| c.is_ascii_graphic())
+        })
+        .map(ToOwned::to_owned)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
 pub fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
 where
     F: FnOnce() -> R + Send + 'static,
@@ -37,3 +132,30 @@ where
     let current_span = tracing::Span::current();
     tokio::task::spawn_blocking(move || current_span.in_scope(f))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{request_id, REQUEST_ID_HEADER};
+    use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+
+    fn headers(id: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HeaderName::from_static(REQUEST_ID_HEADER),
+            HeaderValue::from_str(id).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn an_incoming_request_id_is_kept() {
+        assert_eq!(request_id(&headers("abc-123")), "abc-123");
+    }
+
+    #[test]
+    fn a_missing_or_unusable_request_id_is_replaced() {
+        assert!(uuid::Uuid::parse_str(&request_id(&HeaderMap::new())).is_ok());
+        assert_ne!(request_id(&headers("two words")), "two words");
+        assert_ne!(request_id(&headers(&"a".repeat(129))), "a".repeat(129));
+    }
+}
@@ -1,10 +1,17 @@
 use actix_web::http::header::LOCATION;
 use actix_web::HttpResponse;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 
+use crate::read_only::{is_read_only_cause, read_only_response};
+
+/// A 500, or a 503 if the database refused a write because it is read-only.
 pub fn e500<T>(e: T) -> actix_web::Error
 where
     T: std::fmt::Debug + std::fmt::Display + 'static,
 {
+    if is_read_only_cause(&e) {
+        return actix_web::error::InternalError::from_response(e, read_only_response()).into();
+    }
     actix_web::error::ErrorInternalServerError(e)
 }
 
@@ -20,3 +27,47 @@ pub fn see_other(location: &str) -> HttpResponse {
         .insert_header((LOCATION, location))
         .finish()
 }
+
+/// Reads a date and time typed into a form, either as RFC 3339 or as the
+/// value of a `datetime-local` input (e.g. `2023-11-20T09:30`), taken to
+/// be UTC. An empty value means no date.
+pub fn parse_datetime(s: &str) -> Result<Option<DateTime<Utc>>, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Ok(Some(datetime.with_timezone(&Utc)));
+    }
+    ["%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .map(|datetime| Some(Utc.from_utc_datetime(&datetime)))
+        .ok_or_else(|| format!("{s} is not a valid date and time."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_datetime;
+    use chrono::{TimeZone, Utc};
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn an_empty_datetime_means_none() {
+        assert_ok_eq!(parse_datetime(""), None);
+        assert_ok_eq!(parse_datetime("  "), None);
+    }
+
+    #[test]
+    fn datetimes_can_be_rfc3339_or_datetime_local() {
+        let expected = Utc.with_ymd_and_hms(2023, 11, 20, 9, 30, 0).unwrap();
+        assert_ok_eq!(parse_datetime("2023-11-20T09:30:00Z"), Some(expected));
+        assert_ok_eq!(parse_datetime("2023-11-20T10:30:00+01:00"), Some(expected));
+        assert_ok_eq!(parse_datetime("2023-11-20T09:30"), Some(expected));
+    }
+
+    #[test]
+    fn invalid_datetimes_are_rejected() {
+        assert_err!(parse_datetime("last tuesday"));
+    }
+}
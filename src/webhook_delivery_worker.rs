@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use tracing::field::display;
+use tracing::Span;
+use uuid::Uuid;
+
+use crate::configuration::{Settings, WebhookSettings};
+use crate::issue_delivery_worker::{DeliveryStatus, ExecutionOutcome};
+use crate::startup::get_connection_pool;
+
+pub async fn run_webhook_worker_until_stopped(
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    let http_client = configuration.webhooks.client();
+    worker_loop(connection_pool, http_client, configuration.webhooks).await
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    http_client: reqwest::Client,
+    settings: WebhookSettings,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_webhook_task(&pool, &http_client, &settings).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+/// Adds a webhook call to the queue, as part of the caller's transaction.
+#[tracing::instrument(skip(transaction, payload))]
+pub async fn enqueue_webhook(
+    transaction: &mut Transaction<'_, Postgres>,
+    url: &str,
+    payload: &serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO webhook_delivery_queue (
+            webhook_delivery_id,
+            url,
+            payload,
+            next_attempt_at,
+            created_at
+        )
+        VALUES ($1, $2, $3, now(), now())
+        "#,
+        Uuid::new_v4(),
+        url,
+        payload.to_string()
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        webhook_delivery_id=tracing::field::Empty,
+        url=tracing::field::Empty
+    ),
+    err
+)]
+pub async fn try_execute_webhook_task(
+    pool: &PgPool,
+    http_client: &reqwest::Client,
+    settings: &WebhookSettings,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some((transaction, task)) = dequeue_task(pool).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current()
+        .record("webhook_delivery_id", display(task.webhook_delivery_id))
+        .record("url", &task.url);
+    let outcome = http_client
+        .post(&task.url)
+        .header("Content-Type", "application/json")
+        .body(task.payload.clone())
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+    match outcome {
+        Ok(_) => complete_task(transaction, &task, Ok(()), settings).await?,
+        Err(e) => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to deliver a webhook.",
+            );
+            complete_task(transaction, &task, Err(e.to_string()), settings).await?
+        }
+    }
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+struct WebhookTask {
+    webhook_delivery_id: Uuid,
+    url: String,
+    payload: String,
+    n_attempts: i32,
+}
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_task(
+    pool: &PgPool,
+) -> Result<Option<(PgTransaction, WebhookTask)>, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let task = sqlx::query_as!(
+        WebhookTask,
+        r#"
+        SELECT webhook_delivery_id, url, payload, n_attempts
+        FROM webhook_delivery_queue
+        WHERE status = 'pending' AND next_attempt_at <= now()
+        ORDER BY next_attempt_at
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    Ok(task.map(|t| (transaction, t)))
+}
+
+/// Marks the webhook as sent, or schedules another attempt with an
+/// exponential backoff until `max_attempts` is reached.
+#[tracing::instrument(skip_all)]
+async fn complete_task(
+    mut transaction: PgTransaction,
+    task: &WebhookTask,
+    outcome: Result<(), String>,
+    settings: &WebhookSettings,
+) -> Result<(), anyhow::Error> {
+    let n_attempts = task.n_attempts + 1;
+    let (status, retry_in, error) = match outcome {
+        Ok(()) => (DeliveryStatus::Sent, Duration::ZERO, None),
+        Err(e) if n_attempts >= settings.max_attempts => {
+            (DeliveryStatus::Failed, Duration::ZERO, Some(e))
+        }
+        Err(e) => (
+            DeliveryStatus::Pending,
+            settings.retry_delay(task.n_attempts),
+            Some(e),
+        ),
+    };
+    let query = sqlx::query!(
+        r#"
+        UPDATE webhook_delivery_queue
+        SET
+            status = $2,
+            n_attempts = $3,
+            next_attempt_at = now() + make_interval(secs => $4),
+            last_error = $5
+        WHERE webhook_delivery_id = $1
+        "#,
+        task.webhook_delivery_id,
+        status.as_str(),
+        n_attempts,
+        retry_in.as_secs_f64(),
+        error
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(())
+}
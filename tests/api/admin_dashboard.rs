@@ -1,10 +1,15 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use zero2prod::routes::{get_subscriber_stats, SubscriberStats};
+
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app,
+    spawn_app_with,
+};
 
 #[tokio::test]
 async fn you_must_be_logged_in_to_access_the_admin_dashboard() {
     let app = spawn_app().await;
     let response = app.get_admin_dashboard().await;
-    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&response, "/login?next=%2Fadmin%2Fdashboard");
 }
 
 #[tokio::test]
@@ -28,5 +33,66 @@ async fn logout_clears_session_state() {
     assert!(html_page.contains("You have successfully logged out."));
 
     let response = app.get_admin_dashboard().await;
-    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&response, "/login?next=%2Fadmin%2Fdashboard");
+}
+
+#[tokio::test]
+async fn admin_pages_carry_the_configured_security_headers() {
+    // Arrange
+    let csp = "default-src 'none'; frame-ancestors 'none'";
+    let app = spawn_app_with(|c| c.application.admin_content_security_policy = csp.into()).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let headers = response.headers();
+    assert_eq!(headers["Content-Security-Policy"], csp);
+    assert_eq!(headers["X-Content-Type-Options"], "nosniff");
+    assert_eq!(headers["X-Frame-Options"], "DENY");
+    assert_eq!(headers["Referrer-Policy"], "same-origin");
+}
+
+#[tokio::test]
+async fn the_dashboard_shows_subscriber_stats() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    create_unconfirmed_subscriber(&app).await;
+    sqlx::query!(
+        "UPDATE subscriptions SET subscribed_at = now() - interval '30 days' WHERE status = 'pending_confirmation'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let stats = get_subscriber_stats(&app.db_pool).await.unwrap();
+    let html_page = app.get_admin_dashboard_html().await;
+
+    // Assert
+    assert_eq!(
+        stats,
+        SubscriberStats {
+            confirmed: 2,
+            pending_confirmation: 1,
+            unsubscribed: 0,
+            total: 3,
+            added_last_7_days: 2,
+        }
+    );
+    assert!(html_page.contains("<tr><th>Confirmed</th><td>2</td></tr>"));
+    assert!(html_page.contains("<tr><th>Added in the last 7 days</th><td>2</td></tr>"));
 }
@@ -0,0 +1,85 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with};
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::configuration::ConfirmationTemplate;
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_preview_the_confirmation_email() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_confirmation_email_preview().await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        "/login?next=%2Fadmin%2Femails%2Fconfirmation%2Fpreview",
+    );
+}
+
+#[tokio::test]
+async fn the_preview_contains_a_sample_link_and_sends_nothing() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let html_page = app
+        .get_confirmation_email_preview()
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    let sample_link = format!(
+        "{}/subscriptions/confirm?subscription_token=sample-subscription-token",
+        app.base_url
+    );
+    assert!(html_page.contains("<p>Subject: Welcome!</p>"));
+    assert!(html_page.contains(&format!("Visit {sample_link} to confirm")));
+    assert!(html_page.contains(&format!("href=&quot;{sample_link}&quot;")));
+}
+
+#[tokio::test]
+async fn the_preview_uses_the_configured_template() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.email_client.confirmation_template = ConfirmationTemplate {
+            subject: "Please confirm".into(),
+            html_content: "<p>Confirm at {{confirmation_link}}</p>".into(),
+            text_content: "One more step: {{confirmation_link}}".into(),
+            product_name: String::new(),
+        }
+    })
+    .await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let html_page = app
+        .get_confirmation_email_preview()
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html_page.contains("<p>Subject: Please confirm</p>"));
+    assert!(html_page.contains("One more step: http"));
+    assert!(html_page.contains("&lt;p&gt;Confirm at http"));
+    assert!(!html_page.contains("{{confirmation_link}}"));
+}
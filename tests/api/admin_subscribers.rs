@@ -0,0 +1,793 @@
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::routes::get_subscribers_page;
+
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber,
+    insert_confirmed_subscriber, spawn_app, spawn_app_with,
+};
+
+#[tokio::test]
+async fn subscriber_details_render_status_tags_and_deliveries() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    sqlx::query!(
+        "INSERT INTO subscriber_tags (subscriber_id, tag) VALUES ($1, 'vip')",
+        subscriber_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Our very first issue",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Act
+    let response = app.get_subscriber_details(&subscriber_id.to_string()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h2>Status</h2>"));
+    assert!(html_page.contains("confirmed"));
+    assert!(html_page.contains("<li>vip</li>"));
+    assert!(html_page.contains("<td>Our very first issue</td><td>sent</td>"));
+    assert!(html_page.contains("<p>Suppression: not suppressed</p>"));
+}
+
+#[tokio::test]
+async fn subscriber_details_show_whether_the_address_is_suppressed() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    app.post_suppressions_import("email\nursula@example.com\n")
+        .await;
+
+    // Act
+    let response = app.get_subscriber_details(&subscriber_id.to_string()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<p>Suppression: suppressed since "));
+}
+
+#[tokio::test]
+async fn subscriber_details_returns_404_for_an_unknown_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let response = app
+        .get_subscriber_details(&uuid::Uuid::new_v4().to_string())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn tags_beyond_the_per_subscriber_limit_are_rejected() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscriptions.max_tags_per_subscriber = 2).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+        .to_string();
+    let details = format!("/admin/subscribers/{subscriber_id}");
+
+    // Act - Part 1 - Fill up the tag allowance
+    for tag in ["vip", "beta"] {
+        let response = app.post_subscriber_tag(&subscriber_id, tag).await;
+        assert_is_redirect_to(&response, &details);
+    }
+    let html_page = app
+        .get_subscriber_details(&subscriber_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>The tag has been added.</i></p>"));
+
+    // Act - Part 2 - Go over the limit
+    let response = app
+        .post_subscriber_tag(&subscriber_id, "early-adopter")
+        .await;
+    assert_is_redirect_to(&response, &details);
+
+    // Assert
+    let html_page = app
+        .get_subscriber_details(&subscriber_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page
+        .contains("<p><i>The tag was not added - subscribers can have at most 2 tags.</i></p>"));
+    assert!(!html_page.contains("<li>early-adopter</li>"));
+    let n_tags = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriber_tags"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_tags, 2);
+}
+
+#[tokio::test]
+async fn a_rejected_tag_is_escaped_in_the_error_message() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+        .to_string();
+
+    // Act
+    app.post_subscriber_tag(&subscriber_id, "<script>alert(1)</script>,")
+        .await;
+
+    // Assert
+    let html_page = app
+        .get_subscriber_details(&subscriber_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(!html_page.contains("<script>"));
+    assert!(html_page.contains("&lt;script&gt;alert(1)&lt;/script&gt;, is not a valid tag."));
+}
+
+#[tokio::test]
+async fn the_export_can_be_restricted_to_a_tag() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let subscribers = sqlx::query!("SELECT id, email FROM subscriptions ORDER BY subscribed_at")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    app.post_subscriber_tag(&subscribers[0].id.to_string(), "vip")
+        .await;
+
+    // Act
+    let everyone = app.get_subscribers_export(None).await;
+    let segment = app.get_subscribers_export(Some("vip")).await;
+
+    // Assert
+    assert_eq!(everyone.status().as_u16(), 200);
+    assert_eq!(
+        everyone.headers().get("Content-Type").unwrap(),
+        "text/csv; charset=utf-8"
+    );
+    let everyone = everyone.text().await.unwrap();
+    assert!(everyone.starts_with("email,name,status,subscribed_at\n"));
+    assert!(everyone.contains(&subscribers[0].email));
+    assert!(everyone.contains(&subscribers[1].email));
+
+    assert_eq!(segment.status().as_u16(), 200);
+    let segment = segment.text().await.unwrap();
+    assert_eq!(segment.lines().count(), 2);
+    assert!(segment.contains(&subscribers[0].email));
+    assert!(!segment.contains(&subscribers[1].email));
+}
+
+#[tokio::test]
+async fn keyset_pagination_returns_every_subscriber_exactly_once() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut expected = Vec::new();
+    for i in 0..7 {
+        let email = format!("subscriber{i}@example.com");
+        insert_confirmed_subscriber(&app, &email).await;
+        expected.push(email);
+    }
+    create_unconfirmed_subscriber(&app).await;
+    // Ties on the sign up time must not make subscribers fall between pages.
+    sqlx::query!("UPDATE subscriptions SET subscribed_at = '2024-01-01T00:00:00Z'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let mut exported = Vec::new();
+    let mut after = None;
+    loop {
+        let (page, next) = get_subscribers_page(&app.db_pool, Some("confirmed"), None, after, 3)
+            .await
+            .unwrap();
+        assert!(page.len() <= 3);
+        exported.extend(page.into_iter().map(|s| s.email));
+        match next {
+            Some(next) => after = Some(next),
+            None => break,
+        }
+    }
+
+    // Assert
+    exported.sort();
+    expected.sort();
+    assert_eq!(exported, expected);
+}
+
+#[tokio::test]
+async fn the_export_is_also_served_without_the_extension_as_an_attachment() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/subscribers/export", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Disposition").unwrap(),
+        r#"attachment; filename="subscribers.csv""#
+    );
+    let csv = response.text().await.unwrap();
+    assert!(csv.starts_with("email,name,status,subscribed_at\n"));
+    assert_eq!(csv.lines().count(), 2);
+}
+
+#[tokio::test]
+async fn consent_records_list_when_a_confirmed_subscriber_subscribed_and_confirmed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let subscriber = sqlx::query!("SELECT id, subscribed_at, confirmed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app.get_subscriber_consent(&subscriber.id.to_string()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Disposition").unwrap(),
+        &format!(r#"attachment; filename="consent-{}.json""#, subscriber.id)
+    );
+    let record: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(record["status"], "confirmed");
+    let events = record["events"].as_array().unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["event"], "subscribed");
+    assert_eq!(events[0]["at"], subscriber.subscribed_at.to_rfc3339());
+    assert_eq!(events[1]["event"], "confirmed");
+    assert_eq!(
+        events[1]["at"],
+        subscriber.confirmed_at.unwrap().to_rfc3339()
+    );
+}
+
+#[tokio::test]
+async fn consent_records_list_the_changes_an_admin_made() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+        .to_string();
+    let until = (chrono::Utc::now() + chrono::Duration::days(7)).to_rfc3339();
+    app.post_subscriber_pause(&subscriber_id, &until).await;
+    app.post_subscriber_tag(&subscriber_id, "vip").await;
+    app.post_subscriber_pause(&subscriber_id, "").await;
+
+    // Act
+    let response = app.get_subscriber_consent(&subscriber_id).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let record: serde_json::Value = response.json().await.unwrap();
+    let events: Vec<_> = record["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            "subscribed",
+            "confirmed",
+            "pause_subscriber",
+            "tag_subscriber",
+            "resume_subscriber"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_subscribers_export(None).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login?next=%2Fadmin%2Fsubscribers%2Fexport.csv");
+}
+
+#[tokio::test]
+async fn searching_by_status_and_tag_returns_subscribers_matching_both() {
+    // Arrange
+    let app = spawn_app().await;
+    for (email, status, tag) in [
+        ("confirmed-vip@example.com", "confirmed", Some("vip")),
+        (
+            "pending-vip@example.com",
+            "pending_confirmation",
+            Some("vip"),
+        ),
+        ("confirmed-plain@example.com", "confirmed", None),
+        ("confirmed-other@example.com", "confirmed", Some("other")),
+    ] {
+        let id = uuid::Uuid::new_v4();
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+            VALUES ($1, $2, 'le guin', now(), $3)
+            "#,
+            id,
+            email,
+            status
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+        if let Some(tag) = tag {
+            sqlx::query!(
+                "INSERT INTO subscriber_tags (subscriber_id, tag) VALUES ($1, $2)",
+                id,
+                tag
+            )
+            .execute(&app.db_pool)
+            .await
+            .unwrap();
+        }
+    }
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let html_page = app
+        .get_subscribers_search_html(&[("status", "confirmed"), ("tag", "vip"), ("q", "")])
+        .await;
+
+    // Assert
+    assert!(html_page.contains("confirmed-vip@example.com"));
+    assert!(!html_page.contains("pending-vip@example.com"));
+    assert!(!html_page.contains("confirmed-plain@example.com"));
+    assert!(!html_page.contains("confirmed-other@example.com"));
+
+    // Free text narrows the results further
+    let html_page = app
+        .get_subscribers_search_html(&[("status", "confirmed"), ("q", "plain")])
+        .await;
+    assert!(html_page.contains("confirmed-plain@example.com"));
+    assert!(!html_page.contains("confirmed-vip@example.com"));
+}
+
+#[tokio::test]
+async fn a_page_far_beyond_the_results_is_empty() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let html_page = app
+        .get_subscribers_search_html(&[("page", &i64::MAX.to_string())])
+        .await;
+
+    // Assert
+    assert!(html_page.contains(&format!("Page {}", i64::MAX)));
+}
+
+#[tokio::test]
+async fn paused_subscribers_are_skipped_until_the_pause_ends() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+        .to_string();
+    let newsletter = |title: &str| {
+        serde_json::json!({
+            "title": title,
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        })
+    };
+
+    // Act - Part 1 - Pause emails for a week and publish an issue
+    let until = (chrono::Utc::now() + chrono::Duration::days(7)).to_rfc3339();
+    let response = app.post_subscriber_pause(&subscriber_id, &until).await;
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{subscriber_id}"));
+    let html_page = app
+        .get_subscriber_details(&subscriber_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>Emails to this subscriber are paused until"));
+
+    let paused_send = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .named("No email while paused")
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_newsletter(&newsletter("Sent during the pause"))
+        .await;
+    app.dispatch_all_pending_emails().await;
+    drop(paused_send);
+
+    // Act - Part 2 - The pause ends and another issue is published
+    sqlx::query!("UPDATE subscriptions SET suppressed_until = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .named("Email after the pause")
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&newsletter("Sent after the pause"))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // Mock verifies on Drop that we have sent the newsletter email only once
+}
+
+#[tokio::test]
+async fn a_pause_holds_back_deliveries_that_were_already_queued() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+        .to_string();
+    app.post_newsletter(&serde_json::json!({
+        "title": "Queued before the pause",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+
+    // Act - Part 1 - Pause before the worker gets to the delivery
+    let until = (chrono::Utc::now() + chrono::Duration::days(7)).to_rfc3339();
+    app.post_subscriber_pause(&subscriber_id, &until).await;
+    let paused_send = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .named("No email while paused")
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    drop(paused_send);
+
+    // Act - Part 2 - The pause ends
+    sqlx::query!("UPDATE subscriptions SET suppressed_until = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .named("Email after the pause")
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // Mock verifies on Drop that the queued issue was sent once the pause ended
+}
+
+#[tokio::test]
+async fn subscribers_are_imported_using_the_column_mapping() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let csv = "\
+Full Name,E-mail Address,Segment,Origin
+\"Le Guin, Ursula\",ursula@example.com,authors,Old provider
+Octavia Butler,octavia@example.com,,
+Nobody,not-an-email,,
+";
+
+    // Act
+    let response = app
+        .post_subscribers_import(&serde_json::json!({
+            "csv": csv,
+            "email_column": "E-mail Address",
+            "name_column": "Full Name",
+            "tag_column": "Segment",
+            "source_column": "Origin",
+            "already_confirmed": true,
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 2);
+    assert_eq!(report["invalid"], 1);
+
+    let ursula = sqlx::query!(
+        r#"SELECT id, name, status, confirmed_via, source
+        FROM subscriptions WHERE email = 'ursula@example.com'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(ursula.name, "Le Guin, Ursula");
+    assert_eq!(ursula.status, "confirmed");
+    assert_eq!(ursula.confirmed_via.as_deref(), Some("import"));
+    assert_eq!(ursula.source.as_deref(), Some("Old provider"));
+    let tags = sqlx::query_scalar!(
+        "SELECT tag FROM subscriber_tags WHERE subscriber_id = $1",
+        ursula.id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(tags, ["authors"]);
+
+    let octavia =
+        sqlx::query!("SELECT name, source FROM subscriptions WHERE email = 'octavia@example.com'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(octavia.name, "Octavia Butler");
+    assert_eq!(octavia.source, None);
+
+    let actions = sqlx::query_scalar!("SELECT action FROM audit_log")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        actions,
+        ["import_confirmed_subscriber", "import_confirmed_subscriber"]
+    );
+}
+
+#[tokio::test]
+async fn imported_subscribers_are_asked_to_confirm() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let csv = "\
+name,email
+Ursula,ursula@example.com
+";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscribers_import(&serde_json::json!({
+            "csv": csv,
+            "email_column": "email",
+            "name_column": "name",
+        }))
+        .await;
+    app.dispatch_all_confirmation_resends().await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT status, confirmed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "pending_confirmation");
+    assert_eq!(saved.confirmed_at, None);
+
+    // The link in the email confirms the subscription
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let status = sqlx::query_scalar!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "confirmed");
+}
+
+#[tokio::test]
+async fn quoted_fields_in_an_import_may_span_lines() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let csv = "\
+email,name,source
+ursula@example.com,Ursula,\"Met at the book fair,
+signed up on paper\"
+octavia@example.com,Octavia,
+";
+
+    // Act
+    let response = app
+        .post_subscribers_import(&serde_json::json!({
+            "csv": csv,
+            "email_column": "email",
+            "name_column": "name",
+            "source_column": "source",
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 2);
+    assert_eq!(report["invalid"], 0);
+    let source =
+        sqlx::query_scalar!("SELECT source FROM subscriptions WHERE email = 'ursula@example.com'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        source.as_deref(),
+        Some("Met at the book fair,\nsigned up on paper")
+    );
+}
+
+#[tokio::test]
+async fn importing_with_a_column_missing_from_the_csv_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let response = app
+        .post_subscribers_import(&serde_json::json!({
+            "csv": "email,name\nursula@example.com,Ursula\n",
+            "email_column": "email",
+            "name_column": "name",
+            "tag_column": "segment",
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+    let count = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
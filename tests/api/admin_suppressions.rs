@@ -0,0 +1,97 @@
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, insert_confirmed_subscriber, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_import_suppressions() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_suppressions_import("email\nursula@example.com\n")
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn imported_addresses_are_excluded_from_the_next_send() {
+    // Arrange
+    let app = spawn_app().await;
+    for email in [
+        "a@example.com",
+        "b@example.com",
+        "c@example.com",
+        "keep@example.com",
+    ] {
+        insert_confirmed_subscriber(&app, email).await;
+    }
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Import the suppression list
+    let response = app
+        .post_suppressions_import(
+            "email\na@example.com\nb@example.com\nnot-an-email\nc@example.com\n",
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "suppressed": 3,
+            "already_suppressed": 0,
+            "unsubscribed": 3,
+            "invalid": 1,
+        })
+    );
+
+    // Act - Part 2 - Publish an issue
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["To"], "keep@example.com");
+}
+
+#[tokio::test]
+async fn importing_the_same_list_twice_reports_the_addresses_as_already_suppressed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let csv = "a@example.com\nb@example.com\n";
+    app.post_suppressions_import(csv).await;
+
+    // Act
+    let response = app.post_suppressions_import(csv).await;
+
+    // Assert
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["suppressed"], 0);
+    assert_eq!(report["already_suppressed"], 2);
+}
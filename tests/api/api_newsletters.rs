@@ -0,0 +1,276 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, spawn_app_with, TestApp, TestUser};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::idempotency::{IdempotencyScope, KeyReusePolicy};
+
+fn newsletter_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    })
+}
+
+#[tokio::test]
+async fn requests_without_credentials_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/api/newsletters", &app.address))
+        .json(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.headers()["WWW-Authenticate"],
+        r#"Basic realm="api", Bearer"#
+    );
+}
+
+#[tokio::test]
+async fn requests_with_a_wrong_password_or_token_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let wrong_password = reqwest::Client::new()
+        .post(format!("{}/api/newsletters", &app.address))
+        .basic_auth(&app.test_user.username, Some("wrong-password"))
+        .json(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let wrong_token = app
+        .post_api_newsletters_with_token(&newsletter_request_body(), "not-a-token")
+        .await;
+
+    // Assert
+    assert_eq!(wrong_password.status().as_u16(), 401);
+    assert_eq!(wrong_token.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn too_many_wrong_passwords_lock_basic_auth_out_even_with_the_right_one() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.rate_limits.login_lockout.max_failures_per_username = 2;
+        c.rate_limits.login_lockout.window_seconds = 60;
+    })
+    .await;
+    for _ in 0..2 {
+        reqwest::Client::new()
+            .post(format!("{}/api/newsletters", &app.address))
+            .basic_auth(&app.test_user.username, Some("wrong-password"))
+            .json(&newsletter_request_body())
+            .send()
+            .await
+            .expect("Failed to execute request.");
+    }
+
+    // Act
+    let response = app.post_api_newsletters(&newsletter_request_body()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
+}
+
+#[tokio::test]
+async fn api_requests_are_rate_limited() {
+    // Arrange
+    let app = spawn_app_with(|c| c.rate_limits.api.max_requests = 1).await;
+
+    // Act
+    let first = app
+        .post_api_newsletters_with_token(&newsletter_request_body(), "not-a-token")
+        .await;
+    let second = app
+        .post_api_newsletters_with_token(&newsletter_request_body(), "not-a-token")
+        .await;
+
+    // Assert
+    assert_eq!(first.status().as_u16(), 401);
+    assert_eq!(second.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn publishing_with_basic_auth_reports_how_many_subscribers_were_queued() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_api_newsletters(&newsletter_request_body()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let outcome: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(outcome["status"], "queued");
+    assert_eq!(outcome["queued"], 1);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn publishing_with_an_api_token_is_idempotent() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let token = app.create_api_token().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let body = newsletter_request_body();
+
+    // Act
+    let first = app.post_api_newsletters_with_token(&body, &token).await;
+    let second = app.post_api_newsletters_with_token(&body, &token).await;
+
+    // Assert
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(second.status().as_u16(), 200);
+    let first: serde_json::Value = first.json().await.unwrap();
+    let second: serde_json::Value = second.json().await.unwrap();
+    assert_eq!(first["idempotency_replayed"], false);
+    assert_eq!(second["idempotency_replayed"], true);
+    assert_eq!(first["issue_id"], second["issue_id"]);
+    assert_eq!(
+        first["idempotency_ttl_seconds"],
+        app.idempotency.ttl_seconds
+    );
+    assert_eq!(
+        second["idempotency_ttl_seconds"],
+        app.idempotency.ttl_seconds
+    );
+    assert_eq!(second["queued"], 1);
+    app.dispatch_all_pending_emails().await;
+}
+
+/// Publishes the same body as the test user and as a second user, and
+/// returns both outcomes.
+async fn publish_as_two_users(app: &TestApp) -> (serde_json::Value, serde_json::Value) {
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    let body = newsletter_request_body();
+    let first = app.post_api_newsletters(&body).await;
+    let second = app.post_api_newsletters_as(&other_user, &body).await;
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(second.status().as_u16(), 200);
+    (first.json().await.unwrap(), second.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn the_same_key_from_two_users_is_processed_twice_under_per_user_scope() {
+    // Arrange
+    let app = spawn_app_with(|c| c.idempotency.scope = IdempotencyScope::PerUser).await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    let (first, second) = publish_as_two_users(&app).await;
+
+    // Assert
+    assert_eq!(second["idempotency_replayed"], false);
+    assert_ne!(first["issue_id"], second["issue_id"]);
+}
+
+#[tokio::test]
+async fn the_same_key_from_two_users_collides_under_global_scope() {
+    // Arrange
+    let app = spawn_app_with(|c| c.idempotency.scope = IdempotencyScope::Global).await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    let (first, second) = publish_as_two_users(&app).await;
+
+    // Assert
+    assert_eq!(second["idempotency_replayed"], true);
+    assert_eq!(first["issue_id"], second["issue_id"]);
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 1);
+}
+
+/// The first body, and a different issue sent with the same idempotency key.
+fn two_issues_with_the_same_key() -> (serde_json::Value, serde_json::Value) {
+    let first = newsletter_request_body();
+    let mut second = first.clone();
+    second["title"] = "Another newsletter title".into();
+    (first, second)
+}
+
+#[tokio::test]
+async fn reusing_a_key_for_different_content_is_rejected_under_the_strict_policy() {
+    // Arrange
+    let app = spawn_app_with(|c| c.idempotency.key_reuse = KeyReusePolicy::Strict).await;
+    create_confirmed_subscriber(&app).await;
+    let (first_body, second_body) = two_issues_with_the_same_key();
+
+    // Act
+    let first = app.post_api_newsletters(&first_body).await;
+    let second = app.post_api_newsletters(&second_body).await;
+
+    // Assert
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(second.status().as_u16(), 409);
+    let titles = sqlx::query!("SELECT title FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(titles.len(), 1);
+    assert_eq!(titles[0].title, "Newsletter title");
+}
+
+#[tokio::test]
+async fn reusing_a_key_for_different_content_publishes_it_under_the_latest_wins_policy() {
+    // Arrange
+    let app = spawn_app_with(|c| c.idempotency.key_reuse = KeyReusePolicy::LatestWins).await;
+    create_confirmed_subscriber(&app).await;
+    let (first_body, second_body) = two_issues_with_the_same_key();
+
+    // Act
+    let first = app.post_api_newsletters(&first_body).await;
+    let second = app.post_api_newsletters(&second_body).await;
+    let retry = app.post_api_newsletters(&second_body).await;
+
+    // Assert
+    let first: serde_json::Value = first.json().await.unwrap();
+    let second: serde_json::Value = second.json().await.unwrap();
+    let retry: serde_json::Value = retry.json().await.unwrap();
+    assert_eq!(second["idempotency_replayed"], false);
+    assert_ne!(first["issue_id"], second["issue_id"]);
+    // The second issue's response has replaced the first one's.
+    assert_eq!(retry["idempotency_replayed"], true);
+    assert_eq!(retry["issue_id"], second["issue_id"]);
+}
@@ -7,7 +7,7 @@ use crate::helpers::{assert_is_redirect_to, spawn_app};
 async fn you_must_be_logged_in_to_see_the_change_password_form() {
     let app = spawn_app().await;
     let response = app.get_change_password().await;
-    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&response, "/login?next=%2Fadmin%2Fpassword");
 }
 
 #[tokio::test]
@@ -80,7 +80,7 @@ async fn new_password_fields_must_match() {
 }
 
 #[tokio::test]
-async fn password_must_exceed_12_characters() {
+async fn password_must_be_at_least_12_characters() {
     let app = spawn_app().await;
     let new_password = fake::faker::internet::en::Password(1..12).fake::<String>();
 
@@ -102,7 +102,7 @@ async fn password_must_exceed_12_characters() {
 
     let html_page = app.get_change_password_html().await;
     assert!(html_page.contains(
-        "<p><i>The new password you entered is too short - passwords must be longer than 12 characters.</i></p>"
+        "<p><i>The new password you entered is too short - passwords must be at least 12 characters long.</i></p>"
     ));
 }
 
@@ -145,3 +145,147 @@ async fn changing_password_works() {
         .await;
     assert_is_redirect_to(&response, "/admin/dashboard");
 }
+
+#[tokio::test]
+async fn password_must_not_exceed_128_characters() {
+    let app = spawn_app().await;
+    let new_password = "a".repeat(129);
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains(
+        "<p><i>The new password you entered is too long - passwords must be at most 128 characters long.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn password_must_not_be_the_username() {
+    let app = spawn_app().await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &app.test_user.username,
+            "new_password_check": &app.test_user.username,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(
+        html_page.contains("<p><i>The new password cannot be the same as your username.</i></p>")
+    );
+}
+
+#[tokio::test]
+async fn mismatched_passwords_are_reported_before_weak_ones() {
+    let app = spawn_app().await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": "short",
+            "new_password_check": "different",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains(
+        "<p><i>You entered two different new passwords - the field values must match.</i></p>"
+    ));
+    assert!(!html_page.contains("too short"));
+}
+
+#[tokio::test]
+async fn the_account_email_must_be_valid() {
+    let app = spawn_app().await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app.post_account_email("not-an-email").await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Please enter a valid email address.</i></p>"));
+    let email = sqlx::query!(
+        "SELECT email FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .email;
+    assert_eq!(email, None);
+}
+
+#[tokio::test]
+async fn changing_the_account_email_works() {
+    let app = spawn_app().await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app.post_account_email("admin@example.com").await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Test emails will be sent to admin@example.com.</i></p>"));
+    assert!(html_page.contains(r#"value="admin@example.com""#));
+}
+
+#[tokio::test]
+async fn the_account_email_is_escaped_on_the_page() {
+    let app = spawn_app().await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app.post_account_email("o'brien@example.com").await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(
+        html_page.contains("<p><i>Test emails will be sent to o&#x27;brien@example.com.</i></p>")
+    );
+    assert!(html_page.contains(r#"value="o&#x27;brien@example.com""#));
+}
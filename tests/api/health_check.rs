@@ -1,4 +1,4 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with};
 
 #[tokio::test]
 async fn health_check_works() {
@@ -17,3 +17,68 @@ async fn health_check_works() {
     assert!(response.status().is_success());
     assert_eq!(Some(0), response.content_length());
 }
+
+#[tokio::test]
+async fn readiness_check_passes_when_every_dependency_is_reachable() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_readiness_check().await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["failing"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn readiness_check_names_an_unreachable_email_provider() {
+    // Arrange
+    let app = spawn_app_with(|c| c.email_client.base_url = "http://127.0.0.1:9".into()).await;
+
+    // Act
+    let response = app.get_readiness_check().await;
+
+    // Assert
+    assert_eq!(503, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["failing"], serde_json::json!(["email_provider"]));
+}
+
+#[tokio::test]
+async fn readiness_check_names_an_unreachable_database() {
+    // Arrange
+    let app = spawn_app().await;
+    app.drop_database().await;
+
+    // Act
+    let response = app.get_readiness_check().await;
+    let liveness = app.get_health_check().await;
+
+    // Assert
+    assert_eq!(503, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["failing"], serde_json::json!(["database"]));
+    assert_eq!(200, liveness.status().as_u16());
+}
+
+#[tokio::test]
+async fn migrations_are_applied_on_start_when_enabled() {
+    // Arrange - the database is created but left empty
+    let app = spawn_app_with(|c| c.database.migrate_on_start = true).await;
+    let latest_migration = sqlx::migrate!("./migrations")
+        .iter()
+        .map(|m| m.version)
+        .max()
+        .unwrap();
+
+    // Act
+    let response = app.get_migration_status().await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let status: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(status["latest_applied"], latest_migration);
+    assert_eq!(status["latest_known"], latest_migration);
+}
@@ -1,15 +1,36 @@
 use argon2::password_hash::SaltString;
 use argon2::{Argon2, Params, PasswordHasher};
+use fake::faker::internet::en::SafeEmail;
+use fake::faker::name::en::Name;
+use fake::Fake;
+use hmac::{Hmac, Mac};
 use once_cell::sync::Lazy;
 use reqwest::Url;
+use secrecy::ExposeSecret;
+use sha2::Sha256;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
-use wiremock::MockServer;
-use zero2prod::configuration::{get_configuration, DatabaseSettings};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use zero2prod::configuration::{
+    get_configuration, DatabaseSettings, DeadLetterRetrySettings, IdempotencySettings,
+    PendingExpirySettings, Settings, WebhookSettings,
+};
+use zero2prod::confirmation_reminder_worker::try_resend_confirmation;
+use zero2prod::dead_letter_retry_worker::try_requeue_dead_letters;
+use zero2prod::domain::ConfirmationEmailTemplate;
 use zero2prod::email_client::EmailClient;
-use zero2prod::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
+use zero2prod::events::EventBus;
+use zero2prod::idempotency_expiry_worker::try_delete_expired_keys;
+use zero2prod::issue_delivery_worker::{
+    try_execute_task, worker_loop, ExecutionOutcome, IssueSlots,
+};
+use zero2prod::pending_expiry_worker::try_purge_expired_subscriber;
+use zero2prod::routes::SIGNATURE_HEADER;
+use zero2prod::shutdown::ShutdownSignal;
 use zero2prod::startup::{get_connection_pool, Application};
 use zero2prod::telemetry::{get_subscriber, init_subsciber};
+use zero2prod::webhook_delivery_worker::try_execute_webhook_task;
 
 static TRACING: Lazy<()> = Lazy::new(|| {
     let subscriber_name = "test".to_string();
@@ -32,6 +53,17 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub broadcast_email_client: EmailClient,
+    pub email_batch_size: usize,
+    pub minify_html: bool,
+    pub issue_slots: Option<IssueSlots>,
+    pub webhooks: WebhookSettings,
+    pub dead_letter_retry: DeadLetterRetrySettings,
+    pub pending_expiry: PendingExpirySettings,
+    pub idempotency: IdempotencySettings,
+    pub confirmation_template: ConfirmationEmailTemplate,
+    pub base_url: String,
+    pub event_bus: EventBus,
 }
 
 pub struct ConfirmationLinks {
@@ -40,9 +72,50 @@ pub struct ConfirmationLinks {
 }
 
 impl TestApp {
+    pub async fn get_health_check(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/health_check", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_migration_status(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/health_check/migrations", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_readiness_check(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/health_check/ready", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Drops the app's database from under it, e.g. to simulate an outage.
+    pub async fn drop_database(&self) {
+        let database_name: String = sqlx::query_scalar("SELECT current_database()")
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap();
+        let options = self.db_pool.connect_options().as_ref().clone();
+        self.db_pool.close().await;
+        let mut connection = PgConnection::connect_with(&options.database("postgres"))
+            .await
+            .expect("Failed to connect to Postgres");
+        connection
+            .execute(format!(r#"DROP DATABASE "{database_name}" WITH (FORCE);"#).as_str())
+            .await
+            .expect("Failed to drop database.");
+    }
+
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -65,8 +138,8 @@ impl TestApp {
             confirmation_link
         };
 
-        let html = get_link(&body["HtmlBody"].as_str().unwrap());
-        let plain_text = get_link(&body["TextBody"].as_str().unwrap());
+        let html = get_link(body["HtmlBody"].as_str().unwrap());
+        let plain_text = get_link(body["TextBody"].as_str().unwrap());
         ConfirmationLinks { html, plain_text }
     }
 
@@ -75,16 +148,238 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletter", &self.address))
+            .post(format!("{}/admin/newsletter", &self.address))
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_newsletter_review<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletter/review", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_newsletter_test<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletter/test", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_newsletter_json(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/newsletter", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Publishes through the JSON API, authenticating as the test user
+    /// with basic auth.
+    pub async fn post_api_newsletters(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.post_api_newsletters_as(&self.test_user, body).await
+    }
+
+    pub async fn post_api_newsletters_as(
+        &self,
+        user: &TestUser,
+        body: &serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/newsletters", &self.address))
+            .basic_auth(&user.username, Some(&user.password))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_api_newsletters_with_token(
+        &self,
+        body: &serde_json::Value,
+        token: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/newsletters", &self.address))
+            .bearer_auth(token)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Creates an API token for the logged in user and returns it.
+    pub async fn create_api_token(&self) -> String {
+        let html_page = self
+            .api_client
+            .post(format!("{}/admin/api-tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap();
+        html_page
+            .split_once(r#"<pre id="api-token">"#)
+            .and_then(|(_, rest)| rest.split_once("</pre>"))
+            .expect("The page did not show an API token.")
+            .0
+            .to_string()
+    }
+
+    pub async fn post_dispatch_queue(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/newsletter/dispatch", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_clone_issue(&self, issue_id: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletter/{}/clone",
+                &self.address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_issue(&self, issue_id: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletter/{}/resend-all",
+                &self.address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_newsletter_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/admin/newsletter", &self.address))
+            .get(format!("{}/admin/newsletter", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_idempotency_stats(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/idempotency", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_log_level(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/log-level", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_log_level(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/log-level", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_idempotency_record(&self, idempotency_key: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/idempotency/{}",
+                &self.address, idempotency_key
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_replay_delivery(&self, delivery_id: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/deliveries/{}/replay",
+                &self.address, delivery_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_issue_deliveries(&self, issue_id: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletter/{}/deliveries",
+                &self.address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_issue_deliveries_html(&self, issue_id: &str) -> String {
+        self.get_issue_deliveries(issue_id)
+            .await
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_issue_activity_html(&self, issue_id: &str) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletter/{}/activity",
+                &self.address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_newsletter_template<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletter/templates", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_newsletter_from_template_html(&self, template_id: &str) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletter?template_id={}",
+                &self.address, template_id
+            ))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -98,16 +393,145 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_suppressions_import(&self, csv: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/suppressions/import", &self.address))
+            .header("Content-Type", "text/csv")
+            .body(csv.to_string())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_unsubscribe(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/subscriptions/unsubscribe", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_unsubscribe_reason<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/subscriptions/unsubscribe/reason",
+                &self.address
+            ))
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_unsubscribe_reasons_report_html(&self) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/reports/unsubscribe-reasons",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// Signs the body the way the email provider does.
+    pub fn sign_webhook(&self, body: &str) -> String {
+        let secret = self.webhooks.bounce_signing_secret.expose_secret();
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    pub async fn post_bounce_webhook(&self, body: &str, signature: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/webhooks/bounces", &self.address))
+            .header(SIGNATURE_HEADER, signature)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_engagement_webhook(&self, body: &str, signature: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/webhooks/engagement", &self.address))
+            .header(SIGNATURE_HEADER, signature)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_confirmation_methods_report_html(&self) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/reports/confirmation-methods",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_confirmation_email_preview(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/emails/confirmation/preview",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_signature_failures_report_html(&self) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/reports/webhook-signature-failures",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_login_html_with_next(&self, next: &str) -> String {
+        self.api_client
+            .get(format!("{}/login", &self.address))
+            .query(&[("next", next)])
             .send()
             .await
             .expect("Failed to execute request.")
@@ -118,7 +542,7 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -126,7 +550,7 @@ impl TestApp {
 
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -138,7 +562,7 @@ impl TestApp {
 
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -148,22 +572,173 @@ impl TestApp {
         self.get_change_password().await.text().await.unwrap()
     }
 
+    pub async fn get_subscriber_details(&self, subscriber_id: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}",
+                &self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_consent(&self, subscriber_id: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}/consent.json",
+                &self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribers_search_html(&self, query: &[(&str, &str)]) -> String {
+        self.api_client
+            .get(format!("{}/admin/subscribers", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_subscribers_export(&self, tag: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .api_client
+            .get(format!("{}/admin/subscribers/export.csv", &self.address));
+        if let Some(tag) = tag {
+            request = request.query(&[("tag", tag)]);
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_subscribers_import<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_subscriber_tag(&self, subscriber_id: &str, tag: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{}/tags",
+                &self.address, subscriber_id
+            ))
+            .form(&serde_json::json!({ "tag": tag }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_subscriber_pause(
+        &self,
+        subscriber_id: &str,
+        suppressed_until: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{}/pause",
+                &self.address, subscriber_id
+            ))
+            .form(&serde_json::json!({ "suppressed_until": suppressed_until }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
-    pub async fn dispatch_all_pending_emails(&self) {
+    pub async fn post_account_email(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/account/email", &self.address))
+            .form(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn dispatch_all_pending_webhooks(&self) {
+        let http_client = self.webhooks.client();
+        loop {
+            if let ExecutionOutcome::EmptyQueue =
+                try_execute_webhook_task(&self.db_pool, &http_client, &self.webhooks)
+                    .await
+                    .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    pub async fn dispatch_all_confirmation_resends(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_resend_confirmation(
+                &self.db_pool,
+                &self.email_client,
+                &self.confirmation_template,
+                &self.base_url,
+                self.pending_expiry.confirmation_link_ttl(),
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    pub async fn delete_all_expired_idempotency_keys(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue =
+                try_delete_expired_keys(&self.db_pool, &self.idempotency)
+                    .await
+                    .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    pub async fn purge_all_expired_subscribers(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_purge_expired_subscriber(
+                &self.db_pool,
+                &self.email_client,
+                &self.pending_expiry,
+                &self.base_url,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    pub async fn requeue_all_dead_letters(&self) {
         loop {
             if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
+                try_requeue_dead_letters(&self.db_pool, &self.dead_letter_retry)
                     .await
                     .unwrap()
             {
@@ -171,9 +746,58 @@ impl TestApp {
             }
         }
     }
+
+    pub async fn dispatch_next_pending_emails(&self) {
+        try_execute_task(
+            &self.db_pool,
+            &self.broadcast_email_client,
+            self.email_batch_size,
+            self.minify_html,
+            &self.base_url,
+            self.issue_slots,
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Runs the delivery worker until `shutdown` is triggered.
+    pub async fn run_delivery_worker(&self, shutdown: ShutdownSignal) -> Result<(), anyhow::Error> {
+        worker_loop(
+            &self.db_pool,
+            &self.broadcast_email_client,
+            self.email_batch_size,
+            self.minify_html,
+            &self.base_url,
+            self.issue_slots,
+            shutdown,
+        )
+        .await
+    }
+
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.broadcast_email_client,
+                self.email_batch_size,
+                self.minify_html,
+                &self.base_url,
+                self.issue_slots,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+pub async fn spawn_app_with(customise: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
@@ -183,16 +807,24 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        // Retries are covered by the email client's own tests; here they
+        // would only slow down every test of a failing provider.
+        c.email_client.max_attempts = 1;
+        customise(&mut c);
         c
     };
 
     configure_database(&configuration.database).await;
+    if !configuration.database.migrate_on_start {
+        migrate_database(&configuration.database).await;
+    }
 
     let application = Application::build(configuration.clone())
         .await
         .expect("Failed to build application.");
     let port = application.port();
-    let _ = tokio::spawn(application.run_until_stopped());
+    let event_bus = application.event_bus().clone();
+    drop(tokio::spawn(application.run_until_stopped()));
 
     let client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
@@ -207,7 +839,18 @@ pub async fn spawn_app() -> TestApp {
         email_server,
         test_user: TestUser::generate(),
         api_client: client,
-        email_client: configuration.email_client.client(),
+        email_batch_size: configuration.email_client.batch_size,
+        minify_html: configuration.newsletter.minify_html,
+        issue_slots: IssueSlots::from_settings(&configuration.newsletter),
+        webhooks: configuration.webhooks.clone(),
+        dead_letter_retry: configuration.newsletter.dead_letter_retry.clone(),
+        pending_expiry: configuration.subscriptions.pending_expiry.clone(),
+        idempotency: configuration.idempotency.clone(),
+        confirmation_template: configuration.email_client.confirmation_template().unwrap(),
+        base_url: configuration.application.base_url.clone(),
+        email_client: configuration.email_client.clone().client(),
+        broadcast_email_client: configuration.email_client.broadcast_client(),
+        event_bus,
     };
 
     test_app.test_user.store(&test_app.db_pool).await;
@@ -215,7 +858,7 @@ pub async fn spawn_app() -> TestApp {
     test_app
 }
 
-async fn configure_database(config: &DatabaseSettings) -> PgPool {
+async fn configure_database(config: &DatabaseSettings) {
     let mut connection = PgConnection::connect_with(&config.without_db())
         .await
         .expect("Failed to connect to Postgres");
@@ -224,7 +867,9 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
         .execute(format!(r#"CREATE DATABASE "{}";"#, config.database_name).as_str())
         .await
         .expect("Failed to create database.");
+}
 
+async fn migrate_database(config: &DatabaseSettings) {
     let connection_pool = PgPool::connect_with(config.with_db())
         .await
         .expect("Failed to connect to Postgres.");
@@ -233,8 +878,6 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
         .run(&connection_pool)
         .await
         .expect("Failed to migrate the database");
-
-    connection_pool
 }
 
 pub struct TestUser {
@@ -252,7 +895,7 @@ impl TestUser {
         }
     }
 
-    async fn store(&self, pool: &PgPool) {
+    pub async fn store(&self, pool: &PgPool) {
         let salt = SaltString::generate(&mut rand::thread_rng());
         let password_hash = Argon2::new(
             argon2::Algorithm::Argon2id,
@@ -274,6 +917,97 @@ impl TestUser {
     }
 }
 
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let body = serde_urlencoded::to_string(serde_json::json!({
+        "name": name,
+        "email": email
+    }))
+    .unwrap();
+
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+
+    app.get_confirmation_links(email_request)
+}
+
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    let confirmation_link = create_unconfirmed_subscriber(app).await;
+    reqwest::get(confirmation_link.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+/// Stores a confirmed subscriber straight in the database, without the
+/// confirmation email `create_confirmed_subscriber` goes through.
+pub async fn insert_confirmed_subscriber(app: &TestApp, email: &str) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'le guin', now(), 'confirmed')
+        "#,
+        subscriber_id,
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    subscriber_id
+}
+
+/// Stores a subscriber who unsubscribed, having confirmed
+/// `confirmed_days_ago`.
+pub async fn insert_unsubscribed_subscriber(app: &TestApp, email: &str, confirmed_days_ago: i32) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, confirmed_at)
+        VALUES ($1, $2, 'le guin', now(), 'unsubscribed', now() - make_interval(days => $3))
+        "#,
+        Uuid::new_v4(),
+        email,
+        confirmed_days_ago
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+/// Gives the subscriber a one-click unsubscribe link, returning its token.
+pub async fn insert_unsubscribe_token(app: &TestApp, subscriber_id: Uuid) -> String {
+    let token = "unsubscribetoken123456789".to_string();
+    sqlx::query!(
+        "INSERT INTO unsubscribe_tokens (unsubscribe_token, subscriber_id) VALUES ($1, $2)",
+        token,
+        subscriber_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    token
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), location);
@@ -0,0 +1,55 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_change_the_log_level() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_log_level(&serde_json::json!({"level": "debug"}))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_log_level_can_be_changed_at_runtime() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act - Part 1 - Turn on debug logs
+    let response = app
+        .post_log_level(&serde_json::json!({"level": "debug"}))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Assert - Part 1
+    // The app and the tests share the global subscriber.
+    assert!(tracing::enabled!(tracing::Level::DEBUG));
+    let current: serde_json::Value = app.get_log_level().await.json().await.unwrap();
+    assert_eq!(current["filter"], "debug");
+
+    // Act - Part 2 - Invalid directives are rejected and change nothing
+    let response = app
+        .post_log_level(&serde_json::json!({"level": "info,[unclosed"}))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(tracing::enabled!(tracing::Level::DEBUG));
+
+    // Act - Part 3 - Back to the default
+    app.post_log_level(&serde_json::json!({"level": "info"}))
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert - Part 3
+    assert!(!tracing::enabled!(tracing::Level::DEBUG));
+    assert!(tracing::enabled!(tracing::Level::INFO));
+}
@@ -1,4 +1,4 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with};
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -40,3 +40,193 @@ async fn redirect_to_admin_dashboard_after_login_successful() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
 }
+
+#[tokio::test]
+async fn whitespace_around_the_username_is_ignored() {
+    // Arrange
+    let app = spawn_app().await;
+    let login_body = serde_json::json!({
+        "username": format!("  {} ", app.test_user.username),
+        "password": &app.test_user.password,
+    });
+
+    // Act
+    let response = app.post_login(&login_body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn an_empty_username_is_rejected_before_authenticating() {
+    // Arrange
+    let app = spawn_app().await;
+    let login_body = serde_json::json!({
+        "username": "  ",
+        "password": &app.test_user.password
+    });
+
+    // Act
+    let response = app.post_login(&login_body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Please enter both a username and a password.</i></p>"));
+}
+
+#[tokio::test]
+async fn an_empty_password_is_rejected_before_authenticating() {
+    // Arrange
+    let app = spawn_app().await;
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": ""
+    });
+
+    // Act
+    let response = app.post_login(&login_body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Please enter both a username and a password.</i></p>"));
+}
+
+#[tokio::test]
+async fn a_safe_next_path_is_followed_after_login() {
+    // Arrange
+    let app = spawn_app().await;
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+        "next": "/admin/newsletter",
+    });
+
+    // Act
+    let response = app.post_login(&login_body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+}
+
+#[tokio::test]
+async fn an_external_next_is_ignored() {
+    // Arrange
+    let app = spawn_app().await;
+
+    for next in ["https://evil.com/admin", "//evil.com/admin"] {
+        let login_body = serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+            "next": next,
+        });
+
+        // Act
+        let response = app.post_login(&login_body).await;
+
+        // Assert
+        assert_is_redirect_to(&response, "/admin/dashboard");
+    }
+}
+
+#[tokio::test]
+async fn anonymous_users_are_sent_back_to_the_page_they_asked_for() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act - Part 1 - Open a page that requires a login
+    let response = app.get_change_password().await;
+    assert_is_redirect_to(&response, "/login?next=%2Fadmin%2Fpassword");
+
+    // Act - Part 2 - The login form carries the target along
+    let html_page = app.get_login_html_with_next("/admin/password").await;
+    assert!(html_page
+        .contains(r#"<input hidden type="text" name="next" value="&#x2F;admin&#x2F;password" />"#));
+}
+
+#[tokio::test]
+async fn too_many_failed_logins_lock_the_account_out_even_with_the_right_password() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.rate_limits.login_lockout.max_failures_per_username = 2;
+        c.rate_limits.login_lockout.window_seconds = 60;
+    })
+    .await;
+    let wrong_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "random-password"
+    });
+    for _ in 0..2 {
+        app.post_login(&wrong_password).await;
+    }
+
+    // Act
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(r#"<p><i>Authentication failed.</i></p>"#));
+}
+
+#[tokio::test]
+async fn unknown_usernames_are_locked_out_like_existing_ones() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.rate_limits.login_lockout.max_failures_per_username = 2;
+    })
+    .await;
+    let login_body = serde_json::json!({
+        "username": "random-username",
+        "password": "random-password"
+    });
+    for _ in 0..2 {
+        app.post_login(&login_body).await;
+    }
+
+    // Act
+    let response = app.post_login(&login_body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    assert!(response.headers().contains_key("Retry-After"));
+}
+
+#[tokio::test]
+async fn a_successful_login_resets_the_failed_attempts() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.rate_limits.login_lockout.max_failures_per_username = 2;
+        c.rate_limits.login_lockout.max_failures_per_client = 10;
+    })
+    .await;
+    let wrong_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "random-password"
+    });
+    let right_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    });
+
+    // Act
+    app.post_login(&wrong_password).await;
+    app.post_login(&right_password).await;
+    app.post_login(&wrong_password).await;
+    let response = app.post_login(&right_password).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
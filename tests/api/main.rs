@@ -1,8 +1,19 @@
 mod admin_dashboard;
+mod admin_emails;
+mod admin_subscribers;
+mod admin_suppressions;
+mod api_newsletters;
 mod change_password;
 mod health_check;
 mod helpers;
+mod log_level;
 mod login;
 mod newsletter;
+mod newsletter_templates;
+mod rate_limit;
+mod read_only;
+mod shutdown;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
+mod webhooks;
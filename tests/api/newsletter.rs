@@ -1,12 +1,16 @@
-use std::time::Duration;
+use std::num::NonZeroU32;
+use std::time::{Duration, SystemTime};
 
-use fake::faker::internet::en::SafeEmail;
-use fake::faker::name::en::Name;
-use fake::Fake;
-use wiremock::matchers::{any, method, path};
+use actix_web::http::header::HttpDate;
+
+use wiremock::matchers::{any, body_string_contains, method, path};
 use wiremock::{Mock, ResponseTemplate};
+use zero2prod::events::Event;
 
-use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app,
+    spawn_app_with, TestUser,
+};
 
 #[tokio::test]
 async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
@@ -74,6 +78,31 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
     app.dispatch_all_pending_emails().await;
 }
 
+#[tokio::test]
+async fn issues_up_to_the_body_limit_are_accepted() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act - Well above the default limit on request payloads
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "a".repeat(600_000),
+        "html_content": format!("<p>{}</p>", "a".repeat(600_000)),
+        "idempotency_key": uuid::Uuid::new_v4(),
+    });
+    let response = app.post_newsletter(&newsletter_request_body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>Queued 0 emails; sending in progress.</i></p>"));
+}
+
 #[tokio::test]
 async fn newsletters_returns_400_for_invalid_data() {
     // Arrange
@@ -97,6 +126,41 @@ async fn newsletters_returns_400_for_invalid_data() {
             serde_json::json!({"title": "Newsletter"}),
             "missing content",
         ),
+        (
+            serde_json::json!({
+                "title": "Newsletter",
+                "html_content": "<p>Newsletter body as HTML</p>",
+                "markdown_content": "Newsletter body as *Markdown*",
+                "idempotency_key": uuid::Uuid::new_v4(),
+            }),
+            "both HTML and Markdown content",
+        ),
+        (
+            serde_json::json!({
+                "title": "Newsletter",
+                "text_content": "Newsletter body as plain text",
+                "idempotency_key": uuid::Uuid::new_v4(),
+                "scheduled_at": (chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339(),
+            }),
+            "a scheduled time in the past",
+        ),
+        (
+            serde_json::json!({
+                "title": "Newsletter",
+                "text_content": "Newsletter body as plain text",
+                "idempotency_key": uuid::Uuid::new_v4(),
+                "scheduled_at": (chrono::Utc::now() + chrono::Duration::days(366)).to_rfc3339(),
+            }),
+            "a scheduled time more than a year ahead",
+        ),
+        (
+            serde_json::json!({
+                "title": "Newsletter",
+                "text_content": "Newsletter body as plain text",
+                "idempotency_key": "k".repeat(51),
+            }),
+            "an idempotency key longer than 50 characters",
+        ),
     ];
 
     for (invalid_body, error_message) in test_cases {
@@ -142,11 +206,89 @@ async fn newsletter_creation_is_idempotent() {
         assert_is_redirect_to(&response, "/admin/newsletter");
 
         let html_page = app.get_newsletter_html().await;
-        assert!(html_page.contains(
-            "<p><i>The newsletter issue has been accepted - emails will go out shortly.</i></p>"
-        ));
+        assert!(html_page.contains("<p><i>Queued 1 email; sending in progress.</i></p>"));
+    }
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn the_publish_summary_counts_queued_and_skipped_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    sqlx::query!(
+        "UPDATE subscriptions SET suppressed_until = now() + interval '1 day' WHERE id = (SELECT id FROM subscriptions LIMIT 1)"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page
+        .contains("<p><i>Queued 2 emails; 1 subscriber skipped; sending in progress.</i></p>"));
+}
+
+#[tokio::test]
+async fn publishing_without_confirmed_subscribers_reports_that_nothing_was_sent() {
+    // Arrange
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+
+    // Act - Submit twice: the retry must report the same outcome
+    for _ in 0..2 {
+        let response = app.post_newsletter(&newsletter_request_body).await;
+        assert_is_redirect_to(&response, "/admin/newsletter");
+
+        // Assert
+        let html_page = app.get_newsletter_html().await;
+        assert!(html_page.contains("<p><i>No confirmed subscribers to send to.</i></p>"));
+        assert!(!html_page.contains("sending in progress"));
     }
     app.dispatch_all_pending_emails().await;
+
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 1);
 }
 
 #[tokio::test]
@@ -186,44 +328,2506 @@ async fn concurrent_form_submission_is_handled_gracefully() {
     app.dispatch_all_pending_emails().await;
 }
 
-async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
-    let name: String = Name().fake();
-    let email: String = SafeEmail().fake();
-    let body = serde_urlencoded::to_string(&serde_json::json!({
-        "name": name,
-        "email": email
+#[tokio::test]
+async fn publish_response_advertises_when_the_idempotency_key_expires() {
+    // Arrange
+    let ttl = Duration::from_secs(3600);
+    let app = spawn_app_with(|c| c.idempotency.ttl_seconds = ttl.as_secs()).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
     }))
-    .unwrap();
+    .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+
+    // Act
+    let response = app.post_newsletter(&newsletter_request_body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let expires_at: HttpDate = response
+        .headers()
+        .get("Idempotency-Expires")
+        .expect("Missing Idempotency-Expires header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    let expected = SystemTime::now() + ttl;
+    let drift = SystemTime::from(expires_at)
+        .duration_since(expected - Duration::from_secs(60))
+        .unwrap();
+    assert!(drift <= Duration::from_secs(120));
+}
+
+#[tokio::test]
+async fn newsletters_returns_400_for_a_body_that_is_not_valid_utf8() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let body = format!(
+        "title=Newsletter%FF&text_content=body&html_content=body&idempotency_key={}",
+        uuid::Uuid::new_v4()
+    );
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/newsletter", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("malformed request body"));
+}
+
+#[tokio::test]
+async fn the_deliveries_page_reports_the_issue_throughput() {
+    // Arrange
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
 
-    let _mock_guard = Mock::given(path("/email"))
+    Mock::given(path("/email"))
         .and(method("POST"))
-        .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
-        .expect(1)
-        .mount_as_scoped(&app.email_server)
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(50)))
+        .expect(3)
+        .mount(&app.email_server)
         .await;
 
-    app.post_subscriptions(body.into())
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
         .await
-        .error_for_status()
+        .unwrap()
+        .newsletter_issue_id;
+
+    // Act
+    let html_page = app.get_issue_deliveries_html(&issue_id.to_string()).await;
+
+    // Assert
+    assert!(html_page.contains("<li>Sent: 3</li>"));
+    assert!(html_page.contains("<li>Pending: 0</li>"));
+    let duration: f64 = html_page
+        .split("<li>Duration: ")
+        .nth(1)
+        .and_then(|s| s.split("s</li>").next())
+        .unwrap()
+        .parse()
         .unwrap();
+    assert!(duration > 0.0 && duration < 60.0);
+    assert!(html_page.contains(" emails/s</li>"));
+}
 
-    let email_request = &app
-        .email_server
-        .received_requests()
+#[tokio::test]
+async fn a_failed_delivery_can_be_replayed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    let delivery =
+        sqlx::query!("SELECT delivery_id, newsletter_issue_id, status FROM issue_delivery_queue")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(delivery.status, "failed");
+
+    // Act
+    let response = app
+        .post_replay_delivery(&delivery.delivery_id.to_string())
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!(
+            "/admin/newsletter/{}/deliveries",
+            delivery.newsletter_issue_id
+        ),
+    );
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let status = sqlx::query!("SELECT status FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
         .await
         .unwrap()
-        .pop()
+        .status;
+    assert_eq!(status, "sent");
+    let audit = sqlx::query!("SELECT user_id, action, target FROM audit_log")
+        .fetch_one(&app.db_pool)
+        .await
         .unwrap();
+    assert_eq!(audit.user_id, app.test_user.user_id);
+    assert_eq!(audit.action, "replay_delivery");
+    assert_eq!(audit.target, delivery.delivery_id.to_string());
+    // Mock verifies on Drop that the email was attempted twice
+}
 
-    app.get_confirmation_links(&email_request)
+/// Pretends the delivery's last attempt happened `days` days ago.
+async fn age_last_attempt(app: &crate::helpers::TestApp, days: i32) {
+    sqlx::query!(
+        "UPDATE issue_delivery_queue SET processed_at = now() - make_interval(days => $1)",
+        days
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
 }
 
-async fn create_confirmed_subscriber(app: &TestApp) {
-    let confirmation_link = create_unconfirmed_subscriber(app).await;
-    reqwest::get(confirmation_link.html)
+#[tokio::test]
+async fn dead_letters_are_retried_on_schedule_until_the_retries_run_out() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.newsletter.dead_letter_retry.enabled = true;
+        c.newsletter.dead_letter_retry.interval_seconds = 24 * 60 * 60;
+        c.newsletter.dead_letter_retry.max_retries = 2;
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Act - Part 1 - Not retried before the interval has passed
+    app.requeue_all_dead_letters().await;
+    app.dispatch_all_pending_emails().await;
+
+    // Act - Part 2 - Retried once a day, twice
+    for _ in 0..2 {
+        age_last_attempt(&app, 1).await;
+        app.requeue_all_dead_letters().await;
+        app.dispatch_all_pending_emails().await;
+    }
+
+    // Act - Part 3 - Abandoned for good
+    age_last_attempt(&app, 1).await;
+    app.requeue_all_dead_letters().await;
+
+    // Assert
+    let delivery = sqlx::query!("SELECT status, auto_retries FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
         .await
-        .unwrap()
-        .error_for_status()
         .unwrap();
+    assert_eq!(delivery.status, "failed");
+    assert_eq!(delivery.auto_retries, 2);
+    let n_attempts = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM delivery_attempts"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_attempts, 3);
+    // Mock verifies on Drop that the email was attempted three times
+}
+
+#[tokio::test]
+async fn deliveries_the_provider_rejected_are_not_retried() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.newsletter.dead_letter_retry.enabled = true;
+        c.newsletter.dead_letter_retry.interval_seconds = 24 * 60 * 60;
+        c.newsletter.dead_letter_retry.max_retries = 2;
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Act
+    age_last_attempt(&app, 1).await;
+    app.requeue_all_dead_letters().await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let delivery = sqlx::query!("SELECT status, auto_retries FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.status, "failed");
+    assert_eq!(delivery.auto_retries, 0);
+    // Mock verifies on Drop that the email was attempted once
+}
+
+#[tokio::test]
+async fn issues_are_marked_sent_without_calling_the_provider_in_test_mode() {
+    // Arrange
+    let app = spawn_app_with(|c| c.email_client.test_mode = true).await;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, confirmed_at)
+        VALUES ($1, 'ursula_le_guin@gmail.com', 'le guin', now(), 'confirmed', now())
+        "#,
+        uuid::Uuid::new_v4()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let status = sqlx::query!("SELECT status FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "sent");
+    // Mock verifies on Drop that the provider was never called
+}
+
+#[tokio::test]
+async fn admins_can_drain_the_delivery_queue_on_demand() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+
+    // Act
+    let response = app.post_dispatch_queue().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let n_pending = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue WHERE status = 'pending'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(n_pending, 0);
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("The delivery queue has been dispatched."));
+    // Mock verifies on Drop that both emails were sent
+}
+
+#[tokio::test]
+async fn a_slow_drain_carries_on_after_the_response() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.request_timeout_milliseconds = 500).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+
+    // Act
+    let response = app.post_dispatch_queue().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("The delivery queue is being dispatched in the background."));
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let status = sqlx::query!("SELECT status FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "sent");
+    // Mock verifies on Drop that the email was sent exactly once
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_drain_the_delivery_queue() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_dispatch_queue().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn scheduled_issues_go_out_at_the_same_local_hour_in_every_timezone() {
+    // Arrange
+    let app = spawn_app().await;
+    for (email, timezone) in [
+        ("kiri@example.com", "Pacific/Auckland"),
+        ("jo@example.com", "America/New_York"),
+    ] {
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status, confirmed_at, timezone)
+            VALUES ($1, $2, 'le guin', now(), 'confirmed', now(), $3)
+            "#,
+            uuid::Uuid::new_v4(),
+            email,
+            timezone
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Publish for 9am local time
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "send_at_local_hour": "9",
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert - Part 1 - Scheduled for the next 9am of each subscriber
+    let deliveries = sqlx::query!(
+        r#"
+        SELECT
+            (q.not_before AT TIME ZONE s.timezone)::time AS "local_time!",
+            q.not_before > now() AND q.not_before <= now() + interval '1 day' AS "is_next!",
+            q.status
+        FROM issue_delivery_queue q
+        JOIN subscriptions s ON s.email = q.subscriber_email
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(deliveries.len(), 2);
+    for delivery in deliveries {
+        assert_eq!(delivery.local_time.to_string(), "09:00:00");
+        assert!(delivery.is_next);
+        assert_eq!(delivery.status, "pending");
+    }
+
+    // Act - Part 2 - 9am comes around in Auckland
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue SET not_before = not_before - interval '1 day'
+        WHERE subscriber_email = 'kiri@example.com'
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert - Part 2 - Only the Auckland subscriber has been sent the issue
+    let statuses = sqlx::query!(
+        "SELECT subscriber_email, status FROM issue_delivery_queue ORDER BY subscriber_email"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(statuses[0].subscriber_email, "jo@example.com");
+    assert_eq!(statuses[0].status, "pending");
+    assert_eq!(statuses[1].subscriber_email, "kiri@example.com");
+    assert_eq!(statuses[1].status, "sent");
+}
+
+#[tokio::test]
+async fn a_sent_delivery_cannot_be_replayed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    let delivery =
+        sqlx::query!("SELECT delivery_id, newsletter_issue_id FROM issue_delivery_queue")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+
+    // Act
+    app.post_replay_delivery(&delivery.delivery_id.to_string())
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let html_page = app
+        .get_issue_deliveries_html(&delivery.newsletter_issue_id.to_string())
+        .await;
+    assert!(html_page
+        .contains("<p><i>This delivery has already been sent and cannot be replayed.</i></p>"));
+    let n_entries = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM audit_log"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_entries, 0);
+}
+
+#[tokio::test]
+async fn issue_pages_return_400_for_a_malformed_issue_id() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let response = app.get_issue_deliveries("not-a-uuid").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn issues_published_by_another_admin_are_reported_as_missing() {
+    // Arrange
+    let app = spawn_app().await;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    let issue_id = uuid::Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, published_at, published_by
+        )
+        VALUES ($1, 'Not yours', 'text', '<p>html</p>', now(), $2)
+        "#,
+        issue_id,
+        other_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let response = app.get_issue_deliveries(&issue_id.to_string()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn admins_can_see_the_issues_they_published() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    // Act
+    let response = app.get_issue_deliveries(&issue_id.to_string()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn publishing_a_large_issue_warns_that_it_may_be_clipped() {
+    // Arrange
+    let app = spawn_app_with(|c| c.newsletter.clipping_warning_bytes = 16).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("over the 16 bytes limit - some email clients may clip it."));
+}
+
+#[tokio::test]
+async fn every_delivery_attempt_is_recorded_in_order() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    let delivery_id = sqlx::query!("SELECT delivery_id FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .delivery_id;
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+    for _ in 0..2 {
+        app.post_replay_delivery(&delivery_id.to_string()).await;
+        app.dispatch_all_pending_emails().await;
+    }
+
+    // Assert
+    let attempts = sqlx::query!(
+        "SELECT result, error FROM delivery_attempts WHERE delivery_id = $1 ORDER BY attempted_at",
+        delivery_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    let results: Vec<_> = attempts.iter().map(|a| a.result.as_str()).collect();
+    assert_eq!(results, ["failed", "failed", "sent"]);
+    assert!(attempts[0].error.as_deref().unwrap().contains("500"));
+    assert!(attempts[2].error.is_none());
+}
+
+#[tokio::test]
+async fn newsletters_can_be_delivered_in_a_single_batch_request() {
+    // Arrange
+    let app = spawn_app_with(|c| c.email_client.batch_size = 10).await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            {"ErrorCode": 0, "Message": "OK"},
+            {"ErrorCode": 0, "Message": "OK"},
+            {"ErrorCode": 0, "Message": "OK"},
+        ])))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let n_sent = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue WHERE status = 'sent'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(n_sent, 3);
+}
+
+#[tokio::test]
+async fn recipients_rejected_by_a_batch_are_retried_individually() {
+    // Arrange
+    let app = spawn_app_with(|c| c.email_client.batch_size = 10).await;
+    for _ in 0..2 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            {"ErrorCode": 0, "Message": "OK"},
+            {"ErrorCode": 406, "Message": "Inactive recipient"},
+        ])))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let statuses: Vec<_> = sqlx::query!("SELECT status FROM issue_delivery_queue ORDER BY status")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.status)
+        .collect();
+    assert_eq!(statuses, ["failed", "sent"]);
+}
+
+#[tokio::test]
+async fn the_stored_idempotency_response_can_be_inspected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": idempotency_key
+    }))
+    .await;
+
+    // Act
+    let response = app.get_idempotency_record(&idempotency_key).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<li>State: completed</li>"));
+    assert!(html_page.contains("<li>Status: 303</li>"));
+    assert!(html_page.contains("<tr><td>location</td><td>/admin/newsletter</td></tr>"));
+}
+
+#[tokio::test]
+async fn the_idempotency_store_reports_its_size() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    for (key, age_hours) in [("fresh-key", 0.0), ("old-key", 23.0)] {
+        sqlx::query!(
+            r#"
+            INSERT INTO idempotency (user_id, scope, idempotency_key, created_at)
+            VALUES ($1, $1::uuid::text, $2, now() - make_interval(hours => $3))
+            "#,
+            app.test_user.user_id,
+            key,
+            age_hours as i32
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+
+    // Act
+    let response = app.get_idempotency_stats().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let stats: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(stats["stored_keys"], 2);
+    assert_eq!(stats["expiring_soon"], 1);
+    assert_eq!(stats["expired"], 0);
+    assert!(stats["oldest_key_age_seconds"].as_i64().unwrap() >= 23 * 3600);
+}
+
+#[tokio::test]
+async fn unknown_idempotency_keys_return_404() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let response = app
+        .get_idempotency_record(&uuid::Uuid::new_v4().to_string())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn cloning_a_published_issue_creates_an_editable_draft() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    // Act
+    let response = app.post_clone_issue(&issue_id.to_string()).await;
+
+    // Assert
+    let draft = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, title, text_content, html_content, published_at
+        FROM newsletter_issues
+        WHERE newsletter_issue_id != $1
+        "#,
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletter?draft_id={}", draft.newsletter_issue_id),
+    );
+    assert_eq!(draft.title, "Newsletter title");
+    assert_eq!(draft.text_content, "Newsletter body as plain text");
+    assert_eq!(draft.html_content, "<p>Newsletter body as HTML</p>");
+    assert!(draft.published_at.is_none());
+
+    let html_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/newsletter?draft_id={}",
+            &app.address, draft.newsletter_issue_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("A draft copy of the issue has been created."));
+    assert!(html_page.contains(r#"value="Newsletter title""#));
+    assert!(html_page.contains(&draft.newsletter_issue_id.to_string()));
+
+    // Nothing is sent for the draft
+    app.dispatch_all_pending_emails().await;
+    let n_queued = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue WHERE newsletter_issue_id = $1"#,
+        draft.newsletter_issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(n_queued, 0);
+}
+
+#[tokio::test]
+async fn publishing_a_draft_sends_the_edited_content() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    app.post_clone_issue(&issue_id.to_string()).await;
+    let draft_id = sqlx::query!(
+        "SELECT newsletter_issue_id FROM newsletter_issues WHERE published_at IS NULL"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .newsletter_issue_id;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Corrected title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "draft_id": draft_id.to_string(),
+        }))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let issue = sqlx::query!(
+        "SELECT title, published_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        draft_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(issue.title, "Corrected title");
+    assert!(issue.published_at.is_some());
+}
+
+#[tokio::test]
+async fn resending_an_issue_only_reaches_subscribers_who_have_not_received_it() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    create_confirmed_subscriber(&app).await;
+    let original_send = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    drop(original_send);
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    create_confirmed_subscriber(&app).await;
+    let new_subscriber = sqlx::query!(
+        r#"
+        SELECT email FROM subscriptions
+        WHERE email NOT IN (SELECT subscriber_email FROM issue_delivery_queue)
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .email;
+    let n_requests_before_resend = app.email_server.received_requests().await.unwrap().len();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_resend_issue(&issue_id.to_string()).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletter/{issue_id}/deliveries"),
+    );
+    let requests = app.email_server.received_requests().await.unwrap();
+    let resent: Vec<_> = requests[n_requests_before_resend..]
+        .iter()
+        .map(|r| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap())
+        .collect();
+    assert_eq!(resent.len(), 1);
+    assert_eq!(resent[0]["To"], new_subscriber);
+    let html_page = app.get_issue_deliveries_html(&issue_id.to_string()).await;
+    assert!(html_page.contains("The issue has been queued for 1 more subscribers."));
+}
+
+#[tokio::test]
+async fn confirmations_and_newsletters_use_their_own_sender() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.email_client.transactional_sender_email = "hello@example.com".into();
+        c.email_client.broadcast_sender_email = "news@example.com".into();
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let senders: Vec<_> = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap()["From"].clone())
+        .collect();
+    assert_eq!(senders, vec!["hello@example.com", "news@example.com"]);
+}
+
+#[tokio::test]
+async fn the_sender_name_is_personalised_for_each_recipient() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.email_client.broadcast_sender_email = "news@example.com".into();
+        c.email_client.broadcast_sender_name = Some("{{name}} at Acme".into());
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let names = sqlx::query!("SELECT email, name FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, 'mallory@example.com', $2, now(), 'confirmed')
+        "#,
+        uuid::Uuid::new_v4(),
+        "Mallory\r\nBcc: everyone@example.com"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let n_confirmation_emails = app.email_server.received_requests().await.unwrap().len();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let requests = app.email_server.received_requests().await.unwrap();
+    for request in &requests[n_confirmation_emails..] {
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let recipient = names.iter().find(|n| body["To"] == n.email).unwrap();
+        assert_eq!(
+            body["From"],
+            format!(r#""{} at Acme" <news@example.com>"#, recipient.name)
+        );
+    }
+    let mallory = sqlx::query!(
+        "SELECT status FROM issue_delivery_queue WHERE subscriber_email = 'mallory@example.com'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(mallory.status, "failed");
+}
+
+#[tokio::test]
+async fn an_issue_being_delivered_shows_its_progress() {
+    // Arrange
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    // Act - Part 1 - One of three deliveries has gone out
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue SET status = 'sent', processed_at = now()
+        WHERE subscriber_email = (SELECT MIN(subscriber_email) FROM issue_delivery_queue)
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Assert
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("Newsletter title</a> - sending (1 of 3)"));
+    let html_page = app.get_issue_deliveries_html(&issue_id.to_string()).await;
+    assert!(html_page.contains("<li>State: sending (1 of 3)</li>"));
+
+    // Act - Part 2 - The rest are delivered
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("Newsletter title</a> - completed"));
+    let html_page = app.get_issue_deliveries_html(&issue_id.to_string()).await;
+    assert!(html_page.contains("<li>State: completed</li>"));
+}
+
+#[tokio::test]
+async fn issues_are_minified_before_sending_when_enabled() {
+    // Arrange
+    let app = spawn_app_with(|c| c.newsletter.minify_html = true).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let n_confirmation_emails = app.email_server.received_requests().await.unwrap().len();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let html_content = "<div>\n    <!-- header -->\n    <p>\n        Hello,\n        world!\n    </p>\n\n    <pre>  keep\n    this</pre>\n</div>\n";
+
+    // Act
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": html_content,
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value =
+        serde_json::from_slice(&requests[n_confirmation_emails].body).unwrap();
+    let sent_html = body["HtmlBody"].as_str().unwrap();
+    assert!(!sent_html.contains("\n\n"));
+    assert!(!sent_html.contains("header"));
+    assert!(sent_html.contains("<p> Hello, world! </p>"));
+    assert!(sent_html.contains("<pre>  keep\n    this</pre>"));
+}
+
+#[tokio::test]
+async fn markdown_issues_are_sent_as_sanitized_html_and_plain_text() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let n_confirmation_emails = app.email_server.received_requests().await.unwrap().len();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "markdown_content": "# News\n\nSome **big** news.<script>alert(1)</script>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value =
+        serde_json::from_slice(&requests[n_confirmation_emails].body).unwrap();
+    let sent_html = body["HtmlBody"].as_str().unwrap();
+    assert!(sent_html.contains("<h1>News</h1>"));
+    assert!(sent_html.contains("<strong>big</strong>"));
+    assert!(!sent_html.contains("<script"));
+    assert!(body["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("News\n\nSome big news."));
+}
+
+#[tokio::test]
+async fn a_confirmation_cutoff_leaves_out_subscribers_who_confirmed_later() {
+    // Arrange
+    let app = spawn_app().await;
+    for (email, confirmed_hours_ago) in [("early@example.com", 48), ("late@example.com", 1)] {
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status, confirmed_at)
+            VALUES ($1, $2, 'le guin', now(), 'confirmed', now() - make_interval(hours => $3))
+            "#,
+            uuid::Uuid::new_v4(),
+            email,
+            confirmed_hours_ago
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let cutoff = chrono::Utc::now() - chrono::Duration::hours(24);
+
+    // Act
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "confirmed_before": cutoff.to_rfc3339(),
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["To"], "early@example.com");
+}
+
+#[tokio::test]
+async fn high_priority_issues_are_sent_before_low_priority_ones() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    for (title, priority) in [("Backfill", "low"), ("Breaking news", "high")] {
+        app.post_newsletter(&serde_json::json!({
+            "title": title,
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "priority": priority,
+        }))
+        .await;
+    }
+
+    // Act
+    app.dispatch_next_pending_emails().await;
+
+    // Assert
+    let deliveries = sqlx::query!(
+        r#"
+        SELECT newsletter_issues.title, issue_delivery_queue.status
+        FROM issue_delivery_queue
+        JOIN newsletter_issues USING (newsletter_issue_id)
+        ORDER BY newsletter_issues.title
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    let statuses: Vec<_> = deliveries
+        .iter()
+        .map(|d| (d.title.as_str(), d.status.as_str()))
+        .collect();
+    assert_eq!(
+        statuses,
+        [("Backfill", "pending"), ("Breaking news", "sent")]
+    );
+}
+
+#[tokio::test]
+async fn publishing_as_json_returns_the_outcome_instead_of_a_redirect() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+
+    // Act - Part 1 - Publish
+    let response = app.post_newsletter_json(&newsletter_request_body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let outcome: serde_json::Value = response.json().await.unwrap();
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    assert_eq!(
+        outcome,
+        serde_json::json!({
+            "issue_id": issue_id,
+            "status": "queued",
+            "queued": 1,
+            "skipped": 0,
+            "idempotency_replayed": false,
+            "idempotency_ttl_seconds": app.idempotency.ttl_seconds,
+            "warnings": [],
+        })
+    );
+
+    // Act - Part 2 - Retry with the same idempotency key
+    let response = app.post_newsletter_json(&newsletter_request_body).await;
+
+    // Assert
+    let outcome: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(outcome["issue_id"], issue_id.to_string());
+    assert_eq!(outcome["queued"], 1);
+    assert_eq!(outcome["idempotency_replayed"], true);
+}
+
+#[tokio::test]
+async fn publish_warnings_are_returned_without_blocking_the_send() {
+    // Arrange
+    let app = spawn_app_with(|c| c.newsletter.clipping_warning_bytes = 16).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_newsletter_json(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": r#"<p>Read <a href="/posts/1">the post</a></p>"#,
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let outcome: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(outcome["status"], "queued");
+    let kinds: Vec<_> = outcome["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|w| w["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds, ["clipping", "broken_link"]);
+}
+
+#[tokio::test]
+async fn the_review_page_lists_warnings_before_publishing() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let html_page = app
+        .post_newsletter_review(&serde_json::json!({
+            "title": "HUGE NEWS INSIDE",
+            "text_content": "Act now",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html_page.contains("The title is written in capitals."));
+    assert!(html_page.contains("which spam filters dislike."));
+    assert!(html_page.contains(r#"name="title" value="HUGE NEWS INSIDE""#));
+    let n_issues = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_issues, 0);
+}
+
+#[tokio::test]
+async fn a_test_issue_is_sent_to_the_admin_only() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    app.post_account_email("admin@example.com").await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .and(body_string_contains("admin@example.com"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_newsletter_test(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("A test email has been sent to admin@example.com."));
+    let n_issues = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_issues, 0);
+    let n_keys = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM idempotency"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_keys, 0);
+}
+
+#[tokio::test]
+async fn a_failed_test_send_reports_the_provider_error() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    sqlx::query!(
+        "UPDATE users SET email = 'admin@example.com' WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_newsletter_test(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("Failed to send the test email"));
+}
+
+#[tokio::test]
+async fn issues_published_while_at_the_in_flight_limit_wait_for_a_slot() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.newsletter.max_issues_in_flight = NonZeroU32::new(1);
+        c.email_client.batch_size = 1;
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    for title in ["First issue", "Second issue"] {
+        app.post_newsletter(&serde_json::json!({
+            "title": title,
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    }
+    let second_issue_id = sqlx::query!(
+        "SELECT newsletter_issue_id FROM newsletter_issues WHERE title = 'Second issue'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .newsletter_issue_id
+    .to_string();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(4)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Both batches of the first issue go out
+    app.dispatch_next_pending_emails().await;
+    let html_page = app.get_issue_deliveries_html(&second_issue_id).await;
+    assert!(html_page.contains("<li>State: waiting</li>"));
+    app.dispatch_next_pending_emails().await;
+
+    // Assert
+    let sent = sqlx::query!(
+        r#"
+        SELECT newsletter_issues.title, COUNT(*) FILTER (WHERE status = 'sent') AS "sent!"
+        FROM issue_delivery_queue
+        JOIN newsletter_issues USING (newsletter_issue_id)
+        GROUP BY newsletter_issues.title
+        ORDER BY newsletter_issues.title
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(sent[0].title, "First issue");
+    assert_eq!(sent[0].sent, 2);
+    assert_eq!(sent[1].title, "Second issue");
+    assert_eq!(sent[1].sent, 0);
+
+    // Act - Part 2 - The slot is free, so the second issue is sent
+    let html_page = app.get_issue_deliveries_html(&second_issue_id).await;
+    assert!(html_page.contains("<li>State: sending (0 of 2)</li>"));
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let html_page = app.get_issue_deliveries_html(&second_issue_id).await;
+    assert!(html_page.contains("<li>State: completed</li>"));
+}
+
+#[tokio::test]
+async fn an_issue_waiting_to_be_retried_keeps_its_slot() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.newsletter.max_issues_in_flight = NonZeroU32::new(1);
+        c.newsletter.dead_letter_retry.enabled = true;
+        c.newsletter.dead_letter_retry.interval_seconds = 24 * 60 * 60;
+        c.newsletter.dead_letter_retry.max_retries = 1;
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "First issue",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Act - Part 1 - The first issue's only delivery is backing off
+    app.post_newsletter(&serde_json::json!({
+        "title": "Second issue",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let second_issue_id = sqlx::query!(
+        "SELECT newsletter_issue_id FROM newsletter_issues WHERE title = 'Second issue'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .newsletter_issue_id
+    .to_string();
+    let html_page = app.get_issue_deliveries_html(&second_issue_id).await;
+    assert!(html_page.contains("<li>State: waiting</li>"));
+
+    // Act - Part 2 - Once the retry goes out, the second issue is sent
+    age_last_attempt(&app, 1).await;
+    app.requeue_all_dead_letters().await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let html_page = app.get_issue_deliveries_html(&second_issue_id).await;
+    assert!(html_page.contains("<li>State: completed</li>"));
+}
+
+#[tokio::test]
+async fn an_issue_scheduled_for_later_does_not_take_a_slot() {
+    // Arrange
+    let app = spawn_app_with(|c| c.newsletter.max_issues_in_flight = NonZeroU32::new(1)).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Scheduled issue",
+        "text_content": "Newsletter body as plain text",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "scheduled_at": (chrono::Utc::now() + chrono::Duration::days(30)).to_rfc3339(),
+    }))
+    .await;
+
+    // Act
+    app.post_newsletter(&serde_json::json!({
+        "title": "Immediate issue",
+        "text_content": "Newsletter body as plain text",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let immediate_issue_id = sqlx::query!(
+        "SELECT newsletter_issue_id FROM newsletter_issues WHERE title = 'Immediate issue'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .newsletter_issue_id
+    .to_string();
+    let html_page = app.get_issue_deliveries_html(&immediate_issue_id).await;
+    assert!(html_page.contains("<li>State: completed</li>"));
+    // Mock verifies on Drop that only the immediate issue has been sent
+}
+
+#[tokio::test]
+async fn issues_carry_threading_headers_derived_from_the_issue_when_enabled() {
+    // Arrange
+    let app = spawn_app_with(|c| c.email_client.thread_replies = true).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let delivery =
+        sqlx::query!("SELECT newsletter_issue_id, delivery_id FROM issue_delivery_queue")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    // The first request is the subscriber's confirmation email.
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    let header = |name: &str| {
+        body["Headers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|h| h["Name"] == name)
+            .unwrap_or_else(|| panic!("The {name} header is missing."))["Value"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    let domain = "gmail.com";
+    assert_eq!(
+        header("Message-ID"),
+        format!(
+            "<{}.{}@{domain}>",
+            delivery.newsletter_issue_id, delivery.delivery_id
+        )
+    );
+    let thread_root = format!("<{}@{domain}>", delivery.newsletter_issue_id);
+    assert_eq!(header("In-Reply-To"), thread_root);
+    assert_eq!(header("References"), thread_root);
+}
+
+#[tokio::test]
+async fn publishing_emits_an_event_once_per_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut events = app.event_bus.subscribe();
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4(),
+    });
+
+    // Act - the retry is answered with the saved response
+    app.post_newsletter(&newsletter_request_body).await;
+    app.post_newsletter(&newsletter_request_body).await;
+
+    // Assert
+    let issue_id = sqlx::query_scalar!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        events.try_recv().unwrap(),
+        Event::NewsletterPublished { issue_id }
+    );
+    assert!(events.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn the_issue_activity_lists_sends_and_opens_in_order() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    let delivery =
+        sqlx::query!("SELECT delivery_id, newsletter_issue_id FROM issue_delivery_queue")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let sent: serde_json::Value =
+        serde_json::from_slice(&email_requests.last().unwrap().body).unwrap();
+    assert_eq!(
+        sent["Metadata"]["message_id"],
+        delivery.delivery_id.to_string()
+    );
+    let body = serde_json::json!({
+        "RecordType": "Open",
+        "Recipient": sent["To"],
+        "Metadata": sent["Metadata"]
+    })
+    .to_string();
+    let response = app
+        .post_engagement_webhook(&body, &app.sign_webhook(&body))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act
+    let html_page = app
+        .get_issue_activity_html(&delivery.newsletter_issue_id.to_string())
+        .await;
+
+    // Assert
+    let position = |event: &str| {
+        html_page
+            .find(&format!("<td>{event}</td></tr>"))
+            .unwrap_or_else(|| panic!("No {event} event in the activity feed."))
+    };
+    assert!(position("queued") < position("sent"));
+    assert!(position("sent") < position("opened"));
+}
+
+#[tokio::test]
+async fn a_bounce_is_put_down_to_the_copy_the_provider_names() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    for title in ["First issue", "Second issue"] {
+        app.post_newsletter(&serde_json::json!({
+            "title": title,
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+        app.dispatch_all_pending_emails().await;
+    }
+    let first_copy = sqlx::query!(
+        r#"
+        SELECT q.delivery_id, q.subscriber_email FROM issue_delivery_queue q
+        JOIN newsletter_issues USING (newsletter_issue_id)
+        WHERE newsletter_issues.title = 'First issue'
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let body = serde_json::json!({
+        "RecordType": "Bounce",
+        "Type": "HardBounce",
+        "Email": first_copy.subscriber_email,
+        "Metadata": { "message_id": first_copy.delivery_id }
+    })
+    .to_string();
+    let response = app
+        .post_bounce_webhook(&body, &app.sign_webhook(&body))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let bounced =
+        sqlx::query_scalar!("SELECT delivery_id FROM delivery_events WHERE kind = 'bounced'")
+            .fetch_all(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(bounced, vec![first_copy.delivery_id]);
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_only_delivered_once_their_time_has_come() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Schedule the issue for tomorrow
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "scheduled_at": (chrono::Utc::now() + chrono::Duration::days(1)).to_rfc3339(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert - Part 1 - Nothing has gone out yet, the issue is listed
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>Queued 1 email; sending at "));
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("Newsletter title"));
+    assert!(html_page.contains("/cancel"));
+
+    // Act - Part 2 - The scheduled time comes around
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue SET not_before = now() - interval '1 second'
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Mock verifies on Drop that the issue has been sent exactly once
+}
+
+#[tokio::test]
+async fn retrying_a_scheduled_publish_after_its_time_replays_the_response() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "scheduled_at": (chrono::Utc::now() + chrono::Duration::seconds(2)).to_rfc3339(),
+    });
+    let response = app.post_newsletter_json(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    // Act
+    let response = app.post_newsletter_json(&body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let outcome: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(outcome["idempotency_replayed"], true);
+}
+
+#[tokio::test]
+async fn a_scheduled_issue_can_be_cancelled_before_it_is_sent() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "scheduled_at": (chrono::Utc::now() + chrono::Duration::days(1)).to_rfc3339(),
+    }))
+    .await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/newsletter/{}/cancel",
+            &app.address, issue_id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, &format!("/admin/newsletter?draft_id={issue_id}"));
+    let n_queued = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_queued, 0);
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(!html_page.contains("/cancel"));
+}
+
+#[tokio::test]
+async fn expired_idempotency_keys_are_processed_again() {
+    // Arrange
+    let app = spawn_app_with(|c| c.idempotency.ttl_seconds = 3600).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let body = |idempotency_key: &str| {
+        serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "idempotency_key": idempotency_key,
+        })
+    };
+    let expired_key = uuid::Uuid::new_v4().to_string();
+    let fresh_key = uuid::Uuid::new_v4().to_string();
+    app.post_newsletter_json(&body(&expired_key)).await;
+    sqlx::query!("UPDATE idempotency SET created_at = now() - interval '2 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.post_newsletter_json(&body(&fresh_key)).await;
+
+    // Act
+    app.delete_all_expired_idempotency_keys().await;
+    let expired_outcome: serde_json::Value = app
+        .post_newsletter_json(&body(&expired_key))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let fresh_outcome: serde_json::Value = app
+        .post_newsletter_json(&body(&fresh_key))
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(expired_outcome["idempotency_replayed"], false);
+    assert_eq!(fresh_outcome["idempotency_replayed"], true);
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 3);
+}
+
+#[tokio::test]
+async fn expired_idempotency_keys_are_processed_again_before_they_are_purged() {
+    // Arrange
+    let app = spawn_app_with(|c| c.idempotency.ttl_seconds = 3600).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    app.post_newsletter_json(&body).await;
+    sqlx::query!("UPDATE idempotency SET created_at = now() - interval '2 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let outcome: serde_json::Value = app.post_newsletter_json(&body).await.json().await.unwrap();
+
+    // Assert
+    assert_eq!(outcome["idempotency_replayed"], false);
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 2);
+}
+
+#[tokio::test]
+async fn only_allowlisted_recipients_are_emailed_when_an_allowlist_is_set() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.email_client.recipient_allowlist = Some(vec!["@team.example.com".into()]);
+    })
+    .await;
+    for email in ["dev@team.example.com", "customer@example.com"] {
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status, confirmed_at)
+            VALUES ($1, $2, 'le guin', now(), 'confirmed', now())
+            "#,
+            uuid::Uuid::new_v4(),
+            email
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["To"], "dev@team.example.com");
+    let deliveries = sqlx::query!(
+        "SELECT subscriber_email, status FROM issue_delivery_queue ORDER BY subscriber_email"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(deliveries[0].subscriber_email, "customer@example.com");
+    assert_eq!(deliveries[0].status, "skipped");
+    assert_eq!(deliveries[1].subscriber_email, "dev@team.example.com");
+    assert_eq!(deliveries[1].status, "sent");
+}
+
+#[tokio::test]
+async fn the_deliveries_page_shows_when_a_failed_delivery_will_be_retried() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.newsletter.dead_letter_retry.enabled = true;
+        c.newsletter.dead_letter_retry.interval_seconds = 60 * 60;
+        c.newsletter.dead_letter_retry.max_retries = 2;
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    let delivery =
+        sqlx::query!("SELECT newsletter_issue_id, processed_at FROM issue_delivery_queue")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+
+    // Act
+    let html_page = app
+        .get_issue_deliveries_html(&delivery.newsletter_issue_id.to_string())
+        .await;
+
+    // Assert
+    let retry_at = delivery.processed_at.unwrap() + chrono::Duration::hours(1);
+    assert!(html_page.contains(&format!(
+        "<td>failed</td><td>backing off, retrying at {}</td><td>1</td>",
+        retry_at.to_rfc3339()
+    )));
+}
+
+#[tokio::test]
+async fn a_dry_run_counts_recipients_without_publishing_or_using_the_key() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+
+    // Act - Part 1 - Dry run
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": &idempotency_key,
+            "dry_run": "true",
+        }))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert - Part 1
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains(
+        "Dry run: 2 confirmed subscribers would receive this issue. Nothing has been sent."
+    ));
+    assert!(html_page.contains(r#"srcdoc="&lt;p&gt;Newsletter body as HTML&lt;/p&gt;""#));
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 0);
+
+    // Act - Part 2 - The same key still publishes for real
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": &idempotency_key,
+        }))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert - Part 2
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>Queued 2 emails; sending in progress.</i></p>"));
+    assert!(!html_page.contains("<iframe"));
+    // Mock verifies on Drop that the issue has been sent exactly once
 }
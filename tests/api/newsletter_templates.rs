@@ -0,0 +1,68 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn a_new_issue_can_be_started_from_a_template() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app
+        .post_newsletter_template(&serde_json::json!({
+            "name": "Monthly digest",
+            "title": "Monthly digest",
+            "text_content": "Hello!\n\n--\nThe team",
+            "html_content": "<header>Digest</header><footer>The team</footer>",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter/templates");
+
+    let template_id = sqlx::query!("SELECT template_id FROM newsletter_templates")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .template_id;
+
+    // Act
+    let html_page = app
+        .get_newsletter_from_template_html(&template_id.to_string())
+        .await;
+
+    // Assert
+    assert!(html_page.contains(r#"name="title" value="Monthly digest""#));
+    assert!(html_page.contains(
+        r#"name="html_content" value="&lt;header&gt;Digest&lt;/header&gt;&lt;footer&gt;The team&lt;/footer&gt;""#
+    ));
+}
+
+#[tokio::test]
+async fn templates_must_have_a_name() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let response = app
+        .post_newsletter_template(&serde_json::json!({
+            "name": " ",
+            "title": "Monthly digest",
+            "text_content": "Hello!",
+            "html_content": "<p>Hello!</p>",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletter/templates");
+    let saved = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM newsletter_templates")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.count, 0);
+}
@@ -0,0 +1,99 @@
+use crate::helpers::spawn_app_with;
+
+#[tokio::test]
+async fn each_route_group_enforces_its_own_limit() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.rate_limits.subscriptions.max_requests = 1;
+        c.rate_limits.login.max_requests = 2;
+    })
+    .await;
+    let login_body = serde_json::json!({
+        "username": "random-username",
+        "password": "random-password"
+    });
+
+    // Act - Part 1 - Exhaust the subscriptions limit
+    let first = app.post_subscriptions("name=le%20guin".into()).await;
+    let second = app.post_subscriptions("name=le%20guin".into()).await;
+
+    // Assert
+    assert_eq!(first.status().as_u16(), 400);
+    assert_eq!(second.status().as_u16(), 429);
+
+    // Act - Part 2 - Login is counted separately
+    let first = app.post_login(&login_body).await;
+    let second = app.post_login(&login_body).await;
+    let third = app.post_login(&login_body).await;
+
+    // Assert
+    assert_eq!(first.status().as_u16(), 303);
+    assert_eq!(second.status().as_u16(), 303);
+    assert_eq!(third.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn unsubscribe_links_and_resends_have_their_own_limits() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.rate_limits.validate.max_requests = 1;
+        c.rate_limits.resend.max_requests = 1;
+    })
+    .await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let issue_id = uuid::Uuid::new_v4().to_string();
+
+    // Act - Part 1 - Exhaust the validate limit
+    let first = app.get_unsubscribe("not-a-real-token").await;
+    let second = app.get_unsubscribe("not-a-real-token").await;
+
+    // Assert
+    assert_ne!(first.status().as_u16(), 429);
+    assert_eq!(second.status().as_u16(), 429);
+
+    // Act - Part 2 - Resends are counted separately
+    let first = app.post_resend_issue(&issue_id).await;
+    let second = app.post_resend_issue(&issue_id).await;
+
+    // Assert
+    assert_ne!(first.status().as_u16(), 429);
+    assert_eq!(second.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn limited_clients_are_told_when_to_retry_and_counted_by_forwarded_address() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.rate_limits.subscriptions.max_requests = 1;
+        c.rate_limits.subscriptions.window_seconds = 60;
+    })
+    .await;
+    let subscribe_from = |ip: &'static str| {
+        app.api_client
+            .post(format!("{}/subscriptions", &app.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("X-Forwarded-For", ip)
+            .body("name=le%20guin")
+            .send()
+    };
+
+    // Act
+    let first = subscribe_from("203.0.113.1").await.unwrap();
+    let second = subscribe_from("203.0.113.1").await.unwrap();
+    let other_client = subscribe_from("203.0.113.2").await.unwrap();
+
+    // Assert
+    assert_eq!(first.status().as_u16(), 400);
+    assert_eq!(second.status().as_u16(), 429);
+    let retry_after: u64 = second.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
+    assert_eq!(other_client.status().as_u16(), 400);
+}
@@ -0,0 +1,104 @@
+use sqlx::PgPool;
+
+use crate::helpers::{spawn_app, spawn_app_with};
+
+/// Makes every write to `table` fail the way it does on a read-only
+/// Postgres server, e.g. while a replica is being promoted.
+async fn make_read_only(pool: &PgPool, table: &str) {
+    sqlx::query(
+        r#"
+        CREATE FUNCTION reject_writes() RETURNS trigger AS $$
+        BEGIN
+            RAISE EXCEPTION 'cannot execute % in a read-only transaction', TG_OP
+                USING ERRCODE = 'read_only_sql_transaction';
+        END
+        $$ LANGUAGE plpgsql
+        "#,
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query(&format!(
+        "CREATE TRIGGER reject_writes BEFORE INSERT OR UPDATE OR DELETE ON {table} \
+        FOR EACH STATEMENT EXECUTE FUNCTION reject_writes()"
+    ))
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn subscribing_while_the_database_is_read_only_returns_a_503() {
+    // Arrange
+    let app = spawn_app().await;
+    make_read_only(&app.db_pool, "subscriptions").await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(503, response.status().as_u16());
+    assert_eq!(response.headers()["Retry-After"], "5");
+}
+
+#[tokio::test]
+async fn publishing_while_the_database_is_read_only_returns_a_503() {
+    // Arrange
+    let app = spawn_app_with(|c| c.database.read_only.retry_after_seconds = 30).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    make_read_only(&app.db_pool, "newsletter_issues").await;
+
+    // Act
+    let response = app
+        .post_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4(),
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(503, response.status().as_u16());
+    assert_eq!(response.headers()["Retry-After"], "30");
+}
+
+#[tokio::test]
+async fn reads_are_served_while_the_database_is_read_only() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    make_read_only(&app.db_pool, "subscriptions").await;
+
+    // Act
+    let html_page = app.get_subscribers_search_html(&[]).await;
+
+    // Assert
+    assert!(html_page.contains("<form"));
+}
+
+#[tokio::test]
+async fn read_only_writes_fail_with_a_500_when_degradation_is_disabled() {
+    // Arrange
+    let app = spawn_app_with(|c| c.database.read_only.enabled = false).await;
+    make_read_only(&app.db_pool, "subscriptions").await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(500, response.status().as_u16());
+    assert!(response.headers().get("Retry-After").is_none());
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::shutdown::shutdown_channel;
+
+use crate::helpers::{create_confirmed_subscriber, spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn an_idle_delivery_worker_stops_as_soon_as_shutdown_is_triggered() {
+    // Arrange
+    let app = spawn_app().await;
+    let (shutdown_trigger, shutdown) = shutdown_channel();
+
+    // Act
+    let worker = app.run_delivery_worker(shutdown);
+    let shut_down = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        shutdown_trigger.trigger();
+    };
+    let (outcome, ()) = tokio::time::timeout(Duration::from_secs(2), async {
+        tokio::join!(worker, shut_down)
+    })
+    .await
+    .expect("The worker kept waiting for work after shutdown was triggered.");
+
+    // Assert
+    assert!(outcome.is_ok());
+}
+
+#[tokio::test]
+async fn the_delivery_worker_finishes_its_current_batch_before_stopping() {
+    // Arrange
+    let app = spawn_app_with(|c| c.email_client.batch_size = 1).await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let (shutdown_trigger, shutdown) = shutdown_channel();
+
+    // Act - Shut down while the first batch is being sent
+    let worker = app.run_delivery_worker(shutdown);
+    let shut_down = async {
+        let email_server = &app.email_server;
+        while email_server.received_requests().await.unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        shutdown_trigger.trigger();
+    };
+    let (outcome, ()) = tokio::time::timeout(Duration::from_secs(5), async {
+        tokio::join!(worker, shut_down)
+    })
+    .await
+    .expect("The worker did not stop after its batch.");
+
+    // Assert
+    assert!(outcome.is_ok());
+    let statuses: Vec<String> =
+        sqlx::query_scalar!("SELECT status FROM issue_delivery_queue ORDER BY status")
+            .fetch_all(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(statuses, ["pending", "sent"]);
+}
@@ -3,7 +3,12 @@ use wiremock::{
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::spawn_app;
+use zero2prod::configuration::{ConfirmationEmailFailurePolicy, EmailDomainMode};
+use zero2prod::form::FormWhitespace;
+
+use crate::helpers::{
+    insert_confirmed_subscriber, insert_unsubscribed_subscriber, spawn_app, spawn_app_with,
+};
 
 #[tokio::test]
 async fn subscribe_returns_a_200_for_valid_form_data() {
@@ -96,6 +101,93 @@ async fn subscribe_persists_the_new_subscriber() {
     assert_eq!(saved.status, "pending_confirmation");
 }
 
+#[tokio::test]
+async fn subscribe_trims_whitespace_around_the_submitted_fields() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=%20le%20guin%20&email=%20%20ursula_le_guin%40gmail.com%20";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT email, name FROM subscriptions",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.name, "le guin");
+}
+
+#[tokio::test]
+async fn subscribe_still_rejects_fields_that_are_only_whitespace() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        ("name=%20%20%20&email=ursula%40gmail.com", "a blank name"),
+        ("name=le%20guin&email=%20%09%20", "a blank email"),
+    ];
+
+    for (body, description) in test_cases {
+        // Act
+        let response = app.post_subscriptions(body.into()).await;
+
+        // Assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not return a 400 Bad Request for {description}."
+        );
+    }
+}
+
+#[tokio::test]
+async fn padded_fields_are_left_alone_when_whitespace_is_preserved() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.form_whitespace = FormWhitespace::Preserve).await;
+    let body = "name=le%20guin&email=%20ursula_le_guin%40gmail.com%20";
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn subscribe_formats_the_name_when_configured_to() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.normalize_names = true;
+        c.subscriptions.title_case_names = true;
+    })
+    .await;
+    let body = "name=%20le%20%20GUIN%20&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT name FROM subscriptions",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.name, "Le Guin");
+}
+
 #[tokio::test]
 async fn subscribe_sends_a_confirmation_email_for_valid_data() {
     // Arrange
@@ -133,11 +225,70 @@ async fn subscribe_sends_a_confirmation_email_with_a_link() {
 
     // Assert
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let confirmation_links = app.get_confirmation_links(email_request);
 
     assert_eq!(confirmation_links.html, confirmation_links.plain_text);
 }
 
+#[tokio::test]
+async fn the_confirmation_email_says_when_the_link_expires() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.pending_expiry.enabled = true;
+        c.subscriptions.pending_expiry.max_age_seconds = 48 * 60 * 60;
+    })
+    .await;
+    let body = "name=joe&email=joe%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains("This link expires in 48 hours."));
+    assert!(body["TextBody"]
+        .as_str()
+        .unwrap()
+        .contains("This link expires in 48 hours."));
+    assert!(!body["TextBody"].as_str().unwrap().contains("<br"));
+}
+
+#[tokio::test]
+async fn the_confirmation_subject_is_personalised_when_configured() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        let template = &mut c.email_client.confirmation_template;
+        template.subject = "Confirm your subscription to {{product_name}}, {{first_name}}".into();
+        template.product_name = "Acme".into();
+    })
+    .await;
+    let body = "name=Jane%20Doe&email=jane%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["Subject"], "Confirm your subscription to Acme, Jane");
+}
+
 #[tokio::test]
 async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     // Arrange
@@ -155,3 +306,499 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     // Assert
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn subscribe_stores_a_known_timezone_and_rejects_an_unknown_one() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let unknown = app
+        .post_subscriptions(
+            "name=le%20guin&email=ursula%40gmail.com&timezone=Mars%2FOlympus".into(),
+        )
+        .await;
+    let known = app
+        .post_subscriptions(
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&timezone=Europe%2FRome".into(),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(unknown.status().as_u16(), 400);
+    assert_eq!(known.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT email, timezone FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.timezone.as_deref(), Some("Europe/Rome"));
+}
+
+#[tokio::test]
+async fn subscribing_a_confirmed_address_again_looks_like_a_fresh_sign_up() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    insert_confirmed_subscriber(&app, "ursula_le_guin@gmail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.text().await.unwrap(), "");
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(email["To"], "ursula_le_guin@gmail.com");
+    assert_eq!(email["Subject"], "You are already subscribed");
+    let subscriber = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.status, "confirmed");
+}
+
+#[tokio::test]
+async fn subscribing_a_pending_address_again_sends_a_new_confirmation_link() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into()).await;
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let first_link = app.get_confirmation_links(&email_requests[0]).html;
+    let second_link = app.get_confirmation_links(&email_requests[1]).html;
+    assert_ne!(first_link, second_link);
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_subscribers, 1);
+    reqwest::get(second_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn subscribe_returns_a_400_for_a_body_that_is_not_valid_utf8() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = b"name=le\xffguin&email=ursula_le_guin%40gmail.com".to_vec();
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("malformed request body"));
+}
+
+#[tokio::test]
+async fn subscribe_accepts_allowlisted_domains() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.email_domains.mode = EmailDomainMode::Allowlist;
+        c.subscriptions.email_domains.domains = vec!["example.com".into()];
+    })
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40example.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn subscribe_rejects_domains_missing_from_the_allowlist() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.email_domains.mode = EmailDomainMode::Allowlist;
+        c.subscriptions.email_domains.domains = vec!["example.com".into()];
+    })
+    .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("restricted to approved domains"));
+}
+
+#[tokio::test]
+async fn subscribe_rejects_denylisted_domains() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.email_domains.mode = EmailDomainMode::Denylist;
+        c.subscriptions.email_domains.domains = vec!["mailinator.com".into()];
+    })
+    .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40mailinator.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+}
+
+#[tokio::test]
+async fn a_slow_confirmation_email_does_not_lose_the_subscription() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.request_timeout_milliseconds = 300).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(30)))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let start = std::time::Instant::now();
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    let saved = sqlx::query!("SELECT status, resend_confirmation_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "pending_confirmation");
+    assert!(saved.resend_confirmation_at.is_some());
+}
+
+#[tokio::test]
+async fn a_strict_policy_rolls_back_the_subscription_if_the_confirmation_email_fails() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.confirmation_email_failure = ConfirmationEmailFailurePolicy::Strict
+    })
+    .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 500);
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_optional(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.");
+    assert!(saved.is_none());
+}
+
+#[tokio::test]
+async fn a_lenient_policy_keeps_the_subscription_and_resends_the_confirmation_email_later() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.confirmation_email_failure = ConfirmationEmailFailurePolicy::Lenient
+    })
+    .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    let failing_mock = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Subscribe while the email provider is down
+    let response = app.post_subscriptions(body.into()).await;
+    drop(failing_mock);
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status, resend_confirmation_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "pending_confirmation");
+    assert!(saved.resend_confirmation_at.is_some());
+
+    // Act - Part 2 - The reminder worker retries once the provider is back
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_confirmation_resends().await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT resend_confirmation_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert!(saved.resend_confirmation_at.is_none());
+}
+
+#[tokio::test]
+async fn confirmation_emails_over_the_rate_limit_are_queued_for_the_worker() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.rate_limits.confirmation_emails.max_requests = 2;
+        c.rate_limits.confirmation_emails.window_seconds = 3600;
+    })
+    .await;
+    let sent_now = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    // Act - Part 1 - A burst of sign ups
+    for i in 0..5 {
+        let body = format!("name=le%20guin&email=ursula{i}%40gmail.com");
+        let response = app.post_subscriptions(body).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    drop(sent_now);
+
+    // Assert
+    let n_queued = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM subscriptions WHERE resend_confirmation_at IS NOT NULL"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(n_queued, 3);
+
+    // Act - Part 2 - The reminder worker sends the rest
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_confirmation_resends().await;
+}
+
+#[tokio::test]
+async fn resubscribing_within_the_grace_period_is_confirmed_straight_away() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.subscriptions.resubscribe_grace_seconds = 30 * 24 * 60 * 60).await;
+    insert_unsubscribed_subscriber(&app, "ursula_le_guin@gmail.com", 1).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "confirmed");
+    let resubscription = sqlx::query!("SELECT confirmation_kept FROM resubscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the resubscription.");
+    assert!(resubscription.confirmation_kept);
+}
+
+#[tokio::test]
+async fn resubscribing_after_the_grace_period_requires_a_new_confirmation() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.subscriptions.resubscribe_grace_seconds = 30 * 24 * 60 * 60).await;
+    insert_unsubscribed_subscriber(&app, "ursula_le_guin@gmail.com", 60).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "pending_confirmation");
+
+    // The new link confirms the subscription again
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let saved = sqlx::query!("SELECT status, confirmed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "confirmed");
+    assert!(saved.confirmed_at.unwrap() > chrono::Utc::now() - chrono::Duration::minutes(1));
+}
+
+#[tokio::test]
+async fn a_reopened_subscription_is_not_purged_as_expired() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.resubscribe_grace_seconds = 0;
+        c.subscriptions.pending_expiry.enabled = true;
+        c.subscriptions.pending_expiry.max_age_seconds = 7 * 24 * 60 * 60;
+    })
+    .await;
+    insert_unsubscribed_subscriber(&app, "ursula_le_guin@gmail.com", 60).await;
+    sqlx::query!("UPDATE subscriptions SET subscribed_at = now() - interval '60 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    app.post_subscriptions(body.into()).await;
+
+    // Act
+    app.purge_all_expired_subscribers().await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn expired_pending_subscribers_are_notified_then_purged() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.pending_expiry.enabled = true;
+        c.subscriptions.pending_expiry.max_age_seconds = 7 * 24 * 60 * 60;
+        c.subscriptions.pending_expiry.notify = true;
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    app.post_subscriptions(body.into()).await;
+
+    // Act - Part 1 - Not purged before the maximum age
+    app.purge_all_expired_subscribers().await;
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_subscribers, 1);
+
+    // Act - Part 2 - Purged once it has passed
+    sqlx::query!("UPDATE subscriptions SET subscribed_at = now() - interval '8 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.purge_all_expired_subscribers().await;
+
+    // Assert
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_subscribers, 0);
+    let notification = &app.email_server.received_requests().await.unwrap()[1];
+    let notification: serde_json::Value = serde_json::from_slice(&notification.body).unwrap();
+    assert_eq!(notification["To"], "ursula_le_guin@gmail.com");
+    assert_eq!(
+        notification["Subject"],
+        "Your confirmation link has expired"
+    );
+}
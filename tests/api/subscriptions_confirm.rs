@@ -1,8 +1,13 @@
-use wiremock::matchers::{method, path};
-use wiremock::Mock;
+use wiremock::matchers::{body_string_contains, method, path};
 use wiremock::ResponseTemplate;
+use wiremock::{Mock, MockServer};
 
-use crate::helpers::spawn_app;
+use zero2prod::configuration::{UnknownTokenResponse, WelcomeTemplate};
+use zero2prod::events::Event;
+
+use crate::helpers::{
+    assert_is_redirect_to, create_unconfirmed_subscriber, spawn_app, spawn_app_with,
+};
 
 #[tokio::test]
 async fn confirmations_without_token_are_rejected_with_a_400() {
@@ -32,7 +37,7 @@ async fn the_link_returned_by_subscribe_returns_a_200_if_called() {
 
     app.post_subscriptions(body.into()).await;
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let confirmation_links = app.get_confirmation_links(email_request);
 
     // Act
     let response = reqwest::get(confirmation_links.html).await.unwrap();
@@ -55,7 +60,7 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
 
     app.post_subscriptions(body.into()).await;
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let confirmation_links = app.get_confirmation_links(email_request);
 
     // Act
     reqwest::get(confirmation_links.html)
@@ -74,3 +79,209 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
     assert_eq!(saved.email, "test@gmail.com");
     assert_eq!(saved.status, "confirmed");
 }
+
+#[tokio::test]
+async fn confirming_via_the_link_is_recorded_and_reported() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+
+    // Act
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let saved = sqlx::query!("SELECT confirmed_via FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions");
+    assert_eq!(saved.confirmed_via.as_deref(), Some("link"));
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let html_page = app.get_confirmation_methods_report_html().await;
+    assert!(html_page.contains("<tr><td>Confirmation link</td><td>1</td></tr>"));
+}
+
+#[tokio::test]
+async fn an_unknown_token_shows_a_neutral_page() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(&format!(
+        "{}/subscriptions/confirm?subscription_token=not-a-real-token",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("This link is invalid or has expired."));
+}
+
+#[tokio::test]
+async fn an_unknown_token_can_redirect_to_a_support_page() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.unknown_token =
+            UnknownTokenResponse::Redirect("https://example.com/support".into())
+    })
+    .await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/subscriptions/confirm?subscription_token=not-a-real-token",
+            app.address
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "https://example.com/support");
+}
+
+#[tokio::test]
+async fn the_confirmation_webhook_is_retried_until_it_is_delivered() {
+    // Arrange
+    let webhook_server = MockServer::start().await;
+    let webhook_url = format!("{}/webhook", webhook_server.uri());
+    let app = spawn_app_with(|c| {
+        c.webhooks.subscription_confirmed_url = Some(webhook_url);
+        c.webhooks.retry_base_delay_milliseconds = 0;
+    })
+    .await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+
+    Mock::given(path("/webhook"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&webhook_server)
+        .await;
+    Mock::given(path("/webhook"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&webhook_server)
+        .await;
+
+    // Act
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    app.dispatch_all_pending_webhooks().await;
+
+    // Assert
+    let delivery = sqlx::query!("SELECT status, n_attempts, payload FROM webhook_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.status, "sent");
+    assert_eq!(delivery.n_attempts, 3);
+    let payload: serde_json::Value = serde_json::from_str(&delivery.payload).unwrap();
+    assert_eq!(payload["event"], "subscription.confirmed");
+    // Mock verifies on Drop that the webhook was called three times
+}
+
+#[tokio::test]
+async fn a_welcome_email_is_sent_once_when_configured() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.email_client.welcome_template = Some(WelcomeTemplate {
+            subject: "Welcome aboard".into(),
+            html_content: "<p>Thanks for confirming!</p>".into(),
+            text_content: "Thanks for confirming!".into(),
+        })
+    })
+    .await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .and(body_string_contains("Welcome aboard"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    for _ in 0..2 {
+        let response = reqwest::get(confirmation_links.html.clone()).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Assert
+    // Mock verifies on Drop that the welcome email was sent exactly once
+}
+
+#[tokio::test]
+async fn no_welcome_email_is_sent_by_default() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn subscribing_and_confirming_emit_events() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut events = app.event_bus.subscribe();
+    let body = "name=joel&email=test@gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let subscriber_id = sqlx::query_scalar!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        events.try_recv().unwrap(),
+        Event::SubscriberAdded { subscriber_id }
+    );
+    assert_eq!(
+        events.try_recv().unwrap(),
+        Event::SubscriberConfirmed { subscriber_id }
+    );
+}
@@ -0,0 +1,171 @@
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{
+    create_confirmed_subscriber, insert_confirmed_subscriber, insert_unsubscribe_token, spawn_app,
+    spawn_app_with,
+};
+
+#[tokio::test]
+async fn unsubscribing_with_an_unknown_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_unsubscribe("not-a-real-token").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn unsubscribing_happens_before_the_reason_form_is_shown() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula_le_guin@gmail.com").await;
+    let token = insert_unsubscribe_token(&app, subscriber_id).await;
+
+    // Act
+    let response = app.get_unsubscribe(&token).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("You have been unsubscribed"));
+    assert!(html.contains(r#"value="too_frequent""#));
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn the_reason_form_is_not_shown_when_disabled() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscriptions.ask_unsubscribe_reason = false).await;
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula_le_guin@gmail.com").await;
+    let token = insert_unsubscribe_token(&app, subscriber_id).await;
+
+    // Act
+    let html = app.get_unsubscribe(&token).await.text().await.unwrap();
+
+    // Assert
+    assert!(html.contains("You have been unsubscribed"));
+    assert!(!html.contains("<form"));
+}
+
+#[tokio::test]
+async fn a_reason_is_recorded_and_shows_up_in_the_report() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula_le_guin@gmail.com").await;
+    let token = insert_unsubscribe_token(&app, subscriber_id).await;
+    app.get_unsubscribe(&token).await;
+
+    // Act - Part 1 - Give a reason
+    let response = app
+        .post_unsubscribe_reason(&serde_json::json!({
+            "token": token,
+            "reason": "too_frequent",
+            "details": "One a week would be <plenty>.",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - Part 2 - Look at the report
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let html = app.get_unsubscribe_reasons_report_html().await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT subscriber_id, reason FROM unsubscribe_reasons")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.subscriber_id, subscriber_id);
+    assert_eq!(saved.reason, "too_frequent");
+    assert!(html.contains("<tr><td>I get too many emails</td><td>1</td></tr>"));
+    assert!(html.contains("<tr><td>I never signed up</td><td>0</td></tr>"));
+    assert!(html.contains("One a week would be &lt;plenty&gt;."));
+}
+
+#[tokio::test]
+async fn a_reason_cannot_be_given_while_still_subscribed() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula_le_guin@gmail.com").await;
+    let token = insert_unsubscribe_token(&app, subscriber_id).await;
+
+    // Act
+    let response = app
+        .post_unsubscribe_reason(&serde_json::json!({
+            "token": token,
+            "reason": "not_relevant",
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    let count = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM unsubscribe_reasons"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn the_link_in_an_issue_unsubscribes_from_later_issues() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let issue = |title: &str| {
+        serde_json::json!({
+            "title": title,
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<html><body><p>Newsletter body as HTML</p></body></html>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        })
+    };
+    app.post_newsletter(&issue("First issue")).await;
+    app.dispatch_all_pending_emails().await;
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    let text_body = body["TextBody"].as_str().unwrap();
+    let unsubscribe_link = text_body.split("Unsubscribe: ").nth(1).unwrap().trim();
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    assert!(html_body.contains(&format!(
+        r#"<p><a href="{unsubscribe_link}">Unsubscribe</a></p></body>"#
+    )));
+
+    // Act
+    let mut unsubscribe_link = reqwest::Url::parse(unsubscribe_link).unwrap();
+    unsubscribe_link.set_port(Some(app.port)).unwrap();
+    let response = reqwest::get(unsubscribe_link).await.unwrap();
+    app.post_newsletter(&issue("Second issue")).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+    // Mock verifies on Drop that only the first issue was sent
+}
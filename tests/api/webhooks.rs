@@ -0,0 +1,98 @@
+use crate::helpers::{insert_confirmed_subscriber, spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn a_bounce_with_a_valid_signature_suppresses_the_address() {
+    // Arrange
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    let body = r#"{"email": "ursula@example.com"}"#;
+
+    // Act
+    let response = app.post_bounce_webhook(body, &app.sign_webhook(body)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let status = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "unsubscribed");
+    let suppressed = sqlx::query!("SELECT email FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(suppressed.email, "ursula@example.com");
+}
+
+#[tokio::test]
+async fn a_bounce_with_an_invalid_signature_is_recorded_and_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    let body = r#"{"email": "ursula@example.com"}"#;
+    let signature = app.sign_webhook(r#"{"email": "someone@example.com"}"#);
+
+    // Act
+    let response = app.post_bounce_webhook(body, &signature).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    let failure = sqlx::query!("SELECT endpoint, reason, headers FROM webhook_signature_failures")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The failure was not recorded.");
+    assert_eq!(failure.endpoint, "/webhooks/bounces");
+    assert_eq!(failure.reason, "mismatch");
+    assert!(failure
+        .headers
+        .contains(&format!("x-webhook-signature: {signature}")));
+    let status = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "confirmed");
+}
+
+#[tokio::test]
+async fn webhook_calls_are_rate_limited_across_both_endpoints() {
+    // Arrange
+    let app = spawn_app_with(|c| c.rate_limits.webhooks.max_requests = 2).await;
+    let body = r#"{"email": "ursula@example.com"}"#;
+
+    // Act
+    let bounce = app.post_bounce_webhook(body, "not-a-signature").await;
+    let engagement = app.post_engagement_webhook(body, "not-a-signature").await;
+    let limited = app.post_bounce_webhook(body, "not-a-signature").await;
+
+    // Assert
+    assert_eq!(bounce.status().as_u16(), 401);
+    assert_eq!(engagement.status().as_u16(), 401);
+    assert_eq!(limited.status().as_u16(), 429);
+    let count =
+        sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM webhook_signature_failures"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(count, 2);
+}
+
+#[tokio::test]
+async fn signature_failures_are_listed_for_admins() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_bounce_webhook(r#"{"email": "ursula@example.com"}"#, "not-hex")
+        .await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    // Act
+    let html_page = app.get_signature_failures_report_html().await;
+
+    // Assert
+    assert!(html_page.contains("<td>/webhooks/bounces</td><td>malformed</td>"));
+}